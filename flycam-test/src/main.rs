@@ -1,8 +1,7 @@
 #![allow(dead_code)]
 
-use magellanicus::renderer::{AddBSPParameter, AddBSPParameterLightmapMaterial, AddBSPParameterLightmapSet, AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter, AddShaderBasicShaderData, AddShaderData, AddShaderEnvironmentShaderData, AddShaderParameter, AddSkyParameter, BSP3DNode, BSP3DNodeChild, BSP3DPlane, BSPCluster, BSPData, BSPLeaf, BSPPortal, BSPSubcluster, BitmapFormat, BitmapSprite, BitmapType, Renderer, RendererParameters, Resolution, ShaderType, MSAA};
+use magellanicus::renderer::{DeviceSelection, LogSeverity, Renderer, RendererParameters, Resolution, MSAA};
 use std::collections::HashMap;
-use std::mem::transmute;
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard, Weak};
@@ -11,15 +10,11 @@ use std::time::Instant;
 
 use clap::Parser;
 use glam::Vec3;
-use magellanicus::vertex::{LightmapVertex, ModelTriangle, ModelVertex};
-use ringhopper::definitions::{Bitmap, BitmapDataFormat, BitmapDataType, Scenario, ScenarioStructureBSP, ShaderEnvironment, ShaderModel, ShaderTransparentChicago, ShaderTransparentChicagoExtended, ShaderTransparentGeneric, ShaderTransparentGlass, ShaderTransparentMeter, Sky, UnicodeStringList};
-use ringhopper::primitives::dynamic::DynamicTagDataArray;
+use ringhopper::definitions::{Scenario, ScenarioStructureBSP, UnicodeStringList};
 use ringhopper::primitives::engine::Engine;
-use ringhopper::primitives::primitive::{TagGroup, TagPath};
+use ringhopper::primitives::primitive::TagPath;
 use ringhopper::primitives::tag::{ParseStrictness, PrimaryTagStructDyn};
-use ringhopper::tag::bitmap::MipmapTextureIterator;
 use ringhopper::tag::dependency::recursively_get_dependencies_for_map;
-use ringhopper::tag::scenario_structure_bsp::get_uncompressed_vertices_for_bsp_material;
 use ringhopper::tag::tree::{CachingTagTree, CachingTagTreeWriteStrategy, TagTree, VirtualTagsDirectory};
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
@@ -85,7 +80,21 @@ struct Arguments {
 
     /// Use exclusive fullscreen mode.
     #[arg(long = "fullscreen", short = 'F')]
-    pub fullscreen: bool
+    pub fullscreen: bool,
+
+    /// Device to render with, by index or by name.
+    ///
+    /// By default, the most capable device is used. Use --list-devices to see available devices.
+    #[arg(long = "device", short = 'D')]
+    pub device: Option<String>,
+
+    /// List available devices and exit.
+    #[arg(long = "list-devices")]
+    pub list_devices: bool,
+
+    /// Enable the Vulkan validation layer and print its messages.
+    #[arg(long = "validation")]
+    pub validation: bool
 
 }
 
@@ -107,9 +116,36 @@ fn main() -> Result<(), String> {
         msaa,
         vsync,
         resolution,
-        fullscreen
+        fullscreen,
+        device,
+        list_devices,
+        validation
     } = Arguments::parse();
 
+    if list_devices {
+        let devices = Renderer::enumerate_devices().map_err(|e| format!("Can't enumerate devices: {e:?}"))?;
+        for d in devices {
+            println!(
+                "{}: {} ({:?}, {}, driver: {}){}",
+                d.index,
+                d.name,
+                d.device_type,
+                d.device_local_memory.map(|m| format!("{} MiB", m / 1024 / 1024)).unwrap_or_else(|| "unknown memory".to_owned()),
+                d.driver_name.as_deref().unwrap_or("unknown"),
+                if d.suitable { "" } else { " [unsupported]" }
+            );
+        }
+        return Ok(())
+    }
+
+    let device = match device {
+        Some(d) => match d.parse::<usize>() {
+            Ok(index) => DeviceSelection::Index(index),
+            Err(_) => DeviceSelection::Name(d)
+        },
+        None => DeviceSelection::Automatic
+    };
+
     let sdl = sdl2::init()?;
     let mut events = sdl.event_pump()?;
     let video = sdl.video()?;
@@ -224,7 +260,19 @@ fn main() -> Result<(), String> {
                 number_of_viewports: viewports,
                 vsync,
                 anisotropic_filtering,
-                msaa
+                msaa,
+                device,
+                texture_memory_budget: None,
+                linear_lighting: false,
+                texture_recompression: None,
+                two_channel_normal_maps: false,
+                retain_resources: false,
+                validation,
+                log_callback: Some(Arc::new(|severity, message| {
+                    if severity >= LogSeverity::Warning {
+                        eprintln!("{severity:?}: {message}")
+                    }
+                }))
             })
         }.unwrap();
 
@@ -560,20 +608,16 @@ impl FlycamTestHandler {
     }
 
     fn initialize_and_start(&mut self, camera_rotation_channel: Receiver<(f32, f32, usize)>, fps_channel: Sender<f64>) -> Result<(), String> {
-        if let Err(e) = self.load_bitmaps() {
-            return Err(format!("ERROR LOADING BITMAPS: {e}"))
-        }
-
-        if let Err(e) = self.load_shaders() {
-            return Err(format!("ERROR LOADING shaders: {e}"))
-        }
-
-        if let Err(e) = self.load_skies() {
-            return Err(format!("ERROR LOADING skies: {e}"))
-        }
+        let warnings = self.renderer
+            .as_mut()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .load_ringhopper_scenario(&self.scenario_data.scenario_tag, &self.scenario_data.tags)
+            .map_err(|e| format!("ERROR: {e}"))?;
 
-        if let Err(e) = self.load_bsps() {
-            return Err(format!("ERROR: {e}"))
+        for (path, warning) in warnings {
+            eprintln!("Warning: BSP {path}: {warning}");
         }
 
         if let Some(n) = self.scenario_data.scenario_tag.structure_bsps.items.first().and_then(|b| b.structure_bsp.path()) {
@@ -618,434 +662,6 @@ impl FlycamTestHandler {
 
         Ok(())
     }
-
-    fn load_bitmaps(&mut self) -> Result<(), String> {
-        let mut renderer = self.renderer.as_mut().unwrap().lock().unwrap();
-        let renderer = &mut *renderer;
-        let all_bitmaps = self.scenario_data
-            .tags
-            .iter()
-            .filter(|f| f.0.group() == TagGroup::Bitmap)
-            .map(|f| (f.0, f.1.get_ref::<Bitmap>().unwrap()));
-        
-        for (path, bitmap) in all_bitmaps {
-            Self::load_bitmap(renderer, &path, bitmap).map_err(|e| format!("Failed to load bitmap {path}: {e}"))?;
-        }
-
-        Ok(())
-    }
-
-    fn load_bitmap(renderer: &mut Renderer, path: &&TagPath, bitmap: &Bitmap) -> Result<(), String> {
-        let parameter = AddBitmapParameter {
-            bitmaps: {
-                let mut bitmaps = Vec::with_capacity(bitmap.bitmap_data.items.len());
-                for (bitmap_index, b) in bitmap.bitmap_data.items.iter().enumerate() {
-                    let format = match b.format {
-                        BitmapDataFormat::A8 => BitmapFormat::A8,
-                        BitmapDataFormat::Y8 => BitmapFormat::Y8,
-                        BitmapDataFormat::AY8 => BitmapFormat::AY8,
-                        BitmapDataFormat::A8Y8 => BitmapFormat::A8Y8,
-                        BitmapDataFormat::R5G6B5 => BitmapFormat::R5G6B5,
-                        BitmapDataFormat::A1R5G5B5 => BitmapFormat::A1R5G5B5,
-                        BitmapDataFormat::A4R4G4B4 => BitmapFormat::A4R4G4B4,
-                        BitmapDataFormat::X8R8G8B8 => BitmapFormat::X8R8G8B8,
-                        BitmapDataFormat::A8R8G8B8 => BitmapFormat::A8R8G8B8,
-                        BitmapDataFormat::DXT1 => BitmapFormat::DXT1,
-                        BitmapDataFormat::DXT3 => BitmapFormat::DXT3,
-                        BitmapDataFormat::DXT5 => BitmapFormat::DXT5,
-                        BitmapDataFormat::P8 => BitmapFormat::P8,
-                        BitmapDataFormat::BC7 => BitmapFormat::BC7,
-                    };
-                    let parameter = AddBitmapBitmapParameter {
-                        format,
-                        bitmap_type: match b._type {
-                            BitmapDataType::CubeMap => BitmapType::Cubemap,
-                            BitmapDataType::_3dTexture => BitmapType::Dim3D { depth: b.depth as u32 },
-                            _ => BitmapType::Dim2D
-                        },
-                        resolution: Resolution { width: b.width as u32, height: b.height as u32 },
-                        mipmap_count: b.mipmap_count as u32,
-                        data: {
-                            let length = MipmapTextureIterator::new_from_bitmap_data(b)
-                                .map_err(|e| format!("Error with reading bitmap data #{bitmap_index} from {path}: {e:?}"))?
-                                .map(|b| b.block_count)
-                                .reduce(|a, b| a + b)
-                                .unwrap() * format.block_byte_size();
-                            let start = b.pixel_data_offset as usize;
-                            let data: &[u8] = start.checked_add(length)
-                                .and_then(|end| bitmap.processed_pixel_data.bytes.get(start..end))
-                                .ok_or_else(|| format!("Can't read {length} bytes from {start} in a buffer of {} bytes for bitmap data #{bitmap_index} in {path}", bitmap.processed_pixel_data.bytes.len()))?;
-                            data.to_vec()
-                        }
-                    };
-                    bitmaps.push(parameter);
-                }
-                bitmaps
-            },
-            sequences: {
-                let mut sequences = Vec::with_capacity(bitmap.bitmap_group_sequence.items.len());
-                for (sequence_index, s) in bitmap.bitmap_group_sequence.items.iter().enumerate() {
-                    let result = if bitmap._type == ringhopper::definitions::BitmapType::Sprites {
-                        AddBitmapSequenceParameter::Sprites {
-                            sprites: {
-                                let mut sprites = Vec::with_capacity(s.sprites.items.len());
-                                for (sprite_index, s) in s.sprites.items.iter().enumerate() {
-                                    let sprite = BitmapSprite {
-                                        bitmap: s.bitmap_index.map(|o| o as usize).ok_or_else(|| format!("Sprite {sprite_index} of sequence {sequence_index} of bitmap {path} has a null bitmap index"))?,
-                                        top: s.top as f32,
-                                        left: s.left as f32,
-                                        bottom: s.bottom as f32,
-                                        right: s.right as f32
-                                    };
-                                    sprites.push(sprite);
-                                }
-                                sprites
-                            }
-                        }
-                    } else {
-                        let mut first_bitmap_index = s.first_bitmap_index
-                            .map(|o| o as usize);
-
-                        if first_bitmap_index.is_none() {
-                            if s.bitmap_count > 0 {
-                                return Err(format!("Sequence {sequence_index} of bitmap {path} has a null bitmap index"))
-                            }
-                            else {
-                                first_bitmap_index = Some(0)
-                            }
-                        }
-
-                        AddBitmapSequenceParameter::Bitmap {
-                            first: first_bitmap_index.unwrap(),
-                            count: s.bitmap_count as usize
-                        }
-                    };
-                    sequences.push(result);
-                }
-                sequences
-            }
-        };
-
-        renderer.add_bitmap(&path.to_string(), parameter).map_err(|e| e.to_string())
-    }
-
-    fn load_shaders(&mut self) -> Result<(), String> {
-        let mut renderer = self.renderer.as_mut().unwrap().lock().unwrap();
-        let renderer = &mut *renderer;
-
-        let all_shaders = self.scenario_data
-            .tags
-            .iter()
-            .filter(|f| f.0.group().subgroup() == Some(TagGroup::Shader));
-
-        for (path, tag) in all_shaders {
-            Self::load_shader(renderer, &path, tag).map_err(|e| format!("Failed to load shader {path}: {e}"))?;
-        }
-
-        Ok(())
-    }
-
-    fn load_shader(renderer: &mut Renderer, path: &&TagPath, tag: &Box<dyn PrimaryTagStructDyn>) -> Result<(), String> {
-        let new_shader = match tag.group() {
-            TagGroup::ShaderEnvironment => {
-                let tag = tag.get_ref::<ShaderEnvironment>().unwrap();
-                AddShaderParameter {
-                    data: AddShaderData::ShaderEnvironment(AddShaderEnvironmentShaderData {
-                        alpha_tested: tag.properties.flags.alpha_tested,
-                        bump_map_is_specular_mask: tag.properties.flags.bump_map_is_specular_mask,
-                        base_map: tag.diffuse.base_map.path().map(|p| p.to_string()),
-                        primary_detail_map: tag.diffuse.primary_detail_map.path().map(|p| p.to_string()),
-                        secondary_detail_map: tag.diffuse.secondary_detail_map.path().map(|p| p.to_string()),
-                        micro_detail_map: tag.diffuse.micro_detail_map.path().map(|p| p.to_string()),
-                        bump_map: tag.bump.bump_map.path().map(|p| p.to_string()),
-                        reflection_cube_map: tag.reflection.reflection_cube_map.path().map(|p| p.to_string()),
-                        primary_detail_map_scale: tag.diffuse.primary_detail_map_scale as f32,
-                        secondary_detail_map_scale: tag.diffuse.secondary_detail_map_scale as f32,
-                        micro_detail_map_scale: tag.diffuse.micro_detail_map_scale as f32,
-                        bump_map_scale: tag.bump.bump_map_scale as f32,
-                        parallel_color: [
-                            tag.specular.parallel_color.red as f32,
-                            tag.specular.parallel_color.green as f32,
-                            tag.specular.parallel_color.blue as f32,
-                        ],
-                        perpendicular_color: [
-                            tag.specular.perpendicular_color.red as f32,
-                            tag.specular.perpendicular_color.green as f32,
-                            tag.specular.perpendicular_color.blue as f32,
-                        ],
-                        parallel_brightness: tag.reflection.parallel_brightness as f32,
-                        perpendicular_brightness: tag.reflection.perpendicular_brightness as f32,
-
-                        // SAFETY: 🔥🐶🔥 This is fine 🔥🐶🔥
-                        shader_environment_type: unsafe { transmute(tag.properties.shader_environment_type as u32) },
-                        detail_map_function: unsafe { transmute(tag.diffuse.detail_map_function as u32) },
-                        micro_detail_map_function: unsafe { transmute(tag.diffuse.micro_detail_map_function as u32) },
-                        reflection_type: unsafe { transmute(tag.reflection._type as u32) },
-                    })
-                }
-            },
-            TagGroup::ShaderModel => {
-                let tag = tag.get_ref::<ShaderModel>().unwrap();
-                AddShaderParameter {
-                    data: AddShaderData::BasicShader(AddShaderBasicShaderData {
-                        bitmap: tag.maps.base_map.path().map(|q| q.to_string()),
-                        shader_type: ShaderType::Model,
-                        alpha_tested: !tag.properties.flags.not_alpha_tested
-                    })
-                }
-            },
-            TagGroup::ShaderTransparentChicago => {
-                let tag = tag.get_ref::<ShaderTransparentChicago>().unwrap();
-                AddShaderParameter {
-                    data: AddShaderData::BasicShader(AddShaderBasicShaderData {
-                        bitmap: tag
-                            .maps
-                            .items
-                            .get(0)
-                            .and_then(|b| b.parameters.map.path())
-                            .map(|b| b.to_string()),
-                        shader_type: ShaderType::TransparentChicago,
-                        alpha_tested: true
-                    })
-                }
-            },
-            TagGroup::ShaderTransparentChicagoExtended => {
-                let tag = tag.get_ref::<ShaderTransparentChicagoExtended>().unwrap();
-                AddShaderParameter {
-                    data: AddShaderData::BasicShader(AddShaderBasicShaderData {
-                        bitmap: tag
-                            ._4_stage_maps
-                            .items
-                            .get(0)
-                            .and_then(|b| b.parameters.map.path())
-                            .map(|b| b.to_string()),
-                        shader_type: ShaderType::TransparentChicago,
-                        alpha_tested: true
-                    })
-                }
-            },
-            TagGroup::ShaderTransparentGeneric => {
-                let tag = tag.get_ref::<ShaderTransparentGeneric>().unwrap();
-                AddShaderParameter {
-                    data: AddShaderData::BasicShader(AddShaderBasicShaderData {
-                        bitmap: tag
-                            .maps
-                            .items
-                            .get(0)
-                            .and_then(|b| b.parameters.map.path())
-                            .map(|b| b.to_string()),
-                        shader_type: ShaderType::TransparentGeneric,
-                        alpha_tested: true
-                    })
-                }
-            },
-            TagGroup::ShaderTransparentGlass => {
-                let tag = tag.get_ref::<ShaderTransparentGlass>().unwrap();
-                AddShaderParameter {
-                    data: AddShaderData::BasicShader(AddShaderBasicShaderData {
-                        bitmap: tag
-                            .diffuse
-                            .diffuse_map
-                            .path()
-                            .map(|b| b.to_string()),
-                        shader_type: ShaderType::TransparentGlass,
-                        alpha_tested: true
-                    })
-                }
-            },
-            TagGroup::ShaderTransparentMeter => {
-                let tag = tag.get_ref::<ShaderTransparentMeter>().unwrap();
-                AddShaderParameter {
-                    data: AddShaderData::BasicShader(AddShaderBasicShaderData {
-                        bitmap: tag
-                            .properties
-                            .map
-                            .path()
-                            .map(|b| b.to_string()),
-                        shader_type: ShaderType::TransparentMeter,
-                        alpha_tested: true
-                    })
-                }
-            },
-            TagGroup::ShaderTransparentPlasma => {
-                // let tag = tag.get_ref::<ShaderTransparentPlasma>().unwrap();
-                AddShaderParameter {
-                    data: AddShaderData::BasicShader(AddShaderBasicShaderData {
-                        bitmap: None,
-                        shader_type: ShaderType::TransparentPlasma,
-                        alpha_tested: true
-                    })
-                }
-            },
-            TagGroup::ShaderTransparentWater => {
-                // let tag = tag.get_ref::<ShaderTransparentWater>().unwrap();
-                AddShaderParameter {
-                    data: AddShaderData::BasicShader(AddShaderBasicShaderData {
-                        bitmap: None,
-                        shader_type: ShaderType::TransparentWater,
-                        alpha_tested: true
-                    })
-                }
-            },
-            n => unreachable!("{n}")
-        };
-        renderer.add_shader(&path.to_string(), new_shader).map_err(|e| e.to_string())
-    }
-
-    fn load_skies(&mut self) -> Result<(), String> {
-        let mut renderer = self.renderer.as_mut().unwrap().lock().unwrap();
-        let renderer = &mut *renderer;
-
-        let all_skies = self.scenario_data
-            .tags
-            .iter()
-            .filter(|f| f.0.group() == TagGroup::Sky);
-
-        for (path, tag) in all_skies {
-            Self::load_sky(renderer, path, tag.get_ref().unwrap()).map_err(|e| format!("Failed to load sky {path}: {e}"))?;
-        }
-
-        Ok(())
-    }
-
-    fn load_sky(renderer: &mut Renderer, path: &TagPath, sky: &Sky) -> Result<(), String> {
-        renderer.add_sky(&path.to_string(), AddSkyParameter {
-            geometry: None,
-            outdoor_fog_color: [sky.outdoor_fog.color.red as f32, sky.outdoor_fog.color.green as f32, sky.outdoor_fog.color.blue as f32],
-            outdoor_fog_maximum_density: sky.outdoor_fog.maximum_density as f32,
-            outdoor_fog_start_distance: sky.outdoor_fog.start_distance as f32,
-            outdoor_fog_opaque_distance: sky.outdoor_fog.opaque_distance as f32,
-            indoor_fog_color: [sky.indoor_fog.color.red as f32, sky.indoor_fog.color.green as f32, sky.indoor_fog.color.blue as f32],
-            indoor_fog_maximum_density: sky.indoor_fog.maximum_density as f32,
-            indoor_fog_start_distance: sky.indoor_fog.start_distance as f32,
-            indoor_fog_opaque_distance: sky.indoor_fog.opaque_distance as f32,
-        }).map_err(|e| e.to_string())
-    }
-
-    fn load_bsps(&mut self) -> Result<(), String> {
-        let mut renderer = self.renderer.as_mut().unwrap().lock().unwrap();
-        let renderer = &mut *renderer;
-
-        let all_bsps = self.scenario_data
-            .tags
-            .iter()
-            .filter(|f| f.0.group() == TagGroup::ScenarioStructureBSP)
-            .map(|f| (f.0, f.1.get_ref::<ScenarioStructureBSP>().unwrap()));
-
-        for (path, bsp) in all_bsps {
-            let mut add_bsp = AddBSPParameter {
-                lightmap_bitmap: bsp.lightmaps_bitmap.path().map(|p| p.to_native_path()),
-                lightmap_sets: Vec::with_capacity(bsp.lightmaps.items.len()),
-                bsp_data: BSPData {
-                    nodes: bsp.collision_bsp.items[0].bsp3d_nodes.items.iter().map(|i| BSP3DNode {
-                        front_child: BSP3DNodeChild::from_flagged_u32(i.front_child),
-                        back_child: BSP3DNodeChild::from_flagged_u32(i.back_child),
-                        plane: i.plane as usize
-                    }).collect(),
-                    planes: bsp.collision_bsp.items[0].planes.items.iter().map(|i| BSP3DPlane {
-                        angle: [i.plane.vector.x as f32, i.plane.vector.y as f32, i.plane.vector.z as f32],
-                        offset: i.plane.d as f32
-                    }).collect(),
-                    leaves: bsp.leaves.items.iter().map(|i| BSPLeaf {
-                        cluster: i.cluster.unwrap() as usize
-                    }).collect(),
-                    clusters: bsp.clusters.items.iter().map(|i| BSPCluster {
-                        sky: if let Some(sky) = i.sky {
-                            self.scenario_data
-                                .scenario_tag
-                                .skies
-                                .items.get(sky as usize)
-                                .ok_or_else(|| format!("BSP {path} references sky {sky} which isn't valid on the scenario"))
-                                .unwrap()
-                                .sky
-                                .path()
-                                .map(|s| s.to_string())
-                        }
-                        else {
-                            None
-                        },
-                        subclusters: i.subclusters.items.iter().map(|s| BSPSubcluster {
-                            surface_indices: s.surface_indices.items.iter().map(|i| i.index as usize).collect(),
-                            world_bounds_from: [s.world_bounds_x.lower as f32, s.world_bounds_y.lower as f32, s.world_bounds_z.lower as f32],
-                            world_bounds_to: [s.world_bounds_x.upper as f32, s.world_bounds_y.upper as f32, s.world_bounds_z.upper as f32],
-                        }).collect(),
-                        cluster_portals: i.portals.items.iter().map(|s| s.portal.unwrap_or(0xFFFF) as usize).collect()
-                    }).collect(),
-                    portals: bsp.cluster_portals.items.iter().map(|p| BSPPortal {
-                        front_cluster: p.front_cluster.unwrap_or(0xFFFF) as usize,
-                        back_cluster: p.back_cluster.unwrap_or(0xFFFF) as usize,
-                    }).collect()
-                },
-            };
-
-            for (lightmap_index, lightmap) in bsp.lightmaps.items.iter().enumerate() {
-                let mut add_lightmap = AddBSPParameterLightmapSet {
-                    lightmap_index: lightmap.bitmap.map(|i| i as usize),
-                    materials: Vec::with_capacity(lightmap.materials.len())
-                };
-
-                for (material_index, material) in lightmap.materials.items.iter().enumerate() {
-                    let Some(shader_path) = material.shader.path() else {
-                        continue
-                    };
-
-                    let surfaces: usize = material.surfaces.try_into().unwrap();
-                    let surface_count: usize = material.surface_count.try_into().unwrap();
-
-                    let surface_indices = surfaces.checked_add(surface_count)
-                        .and_then(|range_end| bsp
-                            .surfaces
-                            .items
-                            .get(surfaces..range_end)
-                        );
-                    let Some(surface_indices) = surface_indices else {
-                        return Err(format!("Material #{material_index} of Lightmap #{lightmap_index} of BSP {path} has broken surface indices."));
-                    };
-
-                    let indices = surface_indices
-                        .iter()
-                        .filter_map(|s| {
-                            let a = s.vertex0_index?;
-                            let b = s.vertex1_index?;
-                            let c = s.vertex2_index?;
-                            Some(ModelTriangle { indices: [a,b,c] })
-                    }).collect();
-
-                    let (material, lightmap) = get_uncompressed_vertices_for_bsp_material(material).map_err(|e| {
-                        format!("Material #{material_index} of Lightmap #{lightmap_index} of BSP {path} has broken vertices: {e:?}")
-                    })?;
-
-                    let shader_vertices = material
-                        .map(|f| ModelVertex {
-                            position: [f.position.x as f32, f.position.y as f32, f.position.z as f32],
-                            normal: [f.normal.x as f32, f.normal.y as f32, f.normal.z as f32],
-                            binormal: [f.binormal.x as f32, f.binormal.y as f32, f.binormal.z as f32],
-                            tangent: [f.tangent.x as f32, f.tangent.y as f32, f.tangent.z as f32],
-                            texture_coords: [f.texture_coords.x as f32, f.texture_coords.y as f32]
-                        })
-                        .collect();
-
-                    let lightmap: Vec<LightmapVertex> = lightmap
-                        .map(|f| LightmapVertex {
-                            lightmap_texture_coords: [f.texture_coords.x as f32, f.texture_coords.y as f32]
-                        })
-                        .collect();
-
-                    add_lightmap.materials.push(AddBSPParameterLightmapMaterial {
-                        shader_vertices,
-                        lightmap_vertices: (!lightmap.is_empty()).then_some(lightmap),
-                        surfaces: indices,
-                        shader: shader_path.to_native_path()
-                    });
-                }
-                add_bsp.lightmap_sets.push(add_lightmap);
-            }
-
-            renderer.add_bsp(&path.to_native_path(), add_bsp).map_err(|e| format!("Failed to load BSP {path}: {e}"))?;
-        }
-
-        Ok(())
-    }
 }
 
 fn run_renderer_thread(renderer: Weak<Mutex<Renderer>>, pause_rendering: Arc<AtomicBool>, velocity: Arc<[[AtomicU32; 4]; 4]>, camera_channel: Receiver<(f32, f32, usize)>, fps_channel: Sender<f64>) {
@@ -1097,6 +713,7 @@ fn run_renderer_thread(renderer: Weak<Mutex<Renderer>>, pause_rendering: Arc<Ato
         last_loop = ms_since_start;
 
         let frame_result = renderer.draw_frame();
+        let device_lost = renderer.is_device_lost();
         drop(renderer);
 
         match frame_result {
@@ -1107,6 +724,9 @@ fn run_renderer_thread(renderer: Weak<Mutex<Renderer>>, pause_rendering: Arc<Ato
             },
             Err(e) => {
                 eprintln!("Render fail: {e}");
+                if device_lost {
+                    std::process::exit(1);
+                }
                 continue;
             }
         }
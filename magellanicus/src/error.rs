@@ -8,21 +8,124 @@ pub type MResult<T> = Result<T, Error>;
 /// General Error type
 #[derive(Clone, Debug)]
 pub enum Error {
+    /// The graphics API returned an error not covered by another variant.
     GraphicsAPIError { backend: &'static str, error: String },
-    DataError { error: String }
+
+    /// Data is invalid in a way not covered by another variant.
+    DataError { error: String },
+
+    /// Data refers to a resource by path that is not loaded.
+    MissingDependency { kind: ResourceKind, path: String, error: String },
+
+    /// Data refers to something by an index that is out of bounds.
+    InvalidIndex {
+        /// What the index refers to (e.g. `"sequence"` or `"viewport"`).
+        kind: &'static str,
+        index: usize,
+
+        /// Number of valid indices.
+        count: usize,
+        error: String
+    },
+
+    /// A bitmap's resolution, mipmap count, or data length is invalid or inconsistent.
+    InvalidBitmapDimensions { bitmap_index: usize, error: String },
+
+    /// A scene package is malformed, corrupt, or of an unsupported version.
+    InvalidScenePackage { error: String },
+
+    /// A resource is already loaded at this path, and replacing it is not supported.
+    DuplicatePath { kind: ResourceKind, path: String },
+
+    /// Something was requested that the device or backend does not support.
+    UnsupportedFeature { backend: &'static str, error: String },
+
+    /// The device or host ran out of memory.
+    OutOfMemory { backend: &'static str, error: String },
+
+    /// The device was lost (e.g. from a driver crash or reset), and it must be recreated with
+    /// [`Renderer::recover_lost_device`](crate::renderer::Renderer::recover_lost_device).
+    DeviceLost { backend: &'static str }
+}
+
+/// Type of resource that can be loaded into the renderer.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ResourceKind {
+    Bitmap,
+    Shader,
+    Geometry,
+    Sky,
+    BSP,
+    Font
+}
+
+impl Display for ResourceKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Bitmap => "bitmap",
+            Self::Shader => "shader",
+            Self::Geometry => "geometry",
+            Self::Sky => "sky",
+            Self::BSP => "BSP",
+            Self::Font => "font"
+        })
+    }
+}
+
+impl ResourceKind {
+    fn plural(self) -> &'static str {
+        match self {
+            Self::Bitmap => "bitmaps",
+            Self::Shader => "shaders",
+            Self::Geometry => "geometries",
+            Self::Sky => "skies",
+            Self::BSP => "BSPs",
+            Self::Font => "fonts"
+        }
+    }
 }
 
 impl Error {
     pub(crate) fn from_data_error_string(error: String) -> Self {
         Error::DataError { error }
     }
+
+    pub(crate) fn missing_dependency(kind: ResourceKind, path: impl Into<String>, error: String) -> Self {
+        Error::MissingDependency { kind, path: path.into(), error }
+    }
+
+    pub(crate) fn invalid_index(kind: &'static str, index: usize, count: usize, error: String) -> Self {
+        Error::InvalidIndex { kind, index, count, error }
+    }
+
+    /// Return `true` if the error is from bad data rather than the device, in which case retrying will fail again.
+    pub fn is_data_error(&self) -> bool {
+        matches!(self,
+            Self::DataError { .. }
+            | Self::MissingDependency { .. }
+            | Self::InvalidIndex { .. }
+            | Self::InvalidBitmapDimensions { .. }
+            | Self::DuplicatePath { .. }
+            | Self::InvalidScenePackage { .. }
+        )
+    }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::GraphicsAPIError { backend, error } => write!(f, "{backend} API error: {error}"),
-            Self::DataError { error } => write!(f, "Data error: {error}")
+            Self::DataError { error }
+            | Self::MissingDependency { error, .. }
+            | Self::InvalidIndex { error, .. }
+            | Self::InvalidBitmapDimensions { error, .. } => write!(f, "Data error: {error}"),
+            Self::InvalidScenePackage { error } => write!(f, "Data error: Invalid scene package: {error}"),
+            Self::DuplicatePath { kind, path } => write!(f, "Data error: {path} already exists (replacing {} is not yet supported)", kind.plural()),
+            Self::UnsupportedFeature { backend, error } => write!(f, "{backend} API error: {error}"),
+            Self::OutOfMemory { backend, error } => write!(f, "{backend} API error: {error}"),
+            Self::DeviceLost { backend } => write!(f, "{backend} API error: The device was lost; the renderer needs recreated")
         }
     }
 }
+
+impl core::error::Error for Error {}
@@ -1,10 +1,14 @@
 #![no_std]
 #![allow(dead_code)]
 
-// crate `std` is needed for the Vulkano crate (and thus the vulkan module), but nothing else
+// crate `std` is needed for the Vulkano, wgpu and ringhopper crates (and thus the modules using them), but nothing else
+#[cfg(any(feature = "vulkan", feature = "wgpu", feature = "ringhopper"))]
 extern crate std;
 extern crate alloc;
 
 pub mod vertex;
 pub mod error;
 pub mod renderer;
+
+#[cfg(feature = "ringhopper")]
+pub mod ringhopper;
@@ -5,137 +5,156 @@ use alloc::vec::Vec;
 use alloc::format;
 use alloc::vec;
 use alloc::borrow::ToOwned;
-use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 use data::*;
 
 pub use parameters::*;
-use crate::renderer::vulkan::VulkanRenderer;
+pub use backend::*;
+pub use null::*;
+#[cfg(feature = "scene-package")]
+pub use scene_package::*;
+#[cfg(feature = "wgpu")]
+pub use wgpu::WgpuRenderer;
 use player_viewport::*;
-use crate::error::{Error, MResult};
+use crate::error::{Error, MResult, ResourceKind};
 
 pub use player_viewport::Camera;
 use glam::Vec3;
+use core::time::Duration;
 
 mod parameters;
+mod backend;
+mod null;
+#[cfg(feature = "scene-package")]
+mod scene_package;
+#[cfg(feature = "vulkan")]
 mod vulkan;
+#[cfg(feature = "wgpu")]
+mod wgpu;
 mod data;
 mod player_viewport;
 
-pub struct Renderer {
-    renderer: VulkanRenderer,
+pub struct Renderer<B: RendererBackend = DefaultBackend> {
+    renderer: B,
     player_viewports: Vec<PlayerViewport>,
 
-    bitmaps: BTreeMap<Arc<String>, Bitmap>,
-    shaders: BTreeMap<Arc<String>, Shader>,
-    geometries: BTreeMap<Arc<String>, Geometry>,
-    skies: BTreeMap<Arc<String>, Sky>,
-    bsps: BTreeMap<Arc<String>, Arc<BSP>>,
+    /// Overlays queued to be drawn on the next frame, for each viewport
+    overlays: Vec<Vec<OverlayQuad>>,
+
+    /// Size of the HUD canvas in (width, height)
+    hud_canvas: [f32; 2],
+
+    bitmaps: BTreeMap<Arc<str>, Bitmap<B>>,
+    shaders: BTreeMap<Arc<str>, Shader<B>>,
+    geometries: BTreeMap<Arc<str>, Geometry<B>>,
+    skies: BTreeMap<Arc<str>, Sky>,
+    bsps: BTreeMap<Arc<str>, Arc<BSP<B>>>,
+    fonts: BTreeMap<Arc<str>, Font<B>>,
 
     default_bitmaps: DefaultBitmaps,
-    current_bsp: Option<Arc<String>>
-}
+    current_bsp: Option<Arc<str>>,
+    texture_memory_budget: Option<u64>,
 
-impl Renderer {
-    /// Initialize a new renderer.
-    ///
-    /// Errors if:
-    /// - `parameters` is invalid
-    /// - the renderer backend could not be initialized for some reason
-    pub unsafe fn new(surface: &(impl HasRawWindowHandle + HasRawDisplayHandle), parameters: RendererParameters) -> MResult<Self> {
-        if parameters.resolution.height == 0 || parameters.resolution.width == 0 {
-            return Err(Error::DataError { error: "resolution has 0 on one or more dimensions".to_owned() })
-        }
+    /// Bitmaps, BSPs, and fonts added since the oldest unfinished upload batch began, in the order they were added
+    uploading: Vec<(ResourceKind, Arc<str>)>,
 
-        let mut player_viewports = vec![PlayerViewport::default(); parameters.number_of_viewports];
+    /// Index into `uploading` where the upload batch being recorded begins, if one is being recorded
+    recording_upload_batch: Option<usize>,
 
-        match parameters.number_of_viewports {
-            1 => {
-                player_viewports[0].rel_x = 0.0;
-                player_viewports[0].rel_y = 0.0;
-                player_viewports[0].rel_width = 1.0;
-                player_viewports[0].rel_height = 1.0;
-            }
-            2 => {
-                player_viewports[0].rel_x = 0.0;
-                player_viewports[0].rel_y = 0.0;
-                player_viewports[0].rel_width = 1.0;
-                player_viewports[0].rel_height = 0.5;
-
-                player_viewports[1].rel_x = 0.0;
-                player_viewports[1].rel_y = 0.5;
-                player_viewports[1].rel_width = 1.0;
-                player_viewports[1].rel_height = 0.5;
-            }
-            3 => {
-                player_viewports[0].rel_x = 0.0;
-                player_viewports[0].rel_y = 0.0;
-                player_viewports[0].rel_width = 1.0;
-                player_viewports[0].rel_height = 0.5;
-
-                player_viewports[1].rel_x = 0.0;
-                player_viewports[1].rel_y = 0.5;
-                player_viewports[1].rel_width = 0.5;
-                player_viewports[1].rel_height = 0.5;
-
-                player_viewports[2].rel_x = 0.5;
-                player_viewports[2].rel_y = 0.5;
-                player_viewports[2].rel_width = 0.5;
-                player_viewports[2].rel_height = 0.5;
-            }
-            4 => {
-                player_viewports[0].rel_x = 0.0;
-                player_viewports[0].rel_y = 0.0;
-                player_viewports[0].rel_width = 0.5;
-                player_viewports[0].rel_height = 0.5;
-
-                player_viewports[1].rel_x = 0.5;
-                player_viewports[1].rel_y = 0.0;
-                player_viewports[1].rel_width = 0.5;
-                player_viewports[1].rel_height = 0.5;
-
-                player_viewports[2].rel_x = 0.0;
-                player_viewports[2].rel_y = 0.5;
-                player_viewports[2].rel_width = 0.5;
-                player_viewports[2].rel_height = 0.5;
-
-                player_viewports[3].rel_x = 0.5;
-                player_viewports[3].rel_y = 0.5;
-                player_viewports[3].rel_width = 0.5;
-                player_viewports[3].rel_height = 0.5;
-            }
-            n => return Err(Error::DataError { error: format!("number of viewports was set to {n}, but only 1-4 are supported") })
-        }
+    /// Everything added, in the order it was added, if [`RendererParameters::retain_resources`] is set
+    retained_resources: Option<Vec<(Arc<str>, RetainedResource)>>
+}
+
+/// A copy of the parameters something was added with, so it can be added again after the device is lost.
+enum RetainedResource {
+    Bitmap(AddBitmapParameter),
+    Shader(AddShaderParameter),
+    Sky(AddSkyParameter),
+    Bsp(AddBSPParameter),
+    Font(AddFontParameter)
+}
+
+impl Renderer<NullRenderer> {
+    /// Initialize a new renderer that doesn't use a GPU.
+    ///
+    /// See [`NullRenderer`] for what it does instead.
+    ///
+    /// Errors if `parameters` is invalid.
+    pub fn new_null(parameters: RendererParameters) -> MResult<Self> {
+        let player_viewports = make_player_viewports(&parameters)?;
+        let backend = NullRenderer::new(&parameters);
+        Self::new_with_backend(backend, player_viewports, &parameters)
+    }
+}
 
+impl<B: RendererBackend> Renderer<B> {
+    fn new_with_backend(backend: B, player_viewports: Vec<PlayerViewport>, parameters: &RendererParameters) -> MResult<Self> {
         let mut result = Self {
-            renderer: VulkanRenderer::new(&parameters, surface)?,
+            renderer: backend,
+            overlays: vec![Vec::new(); player_viewports.len()],
+            hud_canvas: [HUD_CANVAS_WIDTH, HUD_CANVAS_HEIGHT],
             player_viewports,
             bitmaps: BTreeMap::new(),
             shaders: BTreeMap::new(),
             geometries: BTreeMap::new(),
             skies: BTreeMap::new(),
             bsps: BTreeMap::new(),
+            fonts: BTreeMap::new(),
             current_bsp: None,
-            default_bitmaps: DefaultBitmaps::default()
+            default_bitmaps: DefaultBitmaps::default(),
+            texture_memory_budget: parameters.texture_memory_budget,
+            uploading: Vec::new(),
+            recording_upload_batch: None,
+            retained_resources: None
         };
 
+        // Default bitmaps are made again when recovering, so they aren't retained.
         populate_default_bitmaps(&mut result)?;
+        result.retained_resources = parameters.retain_resources.then(Vec::new);
 
         Ok(result)
     }
 
+    /// Get the backend.
+    pub fn backend(&self) -> &B {
+        &self.renderer
+    }
+
+    /// Get the backend mutably.
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.renderer
+    }
+
     /// Clear all data without resetting the renderer.
     ///
     /// All objects added with `add_` methods will be cleared.
     pub fn reset(&mut self) {
+        self.clear_resources();
+        self.recording_upload_batch = self.recording_upload_batch.map(|_| 0);
+
+        let retained_resources = self.retained_resources.take().map(|_| Vec::new());
+        populate_default_bitmaps(self).unwrap();
+        self.retained_resources = retained_resources;
+    }
+
+    /// Drop everything that was added, including default bitmaps.
+    fn clear_resources(&mut self) {
         self.bitmaps.clear();
         self.shaders.clear();
         self.geometries.clear();
         self.skies.clear();
         self.bsps.clear();
+        self.fonts.clear();
         self.current_bsp = None;
         self.default_bitmaps = DefaultBitmaps::default();
+        self.clear_overlays();
+        self.uploading.clear();
+    }
 
-        populate_default_bitmaps(self).unwrap();
+    /// Keep a copy of something that was just added, if [`RendererParameters::retain_resources`] is set.
+    fn retain_resource(&mut self, path: Arc<str>, resource: Option<RetainedResource>) {
+        if let (Some(retained_resources), Some(resource)) = (self.retained_resources.as_mut(), resource) {
+            retained_resources.push((path, resource));
+        }
     }
 
     /// Add a bitmap with the given parameters.
@@ -146,14 +165,22 @@ impl Renderer {
     /// - `bitmap` is invalid
     /// - replacing a bitmap would break any dependencies (HUDs, shaders, etc.)
     pub fn add_bitmap(&mut self, path: &str, bitmap: AddBitmapParameter) -> MResult<()> {
-        let bitmap_path = Arc::new(path.to_owned());
-        if self.bsps.contains_key(&bitmap_path) {
-            return Err(Error::from_data_error_string(format!("{path} already exists (replacing bitmaps is not yet supported)")))
+        let bitmap_path = Arc::from(path);
+        if self.bitmaps.contains_key(&bitmap_path) {
+            return Err(Error::DuplicatePath { kind: ResourceKind::Bitmap, path: path.to_owned() })
         }
 
         bitmap.validate()?;
-        let bitmap = Bitmap::load_from_parameters(self, bitmap)?;
-        self.bitmaps.insert(bitmap_path, bitmap);
+        self.add_validated_bitmap(bitmap_path, bitmap)
+    }
+
+    /// Add a bitmap that was already validated and whose path is not in use.
+    fn add_validated_bitmap(&mut self, bitmap_path: Arc<str>, bitmap: AddBitmapParameter) -> MResult<()> {
+        let retained = self.retained_resources.is_some().then(|| RetainedResource::Bitmap(bitmap.clone()));
+        let bitmap = Bitmap::load_from_parameters(self, &bitmap_path, bitmap)?;
+        self.bitmaps.insert(bitmap_path.clone(), bitmap);
+        self.track_upload(ResourceKind::Bitmap, bitmap_path.clone());
+        self.retain_resource(bitmap_path, retained);
         Ok(())
     }
 
@@ -166,14 +193,21 @@ impl Renderer {
     /// - `pipeline` contains invalid dependencies
     /// - replacing a pipeline would break any dependencies
     pub fn add_shader(&mut self, path: &str, shader: AddShaderParameter) -> MResult<()> {
-        let shader_path = Arc::new(path.to_owned());
+        let shader_path = Arc::from(path);
         if self.shaders.contains_key(&shader_path) {
-            return Err(Error::from_data_error_string(format!("{path} already exists (replacing shaders is not yet supported)")))
+            return Err(Error::DuplicatePath { kind: ResourceKind::Shader, path: path.to_owned() })
         }
 
         shader.validate(self)?;
-        let shader = Shader::load_from_parameters(self, shader)?;
-        self.shaders.insert(shader_path, shader);
+        self.add_validated_shader(shader_path, shader)
+    }
+
+    /// Add a shader that was already validated and whose path is not in use.
+    fn add_validated_shader(&mut self, shader_path: Arc<str>, shader: AddShaderParameter) -> MResult<()> {
+        let retained = self.retained_resources.is_some().then(|| RetainedResource::Shader(shader.clone()));
+        let shader = Shader::load_from_parameters(self, &shader_path, shader)?;
+        self.shaders.insert(shader_path.clone(), shader);
+        self.retain_resource(shader_path, retained);
         Ok(())
     }
 
@@ -192,11 +226,24 @@ impl Renderer {
 
     /// Add a sky.
     ///
+    /// Note that replacing skies is not yet supported.
+    ///
     /// This will error if:
     /// - `sky` is invalid
     /// - `sky` contains invalid dependencies
     pub fn add_sky(&mut self, path: &str, sky: AddSkyParameter) -> MResult<()> {
+        let sky_path = Arc::from(path);
+        if self.skies.contains_key(&sky_path) {
+            return Err(Error::DuplicatePath { kind: ResourceKind::Sky, path: path.to_owned() })
+        }
+
         sky.validate(self)?;
+        self.add_validated_sky(sky_path, sky)
+    }
+
+    /// Add a sky that was already validated and whose path is not in use.
+    fn add_validated_sky(&mut self, sky_path: Arc<str>, sky: AddSkyParameter) -> MResult<()> {
+        let retained = self.retained_resources.is_some().then(|| RetainedResource::Sky(sky.clone()));
 
         // tool.exe defaults 0.0 max density to 1.0, so fog should be disabled if both the start and
         // max distance are 0.0.
@@ -220,8 +267,8 @@ impl Renderer {
             indoor_fog_opaque_distance = 1.0;
         }
 
-        self.skies.insert(Arc::new(path.to_owned()), Sky {
-            geometry: sky.geometry.map(|s| self.geometries.get_key_value(&s).unwrap().0.clone()),
+        self.skies.insert(sky_path.clone(), Sky {
+            geometry: sky.geometry.map(|s| self.geometries.get_key_value(s.as_str()).unwrap().0.clone()),
             outdoor_fog_color: sky.outdoor_fog_color,
             outdoor_fog_maximum_density,
             outdoor_fog_start_distance,
@@ -231,6 +278,7 @@ impl Renderer {
             indoor_fog_start_distance,
             indoor_fog_opaque_distance,
         });
+        self.retain_resource(sky_path, retained);
 
         Ok(())
     }
@@ -240,35 +288,159 @@ impl Renderer {
     /// Note that replacing BSPs is not yet supported.
     ///
     /// This will error if:
-    /// - `bsp` is invalid
+    /// - `bsp` is invalid (see [`AddBSPParameter::validation_report`] for every problem at once)
     /// - `bsp` contains invalid dependencies
     pub fn add_bsp(&mut self, path: &str, bsp: AddBSPParameter) -> MResult<()> {
-        let bsp_path = Arc::new(path.to_owned());
+        let bsp_path = Arc::from(path);
         if self.bsps.contains_key(&bsp_path) {
-            return Err(Error::from_data_error_string(format!("{path} already exists (replacing BSPs is not yet supported)")))
+            return Err(Error::DuplicatePath { kind: ResourceKind::BSP, path: path.to_owned() })
         }
 
         bsp.validate(self)?;
-        let bsp = BSP::load_from_parameters(self, bsp)?;
-        self.bsps.insert(bsp_path, Arc::new(bsp));
+        self.add_validated_bsp(bsp_path, bsp)
+    }
+
+    /// Add a BSP that was already validated and whose path is not in use.
+    fn add_validated_bsp(&mut self, bsp_path: Arc<str>, bsp: AddBSPParameter) -> MResult<()> {
+        // Lightmaps are referenced by the BSP directly, so they can't be streamed.
+        if let Some(lightmap_bitmap) = bsp.lightmap_bitmap.as_ref() {
+            let lightmap_bitmap = self.bitmaps.get_key_value(lightmap_bitmap.as_str()).unwrap().0.clone();
+            pin_bitmap(self, &lightmap_bitmap)?;
+        }
+
+        let retained = self.retained_resources.is_some().then(|| RetainedResource::Bsp(bsp.clone()));
+        let bsp = BSP::load_from_parameters(self, &bsp_path, bsp)?;
+        self.bsps.insert(bsp_path.clone(), Arc::new(bsp));
+        self.track_upload(ResourceKind::BSP, bsp_path.clone());
+        self.retain_resource(bsp_path, retained);
         Ok(())
     }
 
+    /// Add a font.
+    ///
+    /// Note that replacing fonts is not yet supported.
+    ///
+    /// This will error if:
+    /// - `font` is invalid
+    pub fn add_font(&mut self, path: &str, font: AddFontParameter) -> MResult<()> {
+        let font_path = Arc::from(path);
+        if self.fonts.contains_key(&font_path) {
+            return Err(Error::DuplicatePath { kind: ResourceKind::Font, path: path.to_owned() })
+        }
+
+        font.validate()?;
+        let retained = self.retained_resources.is_some().then(|| RetainedResource::Font(font.clone()));
+        let font = Font::load_from_parameters(self, path, font)?;
+        self.fonts.insert(font_path.clone(), font);
+        self.track_upload(ResourceKind::Font, font_path.clone());
+        self.retain_resource(font_path, retained);
+        Ok(())
+    }
+
+    /// Start recording an upload batch.
+    ///
+    /// Until [`Renderer::submit_upload_batch`] is called, data uploaded by `add_` methods is collected and submitted
+    /// all at once rather than being submitted immediately. Uploads run on a dedicated transfer queue if the device has
+    /// one.
+    ///
+    /// Anything added while recording can't be drawn until the batch is submitted. Once it is submitted,
+    /// [`Renderer::draw_frame`] waits for the batch to complete before drawing anything that was added in it.
+    ///
+    /// Returns `Err` if a batch is already being recorded.
+    pub fn begin_upload_batch(&mut self) -> MResult<()> {
+        self.renderer.begin_upload_batch()?;
+        self.recording_upload_batch = Some(self.uploading.len());
+        Ok(())
+    }
+
+    /// Submit the current upload batch.
+    ///
+    /// This does not wait for the uploads to finish, so frames can continue to be drawn in the meantime.
+    ///
+    /// Returns `Err` if no batch is being recorded.
+    pub fn submit_upload_batch(&mut self) -> MResult<()> {
+        self.renderer.submit_upload_batch()?;
+        self.recording_upload_batch = None;
+        Ok(())
+    }
+
+    /// Return `true` if any submitted upload batches are still in progress.
+    pub fn uploads_pending(&mut self) -> bool {
+        self.renderer.uploads_pending()
+    }
+
+    /// Block until all submitted upload batches are complete.
+    pub fn wait_for_uploads(&mut self) -> MResult<()> {
+        self.renderer.wait_for_uploads()?;
+        self.uploading.drain(..self.recording_upload_batch.unwrap_or(self.uploading.len()));
+        self.recording_upload_batch = self.recording_upload_batch.map(|_| 0);
+        Ok(())
+    }
+
+    /// Track a resource that was just added, if it may have been uploaded as part of an unfinished upload batch.
+    ///
+    /// Anything depending on an unfinished batch must have been added after that batch began, so everything added
+    /// since the oldest unfinished batch began is tracked.
+    fn track_upload(&mut self, kind: ResourceKind, path: Arc<str>) {
+        if self.recording_upload_batch.is_some() || !self.uploading.is_empty() || self.renderer.uploads_pending() {
+            self.uploading.push((kind, path));
+        }
+    }
+
+    /// Wait for any upload batches containing resources that are about to be drawn.
+    ///
+    /// Returns `Err` if a resource about to be drawn is in the batch being recorded.
+    fn wait_for_drawn_uploads(&mut self) -> MResult<()> {
+        if self.uploading.is_empty() {
+            return Ok(())
+        }
+
+        if self.recording_upload_batch.is_none() && !self.renderer.uploads_pending() {
+            self.uploading.clear();
+            return Ok(())
+        }
+
+        let is_drawn = |kind: ResourceKind, path: &Arc<str>| -> bool {
+            if kind == ResourceKind::BSP && self.current_bsp.as_ref() == Some(path) {
+                return true
+            }
+            self.overlays.iter().flatten().any(|quad| match (&quad.texture, kind) {
+                (OverlayTexture::Bitmap { path: p, .. }, ResourceKind::Bitmap) => p == path,
+                (OverlayTexture::Font { path: p }, ResourceKind::Font) => p == path,
+                _ => false
+            })
+        };
+
+        let Some(drawn) = self.uploading.iter().position(|(kind, path)| is_drawn(*kind, path)) else {
+            return Ok(())
+        };
+
+        if self.recording_upload_batch.is_some_and(|start| drawn >= start) {
+            let (kind, path) = &self.uploading[drawn];
+            return Err(Error::DataError { error: format!("{kind} {path} can't be drawn until its upload batch is submitted") })
+        }
+
+        self.wait_for_uploads()
+    }
+
     /// Set the current BSP.
     ///
     /// If `path` is `None`, the BSP will be unloaded.
     ///
+    /// This will block until all submitted upload batches are complete.
+    ///
     /// Returns `Err` if `path` refers to a BSP that isn't loaded.
     pub fn set_current_bsp(&mut self, path: Option<&str>) -> MResult<()> {
+        self.wait_for_uploads()?;
+
         if let Some(p) = path {
             let key = self
                 .bsps
-                .keys()
-                .find(|f| f.as_str() == p)
-                .map(|b| b.clone());
+                .get_key_value(p)
+                .map(|(b, _)| b.clone());
 
             if key.is_none() {
-                return Err(Error::from_data_error_string(format!("Can't set current BSP to {path:?}: that BSP is not loaded")))
+                return Err(Error::missing_dependency(ResourceKind::BSP, p, format!("Can't set current BSP to {path:?}: that BSP is not loaded")))
             }
 
             self.current_bsp = key;
@@ -326,36 +498,286 @@ impl Renderer {
         self.player_viewports.len()
     }
 
+    /// Queue a sprite to be drawn over the given viewport.
+    ///
+    /// Sprites are drawn in the order they are queued, on top of everything else in the viewport, and they are
+    /// cleared once the frame is drawn.
+    ///
+    /// This will error if:
+    /// - `viewport >= self.get_viewport_count()`
+    /// - `sprite` is invalid
+    pub fn draw_sprite(&mut self, viewport: usize, sprite: DrawSpriteParameter) -> MResult<()> {
+        if viewport >= self.player_viewports.len() {
+            return Err(Error::invalid_index("viewport", viewport, self.player_viewports.len(), format!("Can't draw a sprite on viewport #{viewport}: only {} viewport(s) exist", self.player_viewports.len())))
+        }
+
+        sprite.validate(self)?;
+        let quad = OverlayQuad::from_sprite(self, sprite)?;
+        self.overlays[viewport].push(quad);
+        Ok(())
+    }
+
+    /// Queue text to be drawn over the given viewport.
+    ///
+    /// Like sprites, text is drawn in the order it is queued and cleared once the frame is drawn.
+    ///
+    /// This will error if:
+    /// - `viewport >= self.get_viewport_count()`
+    /// - `text` is invalid
+    pub fn draw_text(&mut self, viewport: usize, text: DrawTextParameter) -> MResult<()> {
+        if viewport >= self.player_viewports.len() {
+            return Err(Error::invalid_index("viewport", viewport, self.player_viewports.len(), format!("Can't draw text on viewport #{viewport}: only {} viewport(s) exist", self.player_viewports.len())))
+        }
+
+        text.validate(self)?;
+        let (path, font) = self.fonts.get_key_value(text.font.as_str()).unwrap();
+        let quads = font.layout_text(path, &text);
+        self.overlays[viewport].extend(quads);
+        Ok(())
+    }
+
+    /// Set the size of the virtual canvas overlays are positioned on in (width, height).
+    ///
+    /// The canvas is scaled uniformly to fit inside each viewport and centered, so a larger canvas draws overlays
+    /// smaller, and a canvas with the same aspect ratio as a viewport fills it. This applies to every overlay drawn
+    /// from the next frame on, including ones already queued.
+    ///
+    /// Default = [`HUD_CANVAS_WIDTH`]x[`HUD_CANVAS_HEIGHT`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` or `height` is not finite and greater than 0
+    pub fn set_hud_canvas(&mut self, width: f32, height: f32) {
+        assert!(width.is_finite() && width > 0.0 && height.is_finite() && height > 0.0, "HUD canvas size is not finite and greater than 0");
+        self.hud_canvas = [width, height];
+    }
+
+    /// Get the size of the HUD canvas in (width, height).
+    pub fn get_hud_canvas(&self) -> [f32; 2] {
+        self.hud_canvas
+    }
+
+    /// Clear all queued overlays without drawing them.
+    pub fn clear_overlays(&mut self) {
+        for overlay in &mut self.overlays {
+            overlay.clear();
+        }
+    }
+
+    /// Take the overlays queued for each viewport, leaving them empty for the next frame.
+    fn take_overlays(&mut self) -> Vec<Vec<OverlayQuad>> {
+        self.overlays.iter_mut().map(core::mem::take).collect()
+    }
+
+    /// Get the time elapsed since the renderer was initialized.
+    ///
+    /// This drives animated sequences.
+    pub fn get_time(&self) -> Duration {
+        self.renderer.get_time()
+    }
+
     /// Draw a frame.
     ///
-    /// If `true`, the swapchain needs rebuilt.
+    /// If no BSP is set, viewports are cleared to black and only their overlays are drawn.
+    ///
+    /// Returns `false` if the frame was skipped, such as if the swapchain needs rebuilt.
+    ///
+    /// This blocks until any submitted upload batches containing the current BSP or a queued overlay's bitmap or font
+    /// are complete.
+    ///
+    /// This will error if:
+    /// - drawing or presenting the frame failed; the next frame can still be attempted
+    /// - the current BSP or a queued overlay was added in an upload batch that hasn't been submitted
+    /// - the device was lost (see [`Renderer::is_device_lost`])
     pub fn draw_frame(&mut self) -> MResult<bool> {
-        VulkanRenderer::draw_frame(self)
+        self.wait_for_drawn_uploads()?;
+        update_texture_residency(self)?;
+        B::draw_frame(self)
+    }
+
+    /// Return `true` if the device was lost, such as from a driver crash or reset.
+    ///
+    /// Nothing can be drawn until [`Renderer::recover_lost_device`] is called.
+    pub fn is_device_lost(&self) -> bool {
+        self.renderer.is_device_lost()
+    }
+
+    /// Replace a lost device with a new one.
+    ///
+    /// `parameters` should be what the renderer was initialized with, aside from `resolution`, which should be the
+    /// current resolution. `number_of_viewports` and `retain_resources` are ignored, and cameras are kept.
+    ///
+    /// If [`RendererParameters::retain_resources`] was set when the renderer was initialized, everything that was
+    /// added is added again, and the current BSP is set again. Otherwise, the renderer is left empty as if
+    /// [`Renderer::reset`] was called, and everything must be added again. Any upload batch being recorded is
+    /// discarded, and queued overlays are cleared.
+    ///
+    /// This can be retried if it fails.
+    ///
+    /// This will error if:
+    /// - `parameters` is invalid
+    /// - a new device could not be created
+    pub fn recover_lost_device(&mut self, parameters: RendererParameters) -> MResult<()> {
+        if parameters.resolution.height == 0 || parameters.resolution.width == 0 {
+            return Err(Error::DataError { error: "resolution has 0 on one or more dimensions".to_owned() })
+        }
+
+        // Everything made with the lost device has to be dropped before it's replaced.
+        let current_bsp = self.current_bsp.clone();
+        self.clear_resources();
+        self.recording_upload_batch = None;
+
+        // Keep the copies out of the way while they're added again, so they aren't retained twice and so recovery can
+        // be retried if this fails partway through.
+        let retained_resources = self.retained_resources.take();
+        let result = self.restore_resources(&parameters, retained_resources.as_deref().unwrap_or_default(), current_bsp);
+        self.retained_resources = retained_resources;
+        result
     }
 
-    fn get_default_2d(&self, default_type: DefaultType) -> &BitmapBitmap {
+    fn restore_resources(&mut self, parameters: &RendererParameters, resources: &[(Arc<str>, RetainedResource)], current_bsp: Option<Arc<str>>) -> MResult<()> {
+        self.renderer.recreate_device(parameters)?;
+        populate_default_bitmaps(self)?;
+
+        for (path, resource) in resources {
+            match resource {
+                RetainedResource::Bitmap(bitmap) => self.add_bitmap(path, bitmap.clone())?,
+                RetainedResource::Shader(shader) => self.add_shader(path, shader.clone())?,
+                RetainedResource::Sky(sky) => self.add_sky(path, sky.clone())?,
+                RetainedResource::Bsp(bsp) => self.add_bsp(path, bsp.clone())?,
+                RetainedResource::Font(font) => self.add_font(path, font.clone())?
+            }
+        }
+
+        match current_bsp {
+            Some(bsp) if self.bsps.contains_key(&bsp) => self.set_current_bsp(Some(&bsp)),
+            _ => Ok(())
+        }
+    }
+
+    fn get_default_2d(&self, default_type: DefaultType) -> &BitmapBitmap<B> {
         &self.bitmaps[&self.default_bitmaps.default_2d].bitmaps[default_type as usize]
     }
-    fn get_or_default_2d(&self, bitmap: &Option<String>, bitmap_index: usize, default_type: DefaultType) -> &BitmapBitmap {
+    fn get_or_default_2d(&self, bitmap: &Option<String>, bitmap_index: usize, default_type: DefaultType) -> &BitmapBitmap<B> {
         match bitmap.as_ref() {
-            Some(n) => &self.bitmaps[n].bitmaps[bitmap_index],
+            Some(n) => &self.bitmaps[n.as_str()].bitmaps[bitmap_index],
             None => &self.get_default_2d(default_type)
         }
     }
-    fn get_or_default_3d(&self, bitmap: &Option<String>, bitmap_index: usize, default_type: DefaultType) -> &BitmapBitmap {
+    fn get_or_default_3d(&self, bitmap: &Option<String>, bitmap_index: usize, default_type: DefaultType) -> &BitmapBitmap<B> {
         match bitmap.as_ref() {
-            Some(n) => &self.bitmaps[n].bitmaps[bitmap_index],
+            Some(n) => &self.bitmaps[n.as_str()].bitmaps[bitmap_index],
             None => &self.bitmaps[&self.default_bitmaps.default_3d].bitmaps[default_type as usize]
         }
     }
-    fn get_or_default_cubemap(&self, bitmap: &Option<String>, bitmap_index: usize, default_type: DefaultType) -> &BitmapBitmap {
+    fn get_or_default_cubemap(&self, bitmap: &Option<String>, bitmap_index: usize, default_type: DefaultType) -> &BitmapBitmap<B> {
         match bitmap.as_ref() {
-            Some(n) => &self.bitmaps[n].bitmaps[bitmap_index],
+            Some(n) => &self.bitmaps[n.as_str()].bitmaps[bitmap_index],
             None => &self.bitmaps[&self.default_bitmaps.default_cubemap].bitmaps[default_type as usize]
         }
     }
 }
 
+impl<B: RendererBackend> ResourceLookup for Renderer<B> {
+    fn bitmap_count(&self, path: &str) -> Option<usize> {
+        self.bitmaps.get(path).map(|b| b.bitmaps.len())
+    }
+
+    fn bitmap_type(&self, path: &str, bitmap: usize) -> Option<BitmapType> {
+        self.bitmaps.get(path)?.bitmaps.get(bitmap).map(|b| b.bitmap_type)
+    }
+
+    fn bitmap_sequences(&self, path: &str) -> Option<&[AddBitmapSequenceParameter]> {
+        self.bitmaps.get(path).map(|b| b.sequences.as_slice())
+    }
+
+    fn shader_type(&self, path: &str) -> Option<ShaderType> {
+        self.shaders.get(path).map(|s| s.shader_type)
+    }
+
+    fn contains(&self, kind: ResourceKind, path: &str) -> bool {
+        match kind {
+            ResourceKind::Bitmap => self.bitmaps.contains_key(path),
+            ResourceKind::Shader => self.shaders.contains_key(path),
+            ResourceKind::Geometry => self.geometries.contains_key(path),
+            ResourceKind::Sky => self.skies.contains_key(path),
+            ResourceKind::BSP => self.bsps.contains_key(path),
+            ResourceKind::Font => self.fonts.contains_key(path)
+        }
+    }
+}
+
+/// Lay out the viewports for the number of viewports in `parameters`.
+///
+/// Errors if `parameters` is invalid.
+fn make_player_viewports(parameters: &RendererParameters) -> MResult<Vec<PlayerViewport>> {
+    if parameters.resolution.height == 0 || parameters.resolution.width == 0 {
+        return Err(Error::DataError { error: "resolution has 0 on one or more dimensions".to_owned() })
+    }
+
+    let mut player_viewports = vec![PlayerViewport::default(); parameters.number_of_viewports];
+
+    match parameters.number_of_viewports {
+        1 => {
+            player_viewports[0].rel_x = 0.0;
+            player_viewports[0].rel_y = 0.0;
+            player_viewports[0].rel_width = 1.0;
+            player_viewports[0].rel_height = 1.0;
+        }
+        2 => {
+            player_viewports[0].rel_x = 0.0;
+            player_viewports[0].rel_y = 0.0;
+            player_viewports[0].rel_width = 1.0;
+            player_viewports[0].rel_height = 0.5;
+
+            player_viewports[1].rel_x = 0.0;
+            player_viewports[1].rel_y = 0.5;
+            player_viewports[1].rel_width = 1.0;
+            player_viewports[1].rel_height = 0.5;
+        }
+        3 => {
+            player_viewports[0].rel_x = 0.0;
+            player_viewports[0].rel_y = 0.0;
+            player_viewports[0].rel_width = 1.0;
+            player_viewports[0].rel_height = 0.5;
+
+            player_viewports[1].rel_x = 0.0;
+            player_viewports[1].rel_y = 0.5;
+            player_viewports[1].rel_width = 0.5;
+            player_viewports[1].rel_height = 0.5;
+
+            player_viewports[2].rel_x = 0.5;
+            player_viewports[2].rel_y = 0.5;
+            player_viewports[2].rel_width = 0.5;
+            player_viewports[2].rel_height = 0.5;
+        }
+        4 => {
+            player_viewports[0].rel_x = 0.0;
+            player_viewports[0].rel_y = 0.0;
+            player_viewports[0].rel_width = 0.5;
+            player_viewports[0].rel_height = 0.5;
+
+            player_viewports[1].rel_x = 0.5;
+            player_viewports[1].rel_y = 0.0;
+            player_viewports[1].rel_width = 0.5;
+            player_viewports[1].rel_height = 0.5;
+
+            player_viewports[2].rel_x = 0.0;
+            player_viewports[2].rel_y = 0.5;
+            player_viewports[2].rel_width = 0.5;
+            player_viewports[2].rel_height = 0.5;
+
+            player_viewports[3].rel_x = 0.5;
+            player_viewports[3].rel_y = 0.5;
+            player_viewports[3].rel_width = 0.5;
+            player_viewports[3].rel_height = 0.5;
+        }
+        n => return Err(Error::DataError { error: format!("number of viewports was set to {n}, but only 1-4 are supported") })
+    }
+
+    Ok(player_viewports)
+}
+
 #[repr(usize)]
 enum DefaultType {
     /// Describes a map with all channels set to 0x00.
@@ -381,3 +803,114 @@ enum DefaultType {
 
 /// Describes the default background color and clear color.
 const DEFAULT_BACKGROUND: [f32; 4] = [0.0f32, 0.0, 0.0, 1.0];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn test_renderer() -> Renderer<NullRenderer> {
+        Renderer::new_null(RendererParameters::default()).unwrap()
+    }
+
+    fn test_sprite(bitmap: &str) -> DrawSpriteParameter {
+        DrawSpriteParameter { bitmap: bitmap.to_owned(), ..Default::default() }
+    }
+
+    #[test]
+    fn adding_bitmap_twice_errors() {
+        let mut renderer = test_renderer();
+        renderer.add_bitmap("hud", test_bitmap()).unwrap();
+
+        let error = renderer.add_bitmap("hud", test_bitmap()).unwrap_err();
+        assert!(matches!(&error, Error::DuplicatePath { kind: ResourceKind::Bitmap, path } if path == "hud"), "{error:?}");
+        assert_eq!(error.to_string(), "Data error: hud already exists (replacing bitmaps is not yet supported)");
+    }
+
+    #[test]
+    fn drawing_unsubmitted_upload_batch_errors() {
+        let mut renderer = test_renderer();
+        renderer.begin_upload_batch().unwrap();
+        renderer.add_bitmap("hud", test_bitmap()).unwrap();
+
+        renderer.draw_sprite(0, test_sprite("hud")).unwrap();
+        assert!(renderer.draw_frame().is_err());
+
+        renderer.submit_upload_batch().unwrap();
+        renderer.draw_sprite(0, test_sprite("hud")).unwrap();
+        assert!(renderer.draw_frame().unwrap());
+    }
+
+    #[test]
+    fn streamed_bitmaps_start_at_lowest_mipmap() {
+        let mut renderer = Renderer::new_null(RendererParameters { texture_memory_budget: Some(1024), ..Default::default() }).unwrap();
+        let mut bitmap = test_bitmap();
+        bitmap.bitmaps[0].resolution = Resolution { width: 4, height: 4 };
+        bitmap.bitmaps[0].mipmap_count = 2;
+        bitmap.bitmaps[0].data = vec![0xFF; (16 + 4 + 1) * 4];
+        renderer.add_bitmap("streamed", bitmap).unwrap();
+
+        assert!(renderer.backend().calls.iter().any(|c| matches!(
+            c,
+            NullRendererCall::CreateBitmap { name, resident_mipmap: Some(2), .. } if name == "streamed[0]"
+        )));
+    }
+
+    #[test]
+    fn drawing_during_upload_batch_ignores_unused_resources() {
+        let mut renderer = test_renderer();
+        renderer.add_bitmap("hud", test_bitmap()).unwrap();
+
+        renderer.begin_upload_batch().unwrap();
+        renderer.add_bitmap("next", test_bitmap()).unwrap();
+
+        renderer.draw_sprite(0, test_sprite("hud")).unwrap();
+        assert!(renderer.draw_frame().unwrap());
+        renderer.submit_upload_batch().unwrap();
+    }
+
+    #[test]
+    fn recovering_lost_device_adds_retained_resources_again() {
+        let mut renderer = Renderer::new_null(RendererParameters { retain_resources: true, ..Default::default() }).unwrap();
+        renderer.add_bitmap("hud", test_bitmap()).unwrap();
+
+        renderer.backend_mut().device_lost = true;
+        assert!(matches!(renderer.draw_frame(), Err(Error::DeviceLost { .. })));
+        assert!(renderer.is_device_lost());
+
+        renderer.backend_mut().calls.clear();
+        renderer.recover_lost_device(RendererParameters::default()).unwrap();
+        assert!(!renderer.is_device_lost());
+
+        let created: Vec<&str> = renderer
+            .backend()
+            .calls
+            .iter()
+            .filter_map(|c| match c {
+                NullRendererCall::CreateBitmap { name, .. } => Some(name.as_str()),
+                _ => None
+            })
+            .collect();
+        assert!(matches!(renderer.backend().calls[0], NullRendererCall::RecreateDevice { .. }));
+        assert_eq!(created.iter().filter(|n| **n == "hud[0]").count(), 1);
+        assert!(created.iter().any(|n| n.starts_with("~default_2d")));
+
+        renderer.draw_sprite(0, test_sprite("hud")).unwrap();
+        assert!(renderer.draw_frame().unwrap());
+
+        // Recovering again still has everything
+        renderer.recover_lost_device(RendererParameters::default()).unwrap();
+        assert!(renderer.contains(ResourceKind::Bitmap, "hud"));
+    }
+
+    #[test]
+    fn recovering_lost_device_without_retaining_clears_resources() {
+        let mut renderer = test_renderer();
+        renderer.add_bitmap("hud", test_bitmap()).unwrap();
+        renderer.backend_mut().device_lost = true;
+
+        renderer.recover_lost_device(RendererParameters::default()).unwrap();
+        assert!(!renderer.contains(ResourceKind::Bitmap, "hud"));
+        assert!(renderer.draw_frame().unwrap());
+    }
+}
@@ -0,0 +1,126 @@
+use alloc::vec::Vec;
+use core::time::Duration;
+use crate::error::MResult;
+use crate::renderer::{AddBSPParameter, AddBSPParameterLightmapMaterial, AddBitmapBitmapParameter, AddShaderParameter, Renderer, RendererParameters};
+use crate::vertex::ModelTriangle;
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "vulkan")] {
+        /// Backend used by [`Renderer`] when one isn't specified.
+        ///
+        /// This is the Vulkan backend if the `vulkan` feature is enabled, the wgpu backend if only the `wgpu` feature
+        /// is enabled, and [`NullRenderer`](crate::renderer::NullRenderer) otherwise.
+        pub type DefaultBackend = crate::renderer::vulkan::VulkanRenderer;
+    }
+    else if #[cfg(feature = "wgpu")] {
+        pub type DefaultBackend = crate::renderer::WgpuRenderer;
+    }
+    else {
+        pub type DefaultBackend = crate::renderer::NullRenderer;
+    }
+}
+
+/// Creates device objects for resources loaded into a [`Renderer`], and draws its frames.
+///
+/// The renderer validates and keeps track of everything added to it, so backends can assume their parameters are valid.
+///
+/// This is sealed, as backends need access to the renderer's internals; it is only implemented by the backends in this
+/// crate.
+pub trait RendererBackend: sealed::Sealed + Sized + 'static {
+    /// Data for a single bitmap of a bitmap, such as an image.
+    type Bitmap;
+
+    /// Data for a shader, such as its pipeline and descriptor sets.
+    type Shader;
+
+    /// Data for a geometry.
+    type Geometry;
+
+    /// Data for a BSP, such as index buffers for each cluster.
+    type BSP;
+
+    /// Data for a single material of a BSP, such as its vertex buffers.
+    type BSPGeometry;
+
+    /// Create a bitmap, named `name` for debugging.
+    ///
+    /// If `resident_mipmap` is set, the bitmap is streamed, and only that mipmap and smaller ones are loaded into device
+    /// memory. The rest are kept in host memory by the backend until they are loaded with
+    /// [`RendererBackend::set_bitmap_resident_mipmap`].
+    fn create_bitmap(&mut self, parameter: &AddBitmapBitmapParameter, resident_mipmap: Option<u32>, name: &str) -> MResult<Self::Bitmap>;
+
+    /// Change the highest resolution mipmap of a streamed bitmap that is loaded into device memory.
+    ///
+    /// Mipmaps that are dropped are moved to host memory, and mipmaps that stay loaded are copied on the device, so each
+    /// mipmap is only ever kept in one place. This replaces the bitmap's image, so shaders using it must be rebuilt.
+    ///
+    /// Returns `Err` if `bitmap` is not streamed.
+    fn set_bitmap_resident_mipmap(&mut self, bitmap: &mut Self::Bitmap, mipmap: u32, name: &str) -> MResult<()>;
+
+    /// Estimate how much device memory a bitmap will use, in bytes.
+    fn estimate_bitmap_memory_usage(&self, parameter: &AddBitmapBitmapParameter) -> u64;
+
+    /// Create a shader.
+    ///
+    /// All bitmaps referenced by `parameter` are loaded in `renderer`.
+    fn create_shader(renderer: &mut Renderer<Self>, parameter: AddShaderParameter, name: &str) -> MResult<Self::Shader>;
+
+    /// Create a material of a BSP.
+    ///
+    /// The material's shader is loaded in `renderer`.
+    fn create_bsp_geometry(
+        renderer: &mut Renderer<Self>,
+        parameter: &AddBSPParameter,
+        material: &AddBSPParameterLightmapMaterial,
+        lightmap_index: Option<usize>,
+        name: &str
+    ) -> MResult<Self::BSPGeometry>;
+
+    /// Create a BSP.
+    ///
+    /// `cluster_surfaces` maps each cluster to each lightmap set to each material to the triangles of that material in
+    /// the cluster.
+    fn create_bsp(
+        renderer: &mut Renderer<Self>,
+        parameter: &AddBSPParameter,
+        cluster_surfaces: &[Vec<Vec<Vec<ModelTriangle>>>],
+        name: &str
+    ) -> MResult<Self::BSP>;
+
+    /// See [`Renderer::begin_upload_batch`].
+    fn begin_upload_batch(&mut self) -> MResult<()>;
+
+    /// See [`Renderer::submit_upload_batch`].
+    fn submit_upload_batch(&mut self) -> MResult<()>;
+
+    /// See [`Renderer::uploads_pending`].
+    fn uploads_pending(&mut self) -> bool;
+
+    /// See [`Renderer::wait_for_uploads`].
+    fn wait_for_uploads(&mut self) -> MResult<()>;
+
+    /// See [`Renderer::rebuild_swapchain`].
+    fn rebuild_swapchain(&mut self, parameters: &RendererParameters) -> MResult<()>;
+
+    /// See [`Renderer::get_time`].
+    fn get_time(&self) -> Duration;
+
+    /// Draw a frame of `renderer`, taking the overlays queued in each viewport.
+    ///
+    /// See [`Renderer::draw_frame`].
+    fn draw_frame(renderer: &mut Renderer<Self>) -> MResult<bool>;
+
+    /// See [`Renderer::is_device_lost`].
+    fn is_device_lost(&self) -> bool;
+
+    /// Replace the device with a new one, such as after it was lost.
+    ///
+    /// The renderer drops everything the backend created before calling this, and it adds everything again afterwards.
+    ///
+    /// See [`Renderer::recover_lost_device`].
+    fn recreate_device(&mut self, parameters: &RendererParameters) -> MResult<()>;
+}
+
+pub(crate) mod sealed {
+    pub trait Sealed {}
+}
@@ -3,9 +3,15 @@ mod geometry;
 mod shader;
 mod bsp;
 mod sky;
+mod overlay;
+mod font;
+mod streaming;
 
 pub use bitmap::*;
 pub use geometry::*;
 pub use shader::*;
 pub use bsp::*;
 pub use sky::*;
+pub use overlay::*;
+pub use font::*;
+pub use streaming::*;
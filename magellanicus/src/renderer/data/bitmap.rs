@@ -1,34 +1,53 @@
+use alloc::format;
 use alloc::vec::Vec;
-use alloc::string::String;
 use alloc::vec;
 use alloc::sync::Arc;
 use core::iter;
 use crate::error::MResult;
-use crate::renderer::{AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter, BitmapFormat, Renderer, Resolution};
-use crate::renderer::vulkan::VulkanBitmapData;
+use crate::renderer::{AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter, BitmapColorSpace, BitmapFormat, BitmapSamplerHints, BitmapType, DefaultBackend, Renderer, RendererBackend, Resolution};
 
 #[derive(Default)]
 pub struct DefaultBitmaps {
-    pub default_2d: Arc<String>,
-    pub default_3d: Arc<String>,
-    pub default_cubemap: Arc<String>,
+    pub default_2d: Arc<str>,
+    pub default_3d: Arc<str>,
+    pub default_cubemap: Arc<str>,
 }
 
-pub struct Bitmap {
-    pub bitmaps: Vec<BitmapBitmap>,
-    pub sequences: Vec<BitmapSequence>
+pub struct Bitmap<B: RendererBackend = DefaultBackend> {
+    pub bitmaps: Vec<BitmapBitmap<B>>,
+    pub sequences: Vec<AddBitmapSequenceParameter>
 }
 
-impl Bitmap {
-    pub fn load_from_parameters(renderer: &mut Renderer, parameter: AddBitmapParameter) -> MResult<Self> {
-        parameter.validate()?;
-
+impl<B: RendererBackend> Bitmap<B> {
+    pub fn load_from_parameters(renderer: &mut Renderer<B>, path: &str, parameter: AddBitmapParameter) -> MResult<Self> {
         let mut bitmaps = Vec::with_capacity(parameter.bitmaps.len());
-        for b in parameter.bitmaps {
+        for (index, b) in parameter.bitmaps.into_iter().enumerate() {
+            let name = format!("{path}[{index}]");
+            let resolution = b.resolution;
+            let bitmap_type = b.bitmap_type;
+
+            // Start at the lowest resolution if streaming; the rest is streamed in when needed.
+            let (backend, streaming) = if renderer.texture_memory_budget.is_some() && b.mipmap_count > 0 {
+                let resident_mipmap = b.mipmap_count;
+                let backend = renderer.renderer.create_bitmap(&b, Some(resident_mipmap), &name)?;
+
+                let total_memory_usage = renderer.renderer.estimate_bitmap_memory_usage(&b);
+                let total_length = b.data.len() as u64;
+                let memory_usage = (0..=b.mipmap_count)
+                    .map(|m| total_memory_usage * (total_length - b.mipmap_data_offset(m) as u64) / total_length)
+                    .collect();
+
+                (backend, Some(BitmapStreaming { resident_mipmap, lowest_mipmap: b.mipmap_count, memory_usage }))
+            }
+            else {
+                (renderer.renderer.create_bitmap(&b, None, &name)?, None)
+            };
+
             let bitmap = BitmapBitmap {
-                resolution: b.resolution,
-                bitmap_type: b.bitmap_type,
-                vulkan: VulkanBitmapData::new(&mut renderer.renderer, &b)?
+                resolution,
+                bitmap_type,
+                backend,
+                streaming
             };
             bitmaps.push(bitmap);
         }
@@ -40,36 +59,37 @@ impl Bitmap {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum BitmapType {
-    Dim2D,
-    Dim3D { depth: u32 },
-    Cubemap
-}
-
-pub struct BitmapBitmap {
-    pub vulkan: VulkanBitmapData,
+pub struct BitmapBitmap<B: RendererBackend = DefaultBackend> {
+    pub backend: B::Bitmap,
     pub resolution: Resolution,
-    pub bitmap_type: BitmapType
+    pub bitmap_type: BitmapType,
+
+    /// Set if the bitmap's mipmaps are streamed in and out.
+    pub streaming: Option<BitmapStreaming>
 }
 
-#[derive(Clone)]
-pub enum BitmapSequence {
-    Bitmap { first: usize, count: usize },
-    Sprites { sprites: Vec<BitmapSprite> }
+pub struct BitmapStreaming {
+    /// Index of the highest resolution mipmap currently loaded.
+    pub resident_mipmap: u32,
+
+    /// Index of the lowest resolution mipmap that can be loaded as the highest resolution mipmap.
+    pub lowest_mipmap: u32,
+
+    /// Estimated memory usage for each possible value of `resident_mipmap`.
+    pub memory_usage: Vec<u64>
 }
 
-#[derive(Clone)]
-pub struct BitmapSprite {
-    pub bitmap: usize,
-    pub top: f32,
-    pub left: f32,
-    pub bottom: f32,
-    pub right: f32
+impl<B: RendererBackend> Bitmap<B> {
+    /// Get the bitmap index and texture coordinates (left, top, right, bottom) of a frame of a sequence.
+    ///
+    /// Returns `None` if the sequence or frame does not exist.
+    pub fn get_frame(&self, sequence: usize, frame: usize) -> Option<(usize, [f32; 4])> {
+        self.sequences.get(sequence)?.get_frame(frame)
+    }
 }
 
-pub fn populate_default_bitmaps(renderer: &mut Renderer) -> MResult<()> {
-    fn make_add_bitmap_parameter(renderer: &mut Renderer, path: &str, bitmap_type: BitmapType) -> MResult<Arc<String>> {
+pub fn populate_default_bitmaps<B: RendererBackend>(renderer: &mut Renderer<B>) -> MResult<()> {
+    fn make_add_bitmap_parameter<B: RendererBackend>(renderer: &mut Renderer<B>, path: &str, bitmap_type: BitmapType) -> MResult<Arc<str>> {
         fn to_rgbaf32(v: [f32; 4]) -> [u8; 16] {
             let r = v[0].to_le_bytes();
             let g = v[1].to_le_bytes();
@@ -127,6 +147,10 @@ pub fn populate_default_bitmaps(renderer: &mut Renderer) -> MResult<()> {
                     bitmap_type,
                     resolution: Resolution { width: 1, height: 1 },
                     mipmap_count: 0,
+                    palette: None,
+                    color_space: BitmapColorSpace::Linear,
+                    sampler: BitmapSamplerHints::default(),
+                    generate_mipmaps: false,
                     data: black_data,
                 },
                 AddBitmapBitmapParameter {
@@ -134,6 +158,10 @@ pub fn populate_default_bitmaps(renderer: &mut Renderer) -> MResult<()> {
                     bitmap_type,
                     resolution: Resolution { width: 1, height: 1 },
                     mipmap_count: 0,
+                    palette: None,
+                    color_space: BitmapColorSpace::Linear,
+                    sampler: BitmapSamplerHints::default(),
+                    generate_mipmaps: false,
                     data: white_data,
                 },
                 AddBitmapBitmapParameter {
@@ -141,6 +169,10 @@ pub fn populate_default_bitmaps(renderer: &mut Renderer) -> MResult<()> {
                     bitmap_type,
                     resolution: Resolution { width: 1, height: 1 },
                     mipmap_count: 0,
+                    palette: None,
+                    color_space: BitmapColorSpace::Linear,
+                    sampler: BitmapSamplerHints::default(),
+                    generate_mipmaps: false,
                     data: gray_data,
                 },
                 AddBitmapBitmapParameter {
@@ -148,6 +180,10 @@ pub fn populate_default_bitmaps(renderer: &mut Renderer) -> MResult<()> {
                     bitmap_type,
                     resolution: Resolution { width: 1, height: 1 },
                     mipmap_count: 0,
+                    palette: None,
+                    color_space: BitmapColorSpace::Linear,
+                    sampler: BitmapSamplerHints::default(),
+                    generate_mipmaps: false,
                     data: blue_gray_data,
                 }
             ],
@@ -159,10 +195,8 @@ pub fn populate_default_bitmaps(renderer: &mut Renderer) -> MResult<()> {
             ],
         };
 
-        use alloc::string::ToString;
-
         renderer.add_bitmap(path, add_data)?;
-        Ok(renderer.bitmaps.get_key_value(&path.to_string()).unwrap().0.clone())
+        Ok(renderer.bitmaps.get_key_value(path).unwrap().0.clone())
     }
 
     let default_2d = make_add_bitmap_parameter(renderer, "~default_2d", BitmapType::Dim2D)?;
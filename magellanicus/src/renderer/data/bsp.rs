@@ -1,16 +1,19 @@
 use crate::error::MResult;
-use crate::renderer::vulkan::{VulkanBSPData, VulkanBSPGeometryData};
-use crate::renderer::{AddBSPParameter, AddBSPParameterLightmapMaterial, BSPData, Renderer};
+use crate::renderer::{AddBSPParameter, AddBSPParameterLightmapMaterial, BSPData, DefaultBackend, Renderer, RendererBackend};
 use crate::vertex::ModelTriangle;
+use alloc::format;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::ops::Range;
+use glam::Vec3;
 
 pub const MIN_DRAW_DISTANCE_LIMIT: f32 = 100.0;
 pub const MAX_DRAW_DISTANCE_LIMIT: f32 = 2250.0;
 
-pub struct BSP {
-    pub vulkan: VulkanBSPData,
-    pub geometries: Vec<BSPGeometry>,
+#[allow(clippy::upper_case_acronyms)]
+pub struct BSP<B: RendererBackend = DefaultBackend> {
+    pub backend: B::BSP,
+    pub geometries: Vec<BSPGeometry<B>>,
     pub bsp_data: BSPData,
     pub cluster_surfaces: Vec<Vec<usize>>,
     pub geometry_indices_sorted_by_material: Vec<usize>,
@@ -19,8 +22,8 @@ pub struct BSP {
     pub draw_distance: f32
 }
 
-impl BSP {
-    pub fn load_from_parameters(renderer: &mut Renderer, mut add_bsp_parameter: AddBSPParameter) -> MResult<Self> {
+impl<B: RendererBackend> BSP<B> {
+    pub fn load_from_parameters(renderer: &mut Renderer<B>, path: &str, mut add_bsp_parameter: AddBSPParameter) -> MResult<Self> {
         struct BSPMaterialData<'a> {
             material_reflexive_index: usize,
             material_data: &'a AddBSPParameterLightmapMaterial,
@@ -60,6 +63,7 @@ impl BSP {
         let mut min_z = f32::INFINITY;
 
         for data in add_bsp_iterator {
+            let mut bounds = [Vec3::INFINITY, Vec3::NEG_INFINITY];
             for p in &data.material_data.shader_vertices {
                 min_x = min_x.min(p.position[0]);
                 min_y = min_y.min(p.position[1]);
@@ -67,19 +71,31 @@ impl BSP {
                 max_x = max_x.max(p.position[0]);
                 max_y = max_y.max(p.position[1]);
                 max_z = max_z.max(p.position[2]);
+                bounds[0] = bounds[0].min(p.position.into());
+                bounds[1] = bounds[1].max(p.position.into());
             }
 
+            let shader = renderer.shaders.get_key_value(data.material_data.shader.as_str()).unwrap().0.clone();
+
             geometries.push(BSPGeometry {
-                vulkan: VulkanBSPGeometryData::new(renderer, &add_bsp_parameter, data.material_data, data.lightmap_bitmap_index)?,
+                backend: B::create_bsp_geometry(
+                    renderer,
+                    &add_bsp_parameter,
+                    data.material_data,
+                    data.lightmap_bitmap_index,
+                    &format!("{path} lightmap #{} material #{}", data.lightmap_reflexive_index, data.material_reflexive_index)
+                )?,
+                shader,
                 lightmap_index: data.material_data.lightmap_vertices.as_ref().and(data.lightmap_bitmap_index),
                 material_reflexive_index: data.material_reflexive_index,
-                lightmap_reflexive_index: data.lightmap_reflexive_index
+                lightmap_reflexive_index: data.lightmap_reflexive_index,
+                bounds
             })
         }
 
         let mut geometry_indices_sorted_by_material = Vec::from_iter(0usize..geometries.len());
         geometry_indices_sorted_by_material.sort_by(|a, b| {
-            geometries[*a].vulkan.shader.cmp(&geometries[*b].vulkan.shader)
+            geometries[*a].shader.cmp(&geometries[*b].shader)
         });
 
         let draw_distance = if max_x == f32::NEG_INFINITY {
@@ -89,7 +105,7 @@ impl BSP {
             let x = max_x - min_x;
             let y = max_y - min_y;
             let z = max_z - min_z;
-            (x*x+y*y+z*z).sqrt() + 10.0 // add some leeway for if the camera goes slightly outside the BSP
+            Vec3::new(x, y, z).length() + 10.0 // add some leeway for if the camera goes slightly outside the BSP
         }.clamp(MIN_DRAW_DISTANCE_LIMIT, MAX_DRAW_DISTANCE_LIMIT);
 
         let bsp_data = &mut add_bsp_parameter.bsp_data;
@@ -151,16 +167,23 @@ impl BSP {
             so_many_vectors.push(surface_ranges_filtered);
         }
 
-        let vulkan = VulkanBSPData::new(renderer, &add_bsp_parameter, &so_many_vectors)?;
+        let backend = B::create_bsp(renderer, &add_bsp_parameter, &so_many_vectors, path)?;
 
-        Ok(Self { vulkan, geometries, bsp_data: add_bsp_parameter.bsp_data, cluster_surfaces, draw_distance, geometry_indices_sorted_by_material })
+        Ok(Self { backend, geometries, bsp_data: add_bsp_parameter.bsp_data, cluster_surfaces, draw_distance, geometry_indices_sorted_by_material })
     }
 }
 
-pub struct BSPGeometry {
-    pub vulkan: VulkanBSPGeometryData,
+pub struct BSPGeometry<B: RendererBackend = DefaultBackend> {
+    pub backend: B::BSPGeometry,
+
+    /// Path to the shader of the geometry.
+    pub shader: Arc<str>,
+
     pub lightmap_index: Option<usize>,
 
     pub material_reflexive_index: usize,
-    pub lightmap_reflexive_index: usize
+    pub lightmap_reflexive_index: usize,
+
+    /// Axis-aligned bounding box of the geometry's vertices in (min, max).
+    pub bounds: [Vec3; 2]
 }
@@ -0,0 +1,248 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use crate::error::MResult;
+use crate::renderer::data::{BitmapBitmap, OverlayQuad, OverlayTexture};
+use crate::renderer::{AddFontCharacter, AddFontParameter, DefaultBackend, DrawTextParameter, Renderer, RendererBackend, TextAlignment};
+
+pub struct Font<B: RendererBackend = DefaultBackend> {
+    pub bitmap: BitmapBitmap<B>,
+    pub characters: BTreeMap<char, AddFontCharacter>,
+    pub ascending_height: u32,
+    pub descending_height: u32,
+    pub leading_height: u32
+}
+
+impl<B: RendererBackend> Font<B> {
+    /// Load a font.
+    ///
+    /// `parameter` must be validated beforehand.
+    pub fn load_from_parameters(renderer: &mut Renderer<B>, path: &str, parameter: AddFontParameter) -> MResult<Self> {
+        let bitmap = BitmapBitmap {
+            resolution: parameter.bitmap.resolution,
+            bitmap_type: parameter.bitmap.bitmap_type,
+            backend: renderer.renderer.create_bitmap(&parameter.bitmap, None, path)?,
+            streaming: None
+        };
+
+        Ok(Self {
+            bitmap,
+            characters: parameter.characters.into_iter().map(|c| (c.character, c)).collect(),
+            ascending_height: parameter.ascending_height,
+            descending_height: parameter.descending_height,
+            leading_height: parameter.leading_height
+        })
+    }
+
+    /// Height of one line in pixels.
+    pub fn line_height(&self) -> u32 {
+        self.ascending_height + self.descending_height + self.leading_height
+    }
+
+    fn advance(&self, character: char) -> i32 {
+        self.characters.get(&character).map(|c| c.advance).unwrap_or(0)
+    }
+
+    fn measure(&self, line: &[char]) -> i32 {
+        line.iter().map(|c| self.advance(*c)).sum()
+    }
+
+    /// Break text into lines.
+    ///
+    /// If `max_width` (in pixels) is set, lines are wrapped at the last whitespace that fits, or at the last character
+    /// that fits if there is no whitespace. Trailing whitespace is removed from each line.
+    fn wrap(&self, text: &str, max_width: Option<f32>) -> Vec<Vec<char>> {
+        let mut lines = Vec::new();
+
+        for paragraph in text.split('\n') {
+            let mut line: Vec<char> = Vec::new();
+            let mut width = 0i32;
+            let mut last_whitespace = None;
+
+            for c in paragraph.chars().filter(|c| *c != '\r') {
+                let advance = self.advance(c);
+
+                if let Some(max_width) = max_width {
+                    if !c.is_whitespace() && !line.is_empty() && (width + advance) as f32 > max_width {
+                        let remainder = match last_whitespace.take() {
+                            Some(w) => line.split_off(w + 1),
+                            None => Vec::new()
+                        };
+                        lines.push(line);
+                        line = remainder;
+                        width = self.measure(&line);
+                    }
+                }
+
+                if c.is_whitespace() {
+                    last_whitespace = Some(line.len());
+                }
+
+                line.push(c);
+                width += advance;
+            }
+
+            lines.push(line);
+        }
+
+        for line in &mut lines {
+            while line.last().is_some_and(|c| c.is_whitespace()) {
+                line.pop();
+            }
+        }
+
+        lines
+    }
+
+    /// Lay out text into quads.
+    ///
+    /// `text` must be validated beforehand.
+    pub fn layout_text(&self, path: &Arc<str>, text: &DrawTextParameter) -> Vec<OverlayQuad> {
+        let scale = text.scale;
+        let atlas_width = self.bitmap.resolution.width as f32;
+        let atlas_height = self.bitmap.resolution.height as f32;
+
+        let lines = self.wrap(&text.text, text.max_width.map(|w| w / scale));
+        let line_height = self.line_height() as f32 * scale;
+
+        let mut quads = Vec::with_capacity(text.text.len());
+
+        for (line_index, line) in lines.iter().enumerate() {
+            let line_width = self.measure(line) as f32 * scale;
+            let box_width = text.max_width.unwrap_or(0.0);
+            let x = text.position[0] + match text.alignment {
+                TextAlignment::Left => 0.0,
+                TextAlignment::Center => (box_width - line_width) / 2.0,
+                TextAlignment::Right => box_width - line_width
+            };
+            let baseline = text.position[1] + line_index as f32 * line_height + self.ascending_height as f32 * scale;
+
+            let mut cursor = 0i32;
+            for c in line {
+                let Some(character) = self.characters.get(c) else {
+                    continue
+                };
+
+                let [w, h] = character.bitmap_size;
+                if w > 0 && h > 0 {
+                    let [bx, by] = character.bitmap_position;
+                    let [ox, oy] = character.origin;
+
+                    quads.push(OverlayQuad {
+                        texture: OverlayTexture::Font { path: path.clone() },
+                        position: [
+                            x + (cursor - ox) as f32 * scale,
+                            baseline - oy as f32 * scale
+                        ],
+                        size: [w as f32 * scale, h as f32 * scale],
+                        texture_coords: [
+                            bx as f32 / atlas_width,
+                            by as f32 / atlas_height,
+                            (bx + w) as f32 / atlas_width,
+                            (by + h) as f32 / atlas_height
+                        ],
+                        tint: text.color,
+                        blend_mode: text.blend_mode
+                    });
+                }
+
+                cursor += character.advance;
+            }
+        }
+
+        quads
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::borrow::ToOwned;
+    use alloc::string::String;
+    use alloc::vec;
+    use crate::renderer::{test_bitmap_bitmap, AddBitmapBitmapParameter, NullRenderer, RendererParameters, Resolution};
+
+    /// Make a renderer with a font at `font`, where `a` is 10 pixels wide and spaces are 5 pixels wide.
+    fn test_renderer() -> Renderer<NullRenderer> {
+        let mut renderer = Renderer::new_null(RendererParameters::default()).unwrap();
+        renderer.add_font("font", AddFontParameter {
+            bitmap: AddBitmapBitmapParameter { resolution: Resolution { width: 16, height: 16 }, data: vec![0xFF; 16 * 16 * 4], ..test_bitmap_bitmap() },
+            characters: vec![
+                AddFontCharacter { character: 'a', advance: 10, bitmap_position: [0, 0], bitmap_size: [8, 8], origin: [0, 8] },
+                AddFontCharacter { character: ' ', advance: 5, bitmap_position: [0, 0], bitmap_size: [0, 0], origin: [0, 0] }
+            ],
+            ascending_height: 8,
+            descending_height: 2,
+            leading_height: 2
+        }).unwrap();
+        renderer
+    }
+
+    fn wrap(renderer: &Renderer<NullRenderer>, text: &str, max_width: Option<f32>) -> Vec<String> {
+        renderer.fonts["font"].wrap(text, max_width).iter().map(|line| line.iter().collect()).collect()
+    }
+
+    fn layout(renderer: &Renderer<NullRenderer>, text: DrawTextParameter) -> Vec<OverlayQuad> {
+        let (path, font) = renderer.fonts.get_key_value("font").unwrap();
+        font.layout_text(path, &DrawTextParameter { font: "font".to_owned(), ..text })
+    }
+
+    #[test]
+    fn wrap_breaks_at_last_whitespace_that_fits() {
+        let renderer = test_renderer();
+        assert_eq!(wrap(&renderer, "aa aa aa", Some(50.0)), ["aa aa", "aa"]);
+        assert_eq!(wrap(&renderer, "aa aa aa", Some(45.0)), ["aa aa", "aa"]);
+        assert_eq!(wrap(&renderer, "aa aa aa", Some(44.0)), ["aa", "aa", "aa"]);
+        assert_eq!(wrap(&renderer, "aa aa aa", None), ["aa aa aa"]);
+    }
+
+    #[test]
+    fn wrap_breaks_words_that_do_not_fit() {
+        let renderer = test_renderer();
+        assert_eq!(wrap(&renderer, "aaaaaaa", Some(30.0)), ["aaa", "aaa", "a"]);
+
+        // At least one character is always put on a line.
+        assert_eq!(wrap(&renderer, "aa", Some(1.0)), ["a", "a"]);
+    }
+
+    #[test]
+    fn wrap_starts_new_lines_at_line_feeds() {
+        let renderer = test_renderer();
+        assert_eq!(wrap(&renderer, "aa \r\n\naa\r\n", None), ["aa", "", "aa", ""]);
+        assert_eq!(wrap(&renderer, "aaaa\naa", Some(30.0)), ["aaa", "a", "aa"]);
+    }
+
+    #[test]
+    fn layout_text_aligns_each_line() {
+        let renderer = test_renderer();
+        let text = DrawTextParameter { text: "aa\na".to_owned(), position: [10.0, 20.0], max_width: Some(100.0), ..Default::default() };
+        let line_starts = |alignment| {
+            let quads = layout(&renderer, DrawTextParameter { alignment, ..text.clone() });
+            assert_eq!(quads.len(), 3);
+            [quads[0].position, quads[2].position]
+        };
+
+        // Lines are 12 pixels apart, and glyphs sit on the baseline 8 pixels down.
+        assert_eq!(line_starts(TextAlignment::Left), [[10.0, 20.0], [10.0, 32.0]]);
+        assert_eq!(line_starts(TextAlignment::Center), [[50.0, 20.0], [55.0, 32.0]]);
+        assert_eq!(line_starts(TextAlignment::Right), [[90.0, 20.0], [100.0, 32.0]]);
+
+        // Without a text box, alignment is relative to the position.
+        let quads = layout(&renderer, DrawTextParameter { max_width: None, alignment: TextAlignment::Right, ..text.clone() });
+        assert_eq!(quads[0].position, [-10.0, 20.0]);
+        assert_eq!(quads[2].position, [0.0, 32.0]);
+    }
+
+    #[test]
+    fn layout_text_scales_and_skips_invisible_characters() {
+        let renderer = test_renderer();
+        let quads = layout(&renderer, DrawTextParameter { text: "a ?a".to_owned(), scale: 2.0, ..Default::default() });
+
+        assert_eq!(quads.len(), 2);
+        assert_eq!(quads[0].position, [0.0, 0.0]);
+        assert_eq!(quads[1].position, [30.0, 0.0]);
+        assert_eq!(quads[1].size, [16.0, 16.0]);
+        assert_eq!(quads[1].texture_coords, [0.0, 0.0, 0.5, 0.5]);
+        assert!(quads[0].batches_with(&quads[1]));
+    }
+}
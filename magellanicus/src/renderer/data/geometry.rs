@@ -1,5 +1,5 @@
-use crate::renderer::vulkan::VulkanMaterialData;
+use crate::renderer::{DefaultBackend, RendererBackend};
 
-pub struct Geometry {
-    pub vulkan: VulkanMaterialData
+pub struct Geometry<B: RendererBackend = DefaultBackend> {
+    pub backend: B::Geometry
 }
@@ -0,0 +1,70 @@
+use alloc::format;
+use alloc::sync::Arc;
+use crate::error::{Error, MResult, ResourceKind};
+use crate::renderer::{DrawSpriteParameter, OverlayBlendMode, Renderer, RendererBackend};
+
+/// A single textured rectangle drawn over a viewport.
+#[derive(Clone)]
+pub struct OverlayQuad {
+    pub texture: OverlayTexture,
+
+    /// Top-left corner in HUD canvas units.
+    pub position: [f32; 2],
+
+    /// Width and height in HUD canvas units.
+    pub size: [f32; 2],
+
+    /// Texture coordinates in (left, top, right, bottom).
+    pub texture_coords: [f32; 4],
+
+    pub tint: [f32; 4],
+    pub blend_mode: OverlayBlendMode
+}
+
+#[derive(Clone, PartialEq)]
+pub enum OverlayTexture {
+    Bitmap { path: Arc<str>, bitmap_index: usize },
+    Font { path: Arc<str> }
+}
+
+impl OverlayQuad {
+    /// Return `true` if `other` can be drawn in the same draw call as this quad.
+    ///
+    /// Backends draw each run of consecutive quads that batch together at once, so text is one draw call.
+    pub fn batches_with(&self, other: &OverlayQuad) -> bool {
+        self.texture == other.texture && self.blend_mode == other.blend_mode && self.tint == other.tint
+    }
+
+    /// Resolve a sprite into a quad.
+    ///
+    /// Errors if `sprite` refers to a bitmap, sequence, or frame that does not exist.
+    pub fn from_sprite<B: RendererBackend>(renderer: &Renderer<B>, sprite: DrawSpriteParameter) -> MResult<Self> {
+        let Some((path, bitmap)) = renderer.bitmaps.get_key_value(sprite.bitmap.as_str()) else {
+            return Err(Error::missing_dependency(ResourceKind::Bitmap, sprite.bitmap.as_str(), format!("Sprite refers to bitmap {} which is not loaded", sprite.bitmap)))
+        };
+        let Some(sequence) = bitmap.sequences.get(sprite.sequence) else {
+            return Err(Error::invalid_index("sequence", sprite.sequence, bitmap.sequences.len(), format!("Sprite refers to sequence #{} of {path}, but it only has {} sequence(s)", sprite.sequence, bitmap.sequences.len())))
+        };
+
+        let frame_count = sequence.frame_count();
+        let Some((bitmap_index, texture_coords)) = sprite.sequence_frame().resolve(frame_count, renderer.get_time()).and_then(|f| sequence.get_frame(f)) else {
+            return Err(Error::from_data_error_string(format!("Sprite refers to sequence #{} of {path}, which has no frames", sprite.sequence)))
+        };
+        let Some(bitmap_bitmap) = bitmap.bitmaps.get(bitmap_index) else {
+            return Err(Error::invalid_index("bitmap", bitmap_index, bitmap.bitmaps.len(), format!("Sprite refers to bitmap #{bitmap_index} of {path} which does not exist")))
+        };
+
+        let resolution = bitmap_bitmap.resolution;
+        let texel_width = (texture_coords[2] - texture_coords[0]).abs() * resolution.width as f32;
+        let texel_height = (texture_coords[3] - texture_coords[1]).abs() * resolution.height as f32;
+
+        Ok(Self {
+            texture: OverlayTexture::Bitmap { path: path.clone(), bitmap_index },
+            position: sprite.position,
+            size: [texel_width * sprite.scale[0], texel_height * sprite.scale[1]],
+            texture_coords,
+            tint: sprite.tint,
+            blend_mode: sprite.blend_mode
+        })
+    }
+}
@@ -1,36 +1,45 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 use crate::error::MResult;
-use crate::renderer::vulkan::VulkanMaterialShaderData;
-use crate::renderer::{AddShaderData, AddShaderParameter, Renderer};
+use crate::renderer::{AddShaderData, AddShaderParameter, DefaultBackend, Renderer, RendererBackend, ShaderType};
 
-pub struct Shader {
-    pub vulkan: VulkanMaterialShaderData,
-    pub shader_type: ShaderType
+pub struct Shader<B: RendererBackend = DefaultBackend> {
+    pub backend: B::Shader,
+    pub shader_type: ShaderType,
+
+    /// All bitmaps referenced by the shader.
+    pub bitmaps: Vec<Arc<str>>,
+
+    /// Parameters the shader was loaded with, used for rebuilding the shader when its bitmaps are reloaded.
+    pub parameter: AddShaderParameter
 }
 
-impl Shader {
-    pub fn load_from_parameters(renderer: &mut Renderer, add_shader_parameter: AddShaderParameter) -> MResult<Self> {
-        let shader_type = match &add_shader_parameter.data {
-            AddShaderData::BasicShader(s) => s.shader_type,
-            AddShaderData::ShaderEnvironment(_) => ShaderType::Environment
-        };
+impl<B: RendererBackend> Shader<B> {
+    pub fn load_from_parameters(renderer: &mut Renderer<B>, path: &str, add_shader_parameter: AddShaderParameter) -> MResult<Self> {
+        let shader_type = add_shader_parameter.shader_type();
+
+        let bitmaps = match &add_shader_parameter.data {
+            AddShaderData::BasicShader(s) => [&s.bitmap].into_iter().collect::<Vec<_>>(),
+            AddShaderData::ShaderEnvironment(s) => [
+                &s.base_map,
+                &s.primary_detail_map,
+                &s.secondary_detail_map,
+                &s.micro_detail_map,
+                &s.bump_map,
+                &s.reflection_cube_map
+            ].into_iter().collect()
+        }
+            .into_iter()
+            .filter_map(|b| renderer.bitmaps.get_key_value(b.as_deref()?))
+            .map(|(path, _)| path.clone())
+            .collect();
 
-        let vulkan = VulkanMaterialShaderData::new_from_parameters(
+        let backend = B::create_shader(
             renderer,
-            add_shader_parameter
+            add_shader_parameter.clone(),
+            path
         )?;
 
-        Ok(Self { vulkan, shader_type })
+        Ok(Self { backend, shader_type, bitmaps, parameter: add_shader_parameter })
     }
 }
-
-#[derive(PartialEq, Debug, Copy, Clone)]
-pub enum ShaderType {
-    Environment,
-    Model,
-    TransparentGeneric,
-    TransparentChicago,
-    TransparentGlass,
-    TransparentMeter,
-    TransparentPlasma,
-    TransparentWater
-}
@@ -1,8 +1,7 @@
 use alloc::sync::Arc;
-use alloc::string::String;
 
 pub struct Sky {
-    pub geometry: Option<Arc<String>>,
+    pub geometry: Option<Arc<str>>,
 
     pub outdoor_fog_color: [f32; 3],
     pub outdoor_fog_maximum_density: f32,
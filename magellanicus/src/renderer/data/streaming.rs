@@ -0,0 +1,373 @@
+use alloc::format;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use glam::Vec3;
+use crate::error::{Error, MResult};
+use crate::renderer::{Renderer, RendererBackend};
+
+/// Distance in world units at which surfaces are drawn with full resolution bitmaps.
+///
+/// Each doubling of the distance past this drops one mipmap.
+pub const STREAMING_FULL_RESOLUTION_DISTANCE: f32 = 4.0;
+
+/// Maximum number of bitmaps reloaded per frame so that fast camera movement doesn't stall rendering.
+pub const STREAMING_MAX_RELOADS_PER_FRAME: usize = 8;
+
+/// Stream mipmaps of bitmaps in and out based on the distance of each viewport's camera to the surfaces of the current
+/// BSP, keeping within the texture memory budget.
+///
+/// Bitmaps used by shaders that aren't in the current BSP are reduced to their lowest resolution. Bitmaps not used by
+/// any shader are kept at full resolution.
+///
+/// If the budget can't be met by dropping mipmaps of bitmaps used by shaders, all of them are dropped to their lowest
+/// resolution.
+pub fn update_texture_residency<B: RendererBackend>(renderer: &mut Renderer<B>) -> MResult<()> {
+    let Some(budget) = renderer.texture_memory_budget else {
+        return Ok(())
+    };
+
+    let cameras: Vec<Vec3> = renderer
+        .player_viewports
+        .iter()
+        .map(|v| Vec3::from(v.camera.position))
+        .collect();
+
+    // Find how close each bitmap used in the current BSP is to any camera
+    let mut distances: BTreeMap<Arc<str>, f32> = BTreeMap::new();
+    if let Some(bsp) = renderer.current_bsp.as_ref().and_then(|b| renderer.bsps.get(b)) {
+        for geometry in &bsp.geometries {
+            let [min, max] = geometry.bounds;
+            if !min.cmple(max).all() {
+                continue
+            }
+
+            let Some(shader) = renderer.shaders.get(&geometry.shader) else {
+                continue
+            };
+
+            let distance = cameras
+                .iter()
+                .map(|c| c.distance(c.clamp(min, max)))
+                .fold(f32::INFINITY, f32::min);
+
+            for bitmap in &shader.bitmaps {
+                let closest = distances.entry(bitmap.clone()).or_insert(f32::INFINITY);
+                *closest = closest.min(distance);
+            }
+        }
+    }
+
+    struct Candidate<'a> {
+        path: &'a Arc<str>,
+        bitmap_index: usize,
+        resident_mipmap: u32,
+        desired_mipmap: u32,
+        lowest_mipmap: u32,
+        memory_usage: &'a [u64],
+        used_by_shaders: bool
+    }
+
+    impl Candidate<'_> {
+        fn target_mipmap(&self, bias: u32) -> u32 {
+            if self.used_by_shaders {
+                self.desired_mipmap.saturating_add(bias).min(self.lowest_mipmap)
+            }
+            else {
+                self.desired_mipmap
+            }
+        }
+    }
+
+    let shader_bitmaps: BTreeSet<&Arc<str>> = renderer
+        .shaders
+        .values()
+        .flat_map(|s| s.bitmaps.iter())
+        .collect();
+
+    let mut candidates = Vec::new();
+    for (path, bitmap) in &renderer.bitmaps {
+        for (bitmap_index, b) in bitmap.bitmaps.iter().enumerate() {
+            let Some(streaming) = b.streaming.as_ref() else {
+                continue
+            };
+
+            let lowest_mipmap = streaming.lowest_mipmap;
+            let used_by_shaders = shader_bitmaps.contains(path);
+            let desired_mipmap = match distances.get(path) {
+                _ if !used_by_shaders => 0,
+                Some(distance) => (((distance / STREAMING_FULL_RESOLUTION_DISTANCE).max(1.0) as u32).ilog2()).min(lowest_mipmap),
+                None => lowest_mipmap
+            };
+
+            candidates.push(Candidate {
+                path,
+                bitmap_index,
+                resident_mipmap: streaming.resident_mipmap,
+                desired_mipmap,
+                lowest_mipmap,
+                memory_usage: &streaming.memory_usage,
+                used_by_shaders
+            });
+        }
+    }
+
+    // Drop mipmaps from everything until it fits
+    let memory_usage = |bias: u32| candidates
+        .iter()
+        .map(|c| c.memory_usage[c.target_mipmap(bias) as usize])
+        .sum::<u64>();
+    let max_bias = candidates.iter().map(|c| c.lowest_mipmap).max().unwrap_or(0);
+    let bias = (0..=max_bias).find(|b| memory_usage(*b) <= budget).unwrap_or(max_bias);
+
+    let mut changes: Vec<(Arc<str>, usize, u32, bool)> = candidates
+        .iter()
+        .map(|c| (c.path.clone(), c.bitmap_index, c.target_mipmap(bias), c.resident_mipmap))
+        .filter(|(.., target, resident)| target != resident)
+        .map(|(path, bitmap_index, target, resident)| (path, bitmap_index, target, target < resident))
+        .collect();
+
+    // Free memory before using more
+    changes.sort_by_key(|(.., increases_resolution)| *increases_resolution);
+    changes.truncate(STREAMING_MAX_RELOADS_PER_FRAME);
+
+    let mut reloaded = BTreeSet::new();
+    for (path, bitmap_index, mipmap, _) in changes {
+        set_resident_mipmap(renderer, &path, bitmap_index, mipmap)?;
+        reloaded.insert(path);
+    }
+
+    rebuild_shaders_using(renderer, &reloaded)
+}
+
+/// Load a bitmap at full resolution and stop streaming it.
+///
+/// This is for bitmaps that are referenced outside of shaders, such as lightmaps.
+pub fn pin_bitmap<B: RendererBackend>(renderer: &mut Renderer<B>, path: &Arc<str>) -> MResult<()> {
+    let Some(bitmap) = renderer.bitmaps.get(path) else {
+        return Ok(())
+    };
+
+    let to_reload: Vec<usize> = bitmap
+        .bitmaps
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| b.streaming.as_ref().is_some_and(|s| s.resident_mipmap != 0))
+        .map(|(i, _)| i)
+        .collect();
+
+    for &bitmap_index in &to_reload {
+        set_resident_mipmap(renderer, path, bitmap_index, 0)?;
+    }
+
+    for b in &mut renderer.bitmaps.get_mut(path).unwrap().bitmaps {
+        b.streaming = None;
+    }
+
+    if to_reload.is_empty() {
+        return Ok(())
+    }
+
+    rebuild_shaders_using(renderer, &BTreeSet::from([path.clone()]))
+}
+
+fn set_resident_mipmap<B: RendererBackend>(renderer: &mut Renderer<B>, path: &Arc<str>, bitmap_index: usize, mipmap: u32) -> MResult<()> {
+    let name = format!("{path}[{bitmap_index}]");
+    let Some(bitmap) = renderer.bitmaps.get_mut(path).and_then(|b| b.bitmaps.get_mut(bitmap_index)) else {
+        return Err(Error::from_data_error_string(format!("Can't stream {name} because it is not loaded")))
+    };
+    let Some(streaming) = bitmap.streaming.as_mut() else {
+        return Err(Error::from_data_error_string(format!("Can't stream {name} because it is not streamed")))
+    };
+
+    renderer.renderer.set_bitmap_resident_mipmap(&mut bitmap.backend, mipmap, &name)?;
+    streaming.resident_mipmap = mipmap;
+
+    Ok(())
+}
+
+/// Shaders reference bitmap images directly, so they need rebuilt when the images are replaced.
+fn rebuild_shaders_using<B: RendererBackend>(renderer: &mut Renderer<B>, bitmaps: &BTreeSet<Arc<str>>) -> MResult<()> {
+    if bitmaps.is_empty() {
+        return Ok(())
+    }
+
+    let shaders: Vec<Arc<str>> = renderer
+        .shaders
+        .iter()
+        .filter(|(_, s)| s.bitmaps.iter().any(|b| bitmaps.contains(b)))
+        .map(|(path, _)| path.clone())
+        .collect();
+
+    for path in shaders {
+        let parameter = renderer.shaders[&path].parameter.clone();
+        let backend = B::create_shader(renderer, parameter, &path)?;
+        renderer.shaders.get_mut(&path).unwrap().backend = backend;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::borrow::ToOwned;
+    use alloc::vec;
+    use crate::renderer::*;
+    use crate::vertex::{ModelTriangle, ModelVertex};
+
+    /// 16x16 bitmap with 4 mipmaps, which use 1364, 340, 84, 20, and 4 bytes when each is the highest loaded.
+    fn streamed_bitmap() -> AddBitmapParameter {
+        AddBitmapParameter {
+            bitmaps: vec![AddBitmapBitmapParameter { resolution: Resolution { width: 16, height: 16 }, mipmap_count: 4, data: vec![0xFF; 1364], ..test_bitmap_bitmap() }],
+            ..test_bitmap()
+        }
+    }
+
+    /// Add a streamed bitmap and a shader that uses it, both at `path`.
+    fn add_shader_bitmap(renderer: &mut Renderer<NullRenderer>, path: &str) {
+        renderer.add_bitmap(path, streamed_bitmap()).unwrap();
+        renderer.add_shader(path, AddShaderParameter {
+            data: AddShaderData::BasicShader(AddShaderBasicShaderData {
+                bitmap: Some(path.to_owned()),
+                sequence: 0,
+                frame: SequenceFrame::default(),
+                shader_type: ShaderType::Environment,
+                alpha_tested: false
+            })
+        }).unwrap();
+    }
+
+    /// Make a renderer with a streaming budget of `budget` and a current BSP with a 1x1 triangle at each `(shader, x)`
+    /// of `materials`, where each shader uses a streamed bitmap at the same path.
+    fn test_renderer(budget: u64, materials: &[(&str, f32)]) -> Renderer<NullRenderer> {
+        let mut renderer = Renderer::new_null(RendererParameters { texture_memory_budget: Some(budget), ..Default::default() }).unwrap();
+
+        let vertex = |position| ModelVertex { position, normal: [0.0, 0.0, 1.0], binormal: [0.0, 1.0, 0.0], tangent: [1.0, 0.0, 0.0], texture_coords: [0.0, 0.0] };
+        let mut bsp_materials = Vec::new();
+        for &(path, x) in materials {
+            add_shader_bitmap(&mut renderer, path);
+            bsp_materials.push(AddBSPParameterLightmapMaterial {
+                shader_vertices: vec![vertex([x, 0.0, 0.0]), vertex([x + 1.0, 0.0, 0.0]), vertex([x, 1.0, 0.0])],
+                lightmap_vertices: None,
+                surfaces: vec![ModelTriangle { indices: [0, 1, 2] }],
+                shader: path.to_owned()
+            });
+        }
+
+        renderer.add_bsp("bsp", AddBSPParameter {
+            lightmap_bitmap: None,
+            lightmap_sets: vec![AddBSPParameterLightmapSet { lightmap_index: None, materials: bsp_materials }],
+            bsp_data: BSPData::default()
+        }).unwrap();
+        renderer.set_current_bsp(Some("bsp")).unwrap();
+        renderer
+    }
+
+    fn resident_mipmap(renderer: &Renderer<NullRenderer>, path: &str) -> Option<u32> {
+        renderer.bitmaps[path].bitmaps[0].streaming.as_ref().map(|s| s.resident_mipmap)
+    }
+
+    fn draw_from(renderer: &mut Renderer<NullRenderer>, position: [f32; 3]) {
+        renderer.set_camera_for_viewport(0, Camera { position, ..Default::default() });
+        assert!(renderer.draw_frame().unwrap());
+    }
+
+    fn mipmaps_set(renderer: &Renderer<NullRenderer>) -> Vec<(String, u32)> {
+        renderer
+            .backend()
+            .calls
+            .iter()
+            .filter_map(|c| match c {
+                NullRendererCall::SetBitmapResidentMipmap { name, mipmap } => Some((name.clone(), *mipmap)),
+                _ => None
+            })
+            .collect()
+    }
+
+    #[test]
+    fn distance_selects_mipmap() {
+        let mut renderer = test_renderer(u64::MAX, &[("near", 0.0)]);
+        add_shader_bitmap(&mut renderer, "elsewhere");
+        renderer.add_bitmap("unused", streamed_bitmap()).unwrap();
+        assert_eq!(resident_mipmap(&renderer, "near"), Some(4));
+
+        // One mipmap is dropped each time the distance doubles past the full resolution distance.
+        for (distance, mipmap) in [(0.0, 0), (4.0, 0), (8.0, 1), (16.0, 2), (31.0, 2), (32.0, 3), (1000.0, 4), (0.0, 0)] {
+            draw_from(&mut renderer, [0.5, 0.25, distance]);
+            assert_eq!(resident_mipmap(&renderer, "near"), Some(mipmap), "at distance {distance}");
+
+            // Bitmaps of shaders not in the BSP are kept at their lowest resolution, and bitmaps not used by shaders
+            // are kept at full resolution.
+            assert_eq!(resident_mipmap(&renderer, "elsewhere"), Some(4));
+            assert_eq!(resident_mipmap(&renderer, "unused"), Some(0));
+        }
+    }
+
+    #[test]
+    fn budget_drops_mipmaps_of_every_shader_bitmap() {
+        let mut renderer = test_renderer(u64::MAX, &[("a", 0.0), ("b", 0.0)]);
+        renderer.add_bitmap("unused", streamed_bitmap()).unwrap();
+
+        draw_from(&mut renderer, [0.0; 3]);
+        assert_eq!([resident_mipmap(&renderer, "a"), resident_mipmap(&renderer, "b")], [Some(0), Some(0)]);
+
+        // Dropping one mipmap from each shader bitmap is enough to fit.
+        renderer.texture_memory_budget = Some(1364 + 340 * 2);
+        draw_from(&mut renderer, [0.0; 3]);
+        assert_eq!([resident_mipmap(&renderer, "a"), resident_mipmap(&renderer, "b")], [Some(1), Some(1)]);
+
+        // Nothing fits, so every shader bitmap is dropped to its lowest resolution.
+        renderer.texture_memory_budget = Some(0);
+        draw_from(&mut renderer, [0.0; 3]);
+        assert_eq!([resident_mipmap(&renderer, "a"), resident_mipmap(&renderer, "b")], [Some(4), Some(4)]);
+        assert_eq!(resident_mipmap(&renderer, "unused"), Some(0));
+    }
+
+    #[test]
+    fn reloads_are_limited_and_reductions_come_first() {
+        let near = ["a0", "a1", "a2", "a3", "a4", "a5"];
+        let far = ["b0", "b1", "b2", "b3", "b4", "b5"];
+        let materials: Vec<(&str, f32)> = near.iter().map(|p| (*p, 0.0)).chain(far.iter().map(|p| (*p, 1000.0))).collect();
+        let mut renderer = test_renderer(u64::MAX, &materials);
+
+        draw_from(&mut renderer, [0.0; 3]);
+        assert!(near.iter().all(|p| resident_mipmap(&renderer, p) == Some(0)));
+        assert!(far.iter().all(|p| resident_mipmap(&renderer, p) == Some(4)));
+
+        // Twelve bitmaps change, so the six reductions are done before the first two increases.
+        renderer.backend_mut().calls.clear();
+        draw_from(&mut renderer, [1000.0, 0.0, 0.0]);
+        let mipmaps: Vec<u32> = mipmaps_set(&renderer).iter().map(|(_, mipmap)| *mipmap).collect();
+        assert_eq!(mipmaps.len(), STREAMING_MAX_RELOADS_PER_FRAME);
+        assert_eq!(mipmaps, [4, 4, 4, 4, 4, 4, 0, 0]);
+        assert!(near.iter().all(|p| resident_mipmap(&renderer, p) == Some(4)));
+
+        draw_from(&mut renderer, [1000.0, 0.0, 0.0]);
+        assert!(far.iter().all(|p| resident_mipmap(&renderer, p) == Some(0)));
+    }
+
+    #[test]
+    fn pinned_bitmaps_are_loaded_and_no_longer_streamed() {
+        let mut renderer = test_renderer(u64::MAX, &[("pinned", 0.0)]);
+        assert_eq!(resident_mipmap(&renderer, "pinned"), Some(4));
+
+        renderer.backend_mut().calls.clear();
+        let path = renderer.bitmaps.get_key_value("pinned").unwrap().0.clone();
+        pin_bitmap(&mut renderer, &path).unwrap();
+        assert_eq!(resident_mipmap(&renderer, "pinned"), None);
+        assert_eq!(renderer.backend().calls, [
+            NullRendererCall::SetBitmapResidentMipmap { name: "pinned[0]".to_owned(), mipmap: 0 },
+            NullRendererCall::CreateShader { name: "pinned".to_owned() }
+        ]);
+
+        // It stays loaded however far away the camera is.
+        draw_from(&mut renderer, [1000.0, 0.0, 0.0]);
+        assert_eq!(mipmaps_set(&renderer).len(), 1);
+
+        // Lightmaps are pinned when their BSP is added.
+        renderer.add_bitmap("lightmaps", streamed_bitmap()).unwrap();
+        renderer.add_bsp("lightmapped", AddBSPParameter { lightmap_bitmap: Some("lightmaps".to_owned()), lightmap_sets: Vec::new(), bsp_data: BSPData::default() }).unwrap();
+        assert_eq!(resident_mipmap(&renderer, "lightmaps"), None);
+    }
+}
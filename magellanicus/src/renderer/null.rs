@@ -0,0 +1,295 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::time::Duration;
+use crate::error::{Error, MResult};
+use crate::renderer::backend::sealed::Sealed;
+use crate::renderer::{AddBSPParameter, AddBSPParameterLightmapMaterial, AddBitmapBitmapParameter, AddShaderParameter, BitmapFormat, BitmapType, Renderer, RendererBackend, RendererParameters, Resolution};
+use crate::vertex::ModelTriangle;
+
+/// Backend that doesn't use a GPU and doesn't draw anything.
+///
+/// Every call made to it is recorded in [`NullRenderer::calls`], so loading and scene logic can be tested with the
+/// real [`Renderer`] API without a GPU (e.g. in CI). Loaded resources can be checked through the renderer's
+/// [`ResourceLookup`](crate::renderer::ResourceLookup) implementation.
+///
+/// Time does not pass on its own; it is advanced by [`NullRenderer::frame_time`] each time a frame is drawn.
+pub struct NullRenderer {
+    /// Every call made to the backend, in the order they were made.
+    pub calls: Vec<NullRendererCall>,
+
+    /// Time returned by [`Renderer::get_time`].
+    pub time: Duration,
+
+    /// Amount [`NullRenderer::time`] is advanced by each time a frame is drawn.
+    ///
+    /// Default = 1/60th of a second
+    pub frame_time: Duration,
+
+    /// If set, [`Renderer::draw_frame`] fails with [`Error::DeviceLost`], and [`Renderer::is_device_lost`] returns `true`.
+    ///
+    /// This is cleared by [`Renderer::recover_lost_device`].
+    pub device_lost: bool,
+
+    resolution: Resolution,
+    recording_upload_batch: bool
+}
+
+/// A call made to a [`NullRenderer`].
+#[derive(Clone, PartialEq, Debug)]
+pub enum NullRendererCall {
+    CreateBitmap {
+        name: String,
+        format: BitmapFormat,
+        bitmap_type: BitmapType,
+        resolution: Resolution,
+        mipmap_count: u32,
+
+        /// Highest resolution mipmap loaded if the bitmap is streamed.
+        resident_mipmap: Option<u32>
+    },
+    SetBitmapResidentMipmap { name: String, mipmap: u32 },
+    CreateShader { name: String },
+    CreateBSPGeometry { name: String, shader: String, vertex_count: usize, triangle_count: usize },
+    CreateBSP { name: String, cluster_count: usize },
+    BeginUploadBatch,
+    SubmitUploadBatch,
+    RebuildSwapchain { resolution: Resolution },
+    RecreateDevice { resolution: Resolution },
+    DrawFrame {
+        /// Path of the BSP that was set, if any.
+        bsp: Option<String>,
+
+        /// Number of overlay quads drawn in each viewport.
+        overlays: Vec<usize>
+    }
+}
+
+impl NullRenderer {
+    pub fn new(parameters: &RendererParameters) -> Self {
+        Self {
+            calls: Vec::new(),
+            time: Duration::ZERO,
+            frame_time: Duration::from_nanos(1_000_000_000 / 60),
+            device_lost: false,
+            resolution: parameters.resolution,
+            recording_upload_batch: false
+        }
+    }
+
+    /// Get the resolution the swapchain was last built with.
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+}
+
+impl Sealed for NullRenderer {}
+
+impl RendererBackend for NullRenderer {
+    type Bitmap = ();
+    type Shader = ();
+    type Geometry = ();
+    type BSP = ();
+    type BSPGeometry = ();
+
+    fn create_bitmap(&mut self, parameter: &AddBitmapBitmapParameter, resident_mipmap: Option<u32>, name: &str) -> MResult<Self::Bitmap> {
+        self.calls.push(NullRendererCall::CreateBitmap {
+            name: name.to_string(),
+            format: parameter.format,
+            bitmap_type: parameter.bitmap_type,
+            resolution: parameter.resolution,
+            mipmap_count: parameter.mipmap_count,
+            resident_mipmap
+        });
+        Ok(())
+    }
+
+    fn set_bitmap_resident_mipmap(&mut self, _bitmap: &mut Self::Bitmap, mipmap: u32, name: &str) -> MResult<()> {
+        self.calls.push(NullRendererCall::SetBitmapResidentMipmap { name: name.to_string(), mipmap });
+        Ok(())
+    }
+
+    fn estimate_bitmap_memory_usage(&self, parameter: &AddBitmapBitmapParameter) -> u64 {
+        parameter.loaded_data_length()
+    }
+
+    fn create_shader(renderer: &mut Renderer<Self>, _parameter: AddShaderParameter, name: &str) -> MResult<Self::Shader> {
+        renderer.renderer.calls.push(NullRendererCall::CreateShader { name: name.to_string() });
+        Ok(())
+    }
+
+    fn create_bsp_geometry(
+        renderer: &mut Renderer<Self>,
+        _parameter: &AddBSPParameter,
+        material: &AddBSPParameterLightmapMaterial,
+        _lightmap_index: Option<usize>,
+        name: &str
+    ) -> MResult<Self::BSPGeometry> {
+        renderer.renderer.calls.push(NullRendererCall::CreateBSPGeometry {
+            name: name.to_string(),
+            shader: material.shader.clone(),
+            vertex_count: material.shader_vertices.len(),
+            triangle_count: material.surfaces.len()
+        });
+        Ok(())
+    }
+
+    fn create_bsp(
+        renderer: &mut Renderer<Self>,
+        _parameter: &AddBSPParameter,
+        cluster_surfaces: &[Vec<Vec<Vec<ModelTriangle>>>],
+        name: &str
+    ) -> MResult<Self::BSP> {
+        renderer.renderer.calls.push(NullRendererCall::CreateBSP { name: name.to_string(), cluster_count: cluster_surfaces.len() });
+        Ok(())
+    }
+
+    fn begin_upload_batch(&mut self) -> MResult<()> {
+        if self.recording_upload_batch {
+            return Err(Error::from_data_error_string("An upload batch is already being recorded".to_string()))
+        }
+        self.recording_upload_batch = true;
+        self.calls.push(NullRendererCall::BeginUploadBatch);
+        Ok(())
+    }
+
+    fn submit_upload_batch(&mut self) -> MResult<()> {
+        if !self.recording_upload_batch {
+            return Err(Error::from_data_error_string("No upload batch is being recorded".to_string()))
+        }
+        self.recording_upload_batch = false;
+        self.calls.push(NullRendererCall::SubmitUploadBatch);
+        Ok(())
+    }
+
+    fn uploads_pending(&mut self) -> bool {
+        // Submitted uploads complete immediately.
+        false
+    }
+
+    fn wait_for_uploads(&mut self) -> MResult<()> {
+        Ok(())
+    }
+
+    fn rebuild_swapchain(&mut self, parameters: &RendererParameters) -> MResult<()> {
+        self.resolution = parameters.resolution;
+        self.calls.push(NullRendererCall::RebuildSwapchain { resolution: parameters.resolution });
+        Ok(())
+    }
+
+    fn get_time(&self) -> Duration {
+        self.time
+    }
+
+    fn draw_frame(renderer: &mut Renderer<Self>) -> MResult<bool> {
+        if renderer.renderer.device_lost {
+            return Err(Error::DeviceLost { backend: "Null" })
+        }
+
+        let overlays = renderer
+            .take_overlays()
+            .iter()
+            .map(|o| o.len())
+            .collect();
+
+        let null_renderer = &mut renderer.renderer;
+        null_renderer.calls.push(NullRendererCall::DrawFrame {
+            bsp: renderer.current_bsp.as_ref().map(|b| b.to_string()),
+            overlays
+        });
+        null_renderer.time += null_renderer.frame_time;
+
+        Ok(true)
+    }
+
+    fn is_device_lost(&self) -> bool {
+        self.device_lost
+    }
+
+    fn recreate_device(&mut self, parameters: &RendererParameters) -> MResult<()> {
+        self.device_lost = false;
+        self.recording_upload_batch = false;
+        self.resolution = parameters.resolution;
+        self.calls.push(NullRendererCall::RecreateDevice { resolution: parameters.resolution });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::borrow::ToOwned;
+    use alloc::vec;
+    use crate::renderer::*;
+    use crate::vertex::ModelVertex;
+
+    fn test_shader() -> AddShaderParameter {
+        AddShaderParameter {
+            data: AddShaderData::BasicShader(AddShaderBasicShaderData {
+                bitmap: Some("hud".to_owned()),
+                sequence: 0,
+                frame: SequenceFrame::default(),
+                shader_type: ShaderType::Environment,
+                alpha_tested: false
+            })
+        }
+    }
+
+    fn test_bsp() -> AddBSPParameter {
+        let vertex = |position| ModelVertex { position, normal: [0.0, 0.0, 1.0], binormal: [0.0, 1.0, 0.0], tangent: [1.0, 0.0, 0.0], texture_coords: [0.0, 0.0] };
+        AddBSPParameter {
+            lightmap_bitmap: None,
+            lightmap_sets: vec![AddBSPParameterLightmapSet {
+                lightmap_index: None,
+                materials: vec![AddBSPParameterLightmapMaterial {
+                    shader_vertices: vec![vertex([0.0, 0.0, 0.0]), vertex([1.0, 0.0, 0.0]), vertex([0.0, 1.0, 0.0])],
+                    lightmap_vertices: None,
+                    surfaces: vec![ModelTriangle { indices: [0, 1, 2] }],
+                    shader: "shader".to_owned()
+                }]
+            }],
+            bsp_data: BSPData::default()
+        }
+    }
+
+    #[test]
+    fn records_calls_in_order() {
+        let mut renderer = Renderer::new_null(RendererParameters::default()).unwrap();
+        renderer.backend_mut().calls.clear();
+
+        renderer.add_bitmap("hud", test_bitmap()).unwrap();
+        renderer.add_shader("shader", test_shader()).unwrap();
+        renderer.add_bsp("bsp", test_bsp()).unwrap();
+        renderer.set_current_bsp(Some("bsp")).unwrap();
+        renderer.draw_sprite(0, DrawSpriteParameter { bitmap: "hud".to_owned(), ..Default::default() }).unwrap();
+        assert!(renderer.draw_frame().unwrap());
+
+        assert_eq!(renderer.backend().calls, [
+            NullRendererCall::CreateBitmap {
+                name: "hud[0]".to_owned(),
+                format: BitmapFormat::A8R8G8B8,
+                bitmap_type: BitmapType::Dim2D,
+                resolution: Resolution { width: 1, height: 1 },
+                mipmap_count: 0,
+                resident_mipmap: None
+            },
+            NullRendererCall::CreateShader { name: "shader".to_owned() },
+            NullRendererCall::CreateBSPGeometry { name: "bsp lightmap #0 material #0".to_owned(), shader: "shader".to_owned(), vertex_count: 3, triangle_count: 1 },
+            NullRendererCall::CreateBSP { name: "bsp".to_owned(), cluster_count: 0 },
+            NullRendererCall::DrawFrame { bsp: Some("bsp".to_owned()), overlays: vec![1] }
+        ]);
+    }
+
+    #[test]
+    fn draw_frame_advances_time() {
+        let mut renderer = Renderer::new_null(RendererParameters::default()).unwrap();
+        renderer.backend_mut().frame_time = Duration::from_millis(10);
+
+        renderer.draw_frame().unwrap();
+        renderer.draw_frame().unwrap();
+        assert_eq!(renderer.get_time(), Duration::from_millis(20));
+
+        renderer.set_current_bsp(None).unwrap();
+        renderer.draw_frame().unwrap();
+        assert_eq!(renderer.backend().calls.last(), Some(&NullRendererCall::DrawFrame { bsp: None, overlays: vec![0] }));
+    }
+}
@@ -3,12 +3,22 @@ mod geometry;
 mod shader;
 mod bsp;
 mod sky;
+mod overlay;
+mod font;
+mod device;
+mod log;
+mod validation;
 
 pub use bitmap::*;
 pub use geometry::*;
 pub use shader::*;
 pub use bsp::*;
 pub use sky::*;
+pub use overlay::*;
+pub use font::*;
+pub use device::*;
+pub use log::*;
+pub use validation::*;
 
 /// Used for initializing a renderer.
 ///
@@ -34,10 +44,87 @@ pub struct RendererParameters {
     pub msaa: MSAA,
 
     /// Anisotropic filtering.
-    pub anisotropic_filtering: Option<f32>
+    pub anisotropic_filtering: Option<f32>,
+
+    /// Physical device to render with.
+    ///
+    /// Default = [`DeviceSelection::Automatic`]
+    pub device: DeviceSelection,
+
+    /// Approximate amount of video memory, in bytes, that bitmaps may use.
+    ///
+    /// If set, bitmaps are loaded at their lowest resolution, and higher resolution mipmaps of bitmaps used by shaders
+    /// are streamed in as the camera approaches surfaces that use them, within this budget. Bitmaps used by lightmaps
+    /// or not used by any shader are kept at full resolution. Mipmaps that aren't loaded are kept in system memory.
+    ///
+    /// Default = None (all bitmaps are always fully loaded)
+    pub texture_memory_budget: Option<u64>,
+
+    /// Light and blend in linear space rather than gamma space.
+    ///
+    /// If set, bitmaps with [`BitmapColorSpace::SRGB`] are decoded to linear space when sampled, colors are blended in
+    /// a higher precision buffer, and the result is converted back to sRGB when presented. This requires an sRGB
+    /// swapchain format.
+    ///
+    /// Default = false (classic gamma space rendering)
+    pub linear_lighting: bool,
+
+    /// Encode uncompressed 32-bit bitmaps (including 8-bit and 16-bit bitmaps, which are expanded to 32-bit) to a
+    /// block-compressed format on the CPU before uploading, reducing video memory usage.
+    ///
+    /// This has no effect if the device doesn't support block-compressed textures.
+    ///
+    /// Default = None (bitmaps are uploaded as-is)
+    pub texture_recompression: Option<TextureRecompression>,
+
+    /// Encode opaque [`BitmapFormat::P8`] bump maps to BC5 two-channel normal maps before uploading, storing only the
+    /// X and Y components of each normal; Z is reconstructed when the bump map is sampled.
+    ///
+    /// This uses a quarter of the video memory of expanding them to 32-bit. P8 bump maps with any transparent pixels
+    /// are left as-is, since BC5 has no alpha channel. This has no effect if the device doesn't support
+    /// block-compressed textures.
+    ///
+    /// Default = false
+    pub two_channel_normal_maps: bool,
+
+    /// Keep a copy of everything added to the renderer in system memory, so that
+    /// [`Renderer::recover_lost_device`](crate::renderer::Renderer::recover_lost_device) can add it all again.
+    ///
+    /// This roughly doubles the system memory used by loaded data.
+    ///
+    /// Default = false (everything must be added again after recovering from a lost device)
+    pub retain_resources: bool,
+
+    /// Enable the Khronos validation layer, and name Vulkan objects after the tags they were loaded from.
+    ///
+    /// Validation messages are sent to `log_callback`. This has a significant performance cost, and it requires the
+    /// Vulkan SDK (or the validation layer) to be installed; if it isn't, a warning is logged and rendering continues
+    /// without validation.
+    ///
+    /// Default = false
+    pub validation: bool,
+
+    /// Receives diagnostic messages from the renderer and, if `validation` is enabled, the validation layer.
+    ///
+    /// Default = None (warnings and errors are printed to stderr)
+    pub log_callback: Option<LogCallback>
+}
+
+/// Block-compressed format to encode uncompressed bitmaps to.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TextureRecompression {
+    /// Encode opaque bitmaps to DXT1 and other bitmaps to DXT5.
+    ///
+    /// This is quick to encode and uses the least memory, but it has visible artifacts on gradients and detailed alpha.
+    Fast,
+
+    /// Encode bitmaps to BC7.
+    ///
+    /// This is slower to encode, but it has much higher quality.
+    HighQuality
 }
 
-#[derive(Copy, Clone, PartialEq, Default)]
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
 pub enum MSAA {
     #[default]
     NoMSAA = 1,
@@ -56,12 +143,21 @@ impl Default for RendererParameters {
             number_of_viewports: 1,
             vsync: false,
             msaa: Default::default(),
-            anisotropic_filtering: None
+            anisotropic_filtering: None,
+            device: DeviceSelection::Automatic,
+            texture_memory_budget: None,
+            linear_lighting: false,
+            texture_recompression: None,
+            two_channel_normal_maps: false,
+            retain_resources: false,
+            validation: false,
+            log_callback: None
         }
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Resolution {
     pub width: u32,
     pub height: u32
@@ -1,14 +1,26 @@
-pub(crate) mod mipmap_iterator;
+pub mod mipmap_iterator;
+pub mod bitmap_decode;
+mod block_compression;
+mod block_encoding;
+mod mipmap_generation;
+
+pub use block_compression::*;
+pub use block_encoding::*;
+pub use mipmap_generation::*;
 
 use alloc::borrow::ToOwned;
+use alloc::vec;
 use alloc::vec::Vec;
+use alloc::string::String;
 use alloc::format;
 use core::num::NonZeroUsize;
+use core::time::Duration;
 use crate::error::{Error, MResult};
-use crate::renderer::parameters::bitmap::mipmap_iterator::{MipmapFaceIterator, MipmapType};
-use crate::renderer::Resolution;
+use crate::renderer::parameters::bitmap::mipmap_iterator::{MipmapFaceIterator, MipmapTextureIterator, MipmapType};
+use crate::renderer::{Resolution, ValidationLocation, ValidationReport};
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AddBitmapParameter {
     pub bitmaps: Vec<AddBitmapBitmapParameter>,
     pub sequences: Vec<AddBitmapSequenceParameter>
@@ -16,129 +28,475 @@ pub struct AddBitmapParameter {
 
 impl AddBitmapParameter {
     pub(crate) fn validate(&self) -> MResult<()> {
+        self.validate_with_data_lengths(|index| self.bitmaps[index].data.len())
+    }
+
+    /// Validate as if each bitmap had `data_length(bitmap_index)` bytes of data.
+    ///
+    /// This lets bitmaps whose data hasn't been read yet (e.g. in a scene package) be validated without reading it.
+    pub(crate) fn validate_with_data_lengths(&self, data_length: impl Fn(usize) -> usize) -> MResult<()> {
+        ValidationReport::check(|report| self.collect_issues(report, &data_length))
+    }
+
+    /// Check the bitmap and return every issue found.
+    ///
+    /// Errors are the same problems that [`Renderer::add_bitmap`](crate::renderer::Renderer::add_bitmap) rejects.
+    /// Warnings are for suspicious data that can still be added, such as empty sequences and bitmaps that no sequence
+    /// uses.
+    pub fn validation_report(&self) -> ValidationReport {
+        ValidationReport::collect(|report| self.collect_issues(report, &|index| self.bitmaps[index].data.len()))
+    }
+
+    fn collect_issues(&self, report: &mut ValidationReport, data_length: &dyn Fn(usize) -> usize) -> MResult<()> {
         if self.sequences.is_empty() {
-            return Err(Error::from_data_error_string("Bitmap has no sequences!".to_owned()))
+            report.error(ValidationLocation::default(), Error::from_data_error_string("Bitmap has no sequences!".to_owned()))?;
         }
         if self.bitmaps.is_empty() {
-            return Err(Error::from_data_error_string("Bitmap has no bitmaps!".to_owned()))
+            report.error(ValidationLocation::default(), Error::from_data_error_string("Bitmap has no bitmaps!".to_owned()))?;
         }
 
-        let invalid_sequence_error = self.sequences
-            .iter()
-            .enumerate()
-            .find_map(|(sequence_index, sequence)| {
-                match sequence {
-                    // Find invalid bitmap ranges
-                    AddBitmapSequenceParameter::Bitmap { count, .. } if *count == 0 => None,
-                    AddBitmapSequenceParameter::Bitmap { count, first } => first
-                        .checked_add(*count - 1)
-                        .and_then(|count| self.bitmaps.get(*first..=count))
-                        .is_none()
-                        .then(|| format!("Sequence {sequence_index} has an invalid range {first}..({first}+{count}); only {} bitmap(s)", self.bitmaps.len())),
-
-                    // Find invalid sprite indices
-                    AddBitmapSequenceParameter::Sprites { sprites } => sprites
-                        .iter()
-                        .enumerate()
-                        .find_map(|(sprite_index, BitmapSprite { bitmap, .. })| {
-                            let Some(b) = self.bitmaps.get(*bitmap) else {
-                                return Some(format!("Sprite {sprite_index} of sequence {sequence_index} refers to bitmap {bitmap} which is not a valid index"))
-                            };
-                            if b.bitmap_type != BitmapType::Dim2D {
-                                return Some(format!("Sprite {sprite_index} of sequence {sequence_index} refers to bitmap {bitmap} which is not a 2D texture"))
-                            }
-                            None
-                        }),
-                }
-            });
+        let mut bitmap_used = vec![false; self.bitmaps.len()];
+        for (sequence_index, sequence) in self.sequences.iter().enumerate() {
+            let location = ValidationLocation { sequence: Some(sequence_index), ..Default::default() };
 
-        if let Some(error) = invalid_sequence_error {
-            return Err(Error::DataError { error })
-        }
+            match sequence {
+                AddBitmapSequenceParameter::Bitmap { count, .. } if *count == 0 => {
+                    report.warning(location, || Error::from_data_error_string(format!("Sequence {sequence_index} has no bitmaps")));
+                },
 
-        let invalid_bitmap_error = self.bitmaps
-            .iter()
-            .enumerate()
-            .find_map(|(bitmap_index, bitmap)| {
-                let Resolution { width, height } = bitmap.resolution;
-                let reported_mipmap_count = bitmap.mipmap_count;
-
-                let (Some(width_nz), Some(height_nz)) = (NonZeroUsize::new(width as usize), NonZeroUsize::new(height as usize)) else {
-                    return Some(format!("Bitmap #{bitmap_index} has 0 on one or more dimensions ({width}x{height})"))
-                };
-                if bitmap.data.is_empty() {
-                    return Some(format!("Bitmap #{bitmap_index} has no pixel data"))
-                }
+                // Find invalid bitmap ranges
+                AddBitmapSequenceParameter::Bitmap { count, first } => {
+                    match first.checked_add(*count - 1).and_then(|last| bitmap_used.get_mut(*first..=last)) {
+                        Some(used) => used.fill(true),
+                        None => report.error(location, Error::from_data_error_string(format!("Sequence {sequence_index} has an invalid range {first}..({first}+{count}); only {} bitmap(s)", self.bitmaps.len())))?
+                    }
+                },
 
-                // Block length
-                let block_length = NonZeroUsize::new(bitmap.format.block_pixel_length()).unwrap();
-
-                // Get mipmap type
-                let mipmap_type = match bitmap.bitmap_type {
-                    BitmapType::Dim2D => MipmapType::TwoDimensional,
-                    BitmapType::Dim3D { depth } => match NonZeroUsize::new(depth as usize) {
-                        Some(n) => MipmapType::ThreeDimensional(n),
-                        None => return Some(format!("Bitmap #{bitmap_index} has a depth of 0"))
-                    },
-                    BitmapType::Cubemap => MipmapType::Cubemap
-                };
-
-                let highest_dimension = width.max(height).max(match bitmap.bitmap_type { BitmapType::Dim3D { depth } => depth, _ => 1 });
-                let log_of_highest_dim = highest_dimension.ilog2();
-                let highest_possible_mipmap_count = if highest_dimension == (1 << log_of_highest_dim) {
-                    log_of_highest_dim - 0
-                }
-                else {
-                    log_of_highest_dim + 1
-                };
+                AddBitmapSequenceParameter::Sprites { sprites } if sprites.is_empty() => {
+                    report.warning(location, || Error::from_data_error_string(format!("Sequence {sequence_index} has no sprites")));
+                },
 
-                if reported_mipmap_count > highest_possible_mipmap_count {
-                    return Some(format!("Bitmap #{bitmap_index} ({width}x{height}) reports a mipmap count of {reported_mipmap_count}, but the highest mipmap count possible is {highest_possible_mipmap_count}"))
+                // Find invalid sprite indices
+                AddBitmapSequenceParameter::Sprites { sprites } => {
+                    for (sprite_index, BitmapSprite { bitmap, .. }) in sprites.iter().enumerate() {
+                        let location = ValidationLocation { bitmap: Some(*bitmap), ..location };
+                        let Some(b) = self.bitmaps.get(*bitmap) else {
+                            report.error(location, Error::invalid_index("bitmap", *bitmap, self.bitmaps.len(), format!("Sprite {sprite_index} of sequence {sequence_index} refers to bitmap {bitmap} which is not a valid index")))?;
+                            continue
+                        };
+                        bitmap_used[*bitmap] = true;
+                        if b.bitmap_type != BitmapType::Dim2D {
+                            report.error(location, Error::from_data_error_string(format!("Sprite {sprite_index} of sequence {sequence_index} refers to bitmap {bitmap} which is not a 2D texture")))?;
+                        }
+                    }
                 }
+            }
+        }
 
-                let mipmaps = MipmapFaceIterator::new(
-                    width_nz, height_nz, mipmap_type, block_length, Some(reported_mipmap_count as usize)
-                );
-
-                let bytes_per_block = bitmap.format.block_byte_size();
-                let Some((block_count, bytes_count)) = mipmaps
-                    .map(|b| (b.block_count as u64, b.block_count as u64 * bytes_per_block as u64))
-                    .reduce(|a, b| (a.0 + b.0, a.1 + b.1))
-                    .and_then(|(a,b)| Some((usize::try_from(a).ok()?, usize::try_from(b).ok()?)))
-                else {
-                    return Some(format!("Bitmap #{bitmap_index} can't get block count"))
-                };
-
-                let actual_length = bitmap.data.len();
-                if bytes_count != actual_length {
-                    return Some(format!("Bitmap #{bitmap_index} ({width}x{height}) has an incorrect number of bytes (expected {bytes_count} ({block_count} * {bytes_per_block}), got {actual_length})"))
-                }
+        for (bitmap_index, bitmap) in self.bitmaps.iter().enumerate() {
+            let location = ValidationLocation { bitmap: Some(bitmap_index), ..Default::default() };
+            if let Err(error) = bitmap.validate_with_data_length(bitmap_index, data_length(bitmap_index)) {
+                report.error(location, error)?;
+            }
+            if !bitmap_used[bitmap_index] {
+                report.warning(location, || Error::from_data_error_string(format!("Bitmap #{bitmap_index} is not used by any sequence")));
+            }
+        }
 
-                None
-            });
+        Ok(())
+    }
+}
 
-        if let Some(error) = invalid_bitmap_error {
-            return Err(Error::DataError { error })
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BitmapType {
+    Dim2D,
+    Dim3D { depth: u32 },
+    Cubemap
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AddBitmapSequenceParameter {
+    Bitmap { first: usize, count: usize },
+    Sprites { sprites: Vec<BitmapSprite> }
+}
+
+impl AddBitmapSequenceParameter {
+    /// Get the number of frames in the sequence.
+    pub fn frame_count(&self) -> usize {
+        match self {
+            Self::Bitmap { count, .. } => *count,
+            Self::Sprites { sprites } => sprites.len()
         }
+    }
 
-        Ok(())
+    /// Get the bitmap index and texture coordinates (left, top, right, bottom) of a frame.
+    ///
+    /// Returns `None` if the frame does not exist.
+    pub fn get_frame(&self, frame: usize) -> Option<(usize, [f32; 4])> {
+        match self {
+            Self::Bitmap { first, count } => (frame < *count).then(|| (first + frame, [0.0, 0.0, 1.0, 1.0])),
+            Self::Sprites { sprites } => sprites.get(frame).map(|s| (s.bitmap, [s.left, s.top, s.right, s.bottom]))
+        }
     }
 }
 
-pub use super::super::data::BitmapSequence as AddBitmapSequenceParameter;
-pub use super::super::data::BitmapSprite;
-pub use super::super::data::BitmapType;
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BitmapSprite {
+    pub bitmap: usize,
+    pub top: f32,
+    pub left: f32,
+    pub bottom: f32,
+    pub right: f32
+}
+
+/// Selects a frame of a bitmap sequence.
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SequenceFrame {
+    /// Use the frame at the given index.
+    Index(usize),
+
+    /// Cycle through each frame of the sequence at the given rate, looping back to the first frame at the end.
+    ///
+    /// This is driven by [`Renderer::get_time`](crate::renderer::Renderer::get_time).
+    Animated { frames_per_second: f32 }
+}
+
+impl Default for SequenceFrame {
+    fn default() -> Self {
+        Self::Index(0)
+    }
+}
+
+impl SequenceFrame {
+    /// Get the frame index for a sequence with `frame_count` frames at the given time.
+    ///
+    /// Returns `None` if `frame_count` is 0.
+    pub fn resolve(self, frame_count: usize, time: Duration) -> Option<usize> {
+        if frame_count == 0 {
+            return None
+        }
+
+        match self {
+            Self::Index(index) => Some(index % frame_count),
+            Self::Animated { frames_per_second } => {
+                let frame = (time.as_secs_f64() * frames_per_second as f64) as u64;
+                Some((frame % frame_count as u64) as usize)
+            }
+        }
+    }
+
+    pub(crate) fn validation_error(self, frame_count: usize) -> Option<String> {
+        if frame_count == 0 {
+            return Some("sequence has no frames".to_owned())
+        }
+
+        match self {
+            Self::Index(index) if index >= frame_count => Some(format!("frame #{index} is out of bounds (sequence has {frame_count} frame(s))")),
+            Self::Animated { frames_per_second } if !(frames_per_second.is_finite() && frames_per_second >= 0.0) => Some(format!("invalid frame rate {frames_per_second}")),
+            _ => None
+        }
+    }
+}
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AddBitmapBitmapParameter {
     pub format: BitmapFormat,
     pub bitmap_type: BitmapType,
     pub resolution: Resolution,
     pub mipmap_count: u32,
+    #[cfg_attr(feature = "serde", serde(with = "serde_bytes"))]
     pub data: Vec<u8>,
+
+    /// Palette for [`BitmapFormat::P8`] bitmaps, in RGBA order.
+    ///
+    /// If set, this must have exactly 256 entries. Otherwise, the built-in Halo bump map palette is used.
+    ///
+    /// This must be `None` for all other formats.
+    pub palette: Option<Vec<[u8; 4]>>,
+
+    /// Color space of the bitmap's data.
+    ///
+    /// This determines whether the bitmap is decoded to linear space when sampled with
+    /// [`RendererParameters::linear_lighting`](crate::renderer::RendererParameters::linear_lighting), and whether
+    /// generated mipmaps are averaged in linear space. Color maps should be set to [`BitmapColorSpace::SRGB`].
+    pub color_space: BitmapColorSpace,
+
+    /// How the bitmap is sampled by materials.
+    ///
+    /// Bitmaps with identical hints share a sampler.
+    pub sampler: BitmapSamplerHints,
+
+    /// Generate any mipmaps past `mipmap_count` down to 1x1 when loading.
+    ///
+    /// Uncompressed formats are generated on the GPU where supported. Otherwise, mipmaps are generated on the CPU with a
+    /// box filter, and block-compressed bitmaps are decompressed to do so.
+    pub generate_mipmaps: bool,
+}
+
+impl AddBitmapBitmapParameter {
+    /// Get the number of mipmaps the bitmap will have once loaded, including generated mipmaps.
+    pub fn loaded_mipmap_count(&self) -> u32 {
+        if !self.generate_mipmaps {
+            return self.mipmap_count
+        }
+
+        let highest_dimension = self.resolution.width
+            .max(self.resolution.height)
+            .max(match self.bitmap_type { BitmapType::Dim3D { depth } => depth, _ => 1 })
+            .max(1);
+
+        self.mipmap_count.max(highest_dimension.ilog2())
+    }
+
+    /// Returns `true` if this is a [`BitmapFormat::P8`] bitmap where every pixel is opaque.
+    ///
+    /// Such bitmaps can be stored as a two-channel normal map without losing anything.
+    pub(crate) fn is_opaque_p8(&self) -> bool {
+        let palette = self.palette.as_deref();
+        self.format == BitmapFormat::P8 && self.data.iter().all(|p| decode_p8_with_palette_to_a8r8g8b8le(*p, palette)[3] == 0xFF)
+    }
+
+    /// Get the offset in bytes of the given mipmap in `data`.
+    ///
+    /// The bitmap must be validated beforehand, and `mipmap` must not exceed `mipmap_count`.
+    pub(crate) fn mipmap_data_offset(&self, mipmap: u32) -> usize {
+        let mipmap_type = match self.bitmap_type {
+            BitmapType::Dim2D => MipmapType::TwoDimensional,
+            BitmapType::Dim3D { depth } => MipmapType::ThreeDimensional(NonZeroUsize::new(depth as usize).unwrap()),
+            BitmapType::Cubemap => MipmapType::Cubemap
+        };
+
+        MipmapTextureIterator::new(
+            NonZeroUsize::new(self.resolution.width as usize).unwrap(),
+            NonZeroUsize::new(self.resolution.height as usize).unwrap(),
+            mipmap_type,
+            NonZeroUsize::new(self.format.block_pixel_length()).unwrap(),
+            Some(self.mipmap_count as usize)
+        )
+            .find(|m| m.mipmap_index == mipmap as usize)
+            .map(|m| m.block_offset * self.format.block_byte_size())
+            .unwrap_or(self.data.len())
+    }
+
+    /// Estimate the length of `data` once any mipmaps past `mipmap_count` are generated.
+    ///
+    /// See [`AddBitmapBitmapParameter::loaded_mipmap_count`].
+    pub(crate) fn loaded_data_length(&self) -> u64 {
+        let depth = match self.bitmap_type { BitmapType::Dim3D { depth } => depth, _ => 1 };
+        let pixel_count = |mipmap_count: u32| (0..=mipmap_count)
+            .map(|m| [self.resolution.width, self.resolution.height, depth].map(|d| (d >> m).max(1) as u64).iter().product::<u64>())
+            .sum::<u64>();
+
+        self.data.len() as u64 * pixel_count(self.loaded_mipmap_count()) / pixel_count(self.mipmap_count)
+    }
+
+    pub(crate) fn validate(&self, bitmap_index: usize) -> MResult<()> {
+        self.validate_with_data_length(bitmap_index, self.data.len())
+    }
+
+    fn validate_with_data_length(&self, bitmap_index: usize, actual_length: usize) -> MResult<()> {
+        let Resolution { width, height } = self.resolution;
+        let reported_mipmap_count = self.mipmap_count;
+        let dimensions_error = |error: String| Err(Error::InvalidBitmapDimensions { bitmap_index, error });
+
+        let (Some(width_nz), Some(height_nz)) = (NonZeroUsize::new(width as usize), NonZeroUsize::new(height as usize)) else {
+            return dimensions_error(format!("Bitmap #{bitmap_index} has 0 on one or more dimensions ({width}x{height})"))
+        };
+        if actual_length == 0 {
+            return dimensions_error(format!("Bitmap #{bitmap_index} has no pixel data"))
+        }
+        if let Some(palette) = self.palette.as_ref() {
+            if self.format != BitmapFormat::P8 {
+                return Err(Error::from_data_error_string(format!("Bitmap #{bitmap_index} has a palette, but it is {:?}, not {:?}", self.format, BitmapFormat::P8)))
+            }
+            if palette.len() != 256 {
+                return Err(Error::from_data_error_string(format!("Bitmap #{bitmap_index} has a palette with {} entries, expected 256", palette.len())))
+            }
+        }
+
+        if let Some(error) = self.sampler.validation_error() {
+            return Err(Error::from_data_error_string(format!("Bitmap #{bitmap_index} has invalid sampler hints: {error}")))
+        }
+
+        // Block length
+        let block_length = NonZeroUsize::new(self.format.block_pixel_length()).unwrap();
+
+        // Get mipmap type
+        let mipmap_type = match self.bitmap_type {
+            BitmapType::Dim2D => MipmapType::TwoDimensional,
+            BitmapType::Dim3D { depth } => match NonZeroUsize::new(depth as usize) {
+                Some(n) => MipmapType::ThreeDimensional(n),
+                None => return dimensions_error(format!("Bitmap #{bitmap_index} has a depth of 0"))
+            },
+            BitmapType::Cubemap => MipmapType::Cubemap
+        };
+
+        let highest_dimension = width.max(height).max(match self.bitmap_type { BitmapType::Dim3D { depth } => depth, _ => 1 });
+        let log_of_highest_dim = highest_dimension.ilog2();
+        let highest_possible_mipmap_count = if highest_dimension == (1 << log_of_highest_dim) {
+            log_of_highest_dim - 0
+        }
+        else {
+            log_of_highest_dim + 1
+        };
+
+        if reported_mipmap_count > highest_possible_mipmap_count {
+            return dimensions_error(format!("Bitmap #{bitmap_index} ({width}x{height}) reports a mipmap count of {reported_mipmap_count}, but the highest mipmap count possible is {highest_possible_mipmap_count}"))
+        }
+
+        let mipmaps = MipmapFaceIterator::new(
+            width_nz, height_nz, mipmap_type, block_length, Some(reported_mipmap_count as usize)
+        );
+
+        let bytes_per_block = self.format.block_byte_size();
+        let Some((block_count, bytes_count)) = mipmaps
+            .map(|b| (b.block_count as u64, b.block_count as u64 * bytes_per_block as u64))
+            .reduce(|a, b| (a.0 + b.0, a.1 + b.1))
+            .and_then(|(a,b)| Some((usize::try_from(a).ok()?, usize::try_from(b).ok()?)))
+        else {
+            return dimensions_error(format!("Bitmap #{bitmap_index} can't get block count"))
+        };
+
+        if bytes_count != actual_length {
+            return dimensions_error(format!("Bitmap #{bitmap_index} ({width}x{height}) has an incorrect number of bytes (expected {bytes_count} ({block_count} * {bytes_per_block}), got {actual_length})"))
+        }
+
+        Ok(())
+    }
 }
 
+/// Make a 1x1 white [`BitmapFormat::A8R8G8B8`] 2D bitmap with no mipmaps, for tests.
+#[cfg(test)]
+pub(crate) fn test_bitmap_bitmap() -> AddBitmapBitmapParameter {
+    AddBitmapBitmapParameter {
+        format: BitmapFormat::A8R8G8B8,
+        bitmap_type: BitmapType::Dim2D,
+        resolution: Resolution { width: 1, height: 1 },
+        mipmap_count: 0,
+        data: vec![0xFF; 4],
+        palette: None,
+        color_space: BitmapColorSpace::default(),
+        sampler: BitmapSamplerHints::default(),
+        generate_mipmaps: false
+    }
+}
+
+/// Make a bitmap with one [`test_bitmap_bitmap`] in one sequence, for tests.
+#[cfg(test)]
+pub(crate) fn test_bitmap() -> AddBitmapParameter {
+    AddBitmapParameter {
+        bitmaps: vec![test_bitmap_bitmap()],
+        sequences: vec![AddBitmapSequenceParameter::Bitmap { first: 0, count: 1 }]
+    }
+}
+
+/// Describes how the color channels of a bitmap are encoded.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BitmapColorSpace {
+    /// Color maps (e.g. base maps, detail maps, and sprites), which are sRGB-encoded.
+    ///
+    /// These are decoded to linear space when sampled if linear lighting is enabled.
+    SRGB,
+
+    /// Non-color data (e.g. bump maps, alpha masks, and vectors), which is always sampled as-is.
+    ///
+    /// This is the default, as decoding non-color data as sRGB corrupts it, while sampling color maps as-is only makes
+    /// them look like they did without linear lighting.
+    #[default]
+    Linear
+}
+
+/// Describes how a bitmap is sampled.
 #[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BitmapSamplerHints {
+    /// How texture coordinates outside of 0-1 are handled on the U, V, and W axes, respectively.
+    pub address_mode: [BitmapAddressMode; 3],
+
+    /// Filter used when magnifying, minifying, and blending between mipmaps.
+    pub filter: BitmapFilter,
+
+    /// Bias added to the mipmap level of detail when sampling.
+    ///
+    /// Positive values make the bitmap blurrier, and negative values make it sharper. This is clamped to the device's
+    /// limits.
+    pub mip_lod_bias: f32,
+
+    /// Maximum anisotropic filtering, if any.
+    ///
+    /// If `None`, [`RendererParameters::anisotropic_filtering`](crate::renderer::RendererParameters::anisotropic_filtering)
+    /// is used. This is clamped to the device's limits, and it has no effect on [`BitmapFilter::Point`].
+    pub max_anisotropy: Option<f32>
+}
+
+impl Default for BitmapSamplerHints {
+    fn default() -> Self {
+        Self {
+            address_mode: [BitmapAddressMode::Repeat; 3],
+            filter: BitmapFilter::Linear,
+            mip_lod_bias: 0.0,
+            max_anisotropy: None
+        }
+    }
+}
+
+impl BitmapSamplerHints {
+    /// Get hints for bitmaps that should not repeat, such as HUD sprites and decals.
+    pub fn clamped() -> Self {
+        Self {
+            address_mode: [BitmapAddressMode::ClampToEdge; 3],
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn validation_error(&self) -> Option<String> {
+        if !self.mip_lod_bias.is_finite() {
+            return Some(format!("invalid mip LOD bias {}", self.mip_lod_bias))
+        }
+        if let Some(n) = self.max_anisotropy {
+            if !(n.is_finite() && n >= 1.0) {
+                return Some(format!("invalid max anisotropy {n}"))
+            }
+        }
+        None
+    }
+}
+
+/// Describes how texture coordinates outside of 0-1 are handled.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BitmapAddressMode {
+    /// Tile the bitmap.
+    #[default]
+    Repeat,
+
+    /// Tile the bitmap, flipping it every other repetition.
+    MirroredRepeat,
+
+    /// Use the pixels on the nearest edge.
+    ClampToEdge
+}
+
+/// Describes how pixels are filtered when sampled.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BitmapFilter {
+    /// Use the nearest pixel and mipmap; this is suitable for pixel art.
+    Point,
+
+    /// Blend between the nearest pixels and mipmaps.
+    #[default]
+    Linear
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BitmapFormat {
     DXT1,
     DXT3,
@@ -156,7 +514,34 @@ pub enum BitmapFormat {
     A8Y8,
     P8,
 
-    R32G32B32A32SFloat
+    R32G32B32A32SFloat,
+
+    /// Single channel block-compressed format (red)
+    BC4,
+
+    /// Two channel block-compressed format (red and green), typically used for normal maps
+    BC5,
+
+    R16G16B16A16SFloat,
+    R8G8,
+
+    /// sRGB variant of [`BitmapFormat::DXT1`]
+    DXT1SRGB,
+
+    /// sRGB variant of [`BitmapFormat::DXT3`]
+    DXT3SRGB,
+
+    /// sRGB variant of [`BitmapFormat::DXT5`]
+    DXT5SRGB,
+
+    /// sRGB variant of [`BitmapFormat::BC7`]
+    BC7SRGB,
+
+    /// sRGB variant of [`BitmapFormat::A8R8G8B8`]
+    A8R8G8B8SRGB,
+
+    /// sRGB variant of [`BitmapFormat::X8R8G8B8`]
+    X8R8G8B8SRGB
 }
 impl BitmapFormat {
     pub fn block_pixel_length(self) -> usize {
@@ -176,6 +561,16 @@ impl BitmapFormat {
             Self::A8Y8 => 1,
             Self::P8 => 1,
             Self::R32G32B32A32SFloat => 1,
+            Self::BC4 => 4,
+            Self::BC5 => 4,
+            Self::R16G16B16A16SFloat => 1,
+            Self::R8G8 => 1,
+            Self::DXT1SRGB => 4,
+            Self::DXT3SRGB => 4,
+            Self::DXT5SRGB => 4,
+            Self::BC7SRGB => 4,
+            Self::A8R8G8B8SRGB => 1,
+            Self::X8R8G8B8SRGB => 1,
         }
     }
     pub fn block_byte_size(self) -> usize {
@@ -195,10 +590,44 @@ impl BitmapFormat {
             Self::AY8 => 1,
             Self::A8Y8 => 2,
             Self::P8 => 1,
+            Self::BC4 => 8,
+            Self::BC5 => 16,
+            Self::R16G16B16A16SFloat => 2*4,
+            Self::R8G8 => 2,
+            Self::DXT1SRGB => 8,
+            Self::DXT3SRGB => 16,
+            Self::DXT5SRGB => 16,
+            Self::BC7SRGB => 16,
+            Self::A8R8G8B8SRGB => 4,
+            Self::X8R8G8B8SRGB => 4,
         }
     }
+
+    /// Return `true` if the color channels are sRGB-encoded.
+    pub fn is_srgb(self) -> bool {
+        matches!(self, Self::DXT1SRGB | Self::DXT3SRGB | Self::DXT5SRGB | Self::BC7SRGB | Self::A8R8G8B8SRGB | Self::X8R8G8B8SRGB)
+    }
+
+    /// Return `true` if the format is block-compressed.
+    pub fn is_block_compressed(self) -> bool {
+        self.block_pixel_length() != 1
+    }
+}
+
+/// Decode a P8 pixel with an RGBA palette, or the built-in Halo bump map palette if `palette` is `None`.
+///
+/// `palette` must have 256 entries.
+pub fn decode_p8_with_palette_to_a8r8g8b8le(p8: u8, palette: Option<&[[u8; 4]]>) -> [u8; 4] {
+    match palette {
+        Some(palette) => {
+            let [r, g, b, a] = palette[p8 as usize];
+            [b, g, r, a]
+        },
+        None => decode_p8_to_a8r8g8b8le(p8)
+    }
 }
 
+/// Decode a P8 pixel with the built-in Halo bump map palette.
 pub fn decode_p8_to_a8r8g8b8le(p8: u8) -> [u8; 4] {
     const P8_PALETTE: [[u8; 4]; 256] = [
         [0xCC, 0x19, 0x7A, 0xFF],
@@ -0,0 +1,324 @@
+//! GPU-independent decoding of bitmaps into plain RGBA images.
+//!
+//! This interprets bitmap data exactly as the renderer does when uploading it, including how monochrome and palettized
+//! formats are expanded and how cubemap faces are ordered, so it can be used for previewing and converting bitmaps.
+
+use alloc::format;
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+use crate::error::{Error, MResult};
+use super::mipmap_iterator::{MipmapFaceIterator, MipmapType};
+use super::{decode_block_compressed_to_r8g8b8a8, decode_p8_with_palette_to_a8r8g8b8le, AddBitmapBitmapParameter, BitmapFormat, BitmapType};
+
+/// A single decoded face of a single mipmap of a bitmap.
+#[derive(Clone, Debug)]
+pub struct DecodedBitmapFace<T> {
+    /// Mipmap index, where 0 is the base map.
+    pub mipmap_index: usize,
+
+    /// Face index.
+    ///
+    /// For cubemaps, this is 0-5 in +X, -X, +Y, -Y, +Z, -Z order (the order the renderer uploads them as), for 3D
+    /// textures, this is the depth slice, and for 2D textures, this is always 0.
+    pub face_index: usize,
+
+    /// Width in pixels.
+    pub width: usize,
+
+    /// Height in pixels.
+    pub height: usize,
+
+    /// Pixels in row-major order, with each pixel being red, green, blue, and alpha.
+    pub pixels: Vec<[T; 4]>
+}
+
+/// Decodes a single face of a bitmap with the given width, height, and data, returning `None` if the format is not
+/// supported.
+type FaceDecoder<T> = fn(&AddBitmapBitmapParameter, usize, usize, &[u8]) -> Option<Vec<[T; 4]>>;
+
+/// Decode every mipmap and face of a bitmap into 8-bit RGBA pixels.
+///
+/// `bitmap_index` is the index of the bitmap in its [`AddBitmapParameter`](super::AddBitmapParameter), and it is only
+/// used for error messages.
+///
+/// Floating point formats are clamped to 0-1.
+///
+/// Faces are sorted by mipmap index, then by face index.
+pub fn decode_bitmap_to_rgba8(bitmap: &AddBitmapBitmapParameter, bitmap_index: usize) -> MResult<Vec<DecodedBitmapFace<u8>>> {
+    bitmap.validate(bitmap_index)?;
+    decode_bitmap(bitmap, decode_face_to_rgba8)
+}
+
+/// Decode every mipmap and face of a bitmap into 32-bit floating point RGBA pixels.
+///
+/// `bitmap_index` is the index of the bitmap in its [`AddBitmapParameter`](super::AddBitmapParameter), and it is only
+/// used for error messages.
+///
+/// Integer formats are normalized to 0-1. No color space conversion is done.
+///
+/// Faces are sorted by mipmap index, then by face index.
+pub fn decode_bitmap_to_rgba32f(bitmap: &AddBitmapBitmapParameter, bitmap_index: usize) -> MResult<Vec<DecodedBitmapFace<f32>>> {
+    bitmap.validate(bitmap_index)?;
+    decode_bitmap(bitmap, decode_face_to_rgba32f)
+}
+
+/// Decode a bitmap that was already validated when its [`AddBitmapParameter`](super::AddBitmapParameter) was loaded
+/// into 8-bit RGBA pixels.
+pub(crate) fn decode_validated_bitmap_to_rgba8(bitmap: &AddBitmapBitmapParameter) -> MResult<Vec<DecodedBitmapFace<u8>>> {
+    decode_bitmap(bitmap, decode_face_to_rgba8)
+}
+
+/// Get the cubemap layer that the given cubemap face index is uploaded to.
+///
+/// Bitmap data stores the second and third faces of cubemaps in the opposite order of Vulkan.
+pub(crate) fn cubemap_face_to_layer(face_index: usize) -> usize {
+    match face_index {
+        1 => 2,
+        2 => 1,
+        n => n
+    }
+}
+
+fn decode_bitmap<T>(bitmap: &AddBitmapBitmapParameter, decode_face: FaceDecoder<T>) -> MResult<Vec<DecodedBitmapFace<T>>> {
+    let mipmap_type = match bitmap.bitmap_type {
+        BitmapType::Dim2D => MipmapType::TwoDimensional,
+        BitmapType::Dim3D { depth } => MipmapType::ThreeDimensional(NonZeroUsize::new(depth as usize).unwrap()),
+        BitmapType::Cubemap => MipmapType::Cubemap
+    };
+
+    let faces = MipmapFaceIterator::new(
+        NonZeroUsize::new(bitmap.resolution.width as usize).unwrap(),
+        NonZeroUsize::new(bitmap.resolution.height as usize).unwrap(),
+        mipmap_type,
+        NonZeroUsize::new(bitmap.format.block_pixel_length()).unwrap(),
+        Some(bitmap.mipmap_count as usize)
+    );
+
+    let block_size = bitmap.format.block_byte_size();
+    let mut decoded = Vec::new();
+    for face in faces {
+        let start = face.block_offset * block_size;
+        let data = &bitmap.data[start..start + face.block_count * block_size];
+        let pixels = decode_face(bitmap, face.width, face.height, data)
+            .ok_or_else(|| Error::from_data_error_string(format!("Unable to decode mipmap #{} face #{} of a {:?} bitmap", face.mipmap_index, face.face_index, bitmap.format)))?;
+
+        decoded.push(DecodedBitmapFace {
+            mipmap_index: face.mipmap_index,
+            face_index: match bitmap.bitmap_type {
+                BitmapType::Cubemap => cubemap_face_to_layer(face.face_index),
+                _ => face.face_index
+            },
+            width: face.width,
+            height: face.height,
+            pixels
+        });
+    }
+
+    // Put cubemap faces in layer order
+    decoded.sort_by_key(|f| (f.mipmap_index, f.face_index));
+
+    Ok(decoded)
+}
+
+fn expand_bits(value: u16, bits: u32) -> u8 {
+    let max = (1u16 << bits) - 1;
+    ((value as u32 * 255 + max as u32 / 2) / max as u32) as u8
+}
+
+fn decode_face_to_rgba8(bitmap: &AddBitmapBitmapParameter, width: usize, height: usize, data: &[u8]) -> Option<Vec<[u8; 4]>> {
+    let pixels_u16 = || data.chunks_exact(2).map(|p| u16::from_le_bytes([p[0], p[1]]));
+
+    let pixels = match bitmap.format {
+        f if f.is_block_compressed() => decode_block_compressed_to_r8g8b8a8(f, width, height, data)?
+            .chunks_exact(4)
+            .map(|p| [p[0], p[1], p[2], p[3]])
+            .collect(),
+
+        BitmapFormat::A8R8G8B8 | BitmapFormat::X8R8G8B8 | BitmapFormat::A8R8G8B8SRGB | BitmapFormat::X8R8G8B8SRGB => data
+            .chunks_exact(4)
+            .map(|p| [p[2], p[1], p[0], p[3]])
+            .collect(),
+
+        BitmapFormat::R5G6B5 => pixels_u16()
+            .map(|p| [expand_bits(p >> 11, 5), expand_bits((p >> 5) & 0x3F, 6), expand_bits(p & 0x1F, 5), 0xFF])
+            .collect(),
+
+        BitmapFormat::A1R5G5B5 => pixels_u16()
+            .map(|p| [expand_bits((p >> 10) & 0x1F, 5), expand_bits((p >> 5) & 0x1F, 5), expand_bits(p & 0x1F, 5), expand_bits(p >> 15, 1)])
+            .collect(),
+
+        BitmapFormat::A4R4G4B4 => pixels_u16()
+            .map(|p| [expand_bits((p >> 8) & 0xF, 4), expand_bits((p >> 4) & 0xF, 4), expand_bits(p & 0xF, 4), expand_bits(p >> 12, 4)])
+            .collect(),
+
+        BitmapFormat::R32G32B32A32SFloat | BitmapFormat::R16G16B16A16SFloat => decode_face_to_rgba32f(bitmap, width, height, data)?
+            .into_iter()
+            .map(|p| p.map(|c| (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8))
+            .collect(),
+
+        // Missing channels are sampled as 0 for color and 1 for alpha
+        BitmapFormat::R8G8 => data.chunks_exact(2).map(|p| [p[0], p[1], 0x00, 0xFF]).collect(),
+
+        BitmapFormat::A8 => data.iter().map(|&a| [0xFF, 0xFF, 0xFF, a]).collect(),
+        BitmapFormat::Y8 => data.iter().map(|&y| [y, y, y, 0xFF]).collect(),
+        BitmapFormat::AY8 => data.iter().map(|&y| [y, y, y, y]).collect(),
+        BitmapFormat::A8Y8 => data.chunks_exact(2).map(|p| [p[1], p[1], p[1], p[0]]).collect(),
+        BitmapFormat::P8 => data
+            .iter()
+            .map(|&p| {
+                let [b, g, r, a] = decode_p8_with_palette_to_a8r8g8b8le(p, bitmap.palette.as_deref());
+                [r, g, b, a]
+            })
+            .collect(),
+
+        _ => return None
+    };
+
+    Some(pixels)
+}
+
+fn decode_face_to_rgba32f(bitmap: &AddBitmapBitmapParameter, width: usize, height: usize, data: &[u8]) -> Option<Vec<[f32; 4]>> {
+    let pixels = match bitmap.format {
+        BitmapFormat::R32G32B32A32SFloat => data
+            .chunks_exact(16)
+            .map(|p| core::array::from_fn(|c| f32::from_le_bytes(p[c * 4..c * 4 + 4].try_into().unwrap())))
+            .collect(),
+
+        BitmapFormat::R16G16B16A16SFloat => data
+            .chunks_exact(8)
+            .map(|p| core::array::from_fn(|c| f16_to_f32(u16::from_le_bytes([p[c * 2], p[c * 2 + 1]]))))
+            .collect(),
+
+        _ => decode_face_to_rgba8(bitmap, width, height, data)?
+            .into_iter()
+            .map(|p| p.map(|c| c as f32 / 255.0))
+            .collect()
+    };
+
+    Some(pixels)
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half >> 15) as u32) << 31;
+    let exponent = ((half >> 10) & 0x1F) as u32;
+    let mantissa = (half & 0x3FF) as u32;
+
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        (0, _) => {
+            // Subnormal; normalize it
+            let shift = mantissa.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | (((mantissa << shift) & 0x3FF) << 13)
+        },
+        (0x1F, _) => sign | 0x7F800000 | (mantissa << 13),
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13)
+    };
+
+    f32::from_bits(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use crate::renderer::{test_bitmap_bitmap, Resolution};
+
+    fn bitmap(format: BitmapFormat, bitmap_type: BitmapType, width: u32, height: u32, data: Vec<u8>) -> AddBitmapBitmapParameter {
+        AddBitmapBitmapParameter { format, bitmap_type, resolution: Resolution { width, height }, data, ..test_bitmap_bitmap() }
+    }
+
+    fn decode_pixel(format: BitmapFormat, data: &[u8]) -> [u8; 4] {
+        let decoded = decode_bitmap_to_rgba8(&bitmap(format, BitmapType::Dim2D, 1, 1, data.to_vec()), 0).unwrap();
+        assert_eq!(decoded.len(), 1);
+        decoded[0].pixels[0]
+    }
+
+    #[test]
+    fn uncompressed_formats() {
+        assert_eq!(decode_pixel(BitmapFormat::A8R8G8B8, &[0x30, 0x20, 0x10, 0x40]), [0x10, 0x20, 0x30, 0x40]);
+        assert_eq!(decode_pixel(BitmapFormat::R5G6B5, &0xF81Fu16.to_le_bytes()), [0xFF, 0x00, 0xFF, 0xFF]);
+        assert_eq!(decode_pixel(BitmapFormat::R5G6B5, &0x07E0u16.to_le_bytes()), [0x00, 0xFF, 0x00, 0xFF]);
+        assert_eq!(decode_pixel(BitmapFormat::A1R5G5B5, &0x7C00u16.to_le_bytes()), [0xFF, 0x00, 0x00, 0x00]);
+        assert_eq!(decode_pixel(BitmapFormat::A1R5G5B5, &0x801Fu16.to_le_bytes()), [0x00, 0x00, 0xFF, 0xFF]);
+        assert_eq!(decode_pixel(BitmapFormat::A4R4G4B4, &0x8F70u16.to_le_bytes()), [0xFF, 0x77, 0x00, 0x88]);
+        assert_eq!(decode_pixel(BitmapFormat::R8G8, &[0x12, 0x34]), [0x12, 0x34, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn monochrome_formats() {
+        assert_eq!(decode_pixel(BitmapFormat::A8, &[0x40]), [0xFF, 0xFF, 0xFF, 0x40]);
+        assert_eq!(decode_pixel(BitmapFormat::Y8, &[0x40]), [0x40, 0x40, 0x40, 0xFF]);
+        assert_eq!(decode_pixel(BitmapFormat::AY8, &[0x40]), [0x40, 0x40, 0x40, 0x40]);
+        assert_eq!(decode_pixel(BitmapFormat::A8Y8, &[0x80, 0x40]), [0x40, 0x40, 0x40, 0x80]);
+    }
+
+    #[test]
+    fn palettized_format() {
+        let mut palette = vec![[0u8; 4]; 256];
+        palette[7] = [0x10, 0x20, 0x30, 0x40];
+
+        let mut p8 = bitmap(BitmapFormat::P8, BitmapType::Dim2D, 1, 1, vec![7]);
+        p8.palette = Some(palette);
+        assert_eq!(decode_bitmap_to_rgba8(&p8, 0).unwrap()[0].pixels[0], [0x10, 0x20, 0x30, 0x40]);
+
+        let [b, g, r, a] = crate::renderer::decode_p8_to_a8r8g8b8le(7);
+        assert_eq!(decode_pixel(BitmapFormat::P8, &[7]), [r, g, b, a]);
+    }
+
+    #[test]
+    fn block_compressed_format() {
+        // Solid red DXT1 block
+        let decoded = decode_bitmap_to_rgba8(&bitmap(BitmapFormat::DXT1, BitmapType::Dim2D, 4, 4, vec![0x00, 0xF8, 0, 0, 0, 0, 0, 0]), 0).unwrap();
+        assert_eq!(decoded[0].pixels, vec![[0xFF, 0x00, 0x00, 0xFF]; 16]);
+    }
+
+    #[test]
+    fn floating_point_formats() {
+        let halves = [0x3C00u16, 0xC000, 0x0001, 0x3800];
+        let data: Vec<u8> = halves.iter().flat_map(|h| h.to_le_bytes()).collect();
+        let decoded = decode_bitmap_to_rgba32f(&bitmap(BitmapFormat::R16G16B16A16SFloat, BitmapType::Dim2D, 1, 1, data.clone()), 0).unwrap();
+        assert_eq!(decoded[0].pixels[0], [1.0, -2.0, 2.0f32.powi(-24), 0.5]);
+
+        // Clamped to 0-1 when decoding to 8-bit
+        assert_eq!(decode_pixel(BitmapFormat::R16G16B16A16SFloat, &data), [0xFF, 0x00, 0x00, 0x80]);
+
+        let data: Vec<u8> = [0.25f32, 2.0, -1.0, 1.0].iter().flat_map(|f| f.to_le_bytes()).collect();
+        assert_eq!(decode_pixel(BitmapFormat::R32G32B32A32SFloat, &data), [0x40, 0xFF, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn f16_special_values() {
+        assert_eq!(f16_to_f32(0x0000), 0.0);
+        assert!(f16_to_f32(0x8000).is_sign_negative());
+        assert_eq!(f16_to_f32(0x7C00), f32::INFINITY);
+        assert_eq!(f16_to_f32(0xFC00), f32::NEG_INFINITY);
+        assert!(f16_to_f32(0x7E00).is_nan());
+        assert_eq!(f16_to_f32(0x03FF), 1023.0 * 2.0f32.powi(-24));
+        assert_eq!(f16_to_f32(0x7BFF), 65504.0);
+    }
+
+    #[test]
+    fn cubemap_faces_are_in_layer_order() {
+        let decoded = decode_bitmap_to_rgba8(&bitmap(BitmapFormat::A8, BitmapType::Cubemap, 1, 1, vec![0, 1, 2, 3, 4, 5]), 0).unwrap();
+        let layers: Vec<(usize, u8)> = decoded.iter().map(|f| (f.face_index, f.pixels[0][3])).collect();
+        assert_eq!(layers, vec![(0, 0), (1, 2), (2, 1), (3, 3), (4, 4), (5, 5)]);
+    }
+
+    #[test]
+    fn mipmaps_are_decoded() {
+        let mut mipmapped = bitmap(BitmapFormat::Y8, BitmapType::Dim2D, 2, 2, vec![1, 2, 3, 4, 5]);
+        mipmapped.mipmap_count = 1;
+
+        let decoded = decode_bitmap_to_rgba8(&mipmapped, 0).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!((decoded[0].width, decoded[0].height, decoded[0].pixels.len()), (2, 2, 4));
+        assert_eq!((decoded[1].mipmap_index, decoded[1].width, decoded[1].height), (1, 1, 1));
+        assert_eq!(decoded[1].pixels[0], [5, 5, 5, 0xFF]);
+    }
+
+    #[test]
+    fn invalid_bitmap_reports_its_index() {
+        let error = decode_bitmap_to_rgba8(&bitmap(BitmapFormat::A8, BitmapType::Dim2D, 0, 1, vec![0]), 3).unwrap_err();
+        assert!(matches!(error, Error::InvalidBitmapDimensions { bitmap_index: 3, .. }), "{error:?}");
+    }
+}
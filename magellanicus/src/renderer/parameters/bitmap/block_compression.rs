@@ -0,0 +1,579 @@
+//! CPU decoders for block-compressed formats.
+//!
+//! These are used when the device cannot sample block-compressed textures, but they are also useful for tooling.
+//!
+//! All decoders output 4x4 blocks of pixels in row-major order, with each pixel being red, green, blue, and alpha.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+use super::mipmap_iterator::{MipmapFaceIterator, MipmapType};
+use super::{AddBitmapBitmapParameter, BitmapFormat, BitmapType};
+
+/// Decode a face of a block-compressed bitmap into R8G8B8A8 pixels (one byte per channel).
+///
+/// `data` must contain exactly one face (a 2D texture, a single cubemap face, or a single 3D texture slice) of the
+/// given dimensions in pixels.
+///
+/// Returns `None` if `format` is not block-compressed or `data` is not the correct length.
+pub fn decode_block_compressed_to_r8g8b8a8(format: BitmapFormat, width: usize, height: usize, data: &[u8]) -> Option<Vec<u8>> {
+    let decode_block: fn(&[u8]) -> [[u8; 4]; 16] = match format {
+        BitmapFormat::DXT1 | BitmapFormat::DXT1SRGB => |b| decode_dxt1_block(b.try_into().unwrap()),
+        BitmapFormat::DXT3 | BitmapFormat::DXT3SRGB => |b| decode_dxt3_block(b.try_into().unwrap()),
+        BitmapFormat::DXT5 | BitmapFormat::DXT5SRGB => |b| decode_dxt5_block(b.try_into().unwrap()),
+        BitmapFormat::BC7 | BitmapFormat::BC7SRGB => |b| decode_bc7_block(b.try_into().unwrap()),
+        BitmapFormat::BC4 => |b| decode_bc4_block(b.try_into().unwrap()),
+        BitmapFormat::BC5 => |b| decode_bc5_block(b.try_into().unwrap()),
+        _ => return None
+    };
+
+    let block_size = format.block_byte_size();
+    let block_width = width.div_ceil(4);
+    let block_height = height.div_ceil(4);
+    if block_width.checked_mul(block_height)?.checked_mul(block_size)? != data.len() {
+        return None
+    }
+
+    let mut output = vec![0u8; width * height * 4];
+    for (block_index, block) in data.chunks_exact(block_size).enumerate() {
+        let block_x = (block_index % block_width) * 4;
+        let block_y = (block_index / block_width) * 4;
+        let pixels = decode_block(block);
+
+        for (pixel_index, pixel) in pixels.iter().enumerate() {
+            let x = block_x + pixel_index % 4;
+            let y = block_y + pixel_index / 4;
+            if x >= width || y >= height {
+                continue
+            }
+            let offset = (x + y * width) * 4;
+            output[offset..offset + 4].copy_from_slice(pixel);
+        }
+    }
+
+    Some(output)
+}
+
+/// Decode every mipmap and face of a block-compressed bitmap into R8G8B8A8 pixels, in the same order as the input.
+///
+/// `bitmap` must be validated beforehand.
+pub(crate) fn decode_block_compressed_bitmap_to_r8g8b8a8(bitmap: &AddBitmapBitmapParameter) -> Option<Vec<u8>> {
+    let block_size = bitmap.format.block_byte_size();
+    let mipmap_type = match bitmap.bitmap_type {
+        BitmapType::Dim2D => MipmapType::TwoDimensional,
+        BitmapType::Dim3D { depth } => MipmapType::ThreeDimensional(NonZeroUsize::new(depth as usize)?),
+        BitmapType::Cubemap => MipmapType::Cubemap
+    };
+
+    let faces = MipmapFaceIterator::new(
+        NonZeroUsize::new(bitmap.resolution.width as usize)?,
+        NonZeroUsize::new(bitmap.resolution.height as usize)?,
+        mipmap_type,
+        NonZeroUsize::new(bitmap.format.block_pixel_length())?,
+        Some(bitmap.mipmap_count as usize)
+    );
+
+    let mut output = Vec::new();
+    for face in faces {
+        let start = face.block_offset * block_size;
+        let end = start + face.block_count * block_size;
+        let data = bitmap.data.get(start..end)?;
+        output.extend(decode_block_compressed_to_r8g8b8a8(bitmap.format, face.width, face.height, data)?);
+    }
+
+    Some(output)
+}
+
+fn decode_r5g6b5(color: u16) -> [u8; 4] {
+    let r = ((color >> 11) & 0x1F) as u8;
+    let g = ((color >> 5) & 0x3F) as u8;
+    let b = (color & 0x1F) as u8;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2), 0xFF]
+}
+
+fn decode_color_block(block: &[u8; 8], allow_transparency: bool) -> [[u8; 4]; 16] {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    let c0 = decode_r5g6b5(color0);
+    let c1 = decode_r5g6b5(color1);
+    let mix = |a_weight: u16, b_weight: u16| -> [u8; 4] {
+        let total = a_weight + b_weight;
+        let channel = |i: usize| ((c0[i] as u16 * a_weight + c1[i] as u16 * b_weight) / total) as u8;
+        [channel(0), channel(1), channel(2), 0xFF]
+    };
+
+    let palette = if color0 > color1 || !allow_transparency {
+        [c0, c1, mix(2, 1), mix(1, 2)]
+    }
+    else {
+        [c0, c1, mix(1, 1), [0, 0, 0, 0]]
+    };
+
+    core::array::from_fn(|i| palette[((indices >> (i * 2)) & 0b11) as usize])
+}
+
+/// Decode a DXT1 (BC1) block.
+pub fn decode_dxt1_block(block: &[u8; 8]) -> [[u8; 4]; 16] {
+    decode_color_block(block, true)
+}
+
+/// Decode a DXT3 (BC2) block.
+pub fn decode_dxt3_block(block: &[u8; 16]) -> [[u8; 4]; 16] {
+    let alpha = u64::from_le_bytes(block[0..8].try_into().unwrap());
+    let mut pixels = decode_color_block(block[8..16].try_into().unwrap(), false);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        pixel[3] = ((alpha >> (i * 4)) & 0xF) as u8 * 0x11;
+    }
+    pixels
+}
+
+/// Decode a DXT5 (BC3) block.
+pub fn decode_dxt5_block(block: &[u8; 16]) -> [[u8; 4]; 16] {
+    let alpha = decode_bc4_block_channel(block[0..8].try_into().unwrap());
+    let mut pixels = decode_color_block(block[8..16].try_into().unwrap(), false);
+    for (pixel, alpha) in pixels.iter_mut().zip(alpha) {
+        pixel[3] = alpha;
+    }
+    pixels
+}
+
+/// Decode a BC4 block.
+///
+/// Green and blue are set to 0, and alpha is set to 255, matching how the GPU samples it.
+pub fn decode_bc4_block(block: &[u8; 8]) -> [[u8; 4]; 16] {
+    decode_bc4_block_channel(block).map(|r| [r, 0, 0, 0xFF])
+}
+
+/// Decode a BC5 block.
+///
+/// Blue is set to 0, and alpha is set to 255, matching how the GPU samples it.
+pub fn decode_bc5_block(block: &[u8; 16]) -> [[u8; 4]; 16] {
+    let red = decode_bc4_block_channel(block[0..8].try_into().unwrap());
+    let green = decode_bc4_block_channel(block[8..16].try_into().unwrap());
+    core::array::from_fn(|i| [red[i], green[i], 0, 0xFF])
+}
+
+/// Decode a single 8-byte interpolated channel block as used by DXT5 alpha.
+fn decode_bc4_block_channel(block: &[u8; 8]) -> [u8; 16] {
+    let a0 = block[0] as u16;
+    let a1 = block[1] as u16;
+    let mut indices = [0u8; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u16) * a0 + i as u16 * a1) / 7) as u8;
+        }
+    }
+    else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u16) * a0 + i as u16 * a1) / 5) as u8;
+        }
+        palette[6] = 0x00;
+        palette[7] = 0xFF;
+    }
+
+    core::array::from_fn(|i| palette[((indices >> (i * 3)) & 0b111) as usize])
+}
+
+struct BitReader<'a> {
+    data: &'a [u8; 16],
+    position: usize
+}
+
+impl<'a> BitReader<'a> {
+    fn read(&mut self, bits: usize) -> u8 {
+        let mut value = 0u8;
+        for i in 0..bits {
+            let bit_position = self.position + i;
+            let bit = (self.data[bit_position / 8] >> (bit_position % 8)) & 1;
+            value |= bit << i;
+        }
+        self.position += bits;
+        value
+    }
+}
+
+struct BC7Mode {
+    subsets: usize,
+    partition_bits: usize,
+    rotation_bits: usize,
+    index_selection_bits: usize,
+    color_bits: usize,
+    alpha_bits: usize,
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: usize,
+    secondary_index_bits: usize
+}
+
+const BC7_MODES: [BC7Mode; 8] = [
+    BC7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 3, secondary_index_bits: 0 },
+    BC7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: true, index_bits: 3, secondary_index_bits: 0 },
+    BC7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    BC7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    BC7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 3 },
+    BC7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 2 },
+    BC7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_p_bits: true, shared_p_bits: false, index_bits: 4, secondary_index_bits: 0 },
+    BC7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+/// Subset of each pixel for two-subset partitions, where bit N is the subset of pixel N.
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80,
+    0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A,
+    0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C,
+    0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Subset of each pixel for three-subset partitions.
+const BC7_PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Anchor index of the second subset for two-subset partitions.
+const BC7_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 15,
+    15,  2,  8,  2,  2,  8,  8, 15,
+     2,  8,  2,  2,  8,  8,  2,  2,
+    15, 15,  6,  8,  2,  8, 15, 15,
+     2,  8,  2,  2,  2, 15, 15,  6,
+     6,  2,  6,  8, 15, 15,  2,  2,
+    15, 15, 15, 15, 15,  2,  2, 15,
+];
+
+/// Anchor index of the second subset for three-subset partitions.
+const BC7_ANCHORS_3_SECOND: [u8; 64] = [
+     3,  3, 15, 15,  8,  3, 15, 15,
+     8,  8,  6,  6,  6,  5,  3,  3,
+     3,  3,  8, 15,  3,  3,  6, 10,
+     5,  8,  8,  6,  8,  5, 15, 15,
+     8, 15,  3,  5,  6, 10,  8, 15,
+    15,  3, 15,  5, 15, 15, 15, 15,
+     3, 15,  5,  5,  5,  8,  5, 10,
+     5, 10,  8, 13, 15, 12,  3,  3,
+];
+
+/// Anchor index of the third subset for three-subset partitions.
+const BC7_ANCHORS_3_THIRD: [u8; 64] = [
+    15,  8,  8,  3, 15, 15,  3,  8,
+    15, 15, 15, 15, 15, 15, 15,  8,
+    15,  8, 15,  3, 15,  8, 15,  8,
+     3, 15,  6, 10, 15, 15, 10,  8,
+    15,  3, 15, 10, 10,  8,  9, 10,
+     6, 15,  8, 15,  3,  6,  6,  8,
+    15,  3, 15, 15, 15, 15, 15, 15,
+    15, 15, 15, 15,  3, 15, 15,  8,
+];
+
+const BC7_WEIGHTS_2: [u16; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u16; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u16; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn bc7_interpolate(a: u8, b: u8, index: u8, index_bits: usize) -> u8 {
+    let weight = match index_bits {
+        2 => BC7_WEIGHTS_2[index as usize],
+        3 => BC7_WEIGHTS_3[index as usize],
+        4 => BC7_WEIGHTS_4[index as usize],
+        _ => unreachable!()
+    };
+    (((64 - weight) * a as u16 + weight * b as u16 + 32) >> 6) as u8
+}
+
+fn bc7_subset(subsets: usize, partition: usize, pixel: usize) -> usize {
+    match subsets {
+        1 => 0,
+        2 => ((BC7_PARTITIONS_2[partition] >> pixel) & 1) as usize,
+        3 => BC7_PARTITIONS_3[partition][pixel] as usize,
+        _ => unreachable!()
+    }
+}
+
+fn bc7_is_anchor(subsets: usize, partition: usize, pixel: usize) -> bool {
+    pixel == 0 || match subsets {
+        2 => BC7_ANCHORS_2[partition] as usize == pixel,
+        3 => BC7_ANCHORS_3_SECOND[partition] as usize == pixel || BC7_ANCHORS_3_THIRD[partition] as usize == pixel,
+        _ => false
+    }
+}
+
+/// Decode a BC7 block.
+///
+/// Reserved (invalid) blocks decode to transparent black.
+pub fn decode_bc7_block(block: &[u8; 16]) -> [[u8; 4]; 16] {
+    if block[0] == 0 {
+        return [[0u8; 4]; 16]
+    }
+
+    let mode_index = block[0].trailing_zeros() as usize;
+    let mode = &BC7_MODES[mode_index];
+    let mut reader = BitReader { data: block, position: mode_index + 1 };
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    // [subset][endpoint][channel]
+    let mut endpoints = [[[0u8; 4]; 2]; 3];
+    for channel in 0..3 {
+        for endpoint in endpoints[..mode.subsets].iter_mut().flatten() {
+            endpoint[channel] = reader.read(mode.color_bits);
+        }
+    }
+    if mode.alpha_bits > 0 {
+        for endpoint in endpoints[..mode.subsets].iter_mut().flatten() {
+            endpoint[3] = reader.read(mode.alpha_bits);
+        }
+    }
+
+    let mut color_bits = mode.color_bits;
+    let mut alpha_bits = mode.alpha_bits;
+    if mode.endpoint_p_bits || mode.shared_p_bits {
+        for subset in &mut endpoints[..mode.subsets] {
+            let shared = if mode.shared_p_bits { Some(reader.read(1)) } else { None };
+            for endpoint in subset {
+                let p = shared.unwrap_or_else(|| reader.read(1));
+                for channel in endpoint {
+                    *channel = (*channel << 1) | p;
+                }
+            }
+        }
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    let expand = |value: u8, bits: usize| -> u8 {
+        let value = (value as u16) << (8 - bits);
+        (value | (value >> bits)) as u8
+    };
+
+    for subset in &mut endpoints[..mode.subsets] {
+        for endpoint in subset {
+            for channel in &mut endpoint[..3] {
+                *channel = expand(*channel, color_bits);
+            }
+            endpoint[3] = if alpha_bits > 0 { expand(endpoint[3], alpha_bits) } else { 0xFF };
+        }
+    }
+
+    let mut primary_indices = [0u8; 16];
+    for (pixel, index) in primary_indices.iter_mut().enumerate() {
+        let anchor = bc7_is_anchor(mode.subsets, partition, pixel);
+        *index = reader.read(mode.index_bits - anchor as usize);
+    }
+
+    let mut secondary_indices = [0u8; 16];
+    if mode.secondary_index_bits > 0 {
+        for (pixel, index) in secondary_indices.iter_mut().enumerate() {
+            *index = reader.read(mode.secondary_index_bits - (pixel == 0) as usize);
+        }
+    }
+
+    core::array::from_fn(|pixel| {
+        let subset = bc7_subset(mode.subsets, partition, pixel);
+        let [e0, e1] = endpoints[subset];
+
+        let (color_index, color_index_bits, alpha_index, alpha_index_bits) = if mode.secondary_index_bits == 0 {
+            (primary_indices[pixel], mode.index_bits, primary_indices[pixel], mode.index_bits)
+        }
+        else if index_selection == 0 {
+            (primary_indices[pixel], mode.index_bits, secondary_indices[pixel], mode.secondary_index_bits)
+        }
+        else {
+            (secondary_indices[pixel], mode.secondary_index_bits, primary_indices[pixel], mode.index_bits)
+        };
+
+        let mut output = [
+            bc7_interpolate(e0[0], e1[0], color_index, color_index_bits),
+            bc7_interpolate(e0[1], e1[1], color_index, color_index_bits),
+            bc7_interpolate(e0[2], e1[2], color_index, color_index_bits),
+            bc7_interpolate(e0[3], e1[3], alpha_index, alpha_index_bits),
+        ];
+
+        match rotation {
+            1 => output.swap(0, 3),
+            2 => output.swap(1, 3),
+            3 => output.swap(2, 3),
+            _ => ()
+        }
+
+        output
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+    const BLUE: [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+
+    #[test]
+    fn dxt1_four_color() {
+        // red, blue, then indices 0, 1, 2, 3 on every row
+        let pixels = decode_dxt1_block(&[0x00, 0xF8, 0x1F, 0x00, 0xE4, 0xE4, 0xE4, 0xE4]);
+        for row in pixels.chunks_exact(4) {
+            assert_eq!(row, [RED, BLUE, [170, 0, 85, 0xFF], [85, 0, 170, 0xFF]]);
+        }
+    }
+
+    #[test]
+    fn dxt1_three_color_with_transparency() {
+        // color0 <= color1 selects the three color mode, where index 3 is transparent black
+        let pixels = decode_dxt1_block(&[0x1F, 0x00, 0x00, 0xF8, 0xE4, 0xE4, 0xE4, 0xE4]);
+        assert_eq!(pixels[..4], [BLUE, RED, [127, 0, 127, 0xFF], [0, 0, 0, 0]]);
+    }
+
+    #[test]
+    fn dxt3_explicit_alpha() {
+        // alpha of pixel N is N * 0x11; color is solid red
+        let mut block = [0u8; 16];
+        block[..8].copy_from_slice(&0xFEDCBA9876543210u64.to_le_bytes());
+        block[8..12].copy_from_slice(&[0x00, 0xF8, 0x00, 0xF8]);
+        let pixels = decode_dxt3_block(&block);
+        for (i, pixel) in pixels.iter().enumerate() {
+            assert_eq!(*pixel, [0xFF, 0x00, 0x00, i as u8 * 0x11]);
+        }
+    }
+
+    #[test]
+    fn dxt5_interpolated_alpha() {
+        // alpha0 = 255 and alpha1 = 0 use the eight value mode; indices 0-7 on the first 8 pixels
+        let mut block = [0u8; 16];
+        block[0] = 0xFF;
+        block[1] = 0x00;
+        block[2..5].copy_from_slice(&[0x88, 0xC6, 0xFA]);
+        block[8..12].copy_from_slice(&[0x00, 0xF8, 0x00, 0xF8]);
+        let alpha: Vec<u8> = decode_dxt5_block(&block).iter().map(|p| p[3]).collect();
+        assert_eq!(alpha[..8], [255, 0, 218, 182, 145, 109, 72, 36]);
+        assert!(alpha[8..].iter().all(|a| *a == 255));
+    }
+
+    #[test]
+    fn bc4_six_value_mode() {
+        // alpha0 <= alpha1 uses the six value mode, where indices 6 and 7 are 0 and 255
+        let pixels = decode_bc4_block(&[0x00, 0xFF, 0x88, 0xC6, 0xFA, 0x00, 0x00, 0x00]);
+        let red: Vec<u8> = pixels.iter().map(|p| p[0]).collect();
+        assert_eq!(red[..8], [0, 255, 51, 102, 153, 204, 0, 255]);
+        assert!(pixels.iter().all(|p| p[1] == 0 && p[2] == 0 && p[3] == 0xFF));
+    }
+
+    #[test]
+    fn bc5_two_channels() {
+        let mut block = [0u8; 16];
+        block[..2].copy_from_slice(&[0x80, 0x80]);
+        block[8..10].copy_from_slice(&[0x40, 0x40]);
+        assert_eq!(decode_bc5_block(&block), [[0x80, 0x40, 0x00, 0xFF]; 16]);
+    }
+
+    #[test]
+    fn bc7_mode_6() {
+        // black to white with p-bits 0 and 1 (so the first alpha is 254), and pixel N uses index N
+        let block = [0x40, 0xC0, 0x1F, 0xF0, 0x07, 0xFC, 0xFF, 0x7F, 0x11, 0x32, 0x54, 0x76, 0x98, 0xBA, 0xDC, 0xFE];
+        let expected = [0, 16, 36, 52, 68, 84, 104, 120, 135, 151, 171, 187, 203, 219, 239, 255];
+        for (pixel, value) in decode_bc7_block(&block).iter().zip(expected) {
+            let alpha = if value < 128 { 254 } else { 255 };
+            assert_eq!(*pixel, [value, value, value, alpha]);
+        }
+    }
+
+    #[test]
+    fn bc7_mode_1_partition() {
+        // partition 0 puts the two right columns in subset 1, which is solid red; subset 0 is black to white
+        let block = [0x02, 0xC0, 0xFF, 0xFF, 0xC0, 0x0F, 0x00, 0xC0, 0x0F, 0x00, 0x12, 0x8D, 0xF5, 0x11, 0x8D, 0xF5];
+        let pixels = decode_bc7_block(&block);
+        let red = [0xFF, 0x02, 0x02, 0xFF];
+        assert_eq!(pixels[..4], [[0, 0, 0, 0xFF], [36, 36, 36, 0xFF], red, red]);
+        assert_eq!(pixels[4..8], [[146, 146, 146, 0xFF], [182, 182, 182, 0xFF], red, red]);
+        assert_eq!(pixels[..8], pixels[8..]);
+    }
+
+    #[test]
+    fn bc7_reserved_block() {
+        assert_eq!(decode_bc7_block(&[0u8; 16]), [[0u8; 4]; 16]);
+    }
+
+    #[test]
+    fn decode_crops_partial_blocks() {
+        let data = [0x00, 0xF8, 0x00, 0xF8, 0x00, 0x00, 0x00, 0x00];
+        let pixels = decode_block_compressed_to_r8g8b8a8(BitmapFormat::DXT1, 2, 3, &data).unwrap();
+        assert_eq!(pixels, RED.repeat(6));
+        assert!(decode_block_compressed_to_r8g8b8a8(BitmapFormat::DXT1, 8, 4, &data).is_none());
+    }
+}
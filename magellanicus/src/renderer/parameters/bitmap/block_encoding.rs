@@ -0,0 +1,465 @@
+//! CPU encoders for block-compressed formats.
+//!
+//! These are used for recompressing uncompressed bitmaps when loading, but they are also useful for tooling.
+//!
+//! All encoders take 4x4 blocks of pixels in row-major order, with each pixel being red, green, blue, and alpha.
+
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+use super::mipmap_iterator::{MipmapFaceIterator, MipmapType};
+use super::{BitmapFormat, BitmapType};
+
+/// Encode a face of R8G8B8A8 pixels (one byte per channel) into a block-compressed format.
+///
+/// `data` must contain exactly one face (a 2D texture, a single cubemap face, or a single 3D texture slice) of the
+/// given dimensions in pixels. Faces that aren't a multiple of 4 pixels are padded by repeating the edge pixels.
+///
+/// DXT1 discards alpha, so it should only be used for opaque bitmaps. BC5 only keeps red and green.
+///
+/// Returns `None` if `format` is not DXT1, DXT5, BC5, or BC7 (or their sRGB variants), or `data` is not the correct
+/// length.
+pub fn encode_r8g8b8a8_to_block_compressed(format: BitmapFormat, width: usize, height: usize, data: &[u8]) -> Option<Vec<u8>> {
+    let encode_block: fn(&[[u8; 4]; 16], &mut Vec<u8>) = match format {
+        BitmapFormat::DXT1 | BitmapFormat::DXT1SRGB => |p, o| o.extend_from_slice(&encode_dxt1_block(p)),
+        BitmapFormat::DXT5 | BitmapFormat::DXT5SRGB => |p, o| o.extend_from_slice(&encode_dxt5_block(p)),
+        BitmapFormat::BC7 | BitmapFormat::BC7SRGB => |p, o| o.extend_from_slice(&encode_bc7_block(p)),
+        BitmapFormat::BC5 => |p, o| o.extend_from_slice(&encode_bc5_block(p)),
+        _ => return None
+    };
+
+    if width == 0 || height == 0 || width.checked_mul(height)?.checked_mul(4)? != data.len() {
+        return None
+    }
+
+    let block_width = width.div_ceil(4);
+    let block_height = height.div_ceil(4);
+
+    let mut output = Vec::with_capacity(block_width * block_height * format.block_byte_size());
+    for block_y in 0..block_height {
+        for block_x in 0..block_width {
+            let pixels: [[u8; 4]; 16] = core::array::from_fn(|i| {
+                let x = (block_x * 4 + i % 4).min(width - 1);
+                let y = (block_y * 4 + i / 4).min(height - 1);
+                let offset = (x + y * width) * 4;
+                data[offset..offset + 4].try_into().unwrap()
+            });
+            encode_block(&pixels, &mut output);
+        }
+    }
+
+    Some(output)
+}
+
+/// Encode every mipmap and face of an R8G8B8A8 bitmap into a block-compressed format, in the same order as the input.
+///
+/// Returns `None` if the format is unsupported (see [`encode_r8g8b8a8_to_block_compressed`]), the dimensions are 0,
+/// or `data` is not the correct length.
+pub(crate) fn encode_r8g8b8a8_bitmap_to_block_compressed(
+    format: BitmapFormat,
+    width: u32,
+    height: u32,
+    bitmap_type: BitmapType,
+    mipmap_count: u32,
+    data: &[u8]
+) -> Option<Vec<u8>> {
+    let mipmap_type = match bitmap_type {
+        BitmapType::Dim2D => MipmapType::TwoDimensional,
+        BitmapType::Dim3D { depth } => MipmapType::ThreeDimensional(NonZeroUsize::new(depth as usize)?),
+        BitmapType::Cubemap => MipmapType::Cubemap
+    };
+
+    let faces = MipmapFaceIterator::new(
+        NonZeroUsize::new(width as usize)?,
+        NonZeroUsize::new(height as usize)?,
+        mipmap_type,
+        NonZeroUsize::new(1).unwrap(),
+        Some(mipmap_count as usize)
+    );
+
+    let mut output = Vec::new();
+    let mut end = 0;
+    for face in faces {
+        let start = face.block_offset * 4;
+        end = start + face.block_count * 4;
+        let data = data.get(start..end)?;
+        output.extend(encode_r8g8b8a8_to_block_compressed(format, face.width, face.height, data)?);
+    }
+
+    (end == data.len()).then_some(output)
+}
+
+/// Encode a DXT1 (BC1) block.
+///
+/// Alpha is discarded.
+pub fn encode_dxt1_block(pixels: &[[u8; 4]; 16]) -> [u8; 8] {
+    encode_color_block(pixels)
+}
+
+/// Encode a DXT5 (BC3) block.
+pub fn encode_dxt5_block(pixels: &[[u8; 4]; 16]) -> [u8; 16] {
+    let mut block = [0u8; 16];
+    block[..8].copy_from_slice(&encode_bc4_block_channel(&pixels.map(|p| p[3])));
+    block[8..].copy_from_slice(&encode_color_block(pixels));
+    block
+}
+
+/// Encode a BC5 block.
+///
+/// Only red and green are kept, such as for the X and Y components of a normal map.
+pub fn encode_bc5_block(pixels: &[[u8; 4]; 16]) -> [u8; 16] {
+    let mut block = [0u8; 16];
+    block[..8].copy_from_slice(&encode_bc4_block_channel(&pixels.map(|p| p[0])));
+    block[8..].copy_from_slice(&encode_bc4_block_channel(&pixels.map(|p| p[1])));
+    block
+}
+
+fn squared_distance<const N: usize>(a: &[u8; 4], b: &[u8; 4]) -> u32 {
+    (0..N).map(|c| (a[c] as i32 - b[c] as i32).pow(2) as u32).sum()
+}
+
+/// Find the two points farthest apart along the principal axis of the first `N` channels of the pixels.
+fn principal_endpoints<const N: usize>(pixels: &[[u8; 4]; 16]) -> ([f32; 4], [f32; 4]) {
+    let mut mean = [0.0f32; 4];
+    for p in pixels {
+        for c in 0..N {
+            mean[c] += p[c] as f32 / 16.0;
+        }
+    }
+
+    let mut covariance = [[0.0f32; 4]; 4];
+    for p in pixels {
+        for i in 0..N {
+            for j in 0..N {
+                covariance[i][j] += (p[i] as f32 - mean[i]) * (p[j] as f32 - mean[j]);
+            }
+        }
+    }
+
+    // Power iteration, starting from the diagonal of the bounding box. Each extent is signed by its covariance with the
+    // channel with the most variance, as channels that go in opposite directions (e.g. a red to green gradient) would
+    // otherwise start orthogonal to the principal axis.
+    let dominant = (0..N).max_by(|&a, &b| covariance[a][a].total_cmp(&covariance[b][b])).unwrap();
+    let normalize = |v: [f32; 4]| glam::Vec4::from_array(v).try_normalize().map(|v| v.to_array());
+
+    let mut axis = [0.0f32; 4];
+    for c in 0..N {
+        let min = pixels.iter().map(|p| p[c]).min().unwrap();
+        let max = pixels.iter().map(|p| p[c]).max().unwrap();
+        let extent = (max - min) as f32;
+        axis[c] = if covariance[c][dominant] < 0.0 { -extent } else { extent };
+    }
+    let mut axis = normalize(axis).unwrap_or(core::array::from_fn(|c| if c == dominant { 1.0 } else { 0.0 }));
+
+    for _ in 0..8 {
+        let mut next = [0.0f32; 4];
+        for i in 0..N {
+            for j in 0..N {
+                next[i] += covariance[i][j] * axis[j];
+            }
+        }
+        match normalize(next) {
+            Some(next) => axis = next,
+            None => break
+        }
+    }
+
+    let project = |p: &[u8; 4]| (0..N).map(|c| (p[c] as f32 - mean[c]) * axis[c]).sum::<f32>();
+    let (min, max) = pixels
+        .iter()
+        .map(project)
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), t| (min.min(t), max.max(t)));
+
+    let point = |t: f32| core::array::from_fn(|c| (mean[c] + axis[c] * t).clamp(0.0, 255.0));
+    (point(min), point(max))
+}
+
+fn encode_r5g6b5(color: &[f32; 4]) -> u16 {
+    let r = (color[0] * 31.0 / 255.0 + 0.5) as u16;
+    let g = (color[1] * 63.0 / 255.0 + 0.5) as u16;
+    let b = (color[2] * 31.0 / 255.0 + 0.5) as u16;
+    (r << 11) | (g << 5) | b
+}
+
+fn decode_r5g6b5(color: u16) -> [u8; 4] {
+    let r = ((color >> 11) & 0x1F) as u8;
+    let g = ((color >> 5) & 0x3F) as u8;
+    let b = (color & 0x1F) as u8;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2), 0xFF]
+}
+
+/// Encode an opaque 4-color block as used by DXT1, DXT3, and DXT5.
+fn encode_color_block(pixels: &[[u8; 4]; 16]) -> [u8; 8] {
+    let (low, high) = principal_endpoints::<3>(pixels);
+    let mut color0 = encode_r5g6b5(&high);
+    let mut color1 = encode_r5g6b5(&low);
+
+    // DXT1 only uses four colors if color0 > color1. If they're equal, the block is a solid color.
+    if color0 < color1 {
+        core::mem::swap(&mut color0, &mut color1);
+    }
+
+    let mut block = [0u8; 8];
+    block[0..2].copy_from_slice(&color0.to_le_bytes());
+    block[2..4].copy_from_slice(&color1.to_le_bytes());
+    if color0 == color1 {
+        return block
+    }
+
+    let c0 = decode_r5g6b5(color0);
+    let c1 = decode_r5g6b5(color1);
+    let mix = |a_weight: u16, b_weight: u16| -> [u8; 4] {
+        let channel = |i: usize| ((c0[i] as u16 * a_weight + c1[i] as u16 * b_weight) / 3) as u8;
+        [channel(0), channel(1), channel(2), 0xFF]
+    };
+    let palette = [c0, c1, mix(2, 1), mix(1, 2)];
+
+    let mut indices = 0u32;
+    for (i, p) in pixels.iter().enumerate() {
+        let index = (0..4).min_by_key(|&n| squared_distance::<3>(p, &palette[n])).unwrap();
+        indices |= (index as u32) << (i * 2);
+    }
+    block[4..8].copy_from_slice(&indices.to_le_bytes());
+    block
+}
+
+/// Encode a single 8-byte interpolated channel block as used by DXT5 alpha.
+fn encode_bc4_block_channel(values: &[u8; 16]) -> [u8; 8] {
+    let a0 = *values.iter().max().unwrap();
+    let a1 = *values.iter().min().unwrap();
+
+    let mut block = [0u8; 8];
+    block[0] = a0;
+    block[1] = a1;
+    if a0 == a1 {
+        return block
+    }
+
+    let mut palette = [0u8; 8];
+    palette[0] = a0;
+    palette[1] = a1;
+    for i in 1..7 {
+        palette[i + 1] = (((7 - i as u16) * a0 as u16 + i as u16 * a1 as u16) / 7) as u8;
+    }
+
+    let mut indices = 0u64;
+    for (i, v) in values.iter().enumerate() {
+        let index = (0..8).min_by_key(|&n| (palette[n] as i16 - *v as i16).abs()).unwrap();
+        indices |= (index as u64) << (i * 3);
+    }
+    block[2..8].copy_from_slice(&indices.to_le_bytes()[..6]);
+    block
+}
+
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+struct BitWriter {
+    data: [u8; 16],
+    position: usize
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u8, bits: usize) {
+        for i in 0..bits {
+            let bit_position = self.position + i;
+            self.data[bit_position / 8] |= ((value >> i) & 1) << (bit_position % 8);
+        }
+        self.position += bits;
+    }
+}
+
+/// Quantize an endpoint to 7 bits per channel plus a p-bit shared by every channel.
+///
+/// Each channel is rounded to the closest value for both p-bits, and the p-bit with the least error is picked. Fully
+/// opaque and fully transparent alpha always picks the p-bit, so it is kept exactly (e.g. 255 is 0x7F with a p-bit
+/// of 1).
+///
+/// Returns the 7-bit channels and the p-bit.
+fn bc7_quantize_mode_6_endpoint(endpoint: &[f32; 4]) -> ([u8; 4], u8) {
+    let quantize = |p: u8| -> ([u8; 4], f32) {
+        let channels = endpoint.map(|c| libm::roundf((c - p as f32) / 2.0).clamp(0.0, 127.0) as u8);
+        let error = (0..4).map(|i| ((channels[i] << 1) | p) as f32 - endpoint[i]).map(|d| d * d).sum();
+        (channels, error)
+    };
+
+    let (channels_0, error_0) = quantize(0);
+    let (channels_1, error_1) = quantize(1);
+    let alpha = libm::roundf(endpoint[3]);
+    if alpha >= 255.0 || (alpha > 0.0 && error_1 < error_0) { (channels_1, 1) } else { (channels_0, 0) }
+}
+
+/// Quantized endpoints, indices, and total squared error of a BC7 mode 6 block.
+type Bc7Fit = ([([u8; 4], u8); 2], [u8; 16], u32);
+
+/// Pick the closest palette entry for each pixel, returning the indices and total squared error.
+fn bc7_fit_indices(pixels: &[[u8; 4]; 16], palette: &[[u8; 4]; 16]) -> ([u8; 16], u32) {
+    let mut indices = [0u8; 16];
+    let mut error = 0;
+    for (i, p) in pixels.iter().enumerate() {
+        let (index, e) = palette
+            .iter()
+            .enumerate()
+            .map(|(n, c)| (n, squared_distance::<4>(p, c)))
+            .min_by_key(|(_, e)| *e)
+            .unwrap();
+        indices[i] = index as u8;
+        error += e;
+    }
+    (indices, error)
+}
+
+/// Encode a BC7 block.
+///
+/// This always uses mode 6 (a single subset with 7-bit RGBA endpoints, unique p-bits, and 4-bit indices), refining the
+/// endpoints with a least squares fit.
+pub fn encode_bc7_block(pixels: &[[u8; 4]; 16]) -> [u8; 16] {
+    let (low, high) = principal_endpoints::<4>(pixels);
+    let mut endpoints = [low, high];
+
+    let mut best: Option<Bc7Fit> = None;
+    for _ in 0..3 {
+        let quantized = endpoints.map(|e| bc7_quantize_mode_6_endpoint(&e));
+        let [e0, e1] = quantized.map(|(channels, p)| channels.map(|c| (c << 1) | p));
+        let palette: [[u8; 4]; 16] = core::array::from_fn(|i| {
+            let weight = BC7_WEIGHTS_4[i];
+            core::array::from_fn(|c| (((64 - weight) * e0[c] as u32 + weight * e1[c] as u32 + 32) >> 6) as u8)
+        });
+
+        let (indices, error) = bc7_fit_indices(pixels, &palette);
+        if best.as_ref().is_none_or(|b| error < b.2) {
+            best = Some((quantized, indices, error));
+        }
+        if error == 0 {
+            break
+        }
+
+        // Solve for the endpoints that best fit these indices
+        let (mut aa, mut ab, mut bb) = (0.0f32, 0.0f32, 0.0f32);
+        let mut ax = [0.0f32; 4];
+        let mut bx = [0.0f32; 4];
+        for (p, &index) in pixels.iter().zip(indices.iter()) {
+            let b = BC7_WEIGHTS_4[index as usize] as f32 / 64.0;
+            let a = 1.0 - b;
+            aa += a * a;
+            ab += a * b;
+            bb += b * b;
+            for c in 0..4 {
+                ax[c] += a * p[c] as f32;
+                bx[c] += b * p[c] as f32;
+            }
+        }
+        let determinant = aa * bb - ab * ab;
+        if determinant.abs() <= f32::EPSILON {
+            break
+        }
+        endpoints = [
+            core::array::from_fn(|c| ((ax[c] * bb - bx[c] * ab) / determinant).clamp(0.0, 255.0)),
+            core::array::from_fn(|c| ((bx[c] * aa - ax[c] * ab) / determinant).clamp(0.0, 255.0))
+        ];
+    }
+
+    let (mut quantized, mut indices, _) = best.unwrap();
+
+    // The anchor index's most significant bit is implied to be 0, so swap the endpoints if it isn't.
+    if indices[0] & 0b1000 != 0 {
+        quantized.swap(0, 1);
+        indices = indices.map(|i| 15 - i);
+    }
+
+    let mut writer = BitWriter { data: [0u8; 16], position: 0 };
+    writer.write(1 << 6, 7);
+    for channel in 0..4 {
+        for (endpoint, _) in &quantized {
+            writer.write(endpoint[channel], 7);
+        }
+    }
+    for (_, p) in &quantized {
+        writer.write(*p, 1);
+    }
+    for (pixel, index) in indices.iter().enumerate() {
+        writer.write(*index, if pixel == 0 { 3 } else { 4 });
+    }
+
+    writer.data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{decode_bc5_block, decode_bc7_block, decode_dxt1_block, decode_dxt5_block};
+
+    /// Encode and decode a block, returning the largest difference of any channel of any pixel.
+    fn round_trip_error(pixels: &[[u8; 4]; 16], channels: usize, round_trip: fn(&[[u8; 4]; 16]) -> [[u8; 4]; 16]) -> u8 {
+        let decoded = round_trip(pixels);
+        pixels
+            .iter()
+            .zip(decoded.iter())
+            .flat_map(|(a, b)| (0..channels).map(|c| a[c].abs_diff(b[c])))
+            .max()
+            .unwrap()
+    }
+
+    fn dxt1(pixels: &[[u8; 4]; 16]) -> [[u8; 4]; 16] {
+        decode_dxt1_block(&encode_dxt1_block(pixels))
+    }
+
+    fn dxt5(pixels: &[[u8; 4]; 16]) -> [[u8; 4]; 16] {
+        decode_dxt5_block(&encode_dxt5_block(pixels))
+    }
+
+    fn bc5(pixels: &[[u8; 4]; 16]) -> [[u8; 4]; 16] {
+        decode_bc5_block(&encode_bc5_block(pixels))
+    }
+
+    fn bc7(pixels: &[[u8; 4]; 16]) -> [[u8; 4]; 16] {
+        decode_bc7_block(&encode_bc7_block(pixels))
+    }
+
+    #[test]
+    fn solid() {
+        let pixels = [[0x80, 0x40, 0x20, 0xFF]; 16];
+        assert!(round_trip_error(&pixels, 3, dxt1) <= 4);
+        assert!(round_trip_error(&pixels, 4, dxt5) <= 4);
+        assert_eq!(round_trip_error(&pixels, 2, bc5), 0);
+        assert!(round_trip_error(&pixels, 4, bc7) <= 1);
+
+        // Opaque alpha must stay opaque
+        assert!(dxt5(&pixels).iter().all(|p| p[3] == 0xFF));
+        assert!(bc7(&pixels).iter().all(|p| p[3] == 0xFF));
+    }
+
+    #[test]
+    fn anti_correlated_gradient() {
+        // Red goes up while green goes down
+        let pixels: [[u8; 4]; 16] = core::array::from_fn(|i| [i as u8 * 17, 255 - i as u8 * 17, 0x80, 0xFF]);
+        assert!(round_trip_error(&pixels, 3, dxt1) <= 48);
+        assert!(round_trip_error(&pixels, 3, dxt5) <= 48);
+
+        // Both ends of the gradient must be kept rather than collapsing to the middle
+        let decoded = dxt1(&pixels);
+        assert!(decoded[0][0] <= 8 && decoded[0][1] >= 247);
+        assert!(decoded[15][0] >= 247 && decoded[15][1] <= 8);
+        assert!(round_trip_error(&pixels, 2, bc5) <= 20);
+        assert!(round_trip_error(&pixels, 4, bc7) <= 4);
+    }
+
+    #[test]
+    fn two_colors() {
+        let pixels: [[u8; 4]; 16] = core::array::from_fn(|i| if (i + i / 4) % 2 == 0 { [0xFF, 0x00, 0x00, 0xFF] } else { [0x00, 0x00, 0xFF, 0xFF] });
+        assert_eq!(round_trip_error(&pixels, 3, dxt1), 0);
+        assert_eq!(round_trip_error(&pixels, 4, dxt5), 0);
+        assert_eq!(round_trip_error(&pixels, 2, bc5), 0);
+        assert!(round_trip_error(&pixels, 4, bc7) <= 1);
+    }
+
+    #[test]
+    fn alpha_only() {
+        let pixels: [[u8; 4]; 16] = core::array::from_fn(|i| [0xFF, 0xFF, 0xFF, i as u8 * 17]);
+        assert!(round_trip_error(&pixels, 4, dxt5) <= 20);
+        assert!(round_trip_error(&pixels, 4, bc7) <= 4);
+
+        // Fully transparent and fully opaque pixels must stay that way
+        let decoded = bc7(&pixels);
+        assert_eq!(decoded[0][3], 0x00);
+        assert_eq!(decoded[15][3], 0xFF);
+    }
+}
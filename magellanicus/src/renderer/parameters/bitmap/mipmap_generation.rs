@@ -0,0 +1,171 @@
+//! CPU mipmap generation.
+
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+use super::mipmap_iterator::{MipmapMetadata, MipmapTextureIterator, MipmapType};
+use super::BitmapType;
+
+/// Generate missing mipmaps for a bitmap with four 8-bit channels per pixel using a box filter.
+///
+/// `data` must contain the base map and `mipmap_count` mipmaps, in the same order as
+/// [`AddBitmapBitmapParameter::data`](super::AddBitmapBitmapParameter::data). Mipmaps are appended to `data` until it
+/// has `target_mipmap_count` mipmaps.
+///
+/// Cubemap faces are filtered independently of each other, and 3D textures are also filtered across depth slices.
+///
+/// If `srgb` is set, red, green, and blue are converted to linear space before they are averaged and back to sRGB
+/// afterwards, so mipmaps don't get darker. Alpha is always averaged as-is.
+///
+/// Returns `None` if the dimensions are 0 or `data` is not the correct length.
+pub fn generate_mipmaps_r8g8b8a8(
+    width: u32,
+    height: u32,
+    bitmap_type: BitmapType,
+    mipmap_count: u32,
+    target_mipmap_count: u32,
+    srgb: bool,
+    data: &mut Vec<u8>
+) -> Option<()> {
+    let (mipmap_type, layers) = match bitmap_type {
+        BitmapType::Dim2D => (MipmapType::TwoDimensional, 1),
+        BitmapType::Dim3D { depth } => (MipmapType::ThreeDimensional(NonZeroUsize::new(depth as usize)?), 1),
+        BitmapType::Cubemap => (MipmapType::Cubemap, 6)
+    };
+
+    let levels: Vec<MipmapMetadata> = MipmapTextureIterator::new(
+        NonZeroUsize::new(width as usize)?,
+        NonZeroUsize::new(height as usize)?,
+        mipmap_type,
+        NonZeroUsize::new(1).unwrap(),
+        Some(target_mipmap_count as usize)
+    ).collect();
+
+    let supplied_length = levels
+        .iter()
+        .take(mipmap_count as usize + 1)
+        .map(|l| l.block_count * 4)
+        .sum::<usize>();
+    if supplied_length != data.len() {
+        return None
+    }
+
+    for pair in levels.windows(2).skip(mipmap_count as usize) {
+        let [source, destination] = pair else {
+            unreachable!()
+        };
+        let source_start = source.block_offset * 4;
+        let source_end = source_start + source.block_count * 4;
+        debug_assert_eq!(destination.block_offset * 4, data.len());
+
+        let downsampled = downsample_box(
+            &data[source_start..source_end],
+            [source.width, source.height, source.depth],
+            [destination.width, destination.height, destination.depth],
+            layers,
+            srgb
+        );
+        data.extend_from_slice(&downsampled);
+    }
+
+    Some(())
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    }
+    else {
+        libm::powf((value + 0.055) / 1.055, 2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    }
+    else {
+        1.055 * libm::powf(value, 1.0 / 2.4) - 0.055
+    };
+    (value * 255.0 + 0.5).clamp(0.0, 255.0) as u8
+}
+
+fn downsample_box(source: &[u8], source_size: [usize; 3], destination_size: [usize; 3], layers: usize, srgb: bool) -> Vec<u8> {
+    let [source_width, source_height, source_depth] = source_size;
+    let [destination_width, destination_height, destination_depth] = destination_size;
+    let source_layer_length = source_width * source_height * source_depth * 4;
+
+    let mut output = Vec::with_capacity(destination_width * destination_height * destination_depth * layers * 4);
+    let to_linear: [f32; 256] = core::array::from_fn(|v| srgb_to_linear(v as u8));
+
+    for layer in source.chunks_exact(source_layer_length).take(layers) {
+        let pixel = |x: usize, y: usize, z: usize| -> &[u8] {
+            let offset = (x.min(source_width - 1) + (y.min(source_height - 1) + z.min(source_depth - 1) * source_height) * source_width) * 4;
+            &layer[offset..offset + 4]
+        };
+
+        for z in 0..destination_depth {
+            for y in 0..destination_height {
+                for x in 0..destination_width {
+                    let mut sum = [0u32; 4];
+                    let mut linear_sum = [0f32; 3];
+                    for (dx, dy, dz) in (0..8).map(|i| (i & 1, (i >> 1) & 1, i >> 2)) {
+                        let p = pixel(x * 2 + dx, y * 2 + dy, z * 2 + dz);
+                        for c in 0..4 {
+                            sum[c] += p[c] as u32;
+                        }
+                        if srgb {
+                            for (s, v) in linear_sum.iter_mut().zip(p) {
+                                *s += to_linear[*v as usize];
+                            }
+                        }
+                    }
+
+                    let mut averaged = sum.map(|s| ((s + 4) / 8) as u8);
+                    if srgb {
+                        for (a, s) in averaged.iter_mut().zip(linear_sum) {
+                            *a = linear_to_srgb(s / 8.0);
+                        }
+                    }
+                    output.extend(averaged);
+                }
+            }
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn srgb_round_trips() {
+        for v in 0..=255u8 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(v)), v);
+        }
+    }
+
+    #[test]
+    fn averages_srgb_in_linear_space() {
+        // Black and white with half alpha
+        let pixels = [[0x00, 0x00, 0x00, 0x00], [0xFF, 0xFF, 0xFF, 0xFF]].concat();
+
+        let mut gamma = pixels.clone();
+        generate_mipmaps_r8g8b8a8(2, 1, BitmapType::Dim2D, 0, 1, false, &mut gamma).unwrap();
+        assert_eq!(gamma[8..], [128, 128, 128, 128]);
+
+        // Linear 0.5 is sRGB 188, but alpha is still averaged as-is
+        let mut linear = pixels.clone();
+        generate_mipmaps_r8g8b8a8(2, 1, BitmapType::Dim2D, 0, 1, true, &mut linear).unwrap();
+        assert_eq!(linear[8..], [188, 188, 188, 128]);
+    }
+
+    #[test]
+    fn rejects_wrong_length() {
+        let mut data = vec![0u8; 12];
+        assert!(generate_mipmaps_r8g8b8a8(2, 2, BitmapType::Dim2D, 0, 1, false, &mut data).is_none());
+    }
+}
@@ -4,11 +4,16 @@ use alloc::format;
 use alloc::borrow::ToOwned;
 use alloc::vec;
 use glam::Vec3;
-use crate::error::{Error, MResult};
-use crate::renderer::data::{Bitmap, Shader, ShaderType};
-use crate::renderer::Renderer;
+use crate::error::{Error, MResult, ResourceKind};
+use crate::renderer::{ResourceLookup, ShaderType, ValidationLocation, ValidationReport};
+use crate::renderer::parameters::geometry::collect_mesh_issues;
 use crate::vertex::{LightmapVertex, ModelTriangle, ModelVertex};
 
+/// How far the length of a plane normal may be from 1 before it is considered non-normalized.
+const PLANE_NORMAL_LENGTH_TOLERANCE: f32 = 0.001;
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AddBSPParameter {
     /// Path to the bitmap.
     ///
@@ -22,6 +27,8 @@ pub struct AddBSPParameter {
     pub bsp_data: BSPData
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AddBSPParameterLightmapSet {
     /// The bitmap index of the lightmap.
     ///
@@ -34,6 +41,8 @@ pub struct AddBSPParameterLightmapSet {
     pub materials: Vec<AddBSPParameterLightmapMaterial>
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AddBSPParameterLightmapMaterial {
     /// Describes pipeline vertices.
     pub shader_vertices: Vec<ModelVertex>,
@@ -51,59 +60,109 @@ pub struct AddBSPParameterLightmapMaterial {
 }
 
 impl AddBSPParameter {
-    pub(crate) fn validate(&self, renderer: &Renderer) -> MResult<()> {
-        let lightmap_bitmap: Option<(&Bitmap, &str)> = if let Some(path) = self.lightmap_bitmap.as_ref() {
-            let Some(bitmap) = renderer.bitmaps.get(path) else {
-                return Err(Error::from_data_error_string(format!("BSP refers to lightmap bitmap {path} which is not loaded in the renderer")))
-            };
-            Some((bitmap, path))
-        }
-        else {
-            None
+    pub(crate) fn validate(&self, resources: &impl ResourceLookup) -> MResult<()> {
+        ValidationReport::check(|report| self.collect_issues(resources, report))
+    }
+
+    /// Check the BSP against the loaded resources and return every issue found.
+    ///
+    /// Errors are the same problems that [`Renderer::add_bsp`](crate::renderer::Renderer::add_bsp) rejects. Warnings
+    /// are for suspicious data that can still be added, such as degenerate triangles, empty materials, and clusters no
+    /// leaf belongs to.
+    pub fn validation_report(&self, resources: &impl ResourceLookup) -> ValidationReport {
+        ValidationReport::collect(|report| self.collect_issues(resources, report))
+    }
+
+    fn collect_issues(&self, resources: &impl ResourceLookup, report: &mut ValidationReport) -> MResult<()> {
+        let lightmap_bitmap: Option<(usize, &str)> = match self.lightmap_bitmap.as_ref() {
+            Some(path) => match resources.bitmap_count(path) {
+                Some(bitmap_count) => Some((bitmap_count, path)),
+                None => {
+                    report.error(ValidationLocation::default(), Error::missing_dependency(ResourceKind::Bitmap, path.as_str(), format!("BSP refers to lightmap bitmap {path} which is not loaded in the renderer")))?;
+                    None
+                }
+            },
+            None => None
         };
 
         for (lightmap_index, lightmap) in self.lightmap_sets.iter().enumerate() {
+            let location = ValidationLocation { lightmap_set: Some(lightmap_index), ..Default::default() };
+
             if let Some(bitmap_index) = lightmap.lightmap_index {
-                let Some((bitmap, path)) = lightmap_bitmap else {
-                    return Err(Error::from_data_error_string(format!("BSP lightmap #{lightmap_index} has a bitmap index, but no lightmap bitmap is set")))
-                };
-                let bitmap_count = bitmap.bitmaps.len();
-                if bitmap_index >= bitmap_count {
-                    return Err(Error::from_data_error_string(format!("BSP lightmap #{lightmap_index} refers to bitmap #{bitmap_index}, but the referenced bitmap {path} has only {bitmap_count} bitmap(s)")))
+                match lightmap_bitmap {
+                    Some((bitmap_count, path)) if bitmap_index >= bitmap_count => {
+                        report.error(location, Error::invalid_index("bitmap", bitmap_index, bitmap_count, format!("BSP lightmap #{lightmap_index} refers to bitmap #{bitmap_index}, but the referenced bitmap {path} has only {bitmap_count} bitmap(s)")))?;
+                    },
+                    None if self.lightmap_bitmap.is_none() => {
+                        report.error(location, Error::from_data_error_string(format!("BSP lightmap #{lightmap_index} has a bitmap index, but no lightmap bitmap is set")))?;
+                    },
+
+                    // Either in bounds or the missing bitmap was already reported
+                    _ => ()
                 }
             }
 
             for (material_index, material) in lightmap.materials.iter().enumerate() {
-                let vertex_count = material.shader_vertices.len();
-                if let Some(lightmap_vertex_count) = material.lightmap_vertices.as_ref().map(|v| v.len()) {
-                    if lightmap_vertex_count != vertex_count {
-                        return Err(Error::from_data_error_string(format!("BSP material #{material_index} of lightmap #{lightmap_index} has a pipeline vertex count of {vertex_count}, but a lightmap vertex count of {lightmap_vertex_count}")))
-                    }
-                    if lightmap_bitmap.is_none() {
-                        return Err(Error::from_data_error_string(format!("BSP material #{material_index} of lightmap #{lightmap_index} has lightmap vertices when no lightmap bitmap is set")))
-                    }
-                }
+                let location = ValidationLocation { material: Some(material_index), ..location };
+                material.collect_issues(resources, lightmap_index, material_index, self.lightmap_bitmap.is_some(), location, report)?;
+            }
+        }
 
-                let shader_path = &material.shader;
-                let Some(Shader { shader_type, .. }) = renderer.shaders.get(shader_path) else {
-                    return Err(Error::from_data_error_string(format!("BSP material #{material_index} of lightmap #{lightmap_index} references pipeline {shader_path} which is not loaded")))
-                };
+        self.bsp_data.collect_issues(resources, self, report)
+    }
+}
 
-                // No reason we can't actually render this on a BSP, but these tags are intended to
-                // only be rendered on objects.
-                if *shader_type == ShaderType::Model {
-                    return Err(Error::from_data_error_string(format!("BSP material #{material_index} of lightmap #{lightmap_index} references pipeline {shader_path}, a {shader_type:?} type which isn't allowed for BSPs")))
-                }
+impl AddBSPParameterLightmapMaterial {
+    fn collect_issues(
+        &self,
+        resources: &impl ResourceLookup,
+        lightmap_index: usize,
+        material_index: usize,
+        has_lightmap_bitmap: bool,
+        location: ValidationLocation,
+        report: &mut ValidationReport
+    ) -> MResult<()> {
+        let vertex_count = self.shader_vertices.len();
+        if let Some(lightmap_vertex_count) = self.lightmap_vertices.as_ref().map(|v| v.len()) {
+            if lightmap_vertex_count != vertex_count {
+                report.error(location, Error::from_data_error_string(format!("BSP material #{material_index} of lightmap #{lightmap_index} has a pipeline vertex count of {vertex_count}, but a lightmap vertex count of {lightmap_vertex_count}")))?;
+            }
+            if !has_lightmap_bitmap {
+                report.error(location, Error::from_data_error_string(format!("BSP material #{material_index} of lightmap #{lightmap_index} has lightmap vertices when no lightmap bitmap is set")))?;
             }
         }
 
-        self.bsp_data.validate(renderer, self)?;
+        let shader_path = &self.shader;
+        match resources.shader_type(shader_path) {
+            None => {
+                report.error(location, Error::missing_dependency(ResourceKind::Shader, shader_path.as_str(), format!("BSP material #{material_index} of lightmap #{lightmap_index} references pipeline {shader_path} which is not loaded")))?;
+            },
+
+            // No reason we can't actually render this on a BSP, but these tags are intended to
+            // only be rendered on objects.
+            Some(shader_type) if shader_type == ShaderType::Model => {
+                report.error(location, Error::from_data_error_string(format!("BSP material #{material_index} of lightmap #{lightmap_index} references pipeline {shader_path}, a {shader_type:?} type which isn't allowed for BSPs")))?;
+            },
+            Some(_) => ()
+        }
 
-        Ok(())
+        if self.surfaces.is_empty() {
+            report.warning(location, || Error::from_data_error_string(format!("BSP material #{material_index} of lightmap #{lightmap_index} has no surfaces")));
+        }
+
+        collect_mesh_issues(
+            &format!("BSP material #{material_index} of lightmap #{lightmap_index}"),
+            &self.shader_vertices,
+            self.lightmap_vertices.as_deref(),
+            &self.surfaces,
+            location,
+            report
+        )
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BSPData {
     pub nodes: Vec<BSP3DNode>,
     pub planes: Vec<BSP3DPlane>,
@@ -125,6 +184,7 @@ impl Default for BSPData {
 }
 
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BSP3DNode {
     pub front_child: Option<BSP3DNodeChild>,
     pub back_child: Option<BSP3DNodeChild>,
@@ -132,11 +192,13 @@ pub struct BSP3DNode {
 }
 
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BSPLeaf {
     pub cluster: usize
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BSPCluster {
     pub sky: Option<String>,
     pub subclusters: Vec<BSPSubcluster>,
@@ -144,6 +206,7 @@ pub struct BSPCluster {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BSPSubcluster {
     pub surface_indices: Vec<usize>,
     pub world_bounds_from: [f32; 3],
@@ -151,6 +214,7 @@ pub struct BSPSubcluster {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BSPPortal {
     pub front_cluster: usize,
     pub back_cluster: usize
@@ -158,6 +222,7 @@ pub struct BSPPortal {
 
 
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BSP3DNodeChild {
     Node(usize),
     Leaf(usize)
@@ -178,6 +243,7 @@ impl BSP3DNodeChild {
 }
 
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BSP3DPlane {
     pub angle: [f32; 3],
     pub offset: f32
@@ -210,18 +276,45 @@ impl BSPData {
         }
     }
 
-    fn validate(&self, renderer: &Renderer, full_parameter: &AddBSPParameter) -> MResult<()> {
+    fn collect_issues(&self, resources: &impl ResourceLookup, full_parameter: &AddBSPParameter, report: &mut ValidationReport) -> MResult<()> {
         if self.nodes.is_empty() {
-            return Err(Error::from_data_error_string("No nodes present".to_owned()))
+            report.error(ValidationLocation::default(), Error::from_data_error_string("No nodes present".to_owned()))?;
+        }
+
+        for (index, plane) in self.planes.iter().enumerate() {
+            let location = ValidationLocation { plane: Some(index), ..Default::default() };
+            let normal = Vec3::from(plane.angle);
+            if !normal.is_finite() || !plane.offset.is_finite() {
+                report.error(location, Error::from_data_error_string(format!("Plane #{index} has a non-finite normal or offset")))?;
+            }
+            else if (normal.length() - 1.0).abs() > PLANE_NORMAL_LENGTH_TOLERANCE {
+                report.error(location, Error::from_data_error_string(format!("Plane #{index} has a non-normalized normal (length {})", normal.length())))?;
+            }
         }
 
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.plane >= self.planes.len() {
+                report.error(ValidationLocation { node: Some(index), ..Default::default() }, Error::invalid_index("plane", node.plane, self.planes.len(), format!("Node #{index} points to plane #{} which does not exist", node.plane)))?;
+            }
+        }
+
+        // A broken node tree would report the same problem from every node above it, so stop at the first one.
         let mut tested_nodes = alloc::vec![false; self.nodes.len()];
         for (index, _node) in self.nodes.iter().enumerate() {
-            self.validate_3d_node(index, self.nodes.len() + 3, &mut tested_nodes)?;
+            if let Err(error) = self.validate_3d_node(index, self.nodes.len() + 3, &mut tested_nodes) {
+                report.error(ValidationLocation { node: Some(index), ..Default::default() }, error)?;
+                break
+            }
         }
+
+        let mut cluster_used = alloc::vec![false; self.clusters.len()];
         for (index, leaf) in self.leaves.iter().enumerate() {
-            if leaf.cluster >= self.clusters.len() {
-                return Err(Error::from_data_error_string(format!("Leaf #{index} points to cluster #{} which does not exist", leaf.cluster)))
+            match cluster_used.get_mut(leaf.cluster) {
+                Some(used) => *used = true,
+                None => report.error(
+                    ValidationLocation { leaf: Some(index), ..Default::default() },
+                    Error::invalid_index("cluster", leaf.cluster, self.clusters.len(), format!("Leaf #{index} points to cluster #{} which does not exist", leaf.cluster))
+                )?
             }
         }
 
@@ -234,26 +327,34 @@ impl BSPData {
             .count();
 
         for (index, cluster) in self.clusters.iter().enumerate() {
+            let location = ValidationLocation { cluster: Some(index), ..Default::default() };
+
             if let Some(sky) = cluster.sky.as_ref() {
-                if !renderer.skies.contains_key(sky) {
-                    return Err(Error::from_data_error_string(format!("Cluster #{index} points to sky {sky} which has not been loaded")))
+                if !resources.contains(ResourceKind::Sky, sky) {
+                    report.error(location, Error::missing_dependency(ResourceKind::Sky, sky.as_str(), format!("Cluster #{index} points to sky {sky} which has not been loaded")))?;
                 }
             }
             for (sc_index, subcluster) in cluster.subclusters.iter().enumerate() {
                 if subcluster.surface_indices.iter().any(|i| *i >= total_surface_count) {
-                    return Err(Error::from_data_error_string(format!("Subcluster {sc_index} of cluster #{index} points to an out-of-bounds surface (there are {total_surface_count} surfaces)")))
+                    report.error(location, Error::from_data_error_string(format!("Subcluster {sc_index} of cluster #{index} points to an out-of-bounds surface (there are {total_surface_count} surfaces)")))?;
                 }
             }
-            for (p_index, _portal) in cluster.cluster_portals.iter().enumerate() {
-                if p_index >= self.portals.len() {
-                    return Err(Error::from_data_error_string(format!("Portal {p_index} of cluster #{index} points to an out-of-bounds portal (there are {} surfaces)", self.portals.len())))
+            for (p_index, portal) in cluster.cluster_portals.iter().enumerate() {
+                if *portal >= self.portals.len() {
+                    report.error(location, Error::invalid_index("portal", *portal, self.portals.len(), format!("Cluster portal {p_index} of cluster #{index} points to portal #{portal} which does not exist")))?;
                 }
             }
+            if !cluster_used[index] {
+                report.warning(location, || Error::from_data_error_string(format!("Cluster #{index} is not used by any leaf")));
+            }
         }
 
         for (p_index, portal) in self.portals.iter().enumerate() {
             if portal.front_cluster >= self.clusters.len() || portal.back_cluster >= self.clusters.len() {
-                return Err(Error::from_data_error_string(format!("Portal {p_index} points to an out-of-bounds cluster (there are {} surfaces)", self.clusters.len())))
+                report.error(
+                    ValidationLocation { portal: Some(p_index), ..Default::default() },
+                    Error::invalid_index("cluster", portal.front_cluster.max(portal.back_cluster), self.clusters.len(), format!("Portal {p_index} points to an out-of-bounds cluster (there are {} clusters)", self.clusters.len()))
+                )?;
             }
         }
 
@@ -291,16 +392,68 @@ impl BSPData {
         match child {
             BSP3DNodeChild::Node(n) => {
                 if n >= self.nodes.len() {
-                    return Err(Error::from_data_error_string(format!("broken BSP: node #{n}, referenced by node #{node}, does not exist")))
+                    return Err(Error::invalid_index("node", n, self.nodes.len(), format!("broken BSP: node #{n}, referenced by node #{node}, does not exist")))
                 }
                 self.validate_3d_node(n, remaining_tests, nodes_tested)?;
             }
             BSP3DNodeChild::Leaf(n) => {
                 if n >= self.leaves.len() {
-                    return Err(Error::from_data_error_string(format!("broken BSP: leaf #{n}, referenced by node #{node}, does not exist")))
+                    return Err(Error::invalid_index("leaf", n, self.leaves.len(), format!("broken BSP: leaf #{n}, referenced by node #{node}, does not exist")))
                 }
             }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "serde")]
+    #[test]
+    fn bsps_round_trip_through_postcard() {
+        use super::*;
+        let vertex = |position| ModelVertex { position, normal: [0.0, 0.0, 1.0], binormal: [0.0, 1.0, 0.0], tangent: [1.0, 0.0, 0.0], texture_coords: [0.25, 0.75] };
+        let bsp = AddBSPParameter {
+            lightmap_bitmap: Some("lightmaps".to_owned()),
+            lightmap_sets: vec![AddBSPParameterLightmapSet {
+                lightmap_index: Some(1),
+                materials: vec![AddBSPParameterLightmapMaterial {
+                    shader_vertices: vec![vertex([0.0, 0.0, 0.0]), vertex([1.0, 0.0, 0.0]), vertex([0.0, 1.0, 0.0])],
+                    lightmap_vertices: Some(vec![LightmapVertex { lightmap_texture_coords: [0.5, 0.5] }; 3]),
+                    surfaces: vec![ModelTriangle { indices: [0, 1, 2] }],
+                    shader: "shader".to_owned()
+                }]
+            }],
+            bsp_data: BSPData {
+                nodes: vec![BSP3DNode { front_child: Some(BSP3DNodeChild::Leaf(0)), back_child: Some(BSP3DNodeChild::Node(1)), plane: 0 }],
+                planes: vec![BSP3DPlane { angle: [0.0, 0.0, 1.0], offset: -2.5 }],
+                leaves: vec![BSPLeaf { cluster: 0 }],
+                clusters: vec![BSPCluster {
+                    sky: Some("sky".to_owned()),
+                    subclusters: vec![BSPSubcluster { surface_indices: vec![0], world_bounds_from: [0.0; 3], world_bounds_to: [1.0; 3] }],
+                    cluster_portals: vec![0]
+                }],
+                portals: vec![BSPPortal { front_cluster: 0, back_cluster: 0 }]
+            }
+        };
+
+        let bytes = postcard::to_allocvec(&bsp).unwrap();
+        let decoded: AddBSPParameter = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(postcard::to_allocvec(&decoded).unwrap(), bytes);
+
+        assert_eq!(decoded.lightmap_bitmap.as_deref(), Some("lightmaps"));
+        let material = &decoded.lightmap_sets[0].materials[0];
+        assert_eq!(decoded.lightmap_sets[0].lightmap_index, Some(1));
+        assert_eq!(material.shader_vertices[1].position, [1.0, 0.0, 0.0]);
+        assert_eq!(material.lightmap_vertices.as_ref().map(|v| v.len()), Some(3));
+        assert_eq!(material.surfaces[0].indices, [0, 1, 2]);
+
+        let bsp_data = &decoded.bsp_data;
+        assert!(matches!(bsp_data.nodes[0].front_child, Some(BSP3DNodeChild::Leaf(0))));
+        assert!(matches!(bsp_data.nodes[0].back_child, Some(BSP3DNodeChild::Node(1))));
+        assert_eq!(bsp_data.planes[0].offset, -2.5);
+        assert_eq!(bsp_data.clusters[0].sky.as_deref(), Some("sky"));
+        assert_eq!(bsp_data.clusters[0].subclusters[0].world_bounds_to, [1.0; 3]);
+        assert_eq!(bsp_data.find_cluster([0.0, 0.0, 0.0]), Some(0));
+    }
+}
@@ -0,0 +1,74 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::renderer::MSAA;
+
+/// Describes which physical device to render with.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub enum DeviceSelection {
+    /// Pick the most capable device, preferring discrete GPUs, then integrated GPUs, then virtual GPUs, then software
+    /// rasterizers.
+    #[default]
+    Automatic,
+
+    /// Use the device at the given index of [`Renderer::enumerate_devices`](crate::renderer::Renderer::enumerate_devices).
+    Index(usize),
+
+    /// Use the first suitable device whose name contains this string, ignoring case.
+    Name(String)
+}
+
+/// Type of physical device.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DeviceType {
+    DiscreteGpu,
+    IntegratedGpu,
+    VirtualGpu,
+
+    /// Software rasterizer (e.g. lavapipe or SwiftShader).
+    Cpu,
+    Other
+}
+
+/// Describes a physical device that can be selected with [`DeviceSelection`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct DeviceInfo {
+    /// Index of the device, for use with [`DeviceSelection::Index`].
+    pub index: usize,
+
+    /// Name of the device.
+    pub name: String,
+
+    /// Type of the device.
+    pub device_type: DeviceType,
+
+    /// Name of the driver, if reported.
+    pub driver_name: Option<String>,
+
+    /// Driver version information, if reported.
+    pub driver_info: Option<String>,
+
+    /// Highest Vulkan version supported as (major, minor, patch).
+    ///
+    /// This is `None` if unknown, which is always the case with the wgpu backend.
+    pub api_version: Option<(u32, u32, u32)>,
+
+    /// Total size of device-local memory heaps, in bytes.
+    ///
+    /// For integrated GPUs and software rasterizers, this is usually shared with system memory. This is `None` if
+    /// unknown, which is always the case with the wgpu backend.
+    pub device_local_memory: Option<u64>,
+
+    /// Block-compressed bitmaps can be sampled without decoding them on the CPU.
+    pub supports_block_compression: bool,
+
+    /// Maximum anisotropic filtering, or `None` if anisotropic filtering is unsupported.
+    pub max_anisotropic_filtering: Option<f32>,
+
+    /// MSAA levels that can be used.
+    pub supported_msaa: Vec<MSAA>,
+
+    /// The device meets the renderer's minimum requirements.
+    ///
+    /// Whether it can present to a particular window is only known when initializing the renderer.
+    pub suitable: bool
+}
@@ -0,0 +1,163 @@
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::error::{Error, MResult, ResourceKind};
+use crate::renderer::{AddBitmapBitmapParameter, BitmapType, OverlayBlendMode, ResourceLookup};
+
+#[derive(Clone)]
+pub struct AddFontParameter {
+    /// Bitmap containing every glyph of the font.
+    ///
+    /// This must be a 2D texture.
+    pub bitmap: AddBitmapBitmapParameter,
+
+    /// All characters of the font.
+    pub characters: Vec<AddFontCharacter>,
+
+    /// Height of the font above the baseline in pixels.
+    pub ascending_height: u32,
+
+    /// Height of the font below the baseline in pixels.
+    pub descending_height: u32,
+
+    /// Additional spacing between lines in pixels.
+    pub leading_height: u32
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct AddFontCharacter {
+    /// Character this glyph is for.
+    pub character: char,
+
+    /// Number of pixels to advance the cursor by after drawing this character.
+    pub advance: i32,
+
+    /// Position of the top-left corner of the glyph in the bitmap in pixels.
+    pub bitmap_position: [u32; 2],
+
+    /// Width and height of the glyph in the bitmap in pixels.
+    ///
+    /// This may be zero for characters with no visible pixels (e.g. spaces).
+    pub bitmap_size: [u32; 2],
+
+    /// Position of the cursor relative to the top-left corner of the glyph in pixels.
+    ///
+    /// The cursor sits on the baseline.
+    pub origin: [i32; 2]
+}
+
+impl AddFontParameter {
+    pub(crate) fn validate(&self) -> MResult<()> {
+        self.bitmap.validate(0).map_err(|e| match e {
+            Error::InvalidBitmapDimensions { bitmap_index, error } => Error::InvalidBitmapDimensions { bitmap_index, error: format!("Font bitmap is invalid: {error}") },
+            Error::DataError { error } => Error::from_data_error_string(format!("Font bitmap is invalid: {error}")),
+            e => e
+        })?;
+        if self.bitmap.bitmap_type != BitmapType::Dim2D {
+            return Err(Error::from_data_error_string(format!("Font bitmap is {:?}, expected {:?}", self.bitmap.bitmap_type, BitmapType::Dim2D)))
+        }
+
+        let width = self.bitmap.resolution.width as u64;
+        let height = self.bitmap.resolution.height as u64;
+
+        let mut characters_found = BTreeSet::new();
+        for (index, c) in self.characters.iter().enumerate() {
+            let character = c.character;
+            if !characters_found.insert(character) {
+                return Err(Error::from_data_error_string(format!("Font character #{index} ({character:?}) is a duplicate")))
+            }
+
+            let [x, y] = c.bitmap_position;
+            let [w, h] = c.bitmap_size;
+            if x as u64 + w as u64 > width || y as u64 + h as u64 > height {
+                return Err(Error::from_data_error_string(format!("Font character #{index} ({character:?}) is out of bounds ({w}x{h} at {x},{y}; bitmap is {width}x{height})")))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Describes text to draw on top of a viewport.
+///
+/// Like [`DrawSpriteParameter`](crate::renderer::DrawSpriteParameter), this is positioned on the HUD canvas.
+///
+/// Characters not in the font are skipped.
+#[derive(Clone)]
+pub struct DrawTextParameter {
+    /// Path to the font.
+    ///
+    /// This font MUST already be imported.
+    pub font: String,
+
+    /// Text to draw.
+    ///
+    /// Line feeds (`\n`) start a new line.
+    pub text: String,
+
+    /// Position of the top-left corner of the text box on the canvas.
+    pub position: [f32; 2],
+
+    /// Width of the text box in canvas units.
+    ///
+    /// If set, lines that don't fit are wrapped at word boundaries, and alignment is relative to the text box.
+    /// Otherwise, lines are never wrapped, and alignment is relative to `position`.
+    pub max_width: Option<f32>,
+
+    /// Horizontal alignment of each line.
+    pub alignment: TextAlignment,
+
+    /// Color of the text (RGBA).
+    pub color: [f32; 4],
+
+    /// Scale of the text.
+    ///
+    /// At 1.0, one pixel of the font takes up one canvas unit.
+    pub scale: f32,
+
+    /// Determines how the text is blended with what is underneath it.
+    pub blend_mode: OverlayBlendMode
+}
+
+impl Default for DrawTextParameter {
+    fn default() -> Self {
+        Self {
+            font: String::new(),
+            text: String::new(),
+            position: [0.0, 0.0],
+            max_width: None,
+            alignment: TextAlignment::default(),
+            color: [1.0, 1.0, 1.0, 1.0],
+            scale: 1.0,
+            blend_mode: OverlayBlendMode::default()
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum TextAlignment {
+    #[default]
+    Left,
+    Center,
+    Right
+}
+
+impl DrawTextParameter {
+    pub(crate) fn validate(&self, resources: &impl ResourceLookup) -> MResult<()> {
+        let path = &self.font;
+        if !resources.contains(ResourceKind::Font, path) {
+            return Err(Error::missing_dependency(ResourceKind::Font, path.as_str(), format!("Text refers to font {path} which is not loaded")))
+        }
+
+        if self.position.iter().chain(self.color.iter()).chain(self.max_width.iter()).any(|f| !f.is_finite()) {
+            return Err(Error::from_data_error_string("Text has a non-finite position, color, or width".into()))
+        }
+
+        if !(self.scale.is_finite() && self.scale > 0.0) {
+            return Err(Error::from_data_error_string(format!("Text has an invalid scale {}", self.scale)))
+        }
+
+        Ok(())
+    }
+}
@@ -1,3 +1,65 @@
+use alloc::format;
+use glam::Vec3;
+use crate::error::{Error, MResult};
+use crate::renderer::{ValidationLocation, ValidationReport};
+use crate::vertex::{LightmapVertex, ModelTriangle, ModelVertex};
+
 pub struct AddGeometryParameter {
 
 }
+
+/// Check a mesh for out-of-bounds indices and non-finite vertex data, which can fault the GPU, as well as degenerate
+/// triangles.
+///
+/// `name` describes the mesh in messages (e.g. `"BSP material #2 of lightmap #0"`), and `location` is where the mesh
+/// is in its parameter. Surface and vertex indices are filled in for each issue.
+pub(crate) fn collect_mesh_issues(
+    name: &str,
+    vertices: &[ModelVertex],
+    lightmap_vertices: Option<&[LightmapVertex]>,
+    triangles: &[ModelTriangle],
+    location: ValidationLocation,
+    report: &mut ValidationReport
+) -> MResult<()> {
+    for (vertex_index, vertex) in vertices.iter().enumerate() {
+        let ModelVertex { position, normal, binormal, tangent, texture_coords } = vertex;
+        let finite = position
+            .iter()
+            .chain(normal)
+            .chain(binormal)
+            .chain(tangent)
+            .chain(texture_coords)
+            .all(|f| f.is_finite());
+
+        if !finite {
+            let location = ValidationLocation { vertex: Some(vertex_index), ..location };
+            report.error(location, Error::from_data_error_string(format!("Vertex #{vertex_index} of {name} has a non-finite position, normal, binormal, tangent, or texture coordinate")))?;
+        }
+    }
+
+    for (vertex_index, vertex) in lightmap_vertices.unwrap_or_default().iter().enumerate() {
+        if !vertex.lightmap_texture_coords.iter().all(|f| f.is_finite()) {
+            let location = ValidationLocation { vertex: Some(vertex_index), ..location };
+            report.error(location, Error::from_data_error_string(format!("Lightmap vertex #{vertex_index} of {name} has non-finite texture coordinates")))?;
+        }
+    }
+
+    let vertex_count = vertices.len();
+    for (surface_index, triangle) in triangles.iter().enumerate() {
+        let location = ValidationLocation { surface: Some(surface_index), ..location };
+        let [a, b, c] = triangle.indices;
+
+        if let Some(&index) = triangle.indices.iter().find(|i| **i as usize >= vertex_count) {
+            report.error(location, Error::invalid_index("vertex", index as usize, vertex_count, format!("Surface #{surface_index} of {name} refers to vertex #{index}, but there are only {vertex_count} vertices")))?;
+            continue
+        }
+
+        let [pa, pb, pc] = triangle.indices.map(|i| Vec3::from(vertices[i as usize].position));
+        let degenerate = a == b || b == c || a == c || (pb - pa).cross(pc - pa).length_squared() == 0.0;
+        if degenerate {
+            report.warning(location, || Error::from_data_error_string(format!("Surface #{surface_index} of {name} is degenerate ({a},{b},{c})")));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,22 @@
+use alloc::sync::Arc;
+
+/// Severity of a log message.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LogSeverity {
+    /// Diagnostic information that is only useful for debugging the renderer or driver.
+    Verbose,
+
+    /// Informational messages.
+    Info,
+
+    /// Something may not render correctly.
+    Warning,
+
+    /// Something went wrong, such as a validation error.
+    Error
+}
+
+/// Receives log messages from the renderer and, if enabled, the Vulkan validation layer.
+///
+/// This may be called from driver threads, so it must be thread-safe.
+pub type LogCallback = Arc<dyn Fn(LogSeverity, &str) + Send + Sync>;
@@ -0,0 +1,122 @@
+use alloc::format;
+use alloc::string::String;
+use crate::error::{Error, MResult, ResourceKind};
+use crate::renderer::{BitmapType, ResourceLookup, SequenceFrame};
+
+/// Default width of the virtual canvas overlays are positioned on.
+pub const HUD_CANVAS_WIDTH: f32 = 640.0;
+
+/// Default height of the virtual canvas overlays are positioned on.
+pub const HUD_CANVAS_HEIGHT: f32 = 480.0;
+
+/// Describes a sprite to draw on top of a viewport.
+///
+/// Positions are in HUD canvas units. The canvas is [`HUD_CANVAS_WIDTH`]x[`HUD_CANVAS_HEIGHT`] unless changed with
+/// [`Renderer::set_hud_canvas`](crate::renderer::Renderer::set_hud_canvas), and is scaled uniformly to fit inside the
+/// viewport, centered, so overlays keep their aspect ratio regardless of the viewport's shape.
+#[derive(Clone)]
+pub struct DrawSpriteParameter {
+    /// Path to the bitmap.
+    ///
+    /// This bitmap MUST already be imported.
+    pub bitmap: String,
+
+    /// Sequence index of the bitmap.
+    pub sequence: usize,
+
+    /// Index of the sprite in the sequence.
+    ///
+    /// For bitmap sequences, this is relative to the first bitmap of the sequence.
+    pub sprite: usize,
+
+    /// If set, cycle through the sequence at this many frames per second instead of drawing `sprite`.
+    ///
+    /// This is driven by [`Renderer::get_time`](crate::renderer::Renderer::get_time).
+    pub frames_per_second: Option<f32>,
+
+    /// Position of the top-left corner of the sprite on the canvas.
+    pub position: [f32; 2],
+
+    /// Scale of the sprite.
+    ///
+    /// At 1.0, one texel of the sprite takes up one canvas unit.
+    pub scale: [f32; 2],
+
+    /// Color to multiply the sprite by (RGBA).
+    pub tint: [f32; 4],
+
+    /// Determines how the sprite is blended with what is underneath it.
+    pub blend_mode: OverlayBlendMode
+}
+
+impl Default for DrawSpriteParameter {
+    fn default() -> Self {
+        Self {
+            bitmap: String::new(),
+            sequence: 0,
+            sprite: 0,
+            frames_per_second: None,
+            position: [0.0, 0.0],
+            scale: [1.0, 1.0],
+            tint: [1.0, 1.0, 1.0, 1.0],
+            blend_mode: OverlayBlendMode::default()
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+#[repr(u32)]
+pub enum OverlayBlendMode {
+    /// Blend using the alpha channel.
+    #[default]
+    AlphaBlend,
+
+    /// Add the color (weighted by alpha) to what is underneath.
+    Add,
+
+    /// Multiply what is underneath by the color (weighted by alpha).
+    Multiply
+}
+
+impl DrawSpriteParameter {
+    pub(crate) fn sequence_frame(&self) -> SequenceFrame {
+        match self.frames_per_second {
+            Some(frames_per_second) => SequenceFrame::Animated { frames_per_second },
+            None => SequenceFrame::Index(self.sprite)
+        }
+    }
+
+    pub(crate) fn validate(&self, resources: &impl ResourceLookup) -> MResult<()> {
+        let path = &self.bitmap;
+        let Some(sequences) = resources.bitmap_sequences(path) else {
+            return Err(Error::missing_dependency(ResourceKind::Bitmap, path.as_str(), format!("Sprite refers to bitmap {path} which is not loaded")))
+        };
+
+        let sequence_index = self.sequence;
+        let Some(sequence) = sequences.get(sequence_index) else {
+            return Err(Error::invalid_index("sequence", sequence_index, sequences.len(), format!("Sprite refers to sequence #{sequence_index} of {path}, but it only has {} sequence(s)", sequences.len())))
+        };
+
+        let frame_count = sequence.frame_count();
+        if let Some(error) = self.sequence_frame().validation_error(frame_count) {
+            return Err(Error::from_data_error_string(format!("Sprite refers to sequence #{sequence_index} of {path}, but {error}")))
+        }
+
+        // Every frame can be drawn if animated, so check them all.
+        for frame in 0..frame_count {
+            let (bitmap_index, _) = sequence.get_frame(frame).unwrap();
+            let Some(bitmap_type) = resources.bitmap_type(path, bitmap_index) else {
+                return Err(Error::invalid_index("bitmap", bitmap_index, resources.bitmap_count(path).unwrap_or(0), format!("Frame #{frame} of sequence #{sequence_index} of {path} refers to bitmap #{bitmap_index} which does not exist")))
+            };
+            if bitmap_type != BitmapType::Dim2D {
+                return Err(Error::from_data_error_string(format!("Sprite refers to bitmap #{bitmap_index} of {path} which is {bitmap_type:?}, expected {:?}", BitmapType::Dim2D)))
+            }
+        }
+
+        if self.position.iter().chain(self.scale.iter()).chain(self.tint.iter()).any(|f| !f.is_finite()) {
+            return Err(Error::from_data_error_string("Sprite has a non-finite position, scale, or tint".into()))
+        }
+
+        Ok(())
+    }
+}
@@ -1,32 +1,48 @@
 use alloc::string::String;
 use alloc::format;
-use crate::error::{Error, MResult};
-pub use crate::renderer::data::ShaderType;
-use crate::renderer::{BitmapType, Renderer};
-use crate::renderer::data::Bitmap;
+use crate::error::{Error, MResult, ResourceKind};
+use crate::renderer::{BitmapType, ResourceLookup, SequenceFrame, ValidationLocation, ValidationReport};
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AddShaderParameter {
     pub data: AddShaderData
 }
 
 impl AddShaderParameter {
-    pub(crate) fn validate(&self, renderer: &Renderer) -> MResult<()> {
+    /// Get the type of shader this is.
+    pub(crate) fn shader_type(&self) -> ShaderType {
         match &self.data {
-            AddShaderData::BasicShader(AddShaderBasicShaderData { bitmap, .. }) => {
+            AddShaderData::BasicShader(s) => s.shader_type,
+            AddShaderData::ShaderEnvironment(_) => ShaderType::Environment
+        }
+    }
+
+    pub(crate) fn validate(&self, resources: &impl ResourceLookup) -> MResult<()> {
+        match &self.data {
+            AddShaderData::BasicShader(AddShaderBasicShaderData { bitmap, sequence, frame, .. }) => {
                 if let Some(bitmap) = bitmap {
-                    if !renderer.bitmaps.contains_key(bitmap) {
-                        return Err(Error::DataError { error: format!("Referenced bitmap {bitmap} is not loaded.") })
+                    let Some(sequences) = resources.bitmap_sequences(bitmap) else {
+                        return Err(Error::missing_dependency(ResourceKind::Bitmap, bitmap.as_str(), format!("Referenced bitmap {bitmap} is not loaded.")))
+                    };
+                    let Some(s) = sequences.get(*sequence) else {
+                        return Err(Error::invalid_index("sequence", *sequence, sequences.len(), format!("Referenced bitmap {bitmap} has no sequence #{sequence} (only {} sequence(s))", sequences.len())))
+                    };
+                    if let Some(error) = frame.validation_error(s.frame_count()) {
+                        return Err(Error::DataError { error: format!("Referenced bitmap {bitmap} sequence #{sequence} is invalid: {error}") })
                     }
                 }
             },
             AddShaderData::ShaderEnvironment(shader_data) => {
-                shader_data.validate(renderer)?;
+                shader_data.validate(resources)?;
             }
         }
         Ok(())
     }
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AddShaderData {
     /// Basic pipeline that just renders a single texture. This does not map to an actual tag group
     /// and is to be removed once all shaders are implemented
@@ -36,13 +52,38 @@ pub enum AddShaderData {
     ShaderEnvironment(AddShaderEnvironmentShaderData)
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AddShaderBasicShaderData {
     pub bitmap: Option<String>,
+
+    /// Sequence of `bitmap` to use.
+    pub sequence: usize,
+
+    /// Frame of the sequence to use.
+    ///
+    /// For sprite sequences, the whole bitmap of the sprite is used.
+    pub frame: SequenceFrame,
+
     pub shader_type: ShaderType,
     pub alpha_tested: bool
 }
 
+#[derive(PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShaderType {
+    Environment,
+    Model,
+    TransparentGeneric,
+    TransparentChicago,
+    TransparentGlass,
+    TransparentMeter,
+    TransparentPlasma,
+    TransparentWater
+}
+
 #[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 pub enum ShaderEnvironmentType {
     Normal,
@@ -51,6 +92,7 @@ pub enum ShaderEnvironmentType {
 }
 
 #[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 pub enum ShaderEnvironmentMapFunction {
     DoubleBiasedMultiply,
@@ -59,6 +101,7 @@ pub enum ShaderEnvironmentMapFunction {
 }
 
 #[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 pub enum ShaderReflectionType {
     BumpedCubeMap,
@@ -66,7 +109,50 @@ pub enum ShaderReflectionType {
     BumpedRadiosity
 }
 
+impl TryFrom<u32> for ShaderEnvironmentType {
+    type Error = Error;
+
+    /// Convert from the value stored in shader_environment tags.
+    fn try_from(value: u32) -> MResult<Self> {
+        match value {
+            0 => Ok(Self::Normal),
+            1 => Ok(Self::Blended),
+            2 => Ok(Self::BlendedBaseSpecular),
+            n => Err(Error::from_data_error_string(format!("Invalid shader environment type {n}")))
+        }
+    }
+}
+
+impl TryFrom<u32> for ShaderEnvironmentMapFunction {
+    type Error = Error;
+
+    /// Convert from the value stored in shader_environment tags.
+    fn try_from(value: u32) -> MResult<Self> {
+        match value {
+            0 => Ok(Self::DoubleBiasedMultiply),
+            1 => Ok(Self::Multiply),
+            2 => Ok(Self::DoubleBiasedAdd),
+            n => Err(Error::from_data_error_string(format!("Invalid detail map function {n}")))
+        }
+    }
+}
+
+impl TryFrom<u32> for ShaderReflectionType {
+    type Error = Error;
+
+    /// Convert from the value stored in shader_environment tags.
+    fn try_from(value: u32) -> MResult<Self> {
+        match value {
+            0 => Ok(Self::BumpedCubeMap),
+            1 => Ok(Self::FlatCubeMap),
+            2 => Ok(Self::BumpedRadiosity),
+            n => Err(Error::from_data_error_string(format!("Invalid reflection type {n}")))
+        }
+    }
+}
+
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AddShaderEnvironmentShaderData {
     pub alpha_tested: bool,
     pub bump_map_is_specular_mask: bool,
@@ -74,6 +160,14 @@ pub struct AddShaderEnvironmentShaderData {
     pub shader_environment_type: ShaderEnvironmentType,
     pub base_map: Option<String>,
 
+    /// Sequence of `base_map` to use.
+    pub base_map_sequence: usize,
+
+    /// Frame of the base map's sequence to use.
+    ///
+    /// For sprite sequences, the whole bitmap of the sprite is used.
+    pub base_map_frame: SequenceFrame,
+
     pub detail_map_function: ShaderEnvironmentMapFunction,
     pub primary_detail_map: Option<String>,
     pub primary_detail_map_scale: f32,
@@ -96,36 +190,163 @@ pub struct AddShaderEnvironmentShaderData {
     pub parallel_brightness: f32,
 }
 impl AddShaderEnvironmentShaderData {
-    pub(crate) fn validate(&self, renderer: &Renderer) -> MResult<()> {
-        check_bitmap(renderer, &self.base_map, BitmapType::Dim2D, "base map")?;
-        check_bitmap(renderer, &self.primary_detail_map, BitmapType::Dim2D, "primary detail map")?;
-        check_bitmap(renderer, &self.secondary_detail_map, BitmapType::Dim2D, "secondary detail map")?;
-        check_bitmap(renderer, &self.micro_detail_map, BitmapType::Dim2D, "micro detail map")?;
-        check_bitmap(renderer, &self.bump_map, BitmapType::Dim2D, "bump map")?;
-        check_bitmap(renderer, &self.reflection_cube_map, BitmapType::Cubemap, "reflection cube map")?;
+    pub(crate) fn validate(&self, resources: &impl ResourceLookup) -> MResult<()> {
+        ValidationReport::check(|report| self.collect_issues(resources, report))
+    }
+
+    /// Check the shader against the loaded bitmaps and return every issue found.
+    ///
+    /// Errors are the same problems that [`Renderer::add_shader`](crate::renderer::Renderer::add_shader) rejects.
+    /// Warnings are for suspicious data that can still be added, such as maps that are scaled to nothing.
+    pub fn validation_report(&self, resources: &impl ResourceLookup) -> ValidationReport {
+        ValidationReport::collect(|report| self.collect_issues(resources, report))
+    }
+
+    fn collect_issues(&self, resources: &impl ResourceLookup, report: &mut ValidationReport) -> MResult<()> {
+        let maps = [
+            (&self.base_map, BitmapType::Dim2D, "base map", None),
+            (&self.primary_detail_map, BitmapType::Dim2D, "primary detail map", Some(self.primary_detail_map_scale)),
+            (&self.secondary_detail_map, BitmapType::Dim2D, "secondary detail map", Some(self.secondary_detail_map_scale)),
+            (&self.micro_detail_map, BitmapType::Dim2D, "micro detail map", Some(self.micro_detail_map_scale)),
+            (&self.bump_map, BitmapType::Dim2D, "bump map", Some(self.bump_map_scale)),
+            (&self.reflection_cube_map, BitmapType::Cubemap, "reflection cube map", None)
+        ];
+
+        for (reference, bitmap_type, name, scale) in maps {
+            if let Err(error) = check_bitmap(resources, reference, bitmap_type, name) {
+                report.error(ValidationLocation::default(), error)?;
+            }
+
+            // A scale of 0 samples a single texel of the map everywhere
+            if reference.is_some() && scale == Some(0.0) {
+                report.warning(ValidationLocation::default(), || Error::from_data_error_string(format!("The {name} has a scale of 0")));
+            }
+        }
+
+        // Maps that are not loaded were already reported above
+        if let Some((bitmap, sequences)) = self.base_map.as_ref().and_then(|b| resources.bitmap_sequences(b).map(|s| (b, s))) {
+            let sequence = self.base_map_sequence;
+            match sequences.get(sequence) {
+                Some(s) => if let Some(error) = self.base_map_frame.validation_error(s.frame_count()) {
+                    report.error(ValidationLocation::default(), Error::DataError { error: format!("Referenced bitmap {bitmap} sequence #{sequence} is invalid: {error}") })?;
+                },
+                None => report.error(ValidationLocation::default(), Error::invalid_index("sequence", sequence, sequences.len(), format!("Referenced bitmap {bitmap} has no sequence #{sequence} (only {} sequence(s))", sequences.len())))?
+            }
+        }
+
         Ok(())
     }
 }
 
-fn check_bitmap(renderer: &Renderer, reference: &Option<String>, bitmap_type: BitmapType, name: &str) -> MResult<()> {
+fn check_bitmap(resources: &impl ResourceLookup, reference: &Option<String>, bitmap_type: BitmapType, name: &str) -> MResult<()> {
     let Some(bitmap_path) = reference.as_ref() else {
         return Ok(())
     };
 
-    let Some(bitmap) = renderer.bitmaps.get(bitmap_path) else {
-        return Err(Error::from_data_error_string(format!("{name} {bitmap_path} is not loaded")))
+    let Some(bitmap_count) = resources.bitmap_count(bitmap_path) else {
+        return Err(Error::missing_dependency(ResourceKind::Bitmap, bitmap_path.as_str(), format!("{name} {bitmap_path} is not loaded")))
     };
 
-    expect_bitmap_or_else(bitmap, bitmap_type, name)
+    expect_bitmap_or_else(resources, bitmap_path, bitmap_count, bitmap_type, name)
 }
 
-fn expect_bitmap_or_else(bitmap: &Bitmap, bitmap_type: BitmapType, name: &str) -> MResult<()> {
-    let Some((bad_index, bad_bitmap)) = bitmap.bitmaps
-        .iter()
-        .enumerate()
-        .find(|a| a.1.bitmap_type != bitmap_type) else {
+fn expect_bitmap_or_else(resources: &impl ResourceLookup, bitmap_path: &str, bitmap_count: usize, bitmap_type: BitmapType, name: &str) -> MResult<()> {
+    let Some((bad_index, bad_type)) = (0..bitmap_count)
+        .filter_map(|i| Some((i, resources.bitmap_type(bitmap_path, i)?)))
+        .find(|a| a.1 != bitmap_type) else {
         return Ok(())
     };
 
-    Err(Error::from_data_error_string(format!("Bitmap #{bad_index} of {name} is {:?}, expected {bitmap_type:?}", bad_bitmap.bitmap_type)))
+    Err(Error::from_data_error_string(format!("Bitmap #{bad_index} of {name} is {bad_type:?}, expected {bitmap_type:?}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "serde")]
+    use alloc::borrow::ToOwned;
+
+    #[test]
+    fn shader_environment_enums_round_trip() {
+        for value in 0..3 {
+            assert_eq!(ShaderEnvironmentType::try_from(value).unwrap() as u32, value);
+            assert_eq!(ShaderEnvironmentMapFunction::try_from(value).unwrap() as u32, value);
+            assert_eq!(ShaderReflectionType::try_from(value).unwrap() as u32, value);
+        }
+    }
+
+    #[test]
+    fn invalid_shader_environment_enums_are_errors() {
+        for value in [3, 0x7FFFFFFF, u32::MAX] {
+            assert!(matches!(ShaderEnvironmentType::try_from(value), Err(Error::DataError { .. })));
+            assert!(matches!(ShaderEnvironmentMapFunction::try_from(value), Err(Error::DataError { .. })));
+            assert!(matches!(ShaderReflectionType::try_from(value), Err(Error::DataError { .. })));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn shaders_round_trip_through_postcard() {
+        let basic = AddShaderParameter {
+            data: AddShaderData::BasicShader(AddShaderBasicShaderData {
+                bitmap: Some("basic".to_owned()),
+                sequence: 2,
+                frame: SequenceFrame::Animated { frames_per_second: 15.0 },
+                shader_type: ShaderType::TransparentWater,
+                alpha_tested: true
+            })
+        };
+        let environment = AddShaderParameter {
+            data: AddShaderData::ShaderEnvironment(AddShaderEnvironmentShaderData {
+                alpha_tested: false,
+                bump_map_is_specular_mask: true,
+                shader_environment_type: ShaderEnvironmentType::BlendedBaseSpecular,
+                base_map: Some("base".to_owned()),
+                base_map_sequence: 1,
+                base_map_frame: SequenceFrame::Index(3),
+                detail_map_function: ShaderEnvironmentMapFunction::DoubleBiasedAdd,
+                primary_detail_map: Some("primary".to_owned()),
+                primary_detail_map_scale: 2.0,
+                secondary_detail_map: None,
+                secondary_detail_map_scale: 0.0,
+                micro_detail_map: Some("micro".to_owned()),
+                micro_detail_map_scale: 8.0,
+                micro_detail_map_function: ShaderEnvironmentMapFunction::Multiply,
+                bump_map: Some("bump".to_owned()),
+                bump_map_scale: 1.5,
+                reflection_cube_map: Some("cube".to_owned()),
+                reflection_type: ShaderReflectionType::BumpedRadiosity,
+                perpendicular_color: [0.1, 0.2, 0.3],
+                perpendicular_brightness: 0.4,
+                parallel_color: [0.5, 0.6, 0.7],
+                parallel_brightness: 0.8
+            })
+        };
+
+        let round_trip = |shader: &AddShaderParameter| {
+            let bytes = postcard::to_allocvec(shader).unwrap();
+            let decoded: AddShaderParameter = postcard::from_bytes(&bytes).unwrap();
+            assert_eq!(postcard::to_allocvec(&decoded).unwrap(), bytes);
+            decoded
+        };
+
+        let AddShaderData::BasicShader(basic) = round_trip(&basic).data else {
+            panic!("basic shader decoded as a different type")
+        };
+        assert_eq!(basic.bitmap.as_deref(), Some("basic"));
+        assert_eq!(basic.sequence, 2);
+        assert_eq!(basic.frame, SequenceFrame::Animated { frames_per_second: 15.0 });
+        assert_eq!(basic.shader_type, ShaderType::TransparentWater);
+        assert!(basic.alpha_tested);
+
+        let AddShaderData::ShaderEnvironment(environment) = round_trip(&environment).data else {
+            panic!("shader_environment decoded as a different type")
+        };
+        assert!(environment.shader_environment_type == ShaderEnvironmentType::BlendedBaseSpecular);
+        assert!(environment.micro_detail_map_function == ShaderEnvironmentMapFunction::Multiply);
+        assert!(environment.reflection_type == ShaderReflectionType::BumpedRadiosity);
+        assert_eq!(environment.secondary_detail_map, None);
+        assert_eq!(environment.reflection_cube_map.as_deref(), Some("cube"));
+        assert_eq!(environment.parallel_color, [0.5, 0.6, 0.7]);
+    }
 }
@@ -1,8 +1,10 @@
 use alloc::format;
 use alloc::string::String;
-use crate::error::{Error, MResult};
-use crate::renderer::Renderer;
+use crate::error::{Error, MResult, ResourceKind};
+use crate::renderer::ResourceLookup;
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AddSkyParameter {
     pub geometry: Option<String>,
 
@@ -18,7 +20,7 @@ pub struct AddSkyParameter {
 }
 
 impl AddSkyParameter {
-    pub(crate) fn validate(&self, renderer: &Renderer) -> MResult<()> {
+    pub(crate) fn validate(&self, resources: &impl ResourceLookup) -> MResult<()> {
         if !(0.0..=1.0).contains(&self.outdoor_fog_maximum_density) {
             return Err(Error::from_data_error_string(format!("Outdoor fog density is {} which is not between 0 and 1", self.outdoor_fog_maximum_density)))
         }
@@ -32,8 +34,8 @@ impl AddSkyParameter {
             return Err(Error::from_data_error_string(format!("Indoor fog starting distance is {} which is not between 0.0 and {} (opaque distance)", self.indoor_fog_maximum_density, self.indoor_fog_opaque_distance)))
         }
         if let Some(s) = self.geometry.as_ref() {
-            if !renderer.geometries.contains_key(s) {
-                return Err(Error::from_data_error_string(format!("Fog references skybox geometry {s} which is not loaded")))
+            if !resources.contains(ResourceKind::Geometry, s) {
+                return Err(Error::missing_dependency(ResourceKind::Geometry, s.as_str(), format!("Fog references skybox geometry {s} which is not loaded")))
             }
         }
         Ok(())
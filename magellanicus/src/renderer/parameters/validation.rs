@@ -0,0 +1,463 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use crate::error::{Error, MResult, ResourceKind};
+use crate::renderer::{AddBitmapParameter, AddBitmapSequenceParameter, BitmapType, ShaderType};
+
+/// Severity of a [`ValidationIssue`].
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ValidationSeverity {
+    /// The data is legal but suspicious (e.g. degenerate triangles). It can still be added.
+    Warning,
+
+    /// The data is invalid and will be rejected.
+    Error
+}
+
+/// Where in a parameter a [`ValidationIssue`] was found.
+///
+/// Fields that don't apply to the issue are `None`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct ValidationLocation {
+    /// Index of the bitmap in [`AddBitmapParameter::bitmaps`](crate::renderer::AddBitmapParameter::bitmaps).
+    pub bitmap: Option<usize>,
+
+    /// Index of the sequence in [`AddBitmapParameter::sequences`](crate::renderer::AddBitmapParameter::sequences).
+    pub sequence: Option<usize>,
+
+    /// Index of the lightmap set in [`AddBSPParameter::lightmap_sets`](crate::renderer::AddBSPParameter::lightmap_sets).
+    pub lightmap_set: Option<usize>,
+
+    /// Index of the material in [`AddBSPParameterLightmapSet::materials`](crate::renderer::AddBSPParameterLightmapSet::materials).
+    pub material: Option<usize>,
+
+    /// Index of the triangle in [`AddBSPParameterLightmapMaterial::surfaces`](crate::renderer::AddBSPParameterLightmapMaterial::surfaces).
+    pub surface: Option<usize>,
+
+    /// Index of the vertex in [`AddBSPParameterLightmapMaterial::shader_vertices`](crate::renderer::AddBSPParameterLightmapMaterial::shader_vertices).
+    pub vertex: Option<usize>,
+
+    /// Index of the node in [`BSPData::nodes`](crate::renderer::BSPData::nodes).
+    pub node: Option<usize>,
+
+    /// Index of the plane in [`BSPData::planes`](crate::renderer::BSPData::planes).
+    pub plane: Option<usize>,
+
+    /// Index of the leaf in [`BSPData::leaves`](crate::renderer::BSPData::leaves).
+    pub leaf: Option<usize>,
+
+    /// Index of the cluster in [`BSPData::clusters`](crate::renderer::BSPData::clusters).
+    pub cluster: Option<usize>,
+
+    /// Index of the portal in [`BSPData::portals`](crate::renderer::BSPData::portals).
+    pub portal: Option<usize>
+}
+
+impl Display for ValidationLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let parts = [
+            ("bitmap", self.bitmap),
+            ("sequence", self.sequence),
+            ("lightmap set", self.lightmap_set),
+            ("material", self.material),
+            ("surface", self.surface),
+            ("vertex", self.vertex),
+            ("node", self.node),
+            ("plane", self.plane),
+            ("leaf", self.leaf),
+            ("cluster", self.cluster),
+            ("portal", self.portal)
+        ];
+
+        let mut first = true;
+        for (name, index) in parts {
+            let Some(index) = index else {
+                continue
+            };
+            if !first {
+                f.write_str(", ")?;
+            }
+            write!(f, "{name} #{index}")?;
+            first = false;
+        }
+
+        if first {
+            f.write_str("(root)")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single problem found when validating a parameter.
+#[derive(Clone, Debug)]
+pub struct ValidationIssue {
+    pub severity: ValidationSeverity,
+    pub location: ValidationLocation,
+    pub error: Error
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?} at {}: {}", self.severity, self.location, self.error)
+    }
+}
+
+/// Every problem found when validating a parameter, in the order they were found.
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+
+    /// Stop at the first error and skip warnings, for when only the result is needed.
+    fail_fast: bool
+}
+
+impl ValidationReport {
+    /// Return `true` if there are no errors. Warnings are allowed.
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// Get all issues with [`ValidationSeverity::Error`].
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|i| i.severity == ValidationSeverity::Error)
+    }
+
+    /// Get all issues with [`ValidationSeverity::Warning`].
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|i| i.severity == ValidationSeverity::Warning)
+    }
+
+    /// Return the first error, if any.
+    pub fn into_result(self) -> MResult<()> {
+        match self.issues.into_iter().find(|i| i.severity == ValidationSeverity::Error) {
+            Some(issue) => Err(issue.error),
+            None => Ok(())
+        }
+    }
+
+    /// Collect every issue into a report.
+    pub(crate) fn collect(collect_issues: impl FnOnce(&mut Self) -> MResult<()>) -> Self {
+        let mut report = Self::default();
+
+        // Only fail-fast reports return errors
+        let _ = collect_issues(&mut report);
+        report
+    }
+
+    /// Return the first error without collecting the rest or building any warnings.
+    pub(crate) fn check(collect_issues: impl FnOnce(&mut Self) -> MResult<()>) -> MResult<()> {
+        collect_issues(&mut Self { issues: Vec::new(), fail_fast: true })
+    }
+
+    /// Record an error.
+    ///
+    /// Errs if the report fails fast, in which case the caller should stop checking.
+    pub(crate) fn error(&mut self, location: ValidationLocation, error: Error) -> MResult<()> {
+        if self.fail_fast {
+            return Err(error)
+        }
+        self.issues.push(ValidationIssue { severity: ValidationSeverity::Error, location, error });
+        Ok(())
+    }
+
+    /// Record a warning. `error` is not called if the report fails fast.
+    pub(crate) fn warning(&mut self, location: ValidationLocation, error: impl FnOnce() -> Error) {
+        if !self.fail_fast {
+            self.issues.push(ValidationIssue { severity: ValidationSeverity::Warning, location, error: error() });
+        }
+    }
+}
+
+/// Already-loaded resources that parameters can refer to by path.
+///
+/// Validation checks references against this. [`Renderer`](crate::renderer::Renderer) implements it for the
+/// resources loaded into it, and [`LoadedResources`] can be filled in to validate without a renderer.
+pub trait ResourceLookup {
+    /// Get the number of bitmaps in a loaded bitmap, or `None` if it is not loaded.
+    fn bitmap_count(&self, path: &str) -> Option<usize>;
+
+    /// Get the type of a bitmap of a loaded bitmap, or `None` if it is not loaded or `bitmap` is out of bounds.
+    fn bitmap_type(&self, path: &str, bitmap: usize) -> Option<BitmapType>;
+
+    /// Get the sequences of a loaded bitmap, or `None` if it is not loaded.
+    fn bitmap_sequences(&self, path: &str) -> Option<&[AddBitmapSequenceParameter]>;
+
+    /// Get the type of a loaded shader, or `None` if it is not loaded.
+    fn shader_type(&self, path: &str) -> Option<ShaderType>;
+
+    /// Return `true` if a resource of the given kind is loaded at `path`.
+    fn contains(&self, kind: ResourceKind, path: &str) -> bool;
+}
+
+/// A loaded bitmap as far as validation is concerned.
+#[derive(Clone, Default)]
+pub struct LoadedBitmap {
+    pub bitmap_types: Vec<BitmapType>,
+    pub sequences: Vec<AddBitmapSequenceParameter>
+}
+
+impl From<&AddBitmapParameter> for LoadedBitmap {
+    fn from(value: &AddBitmapParameter) -> Self {
+        Self {
+            bitmap_types: value.bitmaps.iter().map(|b| b.bitmap_type).collect(),
+            sequences: value.sequences.clone()
+        }
+    }
+}
+
+/// Set of resources for validating parameters without a renderer (e.g. in tag-processing tools).
+///
+/// Add each resource as it would be added to a renderer so later parameters can refer to it.
+#[derive(Clone, Default)]
+pub struct LoadedResources {
+    pub bitmaps: BTreeMap<String, LoadedBitmap>,
+    pub shaders: BTreeMap<String, ShaderType>,
+    pub geometries: BTreeSet<String>,
+    pub skies: BTreeSet<String>,
+    pub bsps: BTreeSet<String>,
+    pub fonts: BTreeSet<String>
+}
+
+impl ResourceLookup for LoadedResources {
+    fn bitmap_count(&self, path: &str) -> Option<usize> {
+        self.bitmaps.get(path).map(|b| b.bitmap_types.len())
+    }
+
+    fn bitmap_type(&self, path: &str, bitmap: usize) -> Option<BitmapType> {
+        self.bitmaps.get(path)?.bitmap_types.get(bitmap).copied()
+    }
+
+    fn bitmap_sequences(&self, path: &str) -> Option<&[AddBitmapSequenceParameter]> {
+        self.bitmaps.get(path).map(|b| b.sequences.as_slice())
+    }
+
+    fn shader_type(&self, path: &str) -> Option<ShaderType> {
+        self.shaders.get(path).copied()
+    }
+
+    fn contains(&self, kind: ResourceKind, path: &str) -> bool {
+        match kind {
+            ResourceKind::Bitmap => self.bitmaps.contains_key(path),
+            ResourceKind::Shader => self.shaders.contains_key(path),
+            ResourceKind::Geometry => self.geometries.contains(path),
+            ResourceKind::Sky => self.skies.contains(path),
+            ResourceKind::BSP => self.bsps.contains(path),
+            ResourceKind::Font => self.fonts.contains(path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::borrow::ToOwned;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use crate::renderer::*;
+    use crate::vertex::{LightmapVertex, ModelTriangle, ModelVertex};
+
+    fn test_bitmap(bitmap_count: usize, sequences: Vec<AddBitmapSequenceParameter>) -> AddBitmapParameter {
+        AddBitmapParameter { bitmaps: vec![test_bitmap_bitmap(); bitmap_count], sequences }
+    }
+
+    fn test_sprite(bitmap: usize) -> BitmapSprite {
+        BitmapSprite { bitmap, top: 0.0, left: 0.0, bottom: 1.0, right: 1.0 }
+    }
+
+    fn test_bsp(lightmap_bitmap: Option<&str>, shader: &str) -> AddBSPParameter {
+        AddBSPParameter {
+            lightmap_bitmap: lightmap_bitmap.map(|b| b.to_owned()),
+            lightmap_sets: vec![AddBSPParameterLightmapSet {
+                lightmap_index: None,
+                materials: vec![AddBSPParameterLightmapMaterial {
+                    shader_vertices: Vec::new(),
+                    lightmap_vertices: None,
+                    surfaces: Vec::new(),
+                    shader: shader.to_owned()
+                }]
+            }],
+            bsp_data: BSPData::default()
+        }
+    }
+
+    fn test_vertex(position: [f32; 3]) -> ModelVertex {
+        ModelVertex { position, normal: [0.0, 0.0, 1.0], binormal: [0.0, 1.0, 0.0], tangent: [1.0, 0.0, 0.0], texture_coords: [0.0, 0.0] }
+    }
+
+    /// BSP with one material that uses the `environment` shader, which `resources` has.
+    fn test_mesh_bsp(shader_vertices: Vec<ModelVertex>, surfaces: Vec<[u16; 3]>) -> (AddBSPParameter, LoadedResources) {
+        let mut bsp = test_bsp(None, "environment");
+        let material = &mut bsp.lightmap_sets[0].materials[0];
+        material.shader_vertices = shader_vertices;
+        material.surfaces = surfaces.into_iter().map(|indices| ModelTriangle { indices }).collect();
+
+        let mut resources = LoadedResources::default();
+        resources.shaders.insert("environment".to_owned(), ShaderType::Environment);
+        (bsp, resources)
+    }
+
+    fn material_location() -> ValidationLocation {
+        ValidationLocation { lightmap_set: Some(0), material: Some(0), ..Default::default() }
+    }
+
+    #[test]
+    fn report_collects_errors_and_warnings_with_locations() {
+        let bitmap = test_bitmap(2, vec![
+            AddBitmapSequenceParameter::Bitmap { first: 0, count: 1 },
+            AddBitmapSequenceParameter::Sprites { sprites: vec![test_sprite(5)] }
+        ]);
+        let report = bitmap.validation_report();
+        assert!(!report.is_valid());
+
+        let errors: Vec<&ValidationIssue> = report.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location, ValidationLocation { bitmap: Some(5), sequence: Some(1), ..Default::default() });
+        assert!(matches!(errors[0].error, Error::InvalidIndex { kind: "bitmap", index: 5, count: 2, .. }), "{:?}", errors[0].error);
+
+        let warnings: Vec<&ValidationIssue> = report.warnings().collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].location, ValidationLocation { bitmap: Some(1), ..Default::default() });
+        assert_eq!(warnings[0].to_string(), "Warning at bitmap #1: Data error: Bitmap #1 is not used by any sequence");
+    }
+
+    #[test]
+    fn warnings_do_not_fail_validation() {
+        let bitmap = test_bitmap(2, vec![AddBitmapSequenceParameter::Bitmap { first: 0, count: 1 }]);
+        let report = bitmap.validation_report();
+        assert!(report.is_valid());
+        assert_eq!(report.warnings().count(), 1);
+        assert!(report.into_result().is_ok());
+        assert!(bitmap.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_returns_first_error_of_report() {
+        let mut resources = LoadedResources::default();
+        resources.shaders.insert("model".to_owned(), ShaderType::Model);
+        let bsp = test_bsp(Some("lightmaps"), "model");
+
+        let report = bsp.validation_report(&resources);
+        let errors: Vec<&ValidationIssue> = report.errors().collect();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(&errors[0].error, Error::MissingDependency { kind: ResourceKind::Bitmap, path, .. } if path == "lightmaps"));
+        assert_eq!(errors[1].location, ValidationLocation { lightmap_set: Some(0), material: Some(0), ..Default::default() });
+        assert!(report.warnings().any(|w| w.location.material == Some(0)));
+
+        let error = bsp.validate(&resources).unwrap_err();
+        assert_eq!(error.to_string(), errors[0].error.to_string());
+
+        resources.bitmaps.insert("lightmaps".to_owned(), LoadedBitmap::default());
+        let error = bsp.validate(&resources).unwrap_err();
+        assert_eq!(error.to_string(), "Data error: BSP material #0 of lightmap #0 references pipeline model, a Model type which isn't allowed for BSPs");
+
+        resources.shaders.insert("model".to_owned(), ShaderType::Environment);
+        assert!(bsp.validate(&resources).is_ok());
+        assert!(!bsp.validation_report(&resources).issues.is_empty());
+    }
+
+    #[test]
+    fn location_lists_only_set_indices() {
+        assert_eq!(ValidationLocation::default().to_string(), "(root)");
+        assert_eq!(ValidationLocation { lightmap_set: Some(1), surface: Some(7), ..Default::default() }.to_string(), "lightmap set #1, surface #7");
+    }
+
+    #[test]
+    fn non_finite_vertices_are_errors() {
+        let (mut bsp, mut resources) = test_mesh_bsp(
+            vec![test_vertex([0.0, 0.0, 0.0]), test_vertex([f32::NAN, 0.0, 0.0]), test_vertex([0.0, 1.0, 0.0])],
+            vec![[0, 1, 2]]
+        );
+        let errors: Vec<ValidationIssue> = bsp.validation_report(&resources).errors().cloned().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location, ValidationLocation { vertex: Some(1), ..material_location() });
+
+        bsp.lightmap_bitmap = Some("lightmaps".to_owned());
+        bsp.lightmap_sets[0].materials[0].shader_vertices[1] = test_vertex([1.0, 0.0, 0.0]);
+        bsp.lightmap_sets[0].materials[0].lightmap_vertices = Some(vec![
+            LightmapVertex { lightmap_texture_coords: [0.0, 0.0] },
+            LightmapVertex { lightmap_texture_coords: [0.0, 0.0] },
+            LightmapVertex { lightmap_texture_coords: [f32::INFINITY, 0.0] }
+        ]);
+        resources.bitmaps.insert("lightmaps".to_owned(), LoadedBitmap::default());
+        let errors: Vec<ValidationIssue> = bsp.validation_report(&resources).errors().cloned().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location, ValidationLocation { vertex: Some(2), ..material_location() });
+        assert!(errors[0].error.to_string().contains("Lightmap vertex #2"), "{:?}", errors[0].error);
+    }
+
+    #[test]
+    fn out_of_range_triangle_indices_are_errors() {
+        let (bsp, resources) = test_mesh_bsp(
+            vec![test_vertex([0.0, 0.0, 0.0]), test_vertex([1.0, 0.0, 0.0]), test_vertex([0.0, 1.0, 0.0])],
+            vec![[0, 1, 2], [0, 1, 3]]
+        );
+        let report = bsp.validation_report(&resources);
+        let errors: Vec<&ValidationIssue> = report.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location, ValidationLocation { surface: Some(1), ..material_location() });
+        assert!(matches!(errors[0].error, Error::InvalidIndex { kind: "vertex", index: 3, count: 3, .. }), "{:?}", errors[0].error);
+
+        // Out-of-range triangles aren't also checked for being degenerate.
+        assert_eq!(report.warnings().count(), 0);
+    }
+
+    #[test]
+    fn non_normalized_planes_are_errors() {
+        let (mut bsp, resources) = test_mesh_bsp(Vec::new(), Vec::new());
+        bsp.bsp_data.planes = vec![
+            BSP3DPlane { angle: [0.0, 1.0005, 0.0], offset: 0.0 },
+            BSP3DPlane { angle: [0.0, 2.0, 0.0], offset: 0.0 },
+            BSP3DPlane { angle: [0.0, 1.0, 0.0], offset: f32::INFINITY }
+        ];
+
+        let report = bsp.validation_report(&resources);
+        let errors: Vec<&ValidationIssue> = report.errors().collect();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].location, ValidationLocation { plane: Some(1), ..Default::default() });
+        assert_eq!(errors[0].error.to_string(), "Data error: Plane #1 has a non-normalized normal (length 2)");
+        assert_eq!(errors[1].location, ValidationLocation { plane: Some(2), ..Default::default() });
+        assert_eq!(errors[1].error.to_string(), "Data error: Plane #2 has a non-finite normal or offset");
+    }
+
+    #[test]
+    fn degenerate_triangles_are_warnings() {
+        let (bsp, resources) = test_mesh_bsp(
+            vec![test_vertex([0.0, 0.0, 0.0]), test_vertex([1.0, 0.0, 0.0]), test_vertex([2.0, 0.0, 0.0]), test_vertex([0.0, 1.0, 0.0])],
+            vec![[0, 1, 3], [0, 0, 3], [0, 1, 2]]
+        );
+        let report = bsp.validation_report(&resources);
+        assert!(report.is_valid());
+
+        let warnings: Vec<&ValidationIssue> = report.warnings().collect();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].location, ValidationLocation { surface: Some(1), ..material_location() });
+        assert_eq!(warnings[0].error.to_string(), "Data error: Surface #1 of BSP material #0 of lightmap #0 is degenerate (0,0,3)");
+        assert_eq!(warnings[1].location, ValidationLocation { surface: Some(2), ..material_location() });
+        assert!(bsp.validate(&resources).is_ok());
+    }
+
+    #[test]
+    fn cluster_portals_are_checked_by_portal_index() {
+        let (mut bsp, resources) = test_mesh_bsp(Vec::new(), Vec::new());
+        bsp.bsp_data.leaves = vec![BSPLeaf { cluster: 0 }];
+        bsp.bsp_data.portals = vec![BSPPortal { front_cluster: 0, back_cluster: 0 }];
+
+        // Portal #1 doesn't exist even though the first cluster portal is at index 0.
+        bsp.bsp_data.clusters = vec![BSPCluster { sky: None, subclusters: Vec::new(), cluster_portals: vec![1] }];
+        let report = bsp.validation_report(&resources);
+        let errors: Vec<&ValidationIssue> = report.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location, ValidationLocation { cluster: Some(0), ..Default::default() });
+        assert!(matches!(errors[0].error, Error::InvalidIndex { kind: "portal", index: 1, count: 1, .. }), "{:?}", errors[0].error);
+
+        // Portal #0 exists even though it's the second cluster portal.
+        bsp.bsp_data.clusters[0].cluster_portals = vec![0, 0];
+        assert!(bsp.validate(&resources).is_ok());
+
+        bsp.bsp_data.portals[0].back_cluster = 1;
+        let error = bsp.validate(&resources).unwrap_err();
+        assert_eq!(error.to_string(), "Data error: Portal 0 points to an out-of-bounds cluster (there are 1 clusters)");
+    }
+}
@@ -0,0 +1,494 @@
+use alloc::borrow::{Cow, ToOwned};
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::size_of;
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, MResult, ResourceKind};
+use crate::renderer::{AddBSPParameter, AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter, AddShaderParameter, AddSkyParameter, BitmapType, Renderer, RendererBackend, ResourceLookup, ShaderType};
+
+/// Identifies a scene package.
+pub const SCENE_PACKAGE_MAGIC: [u8; 8] = *b"MGSCENE\0";
+
+/// Current version of the scene package format.
+///
+/// Packages of any other version are rejected, as the layout of the parameters may have changed.
+pub const SCENE_PACKAGE_VERSION: u32 = 1;
+
+/// Bitmap data in a package starts on a multiple of this many bytes from the start of the package.
+pub const SCENE_PACKAGE_DATA_ALIGNMENT: usize = 16;
+
+// magic + version + checksum + manifest length
+const HEADER_LENGTH: usize = SCENE_PACKAGE_MAGIC.len() + size_of::<u32>() + size_of::<u32>() + size_of::<u64>();
+
+/// Every bitmap, shader, sky, and BSP parameter needed to load a scene, so it can be stored and loaded without
+/// converting tags again.
+///
+/// # Format
+///
+/// All integers are little endian.
+///
+/// | Offset | Type     | Description                                                                         |
+/// |--------|----------|-------------------------------------------------------------------------------------|
+/// | 0x00   | [u8; 8]  | [`SCENE_PACKAGE_MAGIC`]                                                             |
+/// | 0x08   | u32      | [`SCENE_PACKAGE_VERSION`]                                                           |
+/// | 0x0C   | u32      | CRC32 (ISO-HDLC) of the manifest                                                    |
+/// | 0x10   | u64      | Length of the manifest                                                              |
+/// | 0x18   | ...      | Manifest (postcard-encoded parameters, with bitmap data replaced by offsets)        |
+/// | ...    | ...      | Bitmap data, each aligned to [`SCENE_PACKAGE_DATA_ALIGNMENT`] bytes                 |
+///
+/// Bitmap data is stored outside of the manifest so that packages can be memory-mapped, and each bitmap is only
+/// copied out when it is loaded. The manifest stores a CRC32 of each bitmap's data, which is checked before the bitmap
+/// is loaded rather than when the package is opened.
+#[derive(Default)]
+pub struct ScenePackage {
+    pub bitmaps: Vec<(String, AddBitmapParameter)>,
+    pub shaders: Vec<(String, AddShaderParameter)>,
+    pub skies: Vec<(String, AddSkyParameter)>,
+    pub bsps: Vec<(String, AddBSPParameter)>
+}
+
+/// Everything in a package but the bitmap data.
+///
+/// This borrows from a [`ScenePackage`] when encoding and owns everything when decoding.
+#[derive(Serialize, Deserialize)]
+struct Manifest<'a> {
+    bitmaps: Vec<ManifestBitmap>,
+    shaders: Cow<'a, [(String, AddShaderParameter)]>,
+    skies: Cow<'a, [(String, AddSkyParameter)]>,
+    bsps: Cow<'a, [(String, AddBSPParameter)]>
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestBitmap {
+    path: String,
+
+    /// Bitmap with empty data; the data of each of its bitmaps is at the corresponding range of `data`.
+    bitmap: AddBitmapParameter,
+    data: Vec<DataRange>
+}
+
+/// Range of the data section, relative to the start of it.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+struct DataRange {
+    offset: u64,
+    length: u64,
+
+    /// CRC32 (ISO-HDLC) of the data in the range.
+    checksum: u32
+}
+
+impl ScenePackage {
+    /// Encode the package.
+    pub fn to_bytes(&self) -> MResult<Vec<u8>> {
+        let mut data_length = 0usize;
+        let mut bitmaps = Vec::with_capacity(self.bitmaps.len());
+        for (path, bitmap) in &self.bitmaps {
+            let mut ranges = Vec::with_capacity(bitmap.bitmaps.len());
+            let mut stripped = Vec::with_capacity(bitmap.bitmaps.len());
+            for b in &bitmap.bitmaps {
+                ranges.push(DataRange { offset: data_length as u64, length: b.data.len() as u64, checksum: crc32fast::hash(&b.data) });
+                data_length = (data_length + b.data.len()).next_multiple_of(SCENE_PACKAGE_DATA_ALIGNMENT);
+                stripped.push(AddBitmapBitmapParameter { data: Vec::new(), ..b.clone() });
+            }
+            bitmaps.push(ManifestBitmap {
+                path: path.to_owned(),
+                bitmap: AddBitmapParameter { bitmaps: stripped, sequences: bitmap.sequences.clone() },
+                data: ranges
+            });
+        }
+
+        let manifest = Manifest {
+            bitmaps,
+            shaders: Cow::Borrowed(&self.shaders),
+            skies: Cow::Borrowed(&self.skies),
+            bsps: Cow::Borrowed(&self.bsps)
+        };
+        let manifest = postcard::to_allocvec(&manifest)
+            .map_err(|e| Error::InvalidScenePackage { error: format!("Can't encode the manifest: {e}") })?;
+
+        let data_start = (HEADER_LENGTH + manifest.len()).next_multiple_of(SCENE_PACKAGE_DATA_ALIGNMENT);
+        let mut output = Vec::with_capacity(data_start + data_length);
+        output.extend_from_slice(&SCENE_PACKAGE_MAGIC);
+        output.extend_from_slice(&SCENE_PACKAGE_VERSION.to_le_bytes());
+        output.extend_from_slice(&crc32fast::hash(&manifest).to_le_bytes());
+        output.extend_from_slice(&(manifest.len() as u64).to_le_bytes());
+        output.extend_from_slice(&manifest);
+
+        for b in self.bitmaps.iter().flat_map(|b| b.1.bitmaps.iter()) {
+            output.resize(output.len().next_multiple_of(SCENE_PACKAGE_DATA_ALIGNMENT), 0);
+            output.extend_from_slice(&b.data);
+        }
+        output.resize(data_start + data_length, 0);
+
+        Ok(output)
+    }
+
+    /// Decode a package, copying all bitmap data out of it.
+    ///
+    /// Use [`Renderer::load_scene_package`] to load a package directly instead.
+    ///
+    /// This will error if the package is invalid or any bitmap's data does not match its checksum.
+    pub fn from_bytes(package: &[u8]) -> MResult<Self> {
+        let ScenePackageReader { manifest, data } = ScenePackageReader::new(package)?;
+        let mut bitmaps = Vec::with_capacity(manifest.bitmaps.len());
+        for bitmap in manifest.bitmaps {
+            data.check(&bitmap)?;
+            bitmaps.push(data.take_bitmap(bitmap));
+        }
+        let Manifest { shaders, skies, bsps, .. } = manifest;
+        Ok(Self { bitmaps, shaders: shaders.into_owned(), skies: skies.into_owned(), bsps: bsps.into_owned() })
+    }
+}
+
+/// Package whose manifest has been checked, but whose bitmap data has not been read yet.
+struct ScenePackageReader<'a> {
+    manifest: Manifest<'static>,
+    data: PackageData<'a>
+}
+
+/// Bitmap data section of a package, whose ranges were bounds checked against it when the package was opened.
+#[derive(Copy, Clone)]
+struct PackageData<'a>(&'a [u8]);
+
+impl<'a> ScenePackageReader<'a> {
+    fn new(package: &'a [u8]) -> MResult<Self> {
+        let invalid = |error: String| Error::InvalidScenePackage { error };
+
+        if package.len() < HEADER_LENGTH {
+            return Err(invalid(format!("Package is only {} bytes, but the header alone is {HEADER_LENGTH} bytes", package.len())))
+        }
+        if package[0x00..0x08] != SCENE_PACKAGE_MAGIC {
+            return Err(invalid("Package magic is incorrect; this is not a scene package".to_owned()))
+        }
+
+        let read_u32 = |offset: usize| u32::from_le_bytes(package[offset..offset + 4].try_into().unwrap());
+        let version = read_u32(0x08);
+        if version != SCENE_PACKAGE_VERSION {
+            return Err(invalid(format!("Package is version {version}, but only version {SCENE_PACKAGE_VERSION} is supported")))
+        }
+
+        let manifest_length = u64::from_le_bytes(package[0x10..0x18].try_into().unwrap());
+        let manifest_end = usize::try_from(manifest_length)
+            .ok()
+            .and_then(|l| l.checked_add(HEADER_LENGTH))
+            .filter(|end| *end <= package.len())
+            .ok_or_else(|| invalid(format!("Manifest is {manifest_length} bytes, which is longer than the package")))?;
+
+        let manifest_bytes = &package[HEADER_LENGTH..manifest_end];
+        let expected_checksum = read_u32(0x0C);
+        let checksum = crc32fast::hash(manifest_bytes);
+        if checksum != expected_checksum {
+            return Err(invalid(format!("Manifest checksum is 0x{checksum:08X}, but 0x{expected_checksum:08X} was expected; the package is corrupt")))
+        }
+
+        let manifest: Manifest = postcard::from_bytes(manifest_bytes)
+            .map_err(|e| invalid(format!("Can't decode the manifest: {e}")))?;
+
+        let data_start = manifest_end.next_multiple_of(SCENE_PACKAGE_DATA_ALIGNMENT).min(package.len());
+        let data = &package[data_start..];
+
+        for bitmap in &manifest.bitmaps {
+            if bitmap.bitmap.bitmaps.len() != bitmap.data.len() {
+                return Err(invalid(format!("Bitmap {} has {} bitmaps but {} data ranges", bitmap.path, bitmap.bitmap.bitmaps.len(), bitmap.data.len())))
+            }
+            for (index, range) in bitmap.data.iter().enumerate() {
+                let in_bounds = range
+                    .offset
+                    .checked_add(range.length)
+                    .is_some_and(|end| end <= data.len() as u64);
+                if !in_bounds {
+                    return Err(invalid(format!("Data of bitmap #{index} of {} is out of bounds", bitmap.path)))
+                }
+            }
+        }
+
+        Ok(Self { manifest, data: PackageData(data) })
+    }
+
+    /// Check that every resource in the package can be added to `renderer`.
+    ///
+    /// Bitmaps are validated from the manifest and their data is checked against its checksum in place, so nothing is
+    /// copied out of the package.
+    fn validate<B: RendererBackend>(&self, renderer: &Renderer<B>) -> MResult<()> {
+        let lookup = PackageResourceLookup { manifest: &self.manifest, renderer };
+
+        let mut paths = BTreeSet::new();
+        let mut check_path = |kind: ResourceKind, path: &str| {
+            if renderer.contains(kind, path) || !paths.insert((kind, path.to_owned())) {
+                return Err(Error::DuplicatePath { kind, path: path.to_owned() })
+            }
+            Ok(())
+        };
+
+        for bitmap in &self.manifest.bitmaps {
+            check_path(ResourceKind::Bitmap, &bitmap.path)?;
+            bitmap.bitmap.validate_with_data_lengths(|index| bitmap.data[index].length as usize)?;
+            self.data.check(bitmap)?;
+        }
+        for (path, shader) in self.manifest.shaders.iter() {
+            check_path(ResourceKind::Shader, path)?;
+            shader.validate(&lookup)?;
+        }
+        for (path, sky) in self.manifest.skies.iter() {
+            check_path(ResourceKind::Sky, path)?;
+            sky.validate(&lookup)?;
+        }
+        for (path, bsp) in self.manifest.bsps.iter() {
+            check_path(ResourceKind::BSP, path)?;
+            bsp.validate(&lookup)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> PackageData<'a> {
+    /// Get the data in `range` without copying it.
+    fn get(self, range: DataRange) -> &'a [u8] {
+        &self.0[range.offset as usize..(range.offset + range.length) as usize]
+    }
+
+    /// Check that the data of each of `bitmap`'s bitmaps matches its checksum.
+    fn check(self, bitmap: &ManifestBitmap) -> MResult<()> {
+        for (index, range) in bitmap.data.iter().enumerate() {
+            let checksum = crc32fast::hash(self.get(*range));
+            if checksum != range.checksum {
+                return Err(Error::InvalidScenePackage {
+                    error: format!("Data of bitmap #{index} of {} has checksum 0x{checksum:08X}, but 0x{:08X} was expected; the package is corrupt", bitmap.path, range.checksum)
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy a bitmap's data out of the package, as [`AddBitmapParameter`] owns its data.
+    ///
+    /// The data is not checked here; use [`check`](Self::check) first.
+    fn take_bitmap(self, bitmap: ManifestBitmap) -> (String, AddBitmapParameter) {
+        let ManifestBitmap { path, bitmap: mut parameter, data } = bitmap;
+        for (b, range) in parameter.bitmaps.iter_mut().zip(data) {
+            b.data = self.get(range).to_owned();
+        }
+        (path, parameter)
+    }
+}
+
+/// Resources in a renderer plus the ones in a package that haven't been added yet.
+struct PackageResourceLookup<'a, B: RendererBackend> {
+    manifest: &'a Manifest<'static>,
+    renderer: &'a Renderer<B>
+}
+
+impl<B: RendererBackend> PackageResourceLookup<'_, B> {
+    fn bitmap(&self, path: &str) -> Option<&ManifestBitmap> {
+        self.manifest.bitmaps.iter().find(|b| b.path == path)
+    }
+}
+
+impl<B: RendererBackend> ResourceLookup for PackageResourceLookup<'_, B> {
+    fn bitmap_count(&self, path: &str) -> Option<usize> {
+        self.bitmap(path).map(|b| b.bitmap.bitmaps.len()).or_else(|| self.renderer.bitmap_count(path))
+    }
+
+    fn bitmap_type(&self, path: &str, bitmap: usize) -> Option<BitmapType> {
+        match self.bitmap(path) {
+            Some(b) => b.bitmap.bitmaps.get(bitmap).map(|b| b.bitmap_type),
+            None => self.renderer.bitmap_type(path, bitmap)
+        }
+    }
+
+    fn bitmap_sequences(&self, path: &str) -> Option<&[AddBitmapSequenceParameter]> {
+        self.bitmap(path).map(|b| b.bitmap.sequences.as_slice()).or_else(|| self.renderer.bitmap_sequences(path))
+    }
+
+    fn shader_type(&self, path: &str) -> Option<ShaderType> {
+        self.manifest
+            .shaders
+            .iter()
+            .find(|(p, _)| p == path)
+            .map(|(_, s)| s.shader_type())
+            .or_else(|| self.renderer.shader_type(path))
+    }
+
+    fn contains(&self, kind: ResourceKind, path: &str) -> bool {
+        let in_package = match kind {
+            ResourceKind::Bitmap => self.bitmap(path).is_some(),
+            ResourceKind::Shader => self.manifest.shaders.iter().any(|(p, _)| p == path),
+            ResourceKind::Sky => self.manifest.skies.iter().any(|(p, _)| p == path),
+            ResourceKind::BSP => self.manifest.bsps.iter().any(|(p, _)| p == path),
+            ResourceKind::Geometry | ResourceKind::Font => false
+        };
+        in_package || self.renderer.contains(kind, path)
+    }
+}
+
+impl<B: RendererBackend> Renderer<B> {
+    /// Load every resource in a scene package.
+    ///
+    /// `package` can be a memory-mapped file, as each bitmap's data is only copied out of it when the bitmap is
+    /// loaded. Every resource is validated before any are added, so an invalid package adds nothing. Resources are
+    /// then loaded in dependency order (bitmaps, shaders, skies, then BSPs); if the backend fails to load one (e.g. it
+    /// runs out of memory), resources loaded before it are kept.
+    ///
+    /// This will error if:
+    /// - `package` is not a valid scene package, its version is unsupported, or a checksum does not match
+    /// - any resource is invalid or fails to load (see the `add_` methods)
+    pub fn load_scene_package(&mut self, package: &[u8]) -> MResult<()> {
+        let reader = ScenePackageReader::new(package)?;
+        reader.validate(self)?;
+
+        // Everything was validated above, so it isn't validated again as it is added.
+        let Manifest { bitmaps, shaders, skies, bsps } = reader.manifest;
+        for bitmap in bitmaps {
+            let (path, bitmap) = reader.data.take_bitmap(bitmap);
+            self.add_validated_bitmap(Arc::from(path), bitmap)?;
+        }
+        for (path, shader) in shaders.into_owned() {
+            self.add_validated_shader(Arc::from(path), shader)?;
+        }
+        for (path, sky) in skies.into_owned() {
+            self.add_validated_sky(Arc::from(path), sky)?;
+        }
+        for (path, bsp) in bsps.into_owned() {
+            self.add_validated_bsp(Arc::from(path), bsp)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use crate::renderer::{test_bitmap, test_bitmap_bitmap, NullRenderer, RendererParameters};
+
+    fn test_package() -> ScenePackage {
+        let bitmap = |data: Vec<u8>| AddBitmapParameter {
+            bitmaps: vec![AddBitmapBitmapParameter { data, ..test_bitmap_bitmap() }],
+            ..test_bitmap()
+        };
+
+        ScenePackage {
+            bitmaps: vec![("a".to_owned(), bitmap(vec![1, 2, 3, 4])), ("b".to_owned(), bitmap(vec![5, 6, 7, 8]))],
+            ..Default::default()
+        }
+    }
+
+    fn test_sky(outdoor_fog_maximum_density: f32) -> AddSkyParameter {
+        AddSkyParameter {
+            geometry: None,
+            outdoor_fog_color: [0.0; 3],
+            outdoor_fog_maximum_density,
+            outdoor_fog_start_distance: 0.0,
+            outdoor_fog_opaque_distance: 0.0,
+            indoor_fog_color: [0.0; 3],
+            indoor_fog_maximum_density: 0.0,
+            indoor_fog_start_distance: 0.0,
+            indoor_fog_opaque_distance: 0.0
+        }
+    }
+
+    fn test_renderer() -> Renderer<NullRenderer> {
+        Renderer::new_null(RendererParameters::default()).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut package = test_package();
+        package.skies.push(("sky".to_owned(), test_sky(0.5)));
+
+        let bytes = package.to_bytes().unwrap();
+        let decoded = ScenePackage::from_bytes(&bytes).unwrap();
+
+        assert_eq!(decoded.bitmaps.len(), 2);
+        for ((path, bitmap), (expected_path, expected)) in decoded.bitmaps.iter().zip(package.bitmaps.iter()) {
+            assert_eq!(path, expected_path);
+            assert_eq!(bitmap.bitmaps[0].data, expected.bitmaps[0].data);
+        }
+        assert_eq!(decoded.skies.len(), 1);
+        assert_eq!(decoded.skies[0].1.outdoor_fog_maximum_density, 0.5);
+
+        let mut renderer = test_renderer();
+        renderer.load_scene_package(&bytes).unwrap();
+        assert!(renderer.contains(ResourceKind::Bitmap, "a"));
+        assert!(renderer.contains(ResourceKind::Bitmap, "b"));
+        assert!(renderer.contains(ResourceKind::Sky, "sky"));
+    }
+
+    #[test]
+    fn version_mismatch_errors() {
+        let mut bytes = test_package().to_bytes().unwrap();
+        bytes[0x08..0x0C].copy_from_slice(&(SCENE_PACKAGE_VERSION + 1).to_le_bytes());
+
+        let error = ScenePackage::from_bytes(&bytes).err().unwrap();
+        assert!(matches!(&error, Error::InvalidScenePackage { error } if error.contains("version")), "{error:?}");
+    }
+
+    #[test]
+    fn corrupt_manifest_errors() {
+        let mut bytes = test_package().to_bytes().unwrap();
+        bytes[HEADER_LENGTH] ^= 0xFF;
+
+        let error = ScenePackage::from_bytes(&bytes).err().unwrap();
+        assert!(matches!(&error, Error::InvalidScenePackage { error } if error.contains("Manifest checksum")), "{error:?}");
+    }
+
+    #[test]
+    fn corrupt_bitmap_data_errors_when_checked() {
+        let mut bytes = test_package().to_bytes().unwrap();
+        let last = bytes.len() - SCENE_PACKAGE_DATA_ALIGNMENT;
+        bytes[last] ^= 0xFF;
+
+        // Only the manifest is checked when opening the package.
+        let reader = ScenePackageReader::new(&bytes).unwrap();
+        assert!(reader.data.check(&reader.manifest.bitmaps[0]).is_ok());
+
+        let error = reader.data.check(&reader.manifest.bitmaps[1]).err().unwrap();
+        assert!(matches!(&error, Error::InvalidScenePackage { error } if error.contains("of b has checksum")), "{error:?}");
+
+        let mut renderer = test_renderer();
+        assert!(renderer.load_scene_package(&bytes).is_err());
+        assert!(!renderer.contains(ResourceKind::Bitmap, "a"));
+    }
+
+    #[test]
+    fn invalid_package_adds_nothing() {
+        let mut package = test_package();
+        package.skies.push(("sky".to_owned(), test_sky(2.0)));
+        let bytes = package.to_bytes().unwrap();
+
+        let mut renderer = test_renderer();
+        assert!(renderer.load_scene_package(&bytes).is_err());
+        assert!(!renderer.contains(ResourceKind::Bitmap, "a"));
+        assert!(!renderer.contains(ResourceKind::Bitmap, "b"));
+    }
+
+    #[test]
+    fn duplicate_path_adds_nothing() {
+        let mut renderer = test_renderer();
+        renderer.add_bitmap("b", test_package().bitmaps.remove(0).1).unwrap();
+
+        let error = renderer.load_scene_package(&test_package().to_bytes().unwrap()).err().unwrap();
+        assert!(matches!(&error, Error::DuplicatePath { kind: ResourceKind::Bitmap, path } if path == "b"), "{error:?}");
+        assert!(!renderer.contains(ResourceKind::Bitmap, "a"));
+    }
+
+    #[test]
+    fn duplicate_sky_adds_nothing() {
+        let mut renderer = test_renderer();
+        renderer.add_sky("sky", test_sky(0.5)).unwrap();
+
+        let error = renderer.add_sky("sky", test_sky(0.5)).err().unwrap();
+        assert!(matches!(&error, Error::DuplicatePath { kind: ResourceKind::Sky, path } if path == "sky"), "{error:?}");
+
+        let mut package = test_package();
+        package.skies.push(("sky".to_owned(), test_sky(0.5)));
+        let error = renderer.load_scene_package(&package.to_bytes().unwrap()).err().unwrap();
+        assert!(matches!(&error, Error::DuplicatePath { kind: ResourceKind::Sky, .. }), "{error:?}");
+        assert!(!renderer.contains(ResourceKind::Bitmap, "a"));
+    }
+}
@@ -9,38 +9,49 @@ mod helper;
 mod player_viewport;
 mod vertex;
 mod material;
+mod overlay;
+mod upload;
+mod sampler;
+mod debug;
 
 use crate::error::{Error, MResult};
-use crate::renderer::data::{BSPGeometry, BSP};
-use crate::renderer::vulkan::helper::{build_swapchain, LoadedVulkan};
+use crate::renderer::data::{BSPGeometry, OverlayQuad, BSP};
+use crate::renderer::vulkan::debug::VulkanLogger;
+use crate::renderer::vulkan::helper::{build_swapchain, LoadedDevice, LoadedVulkan};
 use crate::renderer::vulkan::vertex::{VulkanFogData, VulkanModelData, VulkanModelVertex};
-use crate::renderer::{Camera, Renderer, RendererParameters, Resolution, MSAA};
+use crate::renderer::{make_player_viewports, AddBSPParameter, AddBSPParameterLightmapMaterial, AddBitmapBitmapParameter, AddShaderParameter, BitmapSamplerHints, Camera, DeviceInfo, LogSeverity, Renderer, RendererBackend, RendererParameters, Resolution, TextureRecompression, DEFAULT_BACKGROUND, MSAA};
+use crate::renderer::backend::sealed::Sealed;
+use crate::vertex::ModelTriangle;
 pub use bitmap::*;
 pub use bsp::*;
 pub use geometry::*;
 use glam::{Mat3, Mat4, Vec3};
 pub use material::*;
 pub use pipeline::*;
+pub use upload::*;
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 use std::boxed::Box;
 use std::collections::BTreeMap;
+use std::any::Any;
 use std::fmt::Display;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::vec::Vec;
-use std::{eprintln, format, vec};
-use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use std::string::ToString;
+use std::{format, vec};
+use vulkano::buffer::{AllocateBufferError, Buffer, BufferCreateInfo, BufferUsage};
 use vulkano::command_buffer::allocator::{StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo};
-use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageInfo, ClearDepthStencilImageInfo, CommandBufferInheritanceInfo, CommandBufferInheritanceRenderPassType, CommandBufferInheritanceRenderingInfo, CommandBufferUsage, PrimaryAutoCommandBuffer, PrimaryCommandBufferAbstract, RenderingAttachmentInfo, RenderingInfo, ResolveImageInfo, SecondaryAutoCommandBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageInfo, ClearColorImageInfo, ClearDepthStencilImageInfo, CommandBufferInheritanceInfo, CommandBufferInheritanceRenderPassType, CommandBufferInheritanceRenderingInfo, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderingAttachmentInfo, RenderingInfo, ResolveImageInfo, SecondaryAutoCommandBuffer};
 use vulkano::descriptor_set::allocator::{StandardDescriptorSetAllocator, StandardDescriptorSetAllocatorCreateInfo};
 use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
 use vulkano::device::{Device, Queue};
-use vulkano::format::{ClearDepthStencilValue, Format};
-use vulkano::image::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::format::{ClearColorValue, ClearDepthStencilValue, Format, NumericFormat};
+use vulkano::image::sampler::{Sampler, SamplerAddressMode, SamplerCreateInfo};
 use vulkano::image::view::ImageView;
-use vulkano::image::{Image, ImageCreateInfo, ImageType, ImageUsage, SampleCount};
+use vulkano::image::{AllocateImageError, Image, ImageCreateInfo, ImageType, ImageUsage, SampleCount};
+use vulkano::instance::debug::DebugUtilsMessenger;
 use vulkano::instance::Instance;
-use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocatorError, MemoryTypeFilter, StandardMemoryAllocator};
 use vulkano::padded::Padded;
 use vulkano::pipeline::graphics::rasterization::CullMode;
 use vulkano::pipeline::graphics::viewport::Viewport;
@@ -50,8 +61,6 @@ use vulkano::swapchain::{acquire_next_image, Surface, Swapchain, SwapchainAcquir
 use vulkano::sync::GpuFuture;
 use vulkano::{Validated, ValidationError, VulkanError};
 
-pub(crate) static OFFLINE_PIPELINE_COLOR_FORMAT: Format = Format::R8G8B8A8_UNORM;
-
 pub struct VulkanRenderer {
     current_resolution: Resolution,
     instance: Arc<Instance>,
@@ -60,13 +69,27 @@ pub struct VulkanRenderer {
     command_buffer_allocator: StandardCommandBufferAllocator,
     descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
     queue: Arc<Queue>,
+    transfer_queue: Option<Arc<Queue>>,
+    upload_batch: Option<VulkanUploadBatch>,
+    pending_uploads: Vec<VulkanPendingUpload>,
     future: Option<Box<dyn GpuFuture + Send + Sync>>,
     pipelines: BTreeMap<VulkanPipelineType, Arc<dyn VulkanPipelineData>>,
-    swapchain: Arc<Swapchain>,
+    swapchain: Option<Arc<Swapchain>>,
     surface: Arc<Surface>,
     swapchain_image_views: Vec<SwapchainImages>,
     default_2d_sampler: Arc<Sampler>,
-    samples_per_pixel: SampleCount
+    overlay_sampler: Arc<Sampler>,
+    samplers: Vec<(BitmapSamplerHints, Arc<Sampler>)>,
+    default_anisotropy: Option<f32>,
+    texture_recompression: Option<TextureRecompression>,
+    two_channel_normal_maps: bool,
+    logger: VulkanLogger,
+    _debug_messenger: Option<DebugUtilsMessenger>,
+    samples_per_pixel: SampleCount,
+    color_format: Format,
+    linear_lighting: bool,
+    device_lost: bool,
+    start_time: Instant
 }
 
 #[derive(Clone)]
@@ -77,12 +100,72 @@ struct SwapchainImages {
     pub resolve: Option<Arc<ImageView>>,
 }
 
+impl Renderer<VulkanRenderer> {
+    /// Initialize a new renderer.
+    ///
+    /// Errors if:
+    /// - `parameters` is invalid
+    /// - the renderer backend could not be initialized for some reason
+    pub unsafe fn new(surface: &(impl HasRawWindowHandle + HasRawDisplayHandle), parameters: RendererParameters) -> MResult<Self> {
+        let player_viewports = make_player_viewports(&parameters)?;
+        let backend = VulkanRenderer::new(&parameters, surface)?;
+        Self::new_with_backend(backend, player_viewports, &parameters)
+    }
+
+    /// Describe all Vulkan devices on the system, including ones that don't meet the renderer's requirements.
+    ///
+    /// Pass [`DeviceInfo::index`] or [`DeviceInfo::name`] to [`RendererParameters::device`] to render with a specific
+    /// device.
+    pub fn enumerate_devices() -> MResult<Vec<DeviceInfo>> {
+        VulkanRenderer::enumerate_devices()
+    }
+}
+
 impl VulkanRenderer {
     pub unsafe fn new(
         renderer_parameters: &RendererParameters,
         surface: &(impl HasRawWindowHandle + HasRawDisplayHandle)
     ) -> MResult<Self> {
-        let LoadedVulkan { device, instance, surface, queue} = helper::load_vulkan_and_get_queue(surface, renderer_parameters.anisotropic_filtering)?;
+        let logger = VulkanLogger::new(renderer_parameters.log_callback.clone());
+        let LoadedVulkan { instance, surface, debug_messenger } = helper::load_vulkan(surface, renderer_parameters, &logger)?;
+        let device = helper::create_device(instance.clone(), surface.clone(), renderer_parameters)?;
+
+        let mut renderer = Self::new_with_device(renderer_parameters, instance, surface, device, logger)?;
+        renderer._debug_messenger = debug_messenger;
+        Ok(renderer)
+    }
+
+    /// Replace the device, such as after it was lost.
+    ///
+    /// Everything made with the old device must be dropped first, as it can't be used with the new one.
+    pub fn recreate_device(&mut self, renderer_parameters: &RendererParameters) -> MResult<()> {
+        // The surface can only have one swapchain, so release the old one (and anything waiting on it) before making
+        // another. Until a new device is made, drawing fails as if the device was lost.
+        self.device_lost = true;
+        self.future = None;
+        self.upload_batch = None;
+        self.pending_uploads.clear();
+        self.swapchain_image_views.clear();
+        self.swapchain = None;
+
+        let device = helper::create_device(self.instance.clone(), self.surface.clone(), renderer_parameters)?;
+        let mut renderer = Self::new_with_device(renderer_parameters, self.instance.clone(), self.surface.clone(), device, self.logger.clone())?;
+        renderer._debug_messenger = self._debug_messenger.take();
+        renderer.start_time = self.start_time;
+        *self = renderer;
+
+        self.log(LogSeverity::Info, "The device was recreated");
+        Ok(())
+    }
+
+    fn new_with_device(
+        renderer_parameters: &RendererParameters,
+        instance: Arc<Instance>,
+        surface: Arc<Surface>,
+        device: LoadedDevice,
+        logger: VulkanLogger
+    ) -> MResult<Self> {
+        let LoadedDevice { device, queue, transfer_queue } = device;
 
         let samples_per_pixel = match renderer_parameters.msaa {
             MSAA::NoMSAA => SampleCount::Sample1,
@@ -98,7 +181,7 @@ impl VulkanRenderer {
             let max = device.physical_device().properties().max_sampler_anisotropy;
             if max < n || n < 1.0 {
                 return Err(
-                    Error::from_vulkan_impl_error(format!("{n}x AF is unsupported by your device; supported values are 1-{max}"))
+                    Error::from_vulkan_unsupported_error(format!("{n}x AF is unsupported by your device; supported values are 1-{max}"))
                 )
             }
         }
@@ -108,9 +191,9 @@ impl VulkanRenderer {
         let intersection = color & depth;
         if !intersection.contains_enum(samples_per_pixel) {
             return Err(
-                Error::from_vulkan_impl_error(format!("{}x MSAA is unsupported by your device; only these are supported:{}",
-                                                      renderer_parameters.msaa as u32,
-                                                      intersection.into_iter().map(|s| format!(" {}", s as u32)).collect::<String>())));
+                Error::from_vulkan_unsupported_error(format!("{}x MSAA is unsupported by your device; only these are supported:{}",
+                                                             renderer_parameters.msaa as u32,
+                                                             intersection.into_iter().map(|s| format!(" {}", s as u32)).collect::<String>())));
         }
 
         let command_buffer_allocator = StandardCommandBufferAllocator::new(
@@ -133,15 +216,27 @@ impl VulkanRenderer {
         let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
         let future = Some(vulkano::sync::now(device.clone()).boxed_send_sync());
 
-        let output_format = device
+        let surface_formats = device
             .physical_device()
-            .surface_formats(surface.as_ref(), Default::default())?[0]
-            .0;
+            .surface_formats(surface.as_ref(), Default::default())?;
+
+        // Linear lighting is rendered into a float target, then converted to sRGB when blitting to the swapchain.
+        let (output_format, color_format) = if renderer_parameters.linear_lighting {
+            let output_format = surface_formats
+                .iter()
+                .map(|f| f.0)
+                .find(|f| f.numeric_format_color() == Some(NumericFormat::SRGB))
+                .ok_or_else(|| Error::from_vulkan_unsupported_error("Linear lighting requires an sRGB surface format, but your device has none".to_string()))?;
+            (output_format, Format::R16G16B16A16_SFLOAT)
+        }
+        else {
+            (surface_formats[0].0, Format::R8G8B8A8_UNORM)
+        };
 
         let (swapchain, swapchain_images) = build_swapchain(device.clone(), surface.clone(), output_format, renderer_parameters)?;
 
-        let pipelines = load_all_pipelines(device.clone(), samples_per_pixel)?;
-        let swapchain_image_views = Self::make_swapchain_images(swapchain_images, memory_allocator.clone(), samples_per_pixel);
+        let pipelines = load_all_pipelines(device.clone(), samples_per_pixel, color_format)?;
+        let swapchain_image_views = Self::make_swapchain_images(swapchain_images, memory_allocator.clone(), samples_per_pixel, color_format)?;
 
         let default_2d_sampler = Sampler::new(
             device.clone(),
@@ -151,6 +246,18 @@ impl VulkanRenderer {
             }
         )?;
 
+        let overlay_sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                address_mode: [
+                    SamplerAddressMode::ClampToEdge,
+                    SamplerAddressMode::ClampToEdge,
+                    SamplerAddressMode::ClampToEdge
+                ],
+                ..SamplerCreateInfo::simple_repeat_linear()
+            }
+        )?;
+
         Ok(Self {
             current_resolution: renderer_parameters.resolution,
             instance,
@@ -158,60 +265,135 @@ impl VulkanRenderer {
             descriptor_set_allocator,
             device,
             queue,
+            transfer_queue,
+            upload_batch: None,
+            pending_uploads: Vec::new(),
             future,
             pipelines,
-            swapchain,
+            swapchain: Some(swapchain),
             surface,
             swapchain_image_views,
             memory_allocator,
+            samplers: vec![(BitmapSamplerHints::default(), default_2d_sampler.clone())],
+            default_anisotropy: renderer_parameters.anisotropic_filtering,
+            texture_recompression: renderer_parameters.texture_recompression,
+            two_channel_normal_maps: renderer_parameters.two_channel_normal_maps,
+            logger,
+            _debug_messenger: None,
             default_2d_sampler,
-            samples_per_pixel
+            overlay_sampler,
+            samples_per_pixel,
+            color_format,
+            linear_lighting: renderer_parameters.linear_lighting,
+            device_lost: false,
+            start_time: Instant::now()
         })
     }
 
     pub fn draw_frame(renderer: &mut Renderer) -> MResult<bool> {
         let vulkan_renderer = &mut renderer.renderer;
+        if vulkan_renderer.device_lost {
+            return Err(Error::DeviceLost { backend: "Vulkan" })
+        }
+
+        // Release finished upload batches
+        vulkan_renderer.uploads_pending();
 
         let (image_index, suboptimal, acquire_future) =
-            match acquire_next_image(vulkan_renderer.swapchain.clone(), None).map_err(Validated::unwrap) {
+            match acquire_next_image(vulkan_renderer.swapchain()?.clone(), None).map_err(Validated::unwrap) {
                 Ok(r) => r,
                 Err(VulkanError::OutOfDate) => return Ok(false),
-                Err(e) => panic!("failed to acquire next image: {e}"),
+                Err(e) => return Err(vulkan_renderer.handle_draw_error(e)),
             };
 
-        Ok(Self::draw_frame_infallible(renderer, image_index, acquire_future) && !suboptimal)
+        let result = Self::draw_frame_to_image(renderer, image_index, acquire_future);
+
+        // If the frame failed partway through, the previous future was consumed, so start over from a clean slate.
+        if renderer.renderer.future.is_none() {
+            renderer.renderer.future = Some(vulkano::sync::now(renderer.renderer.device.clone()).boxed_send_sync());
+        }
+
+        Ok(result? && !suboptimal)
+    }
+
+    /// Return `true` if the device was lost.
+    ///
+    /// Once this happens, nothing else can be drawn until the device is recreated.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost
+    }
+
+    /// Get the swapchain, which is only missing if recreating the device failed.
+    fn swapchain(&self) -> MResult<&Arc<Swapchain>> {
+        self.swapchain.as_ref().ok_or(Error::DeviceLost { backend: "Vulkan" })
+    }
+
+    /// Convert an error that occurred while drawing, noting if the device was lost.
+    fn handle_draw_error(&mut self, error: VulkanError) -> Error {
+        let error = Error::from(error);
+        if matches!(error, Error::DeviceLost { .. }) && !self.device_lost {
+            self.device_lost = true;
+            self.log(LogSeverity::Error, &error.to_string());
+        }
+        error
+    }
+
+    pub fn get_time(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    pub fn enumerate_devices() -> MResult<Vec<DeviceInfo>> {
+        helper::enumerate_devices()
+    }
+
+    /// Convert an sRGB color from tag data into the color space that the pipelines render in.
+    ///
+    /// Alpha is left unchanged.
+    pub fn to_output_color(&self, color: [f32; 4]) -> [f32; 4] {
+        if !self.linear_lighting {
+            return color
+        }
+
+        let to_linear = |c: f32| if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) };
+        [to_linear(color[0]), to_linear(color[1]), to_linear(color[2]), color[3]]
+    }
+
+    /// Return `true` if lighting is done in linear space.
+    pub fn linear_lighting(&self) -> bool {
+        self.linear_lighting
     }
 
     pub fn rebuild_swapchain(&mut self, renderer_parameters: &RendererParameters) -> MResult<()> {
-        let (swapchain, swapchain_images) = self.swapchain.recreate(
+        let swapchain = self.swapchain()?;
+        let (swapchain, swapchain_images) = swapchain.recreate(
             SwapchainCreateInfo {
                 image_extent: [renderer_parameters.resolution.width, renderer_parameters.resolution.height],
-                ..self.swapchain.create_info()
+                ..swapchain.create_info()
             }
         )?;
 
-        self.swapchain = swapchain;
-        self.swapchain_image_views = Self::make_swapchain_images(swapchain_images, self.memory_allocator.clone(), self.samples_per_pixel);
+        self.swapchain = Some(swapchain);
+        self.swapchain_image_views = Self::make_swapchain_images(swapchain_images, self.memory_allocator.clone(), self.samples_per_pixel, self.color_format)?;
         self.current_resolution = renderer_parameters.resolution;
 
         Ok(())
     }
 
-    fn make_swapchain_images(swapchain_images: Vec<Arc<Image>>, memory_allocator: Arc<StandardMemoryAllocator>, samples_per_pixel: SampleCount) -> Vec<SwapchainImages> {
-        swapchain_images.iter().map(|i| SwapchainImages {
-            output: ImageView::new_default(i.clone()).unwrap(),
+    fn make_swapchain_images(swapchain_images: Vec<Arc<Image>>, memory_allocator: Arc<StandardMemoryAllocator>, samples_per_pixel: SampleCount, color_format: Format) -> MResult<Vec<SwapchainImages>> {
+        swapchain_images.iter().map(|i| Ok(SwapchainImages {
+            output: ImageView::new_default(i.clone())?,
             color: ImageView::new_default(Image::new(
                 memory_allocator.clone(),
                 ImageCreateInfo {
                     extent: i.extent(),
-                    format: OFFLINE_PIPELINE_COLOR_FORMAT,
+                    format: color_format,
                     image_type: ImageType::Dim2d,
                     samples: samples_per_pixel,
                     usage: ImageUsage::TRANSFER_SRC | ImageUsage::COLOR_ATTACHMENT,
                     ..Default::default()
                 },
                 AllocationCreateInfo::default(),
-            ).unwrap()).unwrap(),
+            )?)?,
             depth: ImageView::new_default(Image::new(
                 memory_allocator.clone(),
                 ImageCreateInfo {
@@ -223,53 +405,78 @@ impl VulkanRenderer {
                     ..Default::default()
                 },
                 AllocationCreateInfo::default(),
-            ).unwrap()).unwrap(),
+            )?)?,
             resolve: if samples_per_pixel != SampleCount::Sample1 {
                 Some(ImageView::new_default(Image::new(
                     memory_allocator.clone(),
                     ImageCreateInfo {
                         extent: i.extent(),
-                        format: OFFLINE_PIPELINE_COLOR_FORMAT,
+                        format: color_format,
                         image_type: ImageType::Dim2d,
                         samples: SampleCount::Sample1,
                         usage: ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST | ImageUsage::COLOR_ATTACHMENT,
                         ..Default::default()
                     },
                     AllocationCreateInfo::default(),
-                ).unwrap()).unwrap())
+                )?)?)
             } else {
                 None
             },
-        }).collect()
+        })).collect()
     }
 
-    fn draw_frame_infallible(renderer: &mut Renderer, image_index: u32, image_future: SwapchainAcquireFuture) -> bool {
+    fn draw_frame_to_image(renderer: &mut Renderer, image_index: u32, image_future: SwapchainAcquireFuture) -> MResult<bool> {
         let currently_loaded_bsp = renderer
             .current_bsp
             .as_ref()
             .and_then(|f| renderer.bsps.get(f))
-            .expect("no BSP loaded")
-            .clone();
+            .cloned();
 
         let mut command_builder = AutoCommandBufferBuilder::primary(
             &renderer.renderer.command_buffer_allocator,
             renderer.renderer.queue.queue_family_index(),
             CommandBufferUsage::OneTimeSubmit
-        ).expect("failed to init command builder");
+        )?;
 
         let images = renderer.renderer.swapchain_image_views[image_index as usize].clone();
-        image_future.wait(Some(Duration::from_millis(5000))).expect("waited too long");
-        renderer.renderer.future.as_mut().unwrap().cleanup_finished();
+        match image_future.wait(Some(Duration::from_millis(5000))) {
+            Ok(()) => (),
+
+            // The image is still acquired, so it has to be presented to be released. Present it cleared and keep
+            // the overlays for the next frame, which will likely be fine.
+            Err(VulkanError::Timeout) => {
+                renderer.renderer.log(LogSeverity::Warning, "Timed out waiting for the swapchain image; skipping frame");
+                command_builder.clear_color_image(ClearColorImageInfo {
+                    clear_value: ClearColorValue::Float(DEFAULT_BACKGROUND),
+                    ..ClearColorImageInfo::image(images.output.image().clone())
+                })?;
+                Self::submit_frame(renderer, command_builder, image_index, image_future)?;
+                return Ok(false)
+            },
+            Err(e) => return Err(renderer.renderer.handle_draw_error(e))
+        }
+
+        let overlays = renderer.take_overlays();
+        if let Some(future) = renderer.renderer.future.as_mut() {
+            future.cleanup_finished();
+        }
 
         let (width, height) = (renderer.renderer.current_resolution.width as f32, renderer.renderer.current_resolution.height as f32);
 
         command_builder.clear_depth_stencil_image(ClearDepthStencilImageInfo {
             clear_value: ClearDepthStencilValue::from(1.0),
             ..ClearDepthStencilImageInfo::image(images.depth.clone().image().clone())
-        }).expect("failed to clear depth image");
+        })?;
+
+        // With nothing to draw, viewports only get their overlays.
+        if currently_loaded_bsp.is_none() {
+            command_builder.clear_color_image(ClearColorImageInfo {
+                clear_value: ClearColorValue::Float(DEFAULT_BACKGROUND),
+                ..ClearColorImageInfo::image(images.color.image().clone())
+            })?;
+        }
 
-        for i in 0..renderer.player_viewports.len() {
-            let player_viewport = &renderer.player_viewports[i];
+        for (player_viewport, overlay) in renderer.player_viewports.iter().zip(overlays.iter()) {
 
             let viewport = Viewport {
                 offset: [player_viewport.rel_x * width, player_viewport.rel_y * height],
@@ -277,15 +484,18 @@ impl VulkanRenderer {
                 depth_range: 0.0..=1.0,
             };
 
+            let camera = player_viewport.camera;
+
             Self::draw_viewport(
                 renderer,
                 images.color.clone(),
                 images.depth.clone(),
                 viewport,
-                &currently_loaded_bsp,
+                currently_loaded_bsp.as_deref(),
                 &mut command_builder,
-                player_viewport.camera.clone()
-            );
+                camera,
+                overlay
+            )?;
         }
 
         if renderer.player_viewports.len() > 1 {
@@ -301,36 +511,48 @@ impl VulkanRenderer {
                     ..RenderingAttachmentInfo::image_view(images.depth.clone())
                 }),
                 ..Default::default()
-            }).expect("failed to begin rendering for split screen bars");
-            Self::draw_split_screen_bars(renderer, &mut command_builder, width, height);
-            command_builder.end_rendering().expect("failed to end rendering");
+            })?;
+            Self::draw_split_screen_bars(renderer, &mut command_builder, width, height)?;
+            command_builder.end_rendering()?;
         }
 
         let staging_image = if let Some(resolved_color_view) = images.resolve.as_ref().map(|iv| iv.image()) {
             command_builder.resolve_image(
                 ResolveImageInfo::images(images.color.image().clone(), resolved_color_view.clone())
-            ).expect("resolve fail");
+            )?;
             resolved_color_view
         }
         else {
             images.color.image()
         };
 
-        command_builder.blit_image(BlitImageInfo::images(staging_image.clone(), images.output.image().clone())).unwrap();
+        command_builder.blit_image(BlitImageInfo::images(staging_image.clone(), images.output.image().clone()))?;
 
-        let commands = command_builder.build().expect("failed to build command builder");
+        Self::submit_frame(renderer, command_builder, image_index, image_future)
+    }
+
+    /// Execute the frame's commands once the swapchain image is acquired, then present the image.
+    ///
+    /// Returns `false` if the swapchain is out of date.
+    fn submit_frame(
+        renderer: &mut Renderer,
+        command_builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image_index: u32,
+        image_future: SwapchainAcquireFuture
+    ) -> MResult<bool> {
+        let commands = command_builder.build()?;
 
         let future = renderer.renderer
             .future
             .take()
-            .expect("there's no future :(");
+            .unwrap_or_else(|| vulkano::sync::now(renderer.renderer.device.clone()).boxed_send_sync());
 
-        let swapchain_present = SwapchainPresentInfo::swapchain_image_index(renderer.renderer.swapchain.clone(), image_index);
+        let swapchain_present = SwapchainPresentInfo::swapchain_image_index(renderer.renderer.swapchain()?.clone(), image_index);
 
         let future = future
             .join(image_future)
             .then_execute(renderer.renderer.queue.clone(), commands.clone())
-            .expect("can't execute commands")
+            .map_err(|e| Error::from_vulkan_error(format!("Can't execute commands: {e}")))?
             .then_swapchain_present(renderer.renderer.queue.clone(), swapchain_present)
             .then_signal_fence();
 
@@ -346,29 +568,32 @@ impl VulkanRenderer {
                     continue;
                 },
                 Err(Validated::Error(VulkanError::OutOfDate)) => {
-                    renderer.renderer.future = Some(vulkano::sync::now(renderer.renderer.device.clone()).boxed_send_sync());
-                    return false
+                    return Ok(false)
+                },
+                Err(Validated::Error(e)) => {
+                    return Err(renderer.renderer.handle_draw_error(e))
                 },
                 Err(e) => {
-                    panic!("Oh, shit! Some bullshit just happened: {e:?}")
+                    return Err(e.into())
                 }
             }
         }
 
         renderer.renderer.future = Some(future.boxed_send_sync());
-        true
+        Ok(true)
     }
 
     fn draw_viewport(
-        renderer: &mut Renderer,
+        renderer: &Renderer,
         color_view: Arc<ImageView>,
         depth_view: Arc<ImageView>,
         viewport: Viewport,
-        currently_loaded_bsp: &BSP,
+        currently_loaded_bsp: Option<&BSP>,
         command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
-        camera: Camera
-    ) {
-        command_builder.set_viewport(0, [viewport.clone()].into_iter().collect()).unwrap();
+        camera: Camera,
+        overlay: &[OverlayQuad]
+    ) -> MResult<()> {
+        command_builder.set_viewport(0, [viewport.clone()].into_iter().collect())?;
         command_builder.begin_rendering(RenderingInfo {
             color_attachments: vec![Some(RenderingAttachmentInfo {
                 load_op: AttachmentLoadOp::Load,
@@ -381,13 +606,31 @@ impl VulkanRenderer {
                 ..RenderingAttachmentInfo::image_view(depth_view.clone())
             }),
             ..Default::default()
-        }).expect("failed to begin rendering inside viewport w/out sky color");
+        })?;
+
+        if let Some(currently_loaded_bsp) = currently_loaded_bsp {
+            Self::draw_viewport_bsp(renderer, &viewport, currently_loaded_bsp, command_builder, camera)?;
+        }
+
+        overlay::draw_overlay(renderer, overlay, &viewport, command_builder)?;
 
+        command_builder.end_rendering()?;
+
+        Ok(())
+    }
+
+    fn draw_viewport_bsp(
+        renderer: &Renderer,
+        viewport: &Viewport,
+        currently_loaded_bsp: &BSP,
+        command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        camera: Camera
+    ) -> MResult<()> {
         let aspect_ratio = viewport.extent[0] / viewport.extent[1];
 
         let cluster_index = currently_loaded_bsp.bsp_data.find_cluster(camera.position);
         let cluster = cluster_index.map(|c| &currently_loaded_bsp.bsp_data.clusters[c]);
-        let sky = cluster.and_then(|c| c.sky.as_ref()).and_then(|s| renderer.skies.get(s));
+        let sky = cluster.and_then(|c| c.sky.as_ref()).and_then(|s| renderer.skies.get(s.as_str()));
 
         let z_near = 0.0625;
         let mut z_far = currently_loaded_bsp.draw_distance;
@@ -400,7 +643,7 @@ impl VulkanRenderer {
 
             // TODO: determine which fog color
             fog_data = FogData {
-                color: [sky.outdoor_fog_color[0], sky.outdoor_fog_color[1], sky.outdoor_fog_color[2], 0.0],
+                color: renderer.renderer.to_output_color([sky.outdoor_fog_color[0], sky.outdoor_fog_color[1], sky.outdoor_fog_color[2], 0.0]),
                 distance_from: sky.outdoor_fog_start_distance,
                 distance_to: sky.outdoor_fog_opaque_distance,
                 min_opacity: 0.0,
@@ -422,7 +665,7 @@ impl VulkanRenderer {
             1.0,
             sky_color,
             command_builder
-        ).unwrap();
+        )?;
 
         z_far = z_far.max(z_near + 1.0);
         let proj = Mat4::perspective_lh(
@@ -437,30 +680,30 @@ impl VulkanRenderer {
             Vec3::new(0.0, 0.0, -1.0)
         );
 
-        upload_fog_uniform(renderer, &fog_data, command_builder);
+        upload_fog_uniform(renderer, &fog_data, command_builder)?;
 
         let geo_shader_iterator = currently_loaded_bsp
             .geometry_indices_sorted_by_material
             .iter()
             .map(|g| &currently_loaded_bsp.geometries[*g])
-            .map(|g| (g, &renderer.shaders.get(&g.vulkan.shader).expect("no shader?").vulkan.pipeline_data));
+            .filter_map(|g| renderer.shaders.get(&g.shader).map(|s| (g, &s.backend.pipeline_data)));
 
         let opaque = geo_shader_iterator.clone().filter(|s| !s.1.is_transparent());
         let transparent = geo_shader_iterator.clone().filter(|s| s.1.is_transparent());
 
-        upload_main_material_uniform(renderer, camera.position.into(), Vec3::default(), Mat3::IDENTITY, view, proj, command_builder);
-        command_builder.set_cull_mode(CullMode::Back).unwrap();
+        upload_main_material_uniform(renderer, camera.position.into(), Vec3::default(), Mat3::IDENTITY, view, proj, command_builder)?;
+        command_builder.set_cull_mode(CullMode::Back)?;
 
         // Draw non-transparent shaders first
         let mut last_shader = None;
         for (geometry, shader) in opaque {
-            Self::draw_bsp_geometry(renderer, currently_loaded_bsp, command_builder, &camera, &mut last_shader, geometry, shader);
+            Self::draw_bsp_geometry(renderer, currently_loaded_bsp, command_builder, &camera, &mut last_shader, geometry, shader)?;
         }
         for (geometry, shader) in transparent {
-            Self::draw_bsp_geometry(renderer, currently_loaded_bsp, command_builder, &camera, &mut last_shader, geometry, shader);
+            Self::draw_bsp_geometry(renderer, currently_loaded_bsp, command_builder, &camera, &mut last_shader, geometry, shader)?;
         }
 
-        command_builder.end_rendering().expect("failed to end rendering inside viewport");
+        Ok(())
     }
 
     fn draw_bsp_geometry<'a, 'b>(
@@ -468,11 +711,11 @@ impl VulkanRenderer {
         currently_loaded_bsp: &'a BSP,
         mut command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
         camera: &Camera,
-        last_shader: &'b mut Option<&'a Arc<String>>,
+        last_shader: &'b mut Option<&'a Arc<str>>,
         geometry: &'a BSPGeometry,
         shader: &Arc<dyn VulkanMaterial>
-    ) {
-        let this_shader = &geometry.vulkan.shader;
+    ) -> MResult<()> {
+        let this_shader = &geometry.shader;
         let repeat_shader = if *last_shader != Some(this_shader) {
             *last_shader = Some(this_shader);
             false
@@ -486,30 +729,28 @@ impl VulkanRenderer {
             desired_lightmap = None;
         }
 
-        upload_lightmap_descriptor_set(renderer, desired_lightmap, &currently_loaded_bsp, &mut command_builder);
+        upload_lightmap_descriptor_set(renderer, desired_lightmap, &currently_loaded_bsp, &mut command_builder)?;
 
-        let index_buffer = geometry.vulkan.index_buffer.clone();
+        let index_buffer = geometry.backend.index_buffer.clone();
         let index_count = index_buffer.len() as usize;
-        command_builder.bind_index_buffer(index_buffer).expect("can't bind indices");
+        command_builder.bind_index_buffer(index_buffer)?;
 
         command_builder.bind_vertex_buffers(0, (
-            geometry.vulkan.vertex_buffer.clone(),
-            geometry.vulkan.texture_coords_buffer.clone(),
-            if geometry.vulkan.lightmap_texture_coords_buffer.is_none() {
-                geometry.vulkan.texture_coords_buffer.clone()
+            geometry.backend.vertex_buffer.clone(),
+            geometry.backend.texture_coords_buffer.clone(),
+            if geometry.backend.lightmap_texture_coords_buffer.is_none() {
+                geometry.backend.texture_coords_buffer.clone()
             } else {
-                geometry.vulkan.lightmap_texture_coords_buffer.clone().unwrap()
+                geometry.backend.lightmap_texture_coords_buffer.clone().unwrap()
             }
-        )).unwrap();
+        ))?;
 
-        shader
-            .generate_commands(renderer, index_count as u32, repeat_shader, &mut command_builder)
-            .expect("can't generate stage commands");
+        shader.generate_commands(renderer, index_count as u32, repeat_shader, &mut command_builder)
     }
 
-    fn draw_split_screen_bars(renderer: &Renderer, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, width: f32, height: f32) {
+    fn draw_split_screen_bars(renderer: &Renderer, command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, width: f32, height: f32) -> MResult<()> {
         if renderer.player_viewports.len() <= 1 {
-            return;
+            return Ok(());
         }
 
         let color = [0.0, 0.0, 0.0, 1.0];
@@ -518,15 +759,14 @@ impl VulkanRenderer {
             extent: [width, height],
             depth_range: 0.0..=1.0,
         };
-        command_builder.set_viewport(0, [viewport].into_iter().collect()).unwrap();
+        command_builder.set_viewport(0, [viewport].into_iter().collect())?;
 
         let base_thickness = 2.0;
         let scale = (width / 640.0).min(height / 480.0).max(1.0);
         let line_thickness_horizontal = base_thickness / height * scale;
         let line_thickness_vertical = base_thickness / width * scale;
 
-        draw_box(renderer, 0.0, 0.5 - line_thickness_horizontal / 2.0, 1.0, line_thickness_horizontal, color, command_builder)
-            .expect("can't draw split screen vertical bar");
+        draw_box(renderer, 0.0, 0.5 - line_thickness_horizontal / 2.0, 1.0, line_thickness_horizontal, color, command_builder)?;
 
         if renderer.player_viewports.len() > 2 {
             let y;
@@ -540,23 +780,10 @@ impl VulkanRenderer {
                 line_height = 1.0;
             }
 
-            draw_box(renderer, 0.5 - line_thickness_vertical / 2.0, y, line_thickness_vertical, line_height, color, command_builder)
-                .expect("can't draw split screen horizontal bar");
+            draw_box(renderer, 0.5 - line_thickness_vertical / 2.0, y, line_thickness_vertical, line_height, color, command_builder)?;
         }
-    }
 
-    fn execute_command_list(&mut self, command_buffer: Arc<impl PrimaryCommandBufferAbstract + 'static>) {
-        let execution = command_buffer.execute(self.queue.clone()).unwrap();
-
-        let future = self.future
-            .take()
-            .expect("no future?")
-            .join(execution)
-            .then_signal_fence_and_flush()
-            .expect("failed to signal/flush")
-            .boxed_send_sync();
-
-        self.future = Some(future)
+        Ok(())
     }
 
     fn generate_secondary_buffer_builder(&self) -> MResult<AutoCommandBufferBuilder<SecondaryAutoCommandBuffer>> {
@@ -566,7 +793,7 @@ impl VulkanRenderer {
             CommandBufferUsage::MultipleSubmit,
             CommandBufferInheritanceInfo {
                 render_pass: Some(CommandBufferInheritanceRenderPassType::BeginRendering(CommandBufferInheritanceRenderingInfo {
-                    color_attachment_formats: vec![Some(OFFLINE_PIPELINE_COLOR_FORMAT)],
+                    color_attachment_formats: vec![Some(self.color_format)],
                     depth_attachment_format: Some(Format::D32_SFLOAT),
                     ..CommandBufferInheritanceRenderingInfo::default()
                 })),
@@ -577,8 +804,85 @@ impl VulkanRenderer {
     }
 }
 
-extern "C" {
-    fn exit(code: i32) -> !;
+impl Sealed for VulkanRenderer {}
+
+impl RendererBackend for VulkanRenderer {
+    type Bitmap = VulkanBitmapData;
+    type Shader = VulkanMaterialShaderData;
+    type Geometry = VulkanMaterialData;
+    type BSP = VulkanBSPData;
+    type BSPGeometry = VulkanBSPGeometryData;
+
+    fn create_bitmap(&mut self, parameter: &AddBitmapBitmapParameter, resident_mipmap: Option<u32>, name: &str) -> MResult<Self::Bitmap> {
+        VulkanBitmapData::new(self, parameter, resident_mipmap, name)
+    }
+
+    fn set_bitmap_resident_mipmap(&mut self, bitmap: &mut Self::Bitmap, mipmap: u32, name: &str) -> MResult<()> {
+        bitmap.set_resident_mipmap(self, mipmap, name)
+    }
+
+    fn estimate_bitmap_memory_usage(&self, parameter: &AddBitmapBitmapParameter) -> u64 {
+        VulkanBitmapData::estimate_memory_usage(self, parameter)
+    }
+
+    fn create_shader(renderer: &mut Renderer, parameter: AddShaderParameter, _name: &str) -> MResult<Self::Shader> {
+        VulkanMaterialShaderData::new_from_parameters(renderer, parameter)
+    }
+
+    fn create_bsp_geometry(
+        renderer: &mut Renderer,
+        parameter: &AddBSPParameter,
+        material: &AddBSPParameterLightmapMaterial,
+        lightmap_index: Option<usize>,
+        name: &str
+    ) -> MResult<Self::BSPGeometry> {
+        VulkanBSPGeometryData::new(renderer, parameter, material, lightmap_index, name)
+    }
+
+    fn create_bsp(
+        renderer: &mut Renderer,
+        parameter: &AddBSPParameter,
+        cluster_surfaces: &[Vec<Vec<Vec<ModelTriangle>>>],
+        _name: &str
+    ) -> MResult<Self::BSP> {
+        VulkanBSPData::new(renderer, parameter, cluster_surfaces)
+    }
+
+    fn begin_upload_batch(&mut self) -> MResult<()> {
+        VulkanRenderer::begin_upload_batch(self)
+    }
+
+    fn submit_upload_batch(&mut self) -> MResult<()> {
+        VulkanRenderer::submit_upload_batch(self)
+    }
+
+    fn uploads_pending(&mut self) -> bool {
+        VulkanRenderer::uploads_pending(self)
+    }
+
+    fn wait_for_uploads(&mut self) -> MResult<()> {
+        VulkanRenderer::wait_for_uploads(self)
+    }
+
+    fn rebuild_swapchain(&mut self, parameters: &RendererParameters) -> MResult<()> {
+        VulkanRenderer::rebuild_swapchain(self, parameters)
+    }
+
+    fn get_time(&self) -> Duration {
+        VulkanRenderer::get_time(self)
+    }
+
+    fn draw_frame(renderer: &mut Renderer) -> MResult<bool> {
+        VulkanRenderer::draw_frame(renderer)
+    }
+
+    fn is_device_lost(&self) -> bool {
+        VulkanRenderer::is_device_lost(self)
+    }
+
+    fn recreate_device(&mut self, parameters: &RendererParameters) -> MResult<()> {
+        VulkanRenderer::recreate_device(self, parameters)
+    }
 }
 
 fn default_allocation_create_info() -> AllocationCreateInfo {
@@ -588,20 +892,45 @@ fn default_allocation_create_info() -> AllocationCreateInfo {
     }
 }
 
-impl<T: Display> From<Validated<T>> for Error {
+impl<T: Display + 'static> From<Validated<T>> for Error {
     fn from(value: Validated<T>) -> Self {
         match value {
             Validated::ValidationError(v) => v.into(),
-            Validated::Error(e) => Self::from_vulkan_error(format!("Vulkan error! {e}"))
+            Validated::Error(e) => Self::from_vulkan_result(underlying_vulkan_error(&e), format!("Vulkan error! {e}"))
         }
     }
 }
 
+impl From<VulkanError> for Error {
+    fn from(value: VulkanError) -> Self {
+        Self::from_vulkan_result(Some(value), format!("Vulkan error! {value}"))
+    }
+}
+
+/// Get the Vulkan result code that caused an error from vulkano, if any.
+fn underlying_vulkan_error(error: &dyn Any) -> Option<VulkanError> {
+    let memory_error = if let Some(e) = error.downcast_ref::<VulkanError>() {
+        return Some(*e)
+    }
+    else if let Some(AllocateBufferError::AllocateMemory(e)) = error.downcast_ref() {
+        e
+    }
+    else if let Some(AllocateImageError::AllocateMemory(e)) = error.downcast_ref() {
+        e
+    }
+    else {
+        return None
+    };
+
+    match memory_error {
+        MemoryAllocatorError::AllocateDeviceMemory(Validated::Error(e)) => Some(*e),
+        _ => None
+    }
+}
+
 impl From<Box<ValidationError>> for Error {
     fn from(value: Box<ValidationError>) -> Self {
-        // FIXME: figure out a more graceful way to do this
-        eprintln!("Validation error! {value:?}\n\n-----------\n\nBACKTRACE:\n\n{}\n\n-----------\n\n", std::backtrace::Backtrace::force_capture());
-        std::process::abort();
+        Self::from_vulkan_impl_error(format!("Validation error! {value}"))
     }
 }
 
@@ -618,6 +947,16 @@ impl Error {
     fn from_vulkan_impl_error(error: String) -> Self {
         Self::GraphicsAPIError { backend: "Vulkan-IMPL", error }
     }
+    fn from_vulkan_unsupported_error(error: String) -> Self {
+        Self::UnsupportedFeature { backend: "Vulkan-IMPL", error }
+    }
+    fn from_vulkan_result(result: Option<VulkanError>, error: String) -> Self {
+        match result {
+            Some(VulkanError::OutOfHostMemory | VulkanError::OutOfDeviceMemory) => Self::OutOfMemory { backend: "Vulkan", error },
+            Some(VulkanError::DeviceLost) => Self::DeviceLost { backend: "Vulkan" },
+            _ => Self::from_vulkan_error(error)
+        }
+    }
 }
 
 fn upload_lightmap_descriptor_set(
@@ -625,18 +964,20 @@ fn upload_lightmap_descriptor_set(
     lightmap_index: Option<usize>,
     bsp: &BSP,
     builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>
-) {
+) -> MResult<()> {
     let pipeline = renderer.renderer.pipelines[&VulkanPipelineType::ShaderEnvironment].get_pipeline();
     let set = lightmap_index
-        .and_then(|i| bsp.vulkan.lightmap_images.get(&i))
+        .and_then(|i| bsp.backend.lightmap_images.get(&i))
         .map(|b| b.clone())
-        .unwrap_or_else(|| bsp.vulkan.null_lightmaps.clone());
+        .unwrap_or_else(|| bsp.backend.null_lightmaps.clone());
     builder.bind_descriptor_sets(
         PipelineBindPoint::Graphics,
         pipeline.layout().clone(),
         1,
         set
-    ).unwrap();
+    )?;
+
+    Ok(())
 }
 
 struct FogData {
@@ -667,7 +1008,7 @@ fn upload_main_material_uniform(
     view: Mat4,
     proj: Mat4,
     builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>
-) {
+) -> MResult<()> {
     let pipeline = renderer.renderer.pipelines[&VulkanPipelineType::ShaderEnvironment].get_pipeline();
     let model = Mat4::IDENTITY;
 
@@ -689,7 +1030,7 @@ fn upload_main_material_uniform(
         BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
         default_allocation_create_info(),
         model_data
-    ).unwrap();
+    )?;
 
     let set = PersistentDescriptorSet::new(
         renderer.renderer.descriptor_set_allocator.as_ref(),
@@ -698,21 +1039,23 @@ fn upload_main_material_uniform(
             WriteDescriptorSet::buffer(0, model_uniform_buffer),
         ],
         []
-    ).unwrap();
+    )?;
 
     builder.bind_descriptor_sets(
         PipelineBindPoint::Graphics,
         pipeline.layout().clone(),
         0,
         set
-    ).unwrap();
+    )?;
+
+    Ok(())
 }
 
 fn upload_fog_uniform(
     renderer: &Renderer,
     fog: &FogData,
     builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>
-) {
+) -> MResult<()> {
     let pipeline = renderer.renderer.pipelines[&VulkanPipelineType::ShaderEnvironment].get_pipeline();
 
     let fog_data = VulkanFogData {
@@ -728,7 +1071,7 @@ fn upload_fog_uniform(
         BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
         default_allocation_create_info(),
         fog_data
-    ).unwrap();
+    )?;
 
     let set = PersistentDescriptorSet::new(
         renderer.renderer.descriptor_set_allocator.as_ref(),
@@ -737,14 +1080,16 @@ fn upload_fog_uniform(
             WriteDescriptorSet::buffer(0, fog_uniform_buffer),
         ],
         []
-    ).unwrap();
+    )?;
 
     builder.bind_descriptor_sets(
         PipelineBindPoint::Graphics,
         pipeline.layout().clone(),
         2,
         set
-    ).unwrap();
+    )?;
+
+    Ok(())
 }
 
 fn draw_box(renderer: &Renderer, x: f32, y: f32, width: f32, height: f32, color: [f32; 4], command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> MResult<()> {
@@ -802,7 +1147,7 @@ fn draw_box(renderer: &Renderer, x: f32, y: f32, width: f32, height: f32, color:
         BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
         default_allocation_create_info(),
         color
-    ).unwrap();
+    )?;
 
     let set = PersistentDescriptorSet::new(
         renderer.renderer.descriptor_set_allocator.as_ref(),
@@ -811,20 +1156,20 @@ fn draw_box(renderer: &Renderer, x: f32, y: f32, width: f32, height: f32, color:
             WriteDescriptorSet::buffer(0, uniform_buffer),
         ],
         []
-    ).unwrap();
+    )?;
 
     command_builder.bind_descriptor_sets(
         PipelineBindPoint::Graphics,
         pipeline.layout().clone(),
         1,
         set
-    ).unwrap();
+    )?;
 
-    command_builder.set_cull_mode(CullMode::None).unwrap();
-    command_builder.bind_index_buffer(indices).unwrap();
-    command_builder.bind_vertex_buffers(0, vertices).unwrap();
-    command_builder.bind_pipeline_graphics(pipeline).unwrap();
-    command_builder.draw_indexed(6, 1, 0, 0, 0).unwrap();
+    command_builder.set_cull_mode(CullMode::None)?;
+    command_builder.bind_index_buffer(indices)?;
+    command_builder.bind_vertex_buffers(0, vertices)?;
+    command_builder.bind_pipeline_graphics(pipeline)?;
+    command_builder.draw_indexed(6, 1, 0, 0, 0)?;
 
     Ok(())
 }
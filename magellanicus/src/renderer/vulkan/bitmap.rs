@@ -1,36 +1,101 @@
 use crate::error::{Error, MResult};
+use crate::renderer::bitmap_decode::cubemap_face_to_layer;
 use crate::renderer::mipmap_iterator::{MipmapFaceIterator, MipmapMetadata, MipmapTextureIterator, MipmapType};
 use crate::renderer::vulkan::{default_allocation_create_info, VulkanRenderer};
-use crate::renderer::{decode_p8_to_a8r8g8b8le, AddBitmapBitmapParameter, BitmapFormat, BitmapType};
+use crate::renderer::{decode_block_compressed_bitmap_to_r8g8b8a8, decode_p8_with_palette_to_a8r8g8b8le, encode_r8g8b8a8_bitmap_to_block_compressed, generate_mipmaps_r8g8b8a8, AddBitmapBitmapParameter, BitmapColorSpace, BitmapFormat, BitmapType, LogSeverity, TextureRecompression};
+use std::format;
 use std::num::NonZeroUsize;
 use std::string::ToString;
 use std::sync::Arc;
 use std::vec::Vec;
-use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
-use vulkano::command_buffer::{AutoCommandBufferBuilder, BufferImageCopy, CommandBufferUsage, CopyBufferToImageInfo};
-use vulkano::format::Format;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{BlitImageInfo, BufferImageCopy, CopyBufferToImageInfo, CopyImageInfo, CopyImageToBufferInfo, ImageBlit, ImageCopy};
+use vulkano::format::{Format, FormatFeatures};
+use vulkano::image::sampler::{Filter, Sampler};
+use vulkano::image::view::ImageView;
 use vulkano::image::{Image, ImageAspects, ImageCreateFlags, ImageCreateInfo, ImageSubresourceLayers, ImageType, ImageUsage};
 use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocatePreference, MemoryTypeFilter};
+use vulkano::sync::Sharing;
 use vulkano::DeviceSize;
 
 pub struct VulkanBitmapData {
-    pub image: Arc<Image>
+    pub image: Arc<Image>,
+
+    /// Default view of `image`, so one doesn't need to be made each time the bitmap is drawn as an overlay.
+    pub view: Arc<ImageView>,
+
+    pub sampler: Arc<Sampler>,
+
+    /// Set if the bitmap's mipmaps are streamed in and out.
+    streaming: Option<VulkanBitmapStreaming>
+}
+
+struct VulkanBitmapStreaming {
+    /// Image with every mipmap loaded.
+    image_create_info: ImageCreateInfo,
+
+    /// Mipmap of the bitmap that is the image's first mipmap.
+    resident_mipmap: u32,
+
+    /// Lowest resolution mipmap that can be the image's first mipmap.
+    ///
+    /// Generated mipmaps past this are always loaded.
+    lowest_mipmap: u32,
+
+    /// Mipmaps that aren't loaded into the image, with their layers in order, or `None` if they are.
+    host_mipmaps: Vec<Option<Subbuffer<[u8]>>>
 }
 
 impl VulkanBitmapData {
-    pub fn new(vulkan_renderer: &mut VulkanRenderer, parameter: &AddBitmapBitmapParameter) -> MResult<Self> {
+    /// Estimate how much video memory a bitmap will use, in bytes.
+    ///
+    /// This accounts for formats that are converted when uploading and for mipmaps that are generated.
+    pub fn estimate_memory_usage(vulkan_renderer: &VulkanRenderer, parameter: &AddBitmapBitmapParameter) -> u64 {
+        let supports_block_compression = vulkan_renderer.device.enabled_features().texture_compression_bc;
+        let expansion = match parameter.format {
+            BitmapFormat::DXT1 | BitmapFormat::DXT1SRGB | BitmapFormat::BC4 if !supports_block_compression => 8,
+            BitmapFormat::DXT3 | BitmapFormat::DXT5 | BitmapFormat::BC7 | BitmapFormat::BC5 if !supports_block_compression => 4,
+            BitmapFormat::DXT3SRGB | BitmapFormat::DXT5SRGB | BitmapFormat::BC7SRGB if !supports_block_compression => 4,
+            BitmapFormat::A8 | BitmapFormat::Y8 | BitmapFormat::AY8 | BitmapFormat::P8 => 4,
+            BitmapFormat::A8Y8 => 2,
+            _ => 1
+        };
+        let uncompressed_size = parameter.loaded_data_length() * expansion;
+
+        // Assume DXT5/BC7 when recompressing, which is a quarter of the size
+        match get_recompression(vulkan_renderer, parameter) {
+            Some(_) => uncompressed_size / 4,
+            None => uncompressed_size
+        }
+    }
+
+    pub fn new(vulkan_renderer: &mut VulkanRenderer, parameter: &AddBitmapBitmapParameter, resident_mipmap: Option<u32>, name: &str) -> MResult<Self> {
         let (image_type, depth) = match parameter.bitmap_type {
             BitmapType::Dim3D { depth } => (ImageType::Dim3d, depth),
             _ => (ImageType::Dim2d, 1)
         };
 
         let mut transcoded_pixels: Vec<u8> = Vec::new();
+        let supports_block_compression = vulkan_renderer.device.enabled_features().texture_compression_bc;
+
+        let (mut bitmap_format, mut format, mut bytes) = match parameter.format {
+            // Decode on the CPU if we can't sample block-compressed textures
+            f if f.is_block_compressed() && !supports_block_compression => {
+                transcoded_pixels = decode_block_compressed_bitmap_to_r8g8b8a8(parameter)
+                    .ok_or_else(|| Error::from_data_error_string("Unable to decode block-compressed bitmap".to_string()))?;
+                (BitmapFormat::A8R8G8B8, decoded_format(f), &transcoded_pixels)
+            },
 
-        let (bitmap_format, format, bytes) = match parameter.format {
             BitmapFormat::DXT1 => (parameter.format, Format::BC1_RGBA_UNORM_BLOCK, &parameter.data),
             BitmapFormat::DXT3 => (parameter.format, Format::BC2_UNORM_BLOCK, &parameter.data),
             BitmapFormat::DXT5 => (parameter.format, Format::BC3_UNORM_BLOCK, &parameter.data),
             BitmapFormat::BC7 => (parameter.format, Format::BC7_UNORM_BLOCK, &parameter.data),
+            BitmapFormat::BC4 => (parameter.format, Format::BC4_UNORM_BLOCK, &parameter.data),
+            BitmapFormat::BC5 => (parameter.format, Format::BC5_UNORM_BLOCK, &parameter.data),
+            BitmapFormat::DXT1SRGB => (parameter.format, Format::BC1_RGBA_SRGB_BLOCK, &parameter.data),
+            BitmapFormat::DXT3SRGB => (parameter.format, Format::BC2_SRGB_BLOCK, &parameter.data),
+            BitmapFormat::DXT5SRGB => (parameter.format, Format::BC3_SRGB_BLOCK, &parameter.data),
+            BitmapFormat::BC7SRGB => (parameter.format, Format::BC7_SRGB_BLOCK, &parameter.data),
 
             // TODO: VERIFY
             BitmapFormat::A8R8G8B8 => (parameter.format, Format::B8G8R8A8_UNORM, &parameter.data),
@@ -39,6 +104,10 @@ impl VulkanBitmapData {
             BitmapFormat::A1R5G5B5 => (parameter.format, Format::A1R5G5B5_UNORM_PACK16, &parameter.data),
             BitmapFormat::A4R4G4B4 => (parameter.format, Format::A4R4G4B4_UNORM_PACK16, &parameter.data),
             BitmapFormat::R32G32B32A32SFloat => (parameter.format, Format::R32G32B32A32_SFLOAT, &parameter.data),
+            BitmapFormat::R16G16B16A16SFloat => (parameter.format, Format::R16G16B16A16_SFLOAT, &parameter.data),
+            BitmapFormat::R8G8 => (parameter.format, Format::R8G8_UNORM, &parameter.data),
+            BitmapFormat::A8R8G8B8SRGB => (parameter.format, Format::B8G8R8A8_SRGB, &parameter.data),
+            BitmapFormat::X8R8G8B8SRGB => (parameter.format, Format::B8G8R8A8_SRGB, &parameter.data),
 
             // TODO: VERIFY ALL OF THE MONOCHROME MEMES
 
@@ -89,59 +158,130 @@ impl VulkanBitmapData {
                 (BitmapFormat::A8R8G8B8, Format::B8G8R8A8_UNORM, &transcoded_pixels)
             },
 
-            // TODO: P8
             BitmapFormat::P8 => {
+                let palette = parameter.palette.as_deref();
                 transcoded_pixels.reserve_exact(parameter.data.len() * 4);
                 for pixel in parameter.data.iter() {
-                    transcoded_pixels.extend_from_slice(&decode_p8_to_a8r8g8b8le(*pixel));
+                    transcoded_pixels.extend_from_slice(&decode_p8_with_palette_to_a8r8g8b8le(*pixel, palette));
                 }
                 (BitmapFormat::A8R8G8B8, Format::B8G8R8A8_UNORM, &transcoded_pixels)
             }
         };
 
-        let image = Image::new(
-            vulkan_renderer.memory_allocator.clone(),
-            ImageCreateInfo {
-                image_type,
-                format,
-                extent: [parameter.resolution.width, parameter.resolution.height, depth],
-                mip_levels: parameter.mipmap_count + 1,
-                array_layers: if parameter.bitmap_type == BitmapType::Cubemap { 6 } else { 1 },
-                usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
-                flags: if parameter.bitmap_type == BitmapType::Cubemap {
-                    ImageCreateFlags::CUBE_COMPATIBLE
+        let recompression = get_recompression(vulkan_renderer, parameter);
+
+        // Mipmaps that are uploaded, and mipmaps that are blitted from the last uploaded mipmap afterwards.
+        let loaded_mipmap_count = parameter.loaded_mipmap_count();
+        let mut uploaded_mipmap_count = parameter.mipmap_count;
+        let mut blitted_mipmap_count = 0;
+        let mut generated_pixels: Vec<u8> = Vec::new();
+
+        // Color data has to be filtered in linear space, which blitting only does if the image has an sRGB format
+        let srgb = parameter.format.is_srgb() || parameter.color_space == BitmapColorSpace::SRGB;
+        let blit_filters_linearly = !srgb || parameter.format.is_srgb() || vulkan_renderer.linear_lighting;
+
+        if loaded_mipmap_count > parameter.mipmap_count {
+            let blit_features = FormatFeatures::BLIT_SRC | FormatFeatures::BLIT_DST | FormatFeatures::SAMPLED_IMAGE_FILTER_LINEAR;
+            let format_features = vulkan_renderer
+                .device
+                .physical_device()
+                .format_properties(format)?
+                .optimal_tiling_features;
+
+            // Block-compressed images can't be blitted to, so generate mipmaps on the CPU if recompressing
+            if format.compression().is_none() && recompression.is_none() && blit_filters_linearly && format_features.contains(blit_features) {
+                blitted_mipmap_count = loaded_mipmap_count - parameter.mipmap_count;
+            }
+            else {
+                let pixels = if format.compression().is_some() {
+                    decode_block_compressed_bitmap_to_r8g8b8a8(parameter).map(|p| (BitmapFormat::A8R8G8B8, decoded_format(parameter.format), p))
+                }
+                else if bitmap_format.block_byte_size() == 4 && bitmap_format.block_pixel_length() == 1 {
+                    Some((bitmap_format, format, bytes.clone()))
                 }
                 else {
-                    ImageCreateFlags::empty()
+                    None
+                };
+
+                let generated = pixels.and_then(|(new_bitmap_format, new_format, mut pixels)| {
+                    generate_mipmaps_r8g8b8a8(
+                        parameter.resolution.width,
+                        parameter.resolution.height,
+                        parameter.bitmap_type,
+                        parameter.mipmap_count,
+                        loaded_mipmap_count,
+                        srgb,
+                        &mut pixels
+                    )?;
+                    Some((new_bitmap_format, new_format, pixels))
+                });
+
+                match generated {
+                    Some((new_bitmap_format, new_format, pixels)) => {
+                        generated_pixels = pixels;
+                        bitmap_format = new_bitmap_format;
+                        format = new_format;
+                        bytes = &generated_pixels;
+                        uploaded_mipmap_count = loaded_mipmap_count;
+                    },
+                    None => vulkan_renderer.log(LogSeverity::Warning, &format!("Can't generate mipmaps for {:?} bitmaps; only using the supplied mipmaps", parameter.format))
+                }
+            }
+        }
+
+        let mut recompressed_pixels: Vec<u8> = Vec::new();
+        if let Some(recompression) = recompression {
+            match recompress(recompression, parameter, format, uploaded_mipmap_count, bytes) {
+                Some((new_bitmap_format, new_format, pixels)) => {
+                    recompressed_pixels = pixels;
+                    bitmap_format = new_bitmap_format;
+                    format = new_format;
+                    bytes = &recompressed_pixels;
                 },
-                ..Default::default()
+                None => vulkan_renderer.log(LogSeverity::Warning, &format!("Can't recompress {name} ({:?}); uploading uncompressed", parameter.format))
+            }
+        }
+
+        // Color data is stored in sRGB, so have the sampler convert it to linear for linear lighting.
+        if vulkan_renderer.linear_lighting && parameter.color_space == BitmapColorSpace::SRGB {
+            format = srgb_format(format);
+        }
+
+        let streamed = resident_mipmap.is_some();
+        let resident_mipmap = resident_mipmap.unwrap_or(0).min(uploaded_mipmap_count);
+
+        let image_create_info = ImageCreateInfo {
+            image_type,
+            format,
+            extent: [parameter.resolution.width, parameter.resolution.height, depth],
+            mip_levels: uploaded_mipmap_count + blitted_mipmap_count + 1,
+            array_layers: if parameter.bitmap_type == BitmapType::Cubemap { 6 } else { 1 },
+            usage: if blitted_mipmap_count > 0 || streamed {
+                ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED
+            }
+            else {
+                ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED
             },
-            AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
-                allocate_preference: MemoryAllocatePreference::AlwaysAllocate,
-                ..Default::default()
+            flags: if parameter.bitmap_type == BitmapType::Cubemap {
+                ImageCreateFlags::CUBE_COMPATIBLE
+            }
+            else {
+                ImageCreateFlags::empty()
             },
-        )?;
-
-        let upload_buffer = Buffer::new_slice(
-            vulkan_renderer.memory_allocator.clone(),
-            BufferCreateInfo {
-                usage: BufferUsage::TRANSFER_SRC,
-                ..Default::default()
+            sharing: match vulkan_renderer.transfer_queue.as_ref() {
+                // Written on the transfer queue and read on the graphics queue
+                Some(transfer_queue) => Sharing::Concurrent(
+                    [vulkan_renderer.queue.queue_family_index(), transfer_queue.queue_family_index()].into_iter().collect()
+                ),
+                None => Sharing::Exclusive
             },
-            default_allocation_create_info(),
-            bytes.len() as DeviceSize,
-        )?;
-
-        upload_buffer
-            .write()
-            .map_err(|e| Error::from_vulkan_error(e.to_string()))?
-            .copy_from_slice(bytes);
+            ..Default::default()
+        };
 
-        let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
-            &vulkan_renderer.command_buffer_allocator,
-            vulkan_renderer.queue.queue_family_index(),
-            CommandBufferUsage::OneTimeSubmit,
+        let image = Image::new(
+            vulkan_renderer.memory_allocator.clone(),
+            mipmap_image_create_info(&image_create_info, resident_mipmap),
+            image_allocation_create_info()
         )?;
 
         let width_nzus = NonZeroUsize::new(parameter.resolution.width as usize).unwrap();
@@ -152,7 +292,7 @@ impl VulkanBitmapData {
             BitmapType::Dim3D { depth } => MipmapType::ThreeDimensional(NonZeroUsize::new(depth as usize).unwrap())
         };
         let block_pixel_length_nzus = NonZeroUsize::new(bitmap_format.block_pixel_length()).unwrap();
-        let mipmap_count = Some(parameter.mipmap_count as usize);
+        let mipmap_count = Some(uploaded_mipmap_count as usize);
 
         let mut mipmap_face_iterator = MipmapFaceIterator::new(
             width_nzus,
@@ -177,54 +317,363 @@ impl VulkanBitmapData {
             &mut mipmap_face_iterator
         };
 
-        let mut offset = 0;
+        // Mipmaps are stored from largest to smallest, so the resident mipmaps are at the end.
         let block_size = bitmap_format.block_byte_size();
-        let pixel_size = bitmap_format.block_pixel_length();
-        for i in iterator_to_use {
-            let size = block_size * i.block_count;
-            let actual_face_index = if parameter.bitmap_type != BitmapType::Cubemap {
-                0
+        let mipmaps: Vec<MipmapMetadata> = iterator_to_use.collect();
+        let resident_offset = mipmaps
+            .iter()
+            .find(|m| m.mipmap_index as u32 >= resident_mipmap)
+            .map(|m| m.block_offset * block_size)
+            .unwrap_or(bytes.len());
+
+        // Mipmaps that aren't resident are kept in host memory with their layers in order
+        let mut host_mipmaps = Vec::new();
+        if streamed {
+            for mipmap in 0..resident_mipmap {
+                let buffer = host_mipmap_buffer(vulkan_renderer, mipmap_byte_size(&image_create_info, mipmap), true)?;
+                let mut buffer_data = buffer.write().map_err(|e| Error::from_vulkan_error(e.to_string()))?;
+                for m in mipmaps.iter().filter(|m| m.mipmap_index as u32 == mipmap) {
+                    let start = m.block_offset * block_size;
+                    let size = m.block_count * block_size;
+                    let layer = if parameter.bitmap_type == BitmapType::Cubemap { cubemap_face_to_layer(m.face_index) } else { 0 };
+                    buffer_data[layer * size..(layer + 1) * size].copy_from_slice(&bytes[start..start + size]);
+                }
+                drop(buffer_data);
+                host_mipmaps.push(Some(buffer));
             }
-            else {
-                // TODO: IS THIS RIGHT?????? I THINK IT IS BUT IDK :(
-                match i.face_index {
-                    0 => 0,
-                    1 => 2,
-                    2 => 1,
-                    3 => 3,
-                    4 => 4,
-                    5 => 5,
-                    _ => continue
+        }
+
+        let upload_buffer = Buffer::new_slice(
+            vulkan_renderer.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            default_allocation_create_info(),
+            (bytes.len() - resident_offset) as DeviceSize,
+        )?;
+
+        upload_buffer
+            .write()
+            .map_err(|e| Error::from_vulkan_error(e.to_string()))?
+            .copy_from_slice(&bytes[resident_offset..]);
+
+        vulkan_renderer.record_upload(|batch| {
+            let pixel_size = bitmap_format.block_pixel_length();
+            for i in mipmaps.iter().filter(|m| m.mipmap_index as u32 >= resident_mipmap) {
+                let actual_face_index = if parameter.bitmap_type != BitmapType::Cubemap {
+                    0
                 }
+                else {
+                    cubemap_face_to_layer(i.face_index) as u32
+                };
+                batch.transfer_commands().copy_buffer_to_image(CopyBufferToImageInfo {
+                    regions: [
+                        BufferImageCopy {
+                            image_subresource: ImageSubresourceLayers {
+                                aspects: ImageAspects::COLOR,
+                                mip_level: i.mipmap_index as u32 - resident_mipmap,
+                                array_layers: actual_face_index..(actual_face_index + 1)
+                            },
+                            buffer_offset: (i.block_offset * block_size - resident_offset) as DeviceSize,
+                            buffer_image_height: (i.block_height * pixel_size) as u32,
+                            buffer_row_length: (i.block_width * pixel_size) as u32,
+                            image_offset: [0,0,0],
+                            image_extent: [i.width as u32, i.height as u32, i.depth as u32],
+                            ..Default::default()
+                        }
+                    ].into(),
+                    ..CopyBufferToImageInfo::buffer_image(
+                        upload_buffer.clone(),
+                        image.clone()
+                    )
+                })?;
+            }
+
+            // Each mipmap is blitted from the one before it. Cubemap faces are blitted separately as array layers, and 3D
+            // textures are filtered across depth.
+            let mipmap_extent = |mip_level: u32| image.extent().map(|e| (e >> mip_level).max(1));
+            let first_blitted_mip_level = uploaded_mipmap_count - resident_mipmap + 1;
+            for mip_level in first_blitted_mip_level..(first_blitted_mip_level + blitted_mipmap_count) {
+                let subresource = |mip_level: u32| ImageSubresourceLayers {
+                    aspects: ImageAspects::COLOR,
+                    mip_level,
+                    array_layers: 0..image.array_layers()
+                };
+                batch.graphics_commands().blit_image(BlitImageInfo {
+                    regions: [
+                        ImageBlit {
+                            src_subresource: subresource(mip_level - 1),
+                            src_offsets: [[0, 0, 0], mipmap_extent(mip_level - 1)],
+                            dst_subresource: subresource(mip_level),
+                            dst_offsets: [[0, 0, 0], mipmap_extent(mip_level)],
+                            ..Default::default()
+                        }
+                    ].into(),
+                    filter: Filter::Linear,
+                    ..BlitImageInfo::images(image.clone(), image.clone())
+                })?;
+            }
+
+            Ok(())
+        })?;
+
+        let sampler = vulkan_renderer.get_sampler(&parameter.sampler)?;
+
+        vulkan_renderer.set_debug_name(image.as_ref(), name);
+
+        let streaming = streamed.then(|| VulkanBitmapStreaming {
+            image_create_info,
+            resident_mipmap,
+            lowest_mipmap: uploaded_mipmap_count,
+            host_mipmaps
+        });
+
+        let view = ImageView::new_default(image.clone())?;
+        Ok(Self { image, view, sampler, streaming })
+    }
+
+    /// Make `mipmap` the image's first mipmap, copying mipmaps that stay loaded on the device, uploading mipmaps that
+    /// are loaded from host memory, and copying mipmaps that are dropped to host memory.
+    pub fn set_resident_mipmap(&mut self, vulkan_renderer: &mut VulkanRenderer, mipmap: u32, name: &str) -> MResult<()> {
+        let Some(streaming) = self.streaming.as_mut() else {
+            return Err(Error::from_data_error_string(format!("Can't stream {name} because it is not streamed")))
+        };
+
+        let old_mipmap = streaming.resident_mipmap;
+        let new_mipmap = mipmap.min(streaming.lowest_mipmap);
+        if new_mipmap == old_mipmap {
+            return Ok(())
+        }
+
+        let image_create_info = &streaming.image_create_info;
+        let old_image = self.image.clone();
+        let image = Image::new(
+            vulkan_renderer.memory_allocator.clone(),
+            mipmap_image_create_info(image_create_info, new_mipmap),
+            image_allocation_create_info()
+        )?;
+        let view = ImageView::new_default(image.clone())?;
+
+        let uploads = (new_mipmap..old_mipmap)
+            .map(|m| streaming.host_mipmaps[m as usize]
+                .clone()
+                .map(|buffer| (m, buffer))
+                .ok_or_else(|| Error::from_data_error_string(format!("Mipmap {m} of {name} is missing"))))
+            .collect::<MResult<Vec<_>>>()?;
+
+        let readbacks = (old_mipmap..new_mipmap)
+            .map(|m| Ok((m, host_mipmap_buffer(vulkan_renderer, mipmap_byte_size(image_create_info, m), false)?)))
+            .collect::<MResult<Vec<_>>>()?;
+
+        vulkan_renderer.record_immediate_upload(|batch| {
+            let commands = batch.transfer_commands();
+            let subresource = |mip_level: u32| ImageSubresourceLayers {
+                aspects: ImageAspects::COLOR,
+                mip_level,
+                array_layers: 0..image_create_info.array_layers
             };
-            command_buffer_builder.copy_buffer_to_image(CopyBufferToImageInfo {
-                regions: [
-                    BufferImageCopy {
-                        image_subresource: ImageSubresourceLayers {
-                            aspects: ImageAspects::COLOR,
-                            mip_level: i.mipmap_index as u32,
-                            array_layers: actual_face_index..(actual_face_index + 1)
-                        },
-                        buffer_offset: offset,
-                        buffer_image_height: (i.block_height * pixel_size) as u32,
-                        buffer_row_length: (i.block_width * pixel_size) as u32,
-                        image_offset: [0,0,0],
-                        image_extent: [i.width as u32, i.height as u32, i.depth as u32],
-                        ..Default::default()
-                    }
-                ].into(),
-                ..CopyBufferToImageInfo::buffer_image(
-                    upload_buffer.clone(),
-                    image.clone()
-                )
-            })?;
-
-            offset += size as DeviceSize;
+
+            for m in new_mipmap.max(old_mipmap)..image_create_info.mip_levels {
+                commands.copy_image(CopyImageInfo {
+                    regions: [
+                        ImageCopy {
+                            src_subresource: subresource(m - old_mipmap),
+                            dst_subresource: subresource(m - new_mipmap),
+                            extent: mipmap_extent(image_create_info.extent, m),
+                            ..Default::default()
+                        }
+                    ].into(),
+                    ..CopyImageInfo::images(old_image.clone(), image.clone())
+                })?;
+            }
+
+            for (m, buffer) in &uploads {
+                commands.copy_buffer_to_image(CopyBufferToImageInfo {
+                    regions: [mipmap_region(image_create_info, m - new_mipmap, *m)].into(),
+                    ..CopyBufferToImageInfo::buffer_image(buffer.clone(), image.clone())
+                })?;
+            }
+
+            for (m, buffer) in &readbacks {
+                commands.copy_image_to_buffer(CopyImageToBufferInfo {
+                    regions: [mipmap_region(image_create_info, m - old_mipmap, *m)].into(),
+                    ..CopyImageToBufferInfo::image_buffer(old_image.clone(), buffer.clone())
+                })?;
+            }
+
+            Ok(())
+        })?;
+
+        for (m, _) in uploads {
+            streaming.host_mipmaps[m as usize] = None;
+        }
+        for (m, buffer) in readbacks {
+            streaming.host_mipmaps[m as usize] = Some(buffer);
         }
+        streaming.resident_mipmap = new_mipmap;
 
-        let buffer = command_buffer_builder.build()?;
-        vulkan_renderer.execute_command_list(buffer);
+        vulkan_renderer.set_debug_name(image.as_ref(), name);
+        self.image = image;
+        self.view = view;
+        Ok(())
+    }
+}
 
-        Ok(Self { image })
+fn image_allocation_create_info() -> AllocationCreateInfo {
+    AllocationCreateInfo {
+        memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+        allocate_preference: MemoryAllocatePreference::AlwaysAllocate,
+        ..Default::default()
+    }
+}
+
+/// Get the extent of a mipmap of an image with the given extent.
+fn mipmap_extent(extent: [u32; 3], mipmap: u32) -> [u32; 3] {
+    extent.map(|e| (e >> mipmap).max(1))
+}
+
+/// Get the image to create to hold `first_mipmap` and smaller mipmaps of `image_create_info`.
+fn mipmap_image_create_info(image_create_info: &ImageCreateInfo, first_mipmap: u32) -> ImageCreateInfo {
+    ImageCreateInfo {
+        extent: mipmap_extent(image_create_info.extent, first_mipmap),
+        mip_levels: image_create_info.mip_levels - first_mipmap,
+        ..image_create_info.clone()
+    }
+}
+
+/// Get the size in bytes of every layer of a mipmap, tightly packed.
+fn mipmap_byte_size(image_create_info: &ImageCreateInfo, mipmap: u32) -> DeviceSize {
+    let [width, height, depth] = mipmap_extent(image_create_info.extent, mipmap);
+    let [block_width, block_height, _] = image_create_info.format.block_extent();
+    width.div_ceil(block_width) as DeviceSize
+        * height.div_ceil(block_height) as DeviceSize
+        * depth as DeviceSize
+        * image_create_info.array_layers as DeviceSize
+        * image_create_info.format.block_size()
+}
+
+/// Get the copy region for every layer of a mipmap, tightly packed in a buffer.
+fn mipmap_region(image_create_info: &ImageCreateInfo, mip_level: u32, mipmap: u32) -> BufferImageCopy {
+    BufferImageCopy {
+        image_subresource: ImageSubresourceLayers {
+            aspects: ImageAspects::COLOR,
+            mip_level,
+            array_layers: 0..image_create_info.array_layers
+        },
+        image_extent: mipmap_extent(image_create_info.extent, mipmap),
+        ..Default::default()
+    }
+}
+
+/// Allocate a buffer in host memory to hold a mipmap that isn't loaded into device memory.
+fn host_mipmap_buffer(vulkan_renderer: &VulkanRenderer, size: DeviceSize, host_writes: bool) -> MResult<Subbuffer<[u8]>> {
+    Ok(Buffer::new_slice(
+        vulkan_renderer.memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: if host_writes {
+                MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE
+            }
+            else {
+                MemoryTypeFilter::PREFER_HOST
+            },
+            ..Default::default()
+        },
+        size
+    )?)
+}
+
+/// Block-compressed encoding to recompress a bitmap to.
+#[derive(Copy, Clone)]
+enum Recompression {
+    Texture(TextureRecompression),
+
+    /// Keep only X and Y of a bump map, encoded to BC5.
+    TwoChannelNormalMap
+}
+
+/// Get the recompression to use for a bitmap, if any.
+///
+/// Only uncompressed 32-bit bitmaps (and 8-bit and 16-bit bitmaps, which are transcoded to 32-bit) are recompressed.
+fn get_recompression(vulkan_renderer: &VulkanRenderer, parameter: &AddBitmapBitmapParameter) -> Option<Recompression> {
+    let supports_block_compression = vulkan_renderer.device.enabled_features().texture_compression_bc;
+    if !supports_block_compression || matches!(parameter.bitmap_type, BitmapType::Dim3D { .. }) {
+        return None
+    }
+
+    match parameter.format {
+        BitmapFormat::A8R8G8B8
+        | BitmapFormat::X8R8G8B8
+        | BitmapFormat::A8R8G8B8SRGB
+        | BitmapFormat::X8R8G8B8SRGB
+        | BitmapFormat::A8
+        | BitmapFormat::Y8
+        | BitmapFormat::AY8
+        | BitmapFormat::A8Y8 => vulkan_renderer.texture_recompression.map(Recompression::Texture),
+        BitmapFormat::P8 if vulkan_renderer.two_channel_normal_maps && parameter.is_opaque_p8() => Some(Recompression::TwoChannelNormalMap),
+        _ => None
+    }
+}
+
+/// Encode B8G8R8A8 pixels with `mipmap_count` mipmaps to a block-compressed format.
+fn recompress(
+    recompression: Recompression,
+    parameter: &AddBitmapBitmapParameter,
+    format: Format,
+    mipmap_count: u32,
+    bytes: &[u8]
+) -> Option<(BitmapFormat, Format, Vec<u8>)> {
+    let opaque = matches!(parameter.format, BitmapFormat::X8R8G8B8 | BitmapFormat::X8R8G8B8SRGB);
+    let pixels: Vec<u8> = bytes
+        .chunks_exact(4)
+        .flat_map(|p| [p[2], p[1], p[0], if opaque { 0xFF } else { p[3] }])
+        .collect();
+
+    let (bitmap_format, compressed_format) = match recompression {
+        Recompression::Texture(TextureRecompression::Fast) if pixels.chunks_exact(4).all(|p| p[3] == 0xFF) => (BitmapFormat::DXT1, Format::BC1_RGBA_UNORM_BLOCK),
+        Recompression::Texture(TextureRecompression::Fast) => (BitmapFormat::DXT5, Format::BC3_UNORM_BLOCK),
+        Recompression::Texture(TextureRecompression::HighQuality) => (BitmapFormat::BC7, Format::BC7_UNORM_BLOCK),
+        Recompression::TwoChannelNormalMap => (BitmapFormat::BC5, Format::BC5_UNORM_BLOCK)
+    };
+
+    let data = encode_r8g8b8a8_bitmap_to_block_compressed(
+        bitmap_format,
+        parameter.resolution.width,
+        parameter.resolution.height,
+        parameter.bitmap_type,
+        mipmap_count,
+        &pixels
+    )?;
+
+    let compressed_format = if format == Format::B8G8R8A8_SRGB { srgb_format(compressed_format) } else { compressed_format };
+    Some((bitmap_format, compressed_format, data))
+}
+
+/// Get the sRGB variant of a format, which decodes to linear space when sampled.
+///
+/// Formats without an sRGB variant are returned as-is.
+fn srgb_format(format: Format) -> Format {
+    match format {
+        Format::B8G8R8A8_UNORM => Format::B8G8R8A8_SRGB,
+        Format::R8G8B8A8_UNORM => Format::R8G8B8A8_SRGB,
+        Format::BC1_RGBA_UNORM_BLOCK => Format::BC1_RGBA_SRGB_BLOCK,
+        Format::BC2_UNORM_BLOCK => Format::BC2_SRGB_BLOCK,
+        Format::BC3_UNORM_BLOCK => Format::BC3_SRGB_BLOCK,
+        Format::BC7_UNORM_BLOCK => Format::BC7_SRGB_BLOCK,
+        n => n
+    }
+}
+
+/// Get the format to use for block-compressed bitmaps that were decoded on the CPU.
+fn decoded_format(format: BitmapFormat) -> Format {
+    if format.is_srgb() {
+        Format::R8G8B8A8_SRGB
+    }
+    else {
+        Format::R8G8B8A8_UNORM
     }
 }
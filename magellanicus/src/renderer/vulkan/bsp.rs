@@ -5,7 +5,7 @@ use crate::renderer::vulkan::vertex::{VulkanModelVertex, VulkanModelVertexTextur
 use crate::renderer::vulkan::{default_allocation_create_info, VulkanPipelineType};
 use crate::vertex::ModelTriangle;
 use std::collections::BTreeMap;
-use std::string::String;
+use std::format;
 use std::sync::Arc;
 use std::vec::Vec;
 use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
@@ -21,13 +21,13 @@ pub struct VulkanBSPData {
 }
 
 impl VulkanBSPData {
-    pub fn new(renderer: &mut Renderer, param: &AddBSPParameter, surfaces_ranges: &Vec<Vec<Vec<Vec<ModelTriangle>>>>) -> MResult<Self> {
+    pub fn new(renderer: &mut Renderer, param: &AddBSPParameter, surfaces_ranges: &[Vec<Vec<Vec<ModelTriangle>>>]) -> MResult<Self> {
         let shader_environment_pipeline = renderer.renderer.pipelines[&VulkanPipelineType::ShaderEnvironment].get_pipeline();
         let mut images = BTreeMap::new();
         if let Some(n) = &param.lightmap_bitmap {
             let image = renderer
                 .bitmaps
-                .get(n)
+                .get(n.as_str())
                 .unwrap();
 
             for i in param.lightmap_sets.iter().filter_map(|b| b.lightmap_index) {
@@ -35,7 +35,7 @@ impl VulkanBSPData {
                     continue;
                 }
 
-                let image = image.bitmaps[i].vulkan.image.clone();
+                let image = image.bitmaps[i].backend.image.clone();
 
                 let lightmap = ImageView::new(
                     image.clone(),
@@ -105,7 +105,7 @@ impl VulkanBSPData {
             shader_environment_pipeline.layout().set_layouts()[1].clone(),
             [
                 WriteDescriptorSet::sampler(0, renderer.renderer.default_2d_sampler.clone()),
-                WriteDescriptorSet::image_view(1, ImageView::new_default(renderer.get_default_2d(DefaultType::White).vulkan.image.clone())?),
+                WriteDescriptorSet::image_view(1, ImageView::new_default(renderer.get_default_2d(DefaultType::White).backend.image.clone())?),
             ],
             []
         ).unwrap();
@@ -118,12 +118,11 @@ pub struct VulkanBSPGeometryData {
     pub vertex_buffer: Subbuffer<[VulkanModelVertex]>,
     pub texture_coords_buffer: Subbuffer<[VulkanModelVertexTextureCoords]>,
     pub lightmap_texture_coords_buffer: Option<Subbuffer<[VulkanModelVertexTextureCoords]>>,
-    pub index_buffer: Subbuffer<[u16]>,
-    pub shader: Arc<String>
+    pub index_buffer: Subbuffer<[u16]>
 }
 
 impl VulkanBSPGeometryData {
-    pub fn new(renderer: &mut Renderer, _param: &AddBSPParameter, material: &AddBSPParameterLightmapMaterial, lightmap_index: Option<usize>) -> MResult<Self> {
+    pub fn new(renderer: &mut Renderer, _param: &AddBSPParameter, material: &AddBSPParameterLightmapMaterial, lightmap_index: Option<usize>, name: &str) -> MResult<Self> {
         let vertex_buffer = Buffer::from_iter(
             renderer.renderer.memory_allocator.clone(),
             BufferCreateInfo { usage: BufferUsage::VERTEX_BUFFER, ..Default::default() },
@@ -138,8 +137,6 @@ impl VulkanBSPGeometryData {
             })
         )?;
 
-        let (shader, ..) = renderer.shaders.get_key_value(&material.shader).expect("shader?????");
-
         let texture_coords_buffer = Buffer::from_iter(
             renderer.renderer.memory_allocator.clone(),
             BufferCreateInfo { usage: BufferUsage::VERTEX_BUFFER, ..Default::default() },
@@ -185,6 +182,13 @@ impl VulkanBSPGeometryData {
             index_iter
         )?;
 
-        Ok(VulkanBSPGeometryData { vertex_buffer, texture_coords_buffer, lightmap_texture_coords_buffer, shader: shader.clone(), index_buffer })
+        renderer.renderer.set_debug_name(vertex_buffer.buffer().as_ref(), &format!("{name} vertices"));
+        renderer.renderer.set_debug_name(texture_coords_buffer.buffer().as_ref(), &format!("{name} texture coordinates"));
+        if let Some(buffer) = lightmap_texture_coords_buffer.as_ref() {
+            renderer.renderer.set_debug_name(buffer.buffer().as_ref(), &format!("{name} lightmap texture coordinates"));
+        }
+        renderer.renderer.set_debug_name(index_buffer.buffer().as_ref(), &format!("{name} indices"));
+
+        Ok(VulkanBSPGeometryData { vertex_buffer, texture_coords_buffer, lightmap_texture_coords_buffer, index_buffer })
     }
 }
@@ -0,0 +1,100 @@
+use crate::error::MResult;
+use crate::renderer::vulkan::VulkanRenderer;
+use crate::renderer::{LogCallback, LogSeverity};
+use std::eprintln;
+use std::format;
+use std::string::ToString;
+use std::sync::Arc;
+use vulkano::device::DeviceOwned;
+use vulkano::instance::debug::{DebugUtilsMessageSeverity, DebugUtilsMessageType, DebugUtilsMessenger, DebugUtilsMessengerCallback, DebugUtilsMessengerCreateInfo};
+use vulkano::instance::Instance;
+use vulkano::VulkanObject;
+
+pub(crate) const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+/// Sends diagnostics to the user's log callback, or to stderr if there is none.
+#[derive(Clone, Default)]
+pub struct VulkanLogger {
+    callback: Option<LogCallback>
+}
+
+impl VulkanLogger {
+    pub fn new(callback: Option<LogCallback>) -> Self {
+        Self { callback }
+    }
+
+    pub fn log(&self, severity: LogSeverity, message: &str) {
+        match self.callback.as_ref() {
+            Some(callback) => callback(severity, message),
+            None if severity >= LogSeverity::Warning => eprintln!("{severity:?}: {message}"),
+            None => ()
+        }
+    }
+
+    /// Create a messenger that forwards messages from the validation layer (and the driver) to the logger.
+    ///
+    /// The instance must have been created with `ext_debug_utils`.
+    pub fn create_messenger(&self, instance: Arc<Instance>) -> MResult<DebugUtilsMessenger> {
+        let logger = self.clone();
+
+        // SAFETY: The callback does not make any Vulkan calls.
+        let callback = unsafe {
+            DebugUtilsMessengerCallback::new(move |message_severity, _message_type, callback_data| {
+                let severity = if message_severity.intersects(DebugUtilsMessageSeverity::ERROR) {
+                    LogSeverity::Error
+                }
+                else if message_severity.intersects(DebugUtilsMessageSeverity::WARNING) {
+                    LogSeverity::Warning
+                }
+                else if message_severity.intersects(DebugUtilsMessageSeverity::INFO) {
+                    LogSeverity::Info
+                }
+                else {
+                    LogSeverity::Verbose
+                };
+
+                let message = match callback_data.message_id_name {
+                    Some(id) => format!("[{id}] {}", callback_data.message),
+                    None => callback_data.message.to_string()
+                };
+                logger.log(severity, &message);
+            })
+        };
+
+        let messenger = DebugUtilsMessenger::new(
+            instance,
+            DebugUtilsMessengerCreateInfo {
+                message_severity: DebugUtilsMessageSeverity::ERROR
+                    | DebugUtilsMessageSeverity::WARNING
+                    | DebugUtilsMessageSeverity::INFO
+                    | DebugUtilsMessageSeverity::VERBOSE,
+                message_type: DebugUtilsMessageType::GENERAL
+                    | DebugUtilsMessageType::VALIDATION
+                    | DebugUtilsMessageType::PERFORMANCE,
+                ..DebugUtilsMessengerCreateInfo::user_callback(callback)
+            }
+        )?;
+
+        Ok(messenger)
+    }
+}
+
+impl VulkanRenderer {
+    /// Log a message.
+    pub fn log(&self, severity: LogSeverity, message: &str) {
+        self.logger.log(severity, message);
+    }
+
+    /// Name a Vulkan object so it can be identified in validation messages and graphics debuggers.
+    ///
+    /// This does nothing unless validation is enabled.
+    pub(crate) fn set_debug_name<T: VulkanObject + DeviceOwned>(&self, object: &T, name: &str) {
+        if !self.instance.enabled_extensions().ext_debug_utils {
+            return
+        }
+
+        if let Err(e) = self.device.set_debug_utils_object_name(object, Some(name)) {
+            self.log(LogSeverity::Warning, &format!("Can't set the debug name of {name}: {e:?}"));
+        }
+    }
+}
@@ -22,6 +22,7 @@ impl VulkanMaterialVertexBuffers {
     /// - `lightmap_vertices` is not empty but does not have the same number of vertices as `vertices`
     /// - `indices` contains vertex indices that are out-of-bounds (i.e. `index >= vertices.collect().len()`)
     /// - `vertices` contains more than 65535 vertices
+    /// - `vertices` contains non-finite values
     pub fn new(
         vertices: impl IntoIterator<Item = ModelVertex>,
         lightmap_vertices: impl IntoIterator<Item = LightmapVertex>,
@@ -53,6 +54,9 @@ impl VulkanMaterialVertexBuffers {
 
         // Don't take more than MAX_VERTEX_ALLOC_LIMIT in case size_hint vastly underestimated the actual vertex count
         for ModelVertex { position, normal, binormal, tangent, texture_coords } in vertices.take(MAX_VERTEX_ALLOC_LIMIT) {
+            if !position.iter().chain(&normal).chain(&binormal).chain(&tangent).chain(&texture_coords).all(|f| f.is_finite()) {
+                return Err(Error::DataError { error: std::format!("Vertex #{} has a non-finite position, normal, binormal, tangent, or texture coordinate", vertices_buf.len()) })
+            }
             vertices_buf.push(VulkanModelVertex {
                 position,
                 normal,
@@ -1,86 +1,136 @@
 use crate::error::{Error, MResult};
-use crate::renderer::RendererParameters;
+use crate::renderer::vulkan::debug::{VulkanLogger, VALIDATION_LAYER};
+use crate::renderer::{DeviceInfo, DeviceSelection, DeviceType, LogSeverity, RendererParameters, MSAA};
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+use std::format;
 use std::string::ToString;
 use std::sync::Arc;
 use std::vec::Vec;
 use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
 use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Features, Queue, QueueCreateInfo, QueueFlags};
 use vulkano::format::Format;
-use vulkano::image::{Image, ImageUsage};
+use vulkano::image::{Image, ImageUsage, SampleCount};
+use vulkano::instance::debug::DebugUtilsMessenger;
 use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::MemoryHeapFlags;
 use vulkano::swapchain::{PresentMode, Surface, Swapchain, SwapchainCreateInfo};
 use vulkano::{Validated, Version, VulkanError, VulkanLibrary};
 
 pub struct LoadedVulkan {
     pub instance: Arc<Instance>,
+    pub surface: Arc<Surface>,
+    pub debug_messenger: Option<DebugUtilsMessenger>,
+}
+
+pub struct LoadedDevice {
     pub device: Arc<Device>,
     pub queue: Arc<Queue>,
-    pub surface: Arc<Surface>,
+    pub transfer_queue: Option<Arc<Queue>>,
 }
 
-pub unsafe fn load_vulkan_and_get_queue(
+pub unsafe fn load_vulkan(
     surface: &(impl HasRawWindowHandle + HasRawDisplayHandle),
-    anisotropic_filtering: Option<f32>
+    renderer_parameters: &RendererParameters,
+    logger: &VulkanLogger
 ) -> MResult<LoadedVulkan> {
     let library = VulkanLibrary::new()?;
 
-    let enabled_extensions = Surface::required_extensions(surface);
-    let device_extensions_13 = DeviceExtensions {
-        khr_swapchain: true,
-        ..DeviceExtensions::empty()
-    };
+    let mut enabled_extensions = Surface::required_extensions(surface);
+    let mut enabled_layers = Vec::new();
 
-    let device_extensions_12 = DeviceExtensions {
-        khr_dynamic_rendering: true,
-        ext_4444_formats: true,
-        ext_extended_dynamic_state: true,
-        ..device_extensions_13
-    }.clone();
-
-    let required_device_features = Features {
-        sampler_anisotropy: anisotropic_filtering.is_some(),
-        ..Features::empty()
-    };
+    if renderer_parameters.validation {
+        let has_validation_layer = library.layer_properties()?.any(|l| l.name() == VALIDATION_LAYER);
+        if has_validation_layer && library.supported_extensions().ext_debug_utils {
+            enabled_layers.push(VALIDATION_LAYER.to_string());
+            enabled_extensions.ext_debug_utils = true;
+        }
+        else {
+            logger.log(LogSeverity::Warning, &format!("Validation was requested, but {VALIDATION_LAYER} or debug utils is unavailable; continuing without validation"));
+        }
+    }
 
     let instance = Instance::new(library.clone(), InstanceCreateInfo {
         enabled_extensions,
+        enabled_layers,
         ..Default::default()
     })?;
 
+    let debug_messenger = match instance.enabled_extensions().ext_debug_utils {
+        true => Some(logger.create_messenger(instance.clone())?),
+        false => None
+    };
+
     let surface = Surface::from_window_ref(instance.clone(), surface)?;
 
+    Ok(LoadedVulkan { instance, surface, debug_messenger })
+}
+
+/// Pick a device that can present to `surface` and create it.
+///
+/// This is also used to replace a device that was lost.
+pub fn create_device(instance: Arc<Instance>, surface: Arc<Surface>, renderer_parameters: &RendererParameters) -> MResult<LoadedDevice> {
+    let required_device_features = Features {
+        sampler_anisotropy: renderer_parameters.anisotropic_filtering.is_some(),
+        ..Features::empty()
+    };
+
     let (physical_device, queue_family_index, device_extensions) = find_best_gpu(
-        instance.clone(),
-        device_extensions_12,
-        device_extensions_13,
+        instance,
+        &renderer_parameters.device,
         required_device_features,
-        surface.clone()
-    ).ok_or_else(|| Error::from_vulkan_error("No suitable Vulkan-compatible GPUs found".to_string()))?;
+        surface
+    )?;
+
+    let transfer_queue_family_index = find_transfer_queue_family(physical_device.as_ref());
 
     let (device, mut queues) = create_device_and_queues(
         physical_device,
         device_extensions,
-        queue_family_index
+        queue_family_index,
+        transfer_queue_family_index
     )?;
     let queue = queues.next().ok_or_else(|| Error::from_vulkan_error("Unable to make a device queue".to_string()))?;
+    let transfer_queue = queues.next();
+
+    Ok(LoadedDevice { device, queue, transfer_queue })
+}
 
-    Ok(LoadedVulkan { instance, device, queue, surface })
+/// Find a queue family that can only do transfers, if there is one.
+///
+/// Uploads can then run alongside rendering.
+fn find_transfer_queue_family(physical_device: &PhysicalDevice) -> Option<u32> {
+    physical_device
+        .queue_family_properties()
+        .iter()
+        .position(|q| {
+            q.queue_flags.intersects(QueueFlags::TRANSFER)
+                && !q.queue_flags.intersects(QueueFlags::GRAPHICS | QueueFlags::COMPUTE)
+                && q.min_image_transfer_granularity == [1, 1, 1]
+        })
+        .map(|i| i as u32)
 }
 
-fn create_device_and_queues(physical_device: Arc<PhysicalDevice>, device_extensions: DeviceExtensions, queue_family_index: u32) -> Result<(Arc<Device>, impl ExactSizeIterator<Item=Arc<Queue>> + Sized), Validated<VulkanError>> {
+fn create_device_and_queues(physical_device: Arc<PhysicalDevice>, device_extensions: DeviceExtensions, queue_family_index: u32, transfer_queue_family_index: Option<u32>) -> Result<(Arc<Device>, impl ExactSizeIterator<Item=Arc<Queue>> + Sized), Validated<VulkanError>> {
+    // Block-compressed textures are decoded on the CPU if this isn't supported.
+    let texture_compression_bc = physical_device.supported_features().texture_compression_bc;
+
     Device::new(
         physical_device,
         DeviceCreateInfo {
             enabled_extensions: device_extensions,
-            queue_create_infos: alloc::vec![QueueCreateInfo {
-                queue_family_index,
-                ..Default::default()
-            }],
+            queue_create_infos: [Some(queue_family_index), transfer_queue_family_index]
+                .into_iter()
+                .flatten()
+                .map(|queue_family_index| QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                })
+                .collect(),
             enabled_features: Features {
                 dynamic_rendering: true,
                 extended_dynamic_state: true,
                 sampler_anisotropy: true,
+                texture_compression_bc,
                 ..Features::default()
             },
             ..Default::default()
@@ -125,38 +175,114 @@ pub fn build_swapchain(device: Arc<Device>, surface: Arc<Surface>, image_format:
     Ok(result)
 }
 
+/// Get the device extensions to enable on the device, or `None` if the device doesn't meet the minimum requirements.
+fn get_device_extensions(device: &PhysicalDevice) -> Option<DeviceExtensions> {
+    let device_extensions_13 = DeviceExtensions {
+        khr_swapchain: true,
+        ..DeviceExtensions::empty()
+    };
+
+    let device_extensions_12 = DeviceExtensions {
+        khr_dynamic_rendering: true,
+        ext_4444_formats: true,
+        ext_extended_dynamic_state: true,
+        ..device_extensions_13
+    };
+
+    let extensions = if device.api_version() >= Version::V1_3 {
+        device_extensions_13
+    }
+    else if device.api_version() >= Version::V1_2 {
+        device_extensions_12
+    }
+    else {
+        return None
+    };
+
+    device.supported_extensions().contains(&extensions).then_some(extensions)
+}
+
+/// Describe all physical devices, including unsuitable ones.
+pub fn enumerate_devices() -> MResult<Vec<DeviceInfo>> {
+    let library = VulkanLibrary::new()?;
+    let instance = Instance::new(library, InstanceCreateInfo::default())?;
+
+    let devices = instance
+        .enumerate_physical_devices()?
+        .enumerate()
+        .map(|(index, device)| {
+            let properties = device.properties();
+            let api_version = device.api_version();
+            let device_local_memory = device
+                .memory_properties()
+                .memory_heaps
+                .iter()
+                .filter(|h| h.flags.intersects(MemoryHeapFlags::DEVICE_LOCAL))
+                .map(|h| h.size)
+                .sum();
+
+            let sample_counts = properties.sampled_image_color_sample_counts & properties.sampled_image_depth_sample_counts;
+            let supported_msaa = [
+                (SampleCount::Sample1, MSAA::NoMSAA),
+                (SampleCount::Sample2, MSAA::MSAA2x),
+                (SampleCount::Sample4, MSAA::MSAA4x),
+                (SampleCount::Sample8, MSAA::MSAA8x),
+                (SampleCount::Sample16, MSAA::MSAA16x),
+                (SampleCount::Sample32, MSAA::MSAA32x),
+                (SampleCount::Sample64, MSAA::MSAA64x)
+            ]
+                .into_iter()
+                .filter(|(samples, _)| sample_counts.contains_enum(*samples))
+                .map(|(_, msaa)| msaa)
+                .collect();
+
+            DeviceInfo {
+                index,
+                name: properties.device_name.clone(),
+                device_type: match properties.device_type {
+                    PhysicalDeviceType::DiscreteGpu => DeviceType::DiscreteGpu,
+                    PhysicalDeviceType::IntegratedGpu => DeviceType::IntegratedGpu,
+                    PhysicalDeviceType::VirtualGpu => DeviceType::VirtualGpu,
+                    PhysicalDeviceType::Cpu => DeviceType::Cpu,
+                    _ => DeviceType::Other
+                },
+                driver_name: properties.driver_name.clone(),
+                driver_info: properties.driver_info.clone(),
+                api_version: Some((api_version.major, api_version.minor, api_version.patch)),
+                device_local_memory: Some(device_local_memory),
+                supports_block_compression: device.supported_features().texture_compression_bc,
+                max_anisotropic_filtering: device
+                    .supported_features()
+                    .sampler_anisotropy
+                    .then_some(properties.max_sampler_anisotropy),
+                supported_msaa,
+                suitable: device.supported_features().sampler_anisotropy && get_device_extensions(&device).is_some()
+            }
+        })
+        .collect();
+
+    Ok(devices)
+}
+
 fn find_best_gpu(
     instance: Arc<Instance>,
-    device_extensions_12: DeviceExtensions,
-    device_extensions_13: DeviceExtensions,
+    device_selection: &DeviceSelection,
     required_device_features: Features,
     surface: Arc<Surface>
-) -> Option<(Arc<PhysicalDevice>, u32, DeviceExtensions)> {
+) -> MResult<(Arc<PhysicalDevice>, u32, DeviceExtensions)> {
+    let selected = |index: usize, device: &PhysicalDevice| match device_selection {
+        DeviceSelection::Automatic => true,
+        DeviceSelection::Index(i) => *i == index,
+        DeviceSelection::Name(name) => device.properties().device_name.to_lowercase().contains(&name.to_lowercase())
+    };
+
     instance
-        .enumerate_physical_devices()
-        .unwrap()
+        .enumerate_physical_devices()?
+        .enumerate()
+        .filter(|(index, device)| selected(*index, device))
+        .map(|(_, device)| device)
         .filter(|device| device.supported_features().contains(&required_device_features))
-        .filter_map(|device| {
-            if device.api_version() >= Version::V1_3 {
-                if device.supported_extensions().contains(&device_extensions_13) {
-                    Some((device, device_extensions_13))
-                }
-                else {
-                    None
-                }
-            }
-            else if device.api_version() >= Version::V1_2 {
-                if device.supported_extensions().contains(&device_extensions_12) {
-                    Some((device, device_extensions_12))
-                }
-                else {
-                    None
-                }
-            }
-            else {
-                None
-            }
-        })
+        .filter_map(|device| get_device_extensions(&device).map(|extensions| (device, extensions)))
         .filter_map(|(device, extensions)| {
             device.queue_family_properties()
                 .iter()
@@ -173,4 +299,8 @@ fn find_best_gpu(
             PhysicalDeviceType::Cpu => 3,
             _ => u32::MAX,
         })
+        .ok_or_else(|| match device_selection {
+            DeviceSelection::Automatic => Error::from_vulkan_error("No suitable Vulkan-compatible GPUs found".to_string()),
+            n => Error::from_vulkan_error(format!("The selected device ({n:?}) was not found or is not suitable"))
+        })
 }
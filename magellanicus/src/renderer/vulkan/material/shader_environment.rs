@@ -1,17 +1,24 @@
 use crate::error::MResult;
 use crate::renderer::vulkan::{default_allocation_create_info, VulkanMaterial, VulkanPipelineType};
-use crate::renderer::{AddShaderEnvironmentShaderData, DefaultType, Renderer, ShaderEnvironmentMapFunction, ShaderEnvironmentType};
+use crate::renderer::{AddShaderEnvironmentShaderData, DefaultType, Renderer, SequenceFrame, ShaderEnvironmentMapFunction, ShaderEnvironmentType};
 use std::sync::Arc;
+use std::vec;
+use std::vec::Vec;
 use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
 use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
 use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::format::Format;
 use vulkano::image::sampler::Sampler;
 use vulkano::image::view::{ImageView, ImageViewCreateInfo, ImageViewType};
 use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
 
 pub struct VulkanShaderEnvironmentMaterial {
-    map_sampler: Arc<Sampler>,
-    base_map: Arc<ImageView>,
+    base_map_sampler: Arc<Sampler>,
+
+    /// One view for each frame of the base map's sequence
+    base_maps: Vec<Arc<ImageView>>,
+    base_map_frame: SequenceFrame,
+
     primary_detail_map: Arc<ImageView>,
     secondary_detail_map: Arc<ImageView>,
     micro_detail_map: Arc<ImageView>,
@@ -25,46 +32,45 @@ pub struct VulkanShaderEnvironmentMaterial {
     bump_map_scale: f32,
     micro_detail_map_scale: f32,
     pipeline: Arc<GraphicsPipeline>,
-    descriptor_set: Arc<PersistentDescriptorSet>
+
+    /// One descriptor set for each frame of the base map's sequence
+    descriptor_sets: Vec<Arc<PersistentDescriptorSet>>
 }
 
 impl VulkanShaderEnvironmentMaterial {
     pub fn new(renderer: &mut Renderer, add_shader_parameter: AddShaderEnvironmentShaderData) -> MResult<Self> {
-        let base_map = renderer
-            .get_or_default_2d(&add_shader_parameter.base_map, 0, DefaultType::White)
-            .vulkan
-            .image
-            .clone();
-
-        let primary_detail_map = renderer
-            .get_or_default_2d(&add_shader_parameter.primary_detail_map, 0, DefaultType::Gray)
-            .vulkan
-            .image
-            .clone();
-
-        let secondary_detail_map = renderer
-            .get_or_default_2d(&add_shader_parameter.secondary_detail_map, 0, DefaultType::Gray)
-            .vulkan
-            .image
-            .clone();
-
-        let micro_detail_map = renderer
-            .get_or_default_2d(&add_shader_parameter.micro_detail_map, 0, DefaultType::Gray)
-            .vulkan
-            .image
-            .clone();
-
-        let bump_map = renderer
-            .get_or_default_2d(&add_shader_parameter.bump_map, 0, DefaultType::Vector)
-            .vulkan
-            .image
-            .clone();
-
-        let cubemap = renderer
-            .get_or_default_cubemap(&add_shader_parameter.reflection_cube_map, 0, DefaultType::Null)
-            .vulkan
-            .image
-            .clone();
+        let base_map_indices: Vec<usize> = match add_shader_parameter.base_map.as_ref() {
+            Some(b) => {
+                let bitmap = &renderer.bitmaps[b.as_str()];
+                let frame_count = bitmap.sequences[add_shader_parameter.base_map_sequence].frame_count();
+                (0..frame_count)
+                    .map(|f| bitmap.get_frame(add_shader_parameter.base_map_sequence, f).unwrap().0)
+                    .collect()
+            },
+            None => vec![0]
+        };
+
+        // Every frame of the sequence shares the sampler of the first frame
+        let base_map_sampler = renderer.get_or_default_2d(&add_shader_parameter.base_map, base_map_indices[0], DefaultType::White).backend.sampler.clone();
+        let base_maps = base_map_indices
+            .iter()
+            .map(|i| ImageView::new_default(renderer.get_or_default_2d(&add_shader_parameter.base_map, *i, DefaultType::White).backend.image.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let primary_detail_map_data = &renderer.get_or_default_2d(&add_shader_parameter.primary_detail_map, 0, DefaultType::Gray).backend;
+        let (primary_detail_map, primary_detail_map_sampler) = (primary_detail_map_data.image.clone(), primary_detail_map_data.sampler.clone());
+
+        let secondary_detail_map_data = &renderer.get_or_default_2d(&add_shader_parameter.secondary_detail_map, 0, DefaultType::Gray).backend;
+        let (secondary_detail_map, secondary_detail_map_sampler) = (secondary_detail_map_data.image.clone(), secondary_detail_map_data.sampler.clone());
+
+        let micro_detail_map_data = &renderer.get_or_default_2d(&add_shader_parameter.micro_detail_map, 0, DefaultType::Gray).backend;
+        let (micro_detail_map, micro_detail_map_sampler) = (micro_detail_map_data.image.clone(), micro_detail_map_data.sampler.clone());
+
+        let bump_map_data = &renderer.get_or_default_2d(&add_shader_parameter.bump_map, 0, DefaultType::Vector).backend;
+        let (bump_map, bump_map_sampler) = (bump_map_data.image.clone(), bump_map_data.sampler.clone());
+
+        let cubemap_data = &renderer.get_or_default_cubemap(&add_shader_parameter.reflection_cube_map, 0, DefaultType::Null).backend;
+        let (cubemap, cubemap_sampler) = (cubemap_data.image.clone(), cubemap_data.sampler.clone());
 
         let pipeline = renderer
             .renderer
@@ -78,19 +84,18 @@ impl VulkanShaderEnvironmentMaterial {
             micro_detail_map_scale: add_shader_parameter.micro_detail_map_scale,
             flags: {
                 let mut flags = 0;
-                flags |= (add_shader_parameter.alpha_tested as u32) << 0;
+                flags |= add_shader_parameter.alpha_tested as u32;
                 flags |= (add_shader_parameter.bump_map_is_specular_mask as u32) << 1;
+                flags |= ((bump_map.format() == Format::BC5_UNORM_BLOCK) as u32) << 2;
                 flags
             },
             shader_environment_type: add_shader_parameter.shader_environment_type as u32,
             detail_map_function: add_shader_parameter.detail_map_function as u32,
             micro_detail_map_function: add_shader_parameter.micro_detail_map_function as u32,
-            parallel_color: [add_shader_parameter.parallel_color[0], add_shader_parameter.parallel_color[1], add_shader_parameter.parallel_color[2], add_shader_parameter.parallel_brightness],
-            perpendicular_color: [add_shader_parameter.perpendicular_color[0], add_shader_parameter.perpendicular_color[1], add_shader_parameter.perpendicular_color[2], add_shader_parameter.perpendicular_brightness],
+            parallel_color: renderer.renderer.to_output_color([add_shader_parameter.parallel_color[0], add_shader_parameter.parallel_color[1], add_shader_parameter.parallel_color[2], add_shader_parameter.parallel_brightness]),
+            perpendicular_color: renderer.renderer.to_output_color([add_shader_parameter.perpendicular_color[0], add_shader_parameter.perpendicular_color[1], add_shader_parameter.perpendicular_color[2], add_shader_parameter.perpendicular_brightness]),
         };
 
-        let map_sampler = renderer.renderer.default_2d_sampler.clone();
-        let base_map = ImageView::new_default(base_map)?;
         let primary_detail_map = ImageView::new_default(primary_detail_map)?;
         let secondary_detail_map = ImageView::new_default(secondary_detail_map)?;
         let micro_detail_map = ImageView::new_default(micro_detail_map)?;
@@ -110,24 +115,32 @@ impl VulkanShaderEnvironmentMaterial {
             uniform
         )?;
 
-        let descriptor_set = PersistentDescriptorSet::new(
-            renderer.renderer.descriptor_set_allocator.as_ref(),
-            pipeline.layout().set_layouts()[3].clone(),
-            [
-                WriteDescriptorSet::buffer(0, uniform_buffer),
-                WriteDescriptorSet::sampler(1, map_sampler.clone()),
-                WriteDescriptorSet::image_view(2, base_map.clone()),
-                WriteDescriptorSet::image_view(3, primary_detail_map.clone()),
-                WriteDescriptorSet::image_view(4, secondary_detail_map.clone()),
-                WriteDescriptorSet::image_view(5, micro_detail_map.clone()),
-                WriteDescriptorSet::image_view(6, bump_map.clone()),
-                WriteDescriptorSet::image_view(7, cubemap.clone()),
-            ],
-            []
-        )?;
+        let mut descriptor_sets = Vec::with_capacity(base_maps.len());
+        for base_map in &base_maps {
+            descriptor_sets.push(PersistentDescriptorSet::new(
+                renderer.renderer.descriptor_set_allocator.as_ref(),
+                pipeline.layout().set_layouts()[3].clone(),
+                [
+                    WriteDescriptorSet::buffer(0, uniform_buffer.clone()),
+                    WriteDescriptorSet::sampler(1, base_map_sampler.clone()),
+                    WriteDescriptorSet::image_view(2, base_map.clone()),
+                    WriteDescriptorSet::image_view(3, primary_detail_map.clone()),
+                    WriteDescriptorSet::image_view(4, secondary_detail_map.clone()),
+                    WriteDescriptorSet::image_view(5, micro_detail_map.clone()),
+                    WriteDescriptorSet::image_view(6, bump_map.clone()),
+                    WriteDescriptorSet::image_view(7, cubemap.clone()),
+                    WriteDescriptorSet::sampler(8, primary_detail_map_sampler.clone()),
+                    WriteDescriptorSet::sampler(9, secondary_detail_map_sampler.clone()),
+                    WriteDescriptorSet::sampler(10, micro_detail_map_sampler.clone()),
+                    WriteDescriptorSet::sampler(11, bump_map_sampler.clone()),
+                    WriteDescriptorSet::sampler(12, cubemap_sampler.clone()),
+                ],
+                []
+            )?);
+        }
 
         let shader_data = Self {
-            map_sampler,
+            base_map_sampler,
             detail_map_function: add_shader_parameter.detail_map_function,
             micro_detail_map_function: add_shader_parameter.micro_detail_map_function,
             alpha_tested: add_shader_parameter.alpha_tested,
@@ -137,8 +150,9 @@ impl VulkanShaderEnvironmentMaterial {
             micro_detail_map_scale: add_shader_parameter.micro_detail_map_scale,
             shader_environment_type: add_shader_parameter.shader_environment_type,
             pipeline,
-            descriptor_set,
-            base_map,
+            descriptor_sets,
+            base_maps,
+            base_map_frame: add_shader_parameter.base_map_frame,
             primary_detail_map,
             secondary_detail_map,
             micro_detail_map,
@@ -152,19 +166,23 @@ impl VulkanShaderEnvironmentMaterial {
 impl VulkanMaterial for VulkanShaderEnvironmentMaterial {
     fn generate_commands(
         &self,
-        _renderer: &Renderer,
+        renderer: &Renderer,
         index_count: u32,
         repeat_shader: bool,
         to: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>
     ) -> MResult<()> {
         if !repeat_shader {
+            let frame = self.base_map_frame
+                .resolve(self.descriptor_sets.len(), renderer.get_time())
+                .unwrap_or(0);
+
             let pipeline = self.pipeline.clone();
             to.bind_pipeline_graphics(pipeline.clone())?;
             to.bind_descriptor_sets(
                 PipelineBindPoint::Graphics,
                 pipeline.layout().clone(),
                 3,
-                self.descriptor_set.clone()
+                self.descriptor_sets[frame].clone()
             )?;
         }
         to.draw_indexed(index_count, 1, 0, 0, 0)?;
@@ -1,81 +1,99 @@
 use crate::error::MResult;
 use crate::renderer::vulkan::{VulkanMaterial, VulkanPipelineType};
-use crate::renderer::{AddShaderBasicShaderData, DefaultType, Renderer};
-use std::eprintln;
+use crate::renderer::{AddShaderBasicShaderData, DefaultType, LogSeverity, Renderer, SequenceFrame};
+use std::format;
 use std::sync::Arc;
+use std::vec;
+use std::vec::Vec;
 use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
 use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
-use vulkano::image::sampler::Sampler;
 use vulkano::image::view::{ImageView, ImageViewCreateInfo};
 use vulkano::image::{ImageAspects, ImageSubresourceRange, ImageType};
 use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
 
 pub struct VulkanSimpleShaderMaterial {
-    diffuse: Arc<ImageView>,
-    diffuse_sampler: Arc<Sampler>,
     pipeline: Arc<GraphicsPipeline>,
-    descriptor_set: Arc<PersistentDescriptorSet>
+
+    /// One descriptor set for each frame of the sequence
+    descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+    frame: SequenceFrame
 }
 
 impl VulkanSimpleShaderMaterial {
     pub fn new(renderer: &mut Renderer, add_shader_parameter: AddShaderBasicShaderData) -> MResult<Self> {
-        let diffuse = renderer
-            .get_or_default_2d(&add_shader_parameter.bitmap, 0, DefaultType::White)
-            .vulkan
-            .image
-            .clone();
-
-        if diffuse.array_layers() != 1 || diffuse.image_type() != ImageType::Dim2d {
-            eprintln!("Warning: Can't display {} in a simple shader material. Using fallback...", add_shader_parameter.bitmap.as_ref().unwrap());
-            return VulkanSimpleShaderMaterial::new(renderer, AddShaderBasicShaderData {
-                bitmap: None,
-                ..add_shader_parameter
-            })
-        }
-
-        let diffuse = ImageView::new(diffuse.clone(), ImageViewCreateInfo {
-            subresource_range: ImageSubresourceRange {
-                aspects: ImageAspects::COLOR,
-                mip_levels: 0..diffuse.mip_levels(),
-                array_layers: 0..diffuse.array_layers()
+        let bitmap_indices: Vec<usize> = match add_shader_parameter.bitmap.as_ref() {
+            Some(b) => {
+                let bitmap = &renderer.bitmaps[b.as_str()];
+                let frame_count = bitmap.sequences[add_shader_parameter.sequence].frame_count();
+                (0..frame_count)
+                    .map(|f| bitmap.get_frame(add_shader_parameter.sequence, f).unwrap().0)
+                    .collect()
             },
-            format: diffuse.format(),
-            ..Default::default()
-        })?;
+            None => vec![0]
+        };
 
-        let diffuse_sampler = renderer.renderer.default_2d_sampler.clone();
         let pipeline = renderer.renderer.pipelines[&VulkanPipelineType::SimpleTexture].get_pipeline();
 
-        let descriptor_set = PersistentDescriptorSet::new(
-            renderer.renderer.descriptor_set_allocator.as_ref(),
-            pipeline.layout().set_layouts()[3].clone(),
-            [
-                WriteDescriptorSet::sampler(0, diffuse_sampler.clone()),
-                WriteDescriptorSet::image_view(1, diffuse.clone()),
-            ],
-            []
-        )?;
+        let mut descriptor_sets = Vec::with_capacity(bitmap_indices.len());
+        for bitmap_index in bitmap_indices {
+            let diffuse_data = &renderer.get_or_default_2d(&add_shader_parameter.bitmap, bitmap_index, DefaultType::White).backend;
+            let (diffuse, diffuse_sampler) = (diffuse_data.image.clone(), diffuse_data.sampler.clone());
+
+            if diffuse.array_layers() != 1 || diffuse.image_type() != ImageType::Dim2d {
+                renderer.renderer.log(LogSeverity::Warning, &format!("Can't display {} in a simple shader material. Using fallback...", add_shader_parameter.bitmap.as_ref().unwrap()));
+                return VulkanSimpleShaderMaterial::new(renderer, AddShaderBasicShaderData {
+                    bitmap: None,
+                    sequence: 0,
+                    frame: SequenceFrame::default(),
+                    ..add_shader_parameter
+                })
+            }
 
-        Ok(Self { diffuse, diffuse_sampler, pipeline, descriptor_set })
+            let diffuse = ImageView::new(diffuse.clone(), ImageViewCreateInfo {
+                subresource_range: ImageSubresourceRange {
+                    aspects: ImageAspects::COLOR,
+                    mip_levels: 0..diffuse.mip_levels(),
+                    array_layers: 0..diffuse.array_layers()
+                },
+                format: diffuse.format(),
+                ..Default::default()
+            })?;
+
+            descriptor_sets.push(PersistentDescriptorSet::new(
+                renderer.renderer.descriptor_set_allocator.as_ref(),
+                pipeline.layout().set_layouts()[3].clone(),
+                [
+                    WriteDescriptorSet::sampler(0, diffuse_sampler),
+                    WriteDescriptorSet::image_view(1, diffuse),
+                ],
+                []
+            )?);
+        }
+
+        Ok(Self { pipeline, descriptor_sets, frame: add_shader_parameter.frame })
     }
 }
 
 impl VulkanMaterial for VulkanSimpleShaderMaterial {
     fn generate_commands(
         &self,
-        _renderer: &Renderer,
+        renderer: &Renderer,
         index_count: u32,
         repeat_shader: bool,
         to: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>
     ) -> MResult<()> {
         if !repeat_shader {
+            let frame = self.frame
+                .resolve(self.descriptor_sets.len(), renderer.get_time())
+                .unwrap_or(0);
+
             let pipeline = self.pipeline.clone();
             to.bind_pipeline_graphics(pipeline.clone())?;
             to.bind_descriptor_sets(
                 PipelineBindPoint::Graphics,
                 pipeline.layout().clone(),
                 3,
-                self.descriptor_set.clone()
+                self.descriptor_sets[frame].clone()
             )?;
         }
         to.draw_indexed(index_count, 1, 0, 0, 0)?;
@@ -0,0 +1,144 @@
+use crate::error::{Error, MResult, ResourceKind};
+use crate::renderer::data::{OverlayQuad, OverlayTexture};
+use crate::renderer::vulkan::vertex::{VulkanModelVertex, VulkanModelVertexTextureCoords, VulkanOverlayData};
+use crate::renderer::vulkan::{default_allocation_create_info, VulkanPipelineType};
+use crate::renderer::Renderer;
+use std::format;
+use std::vec::Vec;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::pipeline::graphics::rasterization::CullMode;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::{Pipeline, PipelineBindPoint};
+
+/// Draw all queued overlay quads for a viewport.
+///
+/// The HUD canvas is scaled uniformly to fit the viewport and centered inside of it. Consecutive quads that share a
+/// texture, blend mode, and tint are drawn together.
+pub fn draw_overlay(
+    renderer: &Renderer,
+    overlay: &[OverlayQuad],
+    viewport: &Viewport,
+    command_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>
+) -> MResult<()> {
+    if overlay.is_empty() {
+        return Ok(())
+    }
+
+    let [width, height] = viewport.extent;
+    let [canvas_width, canvas_height] = renderer.hud_canvas;
+    let scale = (width / canvas_width).min(height / canvas_height);
+    let offset_x = (width - canvas_width * scale) / 2.0;
+    let offset_y = (height - canvas_height * scale) / 2.0;
+    let canvas_to_viewport = |x: f32, y: f32| [(offset_x + x * scale) / width, (offset_y + y * scale) / height, 0.5];
+
+    let mut vertices = Vec::with_capacity(overlay.len() * 6);
+    let mut texture_coords = Vec::with_capacity(overlay.len() * 6);
+    for quad in overlay {
+        let [x, y] = quad.position;
+        let [w, h] = quad.size;
+        let [left, top, right, bottom] = quad.texture_coords;
+
+        let corners = [
+            (canvas_to_viewport(x, y), [left, top]),
+            (canvas_to_viewport(x, y + h), [left, bottom]),
+            (canvas_to_viewport(x + w, y + h), [right, bottom]),
+            (canvas_to_viewport(x + w, y), [right, top]),
+        ];
+
+        for i in [0, 1, 2, 0, 2, 3] {
+            let (position, uv) = corners[i];
+            vertices.push(VulkanModelVertex {
+                position,
+                normal: [1.0, 0.0, 0.0],
+                binormal: [1.0, 0.0, 0.0],
+                tangent: [1.0, 0.0, 0.0]
+            });
+            texture_coords.push(VulkanModelVertexTextureCoords { texture_coords: uv });
+        }
+    }
+
+    let vertices = Buffer::from_iter(
+        renderer.renderer.memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        default_allocation_create_info(),
+        vertices
+    )?;
+    let texture_coords = Buffer::from_iter(
+        renderer.renderer.memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        default_allocation_create_info(),
+        texture_coords
+    )?;
+
+    command_builder.set_cull_mode(CullMode::None)?;
+    command_builder.bind_vertex_buffers(0, (vertices, texture_coords))?;
+
+    let mut first_vertex = 0;
+    for batch in overlay.chunk_by(OverlayQuad::batches_with) {
+        let quad = &batch[0];
+        let view = match &quad.texture {
+            OverlayTexture::Bitmap { path, bitmap_index } => &renderer
+                .bitmaps
+                .get(path)
+                .and_then(|b| b.bitmaps.get(*bitmap_index))
+                .ok_or_else(|| Error::missing_dependency(ResourceKind::Bitmap, &**path, format!("Overlay refers to bitmap #{bitmap_index} of {path} which is no longer loaded")))?
+                .backend
+                .view,
+            OverlayTexture::Font { path } => &renderer
+                .fonts
+                .get(path)
+                .ok_or_else(|| Error::missing_dependency(ResourceKind::Font, &**path, format!("Overlay refers to font {path} which is no longer loaded")))?
+                .bitmap
+                .backend
+                .view
+        };
+
+        let pipeline = renderer
+            .renderer
+            .pipelines[&VulkanPipelineType::overlay(quad.blend_mode)]
+            .get_pipeline();
+
+        let uniform_buffer = Buffer::from_data(
+            renderer.renderer.memory_allocator.clone(),
+            BufferCreateInfo { usage: BufferUsage::UNIFORM_BUFFER, ..Default::default() },
+            default_allocation_create_info(),
+            VulkanOverlayData {
+                tint: renderer.renderer.to_output_color(quad.tint),
+                blend_mode: quad.blend_mode as u32
+            }
+        )?;
+
+        let set = PersistentDescriptorSet::new(
+            renderer.renderer.descriptor_set_allocator.as_ref(),
+            pipeline.layout().set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::buffer(0, uniform_buffer),
+                WriteDescriptorSet::sampler(1, renderer.renderer.overlay_sampler.clone()),
+                WriteDescriptorSet::image_view(2, view.clone()),
+            ],
+            []
+        )?;
+
+        command_builder.bind_pipeline_graphics(pipeline.clone())?;
+        command_builder.bind_descriptor_sets(
+            PipelineBindPoint::Graphics,
+            pipeline.layout().clone(),
+            0,
+            set
+        )?;
+
+        let vertex_count = (batch.len() * 6) as u32;
+        command_builder.draw(vertex_count, 1, first_vertex, 0)?;
+        first_vertex += vertex_count;
+    }
+
+    Ok(())
+}
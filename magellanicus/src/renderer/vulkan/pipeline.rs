@@ -1,27 +1,33 @@
 use std::collections::BTreeMap;
 use std::sync::Arc;
 use vulkano::device::Device;
+use vulkano::format::Format;
 use vulkano::image::SampleCount;
 use vulkano::pipeline::GraphicsPipeline;
 use crate::error::MResult;
+use crate::renderer::OverlayBlendMode;
 
 pub mod solid_color;
 pub mod simple_texture;
 mod pipeline_loader;
 mod color_box;
 pub mod shader_environment;
+mod overlay;
 
 pub trait VulkanPipelineData: Send + Sync + 'static {
     fn get_pipeline(&self) -> Arc<GraphicsPipeline>;
 }
 
-pub fn load_all_pipelines(device: Arc<Device>, samples: SampleCount) -> MResult<BTreeMap<VulkanPipelineType, Arc<dyn VulkanPipelineData>>> {
+pub fn load_all_pipelines(device: Arc<Device>, samples: SampleCount, color_format: Format) -> MResult<BTreeMap<VulkanPipelineType, Arc<dyn VulkanPipelineData>>> {
     let mut pipelines: BTreeMap<VulkanPipelineType, Arc<dyn VulkanPipelineData>> = BTreeMap::new();
 
-    pipelines.insert(VulkanPipelineType::SolidColor, Arc::new(solid_color::SolidColorShader::new(device.clone(), samples)?));
-    pipelines.insert(VulkanPipelineType::SimpleTexture, Arc::new(simple_texture::SimpleTextureShader::new(device.clone(), samples)?));
-    pipelines.insert(VulkanPipelineType::ColorBox, Arc::new(color_box::ColorBox::new(device.clone(), samples)?));
-    pipelines.insert(VulkanPipelineType::ShaderEnvironment, Arc::new(shader_environment::ShaderEnvironment::new(device.clone(), samples)?));
+    pipelines.insert(VulkanPipelineType::SolidColor, Arc::new(solid_color::SolidColorShader::new(device.clone(), samples, color_format)?));
+    pipelines.insert(VulkanPipelineType::SimpleTexture, Arc::new(simple_texture::SimpleTextureShader::new(device.clone(), samples, color_format)?));
+    pipelines.insert(VulkanPipelineType::ColorBox, Arc::new(color_box::ColorBox::new(device.clone(), samples, color_format)?));
+    pipelines.insert(VulkanPipelineType::ShaderEnvironment, Arc::new(shader_environment::ShaderEnvironment::new(device.clone(), samples, color_format)?));
+    pipelines.insert(VulkanPipelineType::OverlayAlphaBlend, Arc::new(overlay::Overlay::new(device.clone(), samples, color_format, OverlayBlendMode::AlphaBlend)?));
+    pipelines.insert(VulkanPipelineType::OverlayAdd, Arc::new(overlay::Overlay::new(device.clone(), samples, color_format, OverlayBlendMode::Add)?));
+    pipelines.insert(VulkanPipelineType::OverlayMultiply, Arc::new(overlay::Overlay::new(device.clone(), samples, color_format, OverlayBlendMode::Multiply)?));
 
     Ok(pipelines)
 }
@@ -42,4 +48,23 @@ pub enum VulkanPipelineType {
 
     /// shader_environment
     ShaderEnvironment,
+
+    /// Draws an alpha blended sprite over a viewport.
+    OverlayAlphaBlend,
+
+    /// Draws an additive sprite over a viewport.
+    OverlayAdd,
+
+    /// Draws a multiplied sprite over a viewport.
+    OverlayMultiply,
+}
+
+impl VulkanPipelineType {
+    pub fn overlay(blend_mode: OverlayBlendMode) -> Self {
+        match blend_mode {
+            OverlayBlendMode::AlphaBlend => Self::OverlayAlphaBlend,
+            OverlayBlendMode::Add => Self::OverlayAdd,
+            OverlayBlendMode::Multiply => Self::OverlayMultiply
+        }
+    }
 }
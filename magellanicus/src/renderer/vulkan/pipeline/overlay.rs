@@ -0,0 +1,77 @@
+use crate::error::MResult;
+use crate::renderer::vulkan::pipeline::pipeline_loader::{load_pipeline, DepthAccess, PipelineSettings};
+use crate::renderer::vulkan::vertex::{VulkanModelVertex, VulkanModelVertexTextureCoords};
+use crate::renderer::vulkan::VulkanPipelineData;
+use crate::renderer::OverlayBlendMode;
+use std::sync::Arc;
+use std::vec;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::image::SampleCount;
+use vulkano::pipeline::graphics::color_blend::{AttachmentBlend, BlendFactor, BlendOp, ColorBlendAttachmentState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::GraphicsPipeline;
+
+mod vertex {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/renderer/vulkan/pipeline/overlay/vertex.vert"
+    }
+}
+
+mod fragment {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/renderer/vulkan/pipeline/overlay/fragment.frag"
+    }
+}
+
+pub struct Overlay {
+    pub pipeline: Arc<GraphicsPipeline>
+}
+
+impl Overlay {
+    pub fn new(device: Arc<Device>, samples: SampleCount, color_format: Format, blend_mode: OverlayBlendMode) -> MResult<Self> {
+        let blend = match blend_mode {
+            OverlayBlendMode::AlphaBlend => AttachmentBlend::alpha(),
+            OverlayBlendMode::Add => AttachmentBlend {
+                src_color_blend_factor: BlendFactor::SrcAlpha,
+                dst_color_blend_factor: BlendFactor::One,
+                color_blend_op: BlendOp::Add,
+                src_alpha_blend_factor: BlendFactor::Zero,
+                dst_alpha_blend_factor: BlendFactor::One,
+                alpha_blend_op: BlendOp::Add
+            },
+            OverlayBlendMode::Multiply => AttachmentBlend {
+                src_color_blend_factor: BlendFactor::DstColor,
+                dst_color_blend_factor: BlendFactor::Zero,
+                color_blend_op: BlendOp::Add,
+                src_alpha_blend_factor: BlendFactor::Zero,
+                dst_alpha_blend_factor: BlendFactor::One,
+                alpha_blend_op: BlendOp::Add
+            }
+        };
+
+        let pipeline = load_pipeline(device, vertex::load, fragment::load, &PipelineSettings {
+            depth_access: DepthAccess::NoDepth,
+            vertex_buffer_descriptions: vec![
+                VulkanModelVertex::per_vertex(),
+                VulkanModelVertexTextureCoords::per_vertex()
+            ],
+            alpha_blending: false,
+            color_blend_attachment_state: ColorBlendAttachmentState {
+                blend: Some(blend),
+                ..ColorBlendAttachmentState::default()
+            },
+            samples
+        }, color_format)?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+impl VulkanPipelineData for Overlay {
+    fn get_pipeline(&self) -> Arc<GraphicsPipeline> {
+        self.pipeline.clone()
+    }
+}
@@ -0,0 +1,27 @@
+#version 450
+
+#define OVERLAY_BLEND_MODE_ALPHA_BLEND 0
+#define OVERLAY_BLEND_MODE_ADD 1
+#define OVERLAY_BLEND_MODE_MULTIPLY 2
+
+layout(location = 0) in vec2 tex_coords;
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform OverlayData {
+    vec4 tint;
+    uint blend_mode;
+} overlay_data;
+
+layout(set = 0, binding = 1) uniform sampler s;
+layout(set = 0, binding = 2) uniform texture2D tex;
+
+void main() {
+    vec4 color = texture(sampler2D(tex, s), tex_coords) * overlay_data.tint;
+
+    // Multiplying is done by the blender, so fade towards white instead of relying on alpha.
+    if(overlay_data.blend_mode == OVERLAY_BLEND_MODE_MULTIPLY) {
+        color.rgb = mix(vec3(1.0), color.rgb, color.a);
+    }
+
+    f_color = color;
+}
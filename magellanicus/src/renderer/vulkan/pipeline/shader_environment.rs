@@ -1,10 +1,11 @@
 use crate::error::MResult;
 use crate::renderer::vulkan::pipeline::pipeline_loader::{load_pipeline, DepthAccess, PipelineSettings};
 use crate::renderer::vulkan::vertex::{VulkanModelVertex, VulkanModelVertexLightmapTextureCoords, VulkanModelVertexTextureCoords};
-use crate::renderer::vulkan::{VulkanPipelineData, OFFLINE_PIPELINE_COLOR_FORMAT};
+use crate::renderer::vulkan::VulkanPipelineData;
 use std::sync::Arc;
 use std::vec;
 use vulkano::device::Device;
+use vulkano::format::Format;
 use vulkano::image::SampleCount;
 use vulkano::pipeline::graphics::color_blend::ColorBlendAttachmentState;
 use vulkano::pipeline::graphics::vertex_input::Vertex;
@@ -32,14 +33,14 @@ pub struct ShaderEnvironment {
 }
 
 impl ShaderEnvironment {
-    pub fn new(device: Arc<Device>, samples: SampleCount) -> MResult<Self> {
+    pub fn new(device: Arc<Device>, samples: SampleCount, color_format: Format) -> MResult<Self> {
         let pipeline = load_pipeline(device, vertex::load, fragment::load, &PipelineSettings {
             depth_access: DepthAccess::DepthWrite,
             vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex(), VulkanModelVertexTextureCoords::per_vertex(), VulkanModelVertexLightmapTextureCoords::per_vertex()],
             alpha_blending: false,
             samples,
             color_blend_attachment_state: ColorBlendAttachmentState::default()
-        }, OFFLINE_PIPELINE_COLOR_FORMAT)?;
+        }, color_format)?;
 
         Ok(Self { pipeline })
     }
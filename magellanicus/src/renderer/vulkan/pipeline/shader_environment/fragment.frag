@@ -18,13 +18,18 @@ layout(location = 4) in vec3 normal;
 layout(location = 5) in vec3 binormal;
 layout(location = 6) in vec3 tangent;
 
-layout(set = 3, binding = 1) uniform sampler map_sampler;
+layout(set = 3, binding = 1) uniform sampler base_map_sampler;
 layout(set = 3, binding = 2) uniform texture2D base_map;
 layout(set = 3, binding = 3) uniform texture2D primary_detail_map;
 layout(set = 3, binding = 4) uniform texture2D secondary_detail_map;
 layout(set = 3, binding = 5) uniform texture2D micro_detail_map;
 layout(set = 3, binding = 6) uniform texture2D bump_map;
 layout(set = 3, binding = 7) uniform textureCube cubemap;
+layout(set = 3, binding = 8) uniform sampler primary_detail_map_sampler;
+layout(set = 3, binding = 9) uniform sampler secondary_detail_map_sampler;
+layout(set = 3, binding = 10) uniform sampler micro_detail_map_sampler;
+layout(set = 3, binding = 11) uniform sampler bump_map_sampler;
+layout(set = 3, binding = 12) uniform sampler cubemap_sampler;
 
 vec3 calculate_world_tangent(vec3 base) {
     return base.xxx * tangent + base.yyy * binormal + base.zzz * normal;
@@ -47,10 +52,10 @@ void main() {
     vec3 camera_difference = camera_position - vertex_position;
     float distance_from_camera = distance(camera_position, vertex_position);
 
-    vec4 base_map_color = texture(sampler2D(base_map, map_sampler), base_map_texture_coordinates);
+    vec4 base_map_color = texture(sampler2D(base_map, base_map_sampler), base_map_texture_coordinates);
 
     vec4 bump_color = texture(
-        sampler2D(bump_map, map_sampler),
+        sampler2D(bump_map, bump_map_sampler),
         base_map_texture_coordinates * shader_environment_data.bump_map_scale
     );
 
@@ -70,18 +75,23 @@ void main() {
 
     vec3 bump_vector = bump_color.rgb * 2.0 - 1.0;
 
+    // Two-channel bump maps only store X and Y
+    if((shader_environment_data.flags & SHADER_ENVIRONMENT_FLAGS_TWO_CHANNEL_BUMP_MAP) != 0) {
+        bump_vector.z = sqrt(clamp(1.0 - dot(bump_vector.xy, bump_vector.xy), 0.0, 1.0));
+    }
+
     vec4 primary_detail_map_color = texture(
-        sampler2D(primary_detail_map, map_sampler),
+        sampler2D(primary_detail_map, primary_detail_map_sampler),
         base_map_texture_coordinates * shader_environment_data.primary_detail_map_scale
     );
 
     vec4 secondary_detail_map_color = texture(
-        sampler2D(secondary_detail_map, map_sampler),
+        sampler2D(secondary_detail_map, secondary_detail_map_sampler),
         base_map_texture_coordinates * shader_environment_data.secondary_detail_map_scale
     );
 
     vec4 micro_detail_map_color = texture(
-        sampler2D(micro_detail_map, map_sampler),
+        sampler2D(micro_detail_map, micro_detail_map_sampler),
         base_map_texture_coordinates * shader_environment_data.micro_detail_map_scale
     );
 
@@ -109,7 +119,7 @@ void main() {
     float normal_on_camera = dot(normal, camera_normal);
     vec3 reflection_tangent = calculate_world_tangent(vec3(0.0, 0.0, 1.0));
     vec3 reflection_normal = normalize(2.0 * normal_on_camera * normal - camera_normal);
-    vec3 reflection_color = texture(samplerCube(cubemap, map_sampler), reflection_normal + vec3(bump_vector.xy, 0.0)).xyz;
+    vec3 reflection_color = texture(samplerCube(cubemap, cubemap_sampler), reflection_normal + vec3(bump_vector.xy, 0.0)).xyz;
     vec3 specular_color = pow(reflection_color, vec3(8.0));
     float diffuse_reflection = normal_on_camera * normal_on_camera;
     float reflect_attenuation = mix(shader_environment_data.parallel_color.a, shader_environment_data.perpendicular_color.a, diffuse_reflection);
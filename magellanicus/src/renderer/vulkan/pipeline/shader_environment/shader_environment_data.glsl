@@ -19,3 +19,4 @@ layout(set = 3, binding = 0) uniform ShaderEnvironmentData {
 
 #define SHADER_ENVIRONMENT_FLAGS_ALPHA_TEST 1
 #define SHADER_ENVIRONMENT_FLAGS_BUMPMAP_ALPHA_SPECULAR_MASK 2
+#define SHADER_ENVIRONMENT_FLAGS_TWO_CHANNEL_BUMP_MAP 4
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 use vulkano::device::Device;
+use vulkano::format::Format;
 use std::vec;
 use vulkano::image::SampleCount;
 use vulkano::pipeline::graphics::color_blend::{AttachmentBlend, ColorBlendAttachmentState};
@@ -8,7 +9,7 @@ use vulkano::pipeline::graphics::vertex_input::Vertex;
 use crate::error::MResult;
 use crate::renderer::vulkan::pipeline::pipeline_loader::{load_pipeline, DepthAccess, PipelineSettings};
 use crate::renderer::vulkan::vertex::{VulkanModelVertex, VulkanModelVertexLightmapTextureCoords, VulkanModelVertexTextureCoords};
-use crate::renderer::vulkan::{VulkanPipelineData, OFFLINE_PIPELINE_COLOR_FORMAT};
+use crate::renderer::vulkan::VulkanPipelineData;
 
 mod vertex {
     vulkano_shaders::shader! {
@@ -29,7 +30,7 @@ pub struct SimpleTextureShader {
 }
 
 impl SimpleTextureShader {
-    pub fn new(device: Arc<Device>, samples: SampleCount) -> MResult<Self> {
+    pub fn new(device: Arc<Device>, samples: SampleCount, color_format: Format) -> MResult<Self> {
         let pipeline = load_pipeline(device, vertex::load, fragment::load, &PipelineSettings {
             depth_access: DepthAccess::DepthReadOnlyTransparent,
             vertex_buffer_descriptions: vec![
@@ -43,7 +44,7 @@ impl SimpleTextureShader {
                 ..ColorBlendAttachmentState::default()
             },
             samples
-        }, OFFLINE_PIPELINE_COLOR_FORMAT)?;
+        }, color_format)?;
 
         Ok(Self { pipeline })
     }
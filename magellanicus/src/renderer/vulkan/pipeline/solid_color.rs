@@ -1,10 +1,11 @@
 use crate::error::MResult;
 use crate::renderer::vulkan::pipeline::pipeline_loader::{load_pipeline, DepthAccess, PipelineSettings};
 use crate::renderer::vulkan::vertex::VulkanModelVertex;
-use crate::renderer::vulkan::{VulkanPipelineData, OFFLINE_PIPELINE_COLOR_FORMAT};
+use crate::renderer::vulkan::VulkanPipelineData;
 use alloc::sync::Arc;
 use std::vec;
 use vulkano::device::Device;
+use vulkano::format::Format;
 use vulkano::image::SampleCount;
 use vulkano::pipeline::graphics::color_blend::ColorBlendAttachmentState;
 use vulkano::pipeline::graphics::vertex_input::Vertex;
@@ -29,14 +30,14 @@ pub struct SolidColorShader {
 }
 
 impl SolidColorShader {
-    pub fn new(device: Arc<Device>, samples: SampleCount) -> MResult<Self> {
+    pub fn new(device: Arc<Device>, samples: SampleCount, color_format: Format) -> MResult<Self> {
         let pipeline = load_pipeline(device, vertex::load, fragment::load, &PipelineSettings {
             depth_access: DepthAccess::DepthWrite,
             vertex_buffer_descriptions: vec![VulkanModelVertex::per_vertex()],
             alpha_blending: false,
             color_blend_attachment_state: ColorBlendAttachmentState::default(),
             samples
-        }, OFFLINE_PIPELINE_COLOR_FORMAT)?;
+        }, color_format)?;
 
         Ok(Self { pipeline })
     }
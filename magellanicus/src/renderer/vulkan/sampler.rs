@@ -0,0 +1,54 @@
+use crate::error::MResult;
+use crate::renderer::vulkan::VulkanRenderer;
+use crate::renderer::{BitmapAddressMode, BitmapFilter, BitmapSamplerHints};
+use std::sync::Arc;
+use vulkano::image::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode};
+
+impl VulkanRenderer {
+    /// Get a sampler matching the given hints, creating it if no bitmap has used them yet.
+    pub(crate) fn get_sampler(&mut self, hints: &BitmapSamplerHints) -> MResult<Arc<Sampler>> {
+        if let Some((_, sampler)) = self.samplers.iter().find(|(h, _)| h == hints) {
+            return Ok(sampler.clone())
+        }
+
+        let properties = self.device.physical_device().properties();
+
+        let address_mode = hints.address_mode.map(|m| match m {
+            BitmapAddressMode::Repeat => SamplerAddressMode::Repeat,
+            BitmapAddressMode::MirroredRepeat => SamplerAddressMode::MirroredRepeat,
+            BitmapAddressMode::ClampToEdge => SamplerAddressMode::ClampToEdge
+        });
+
+        let (filter, mipmap_mode) = match hints.filter {
+            BitmapFilter::Point => (Filter::Nearest, SamplerMipmapMode::Nearest),
+            BitmapFilter::Linear => (Filter::Linear, SamplerMipmapMode::Linear)
+        };
+
+        // Anisotropy requires linear filtering.
+        let anisotropy = match hints.filter {
+            BitmapFilter::Point => None,
+            BitmapFilter::Linear => hints
+                .max_anisotropy
+                .or(self.default_anisotropy)
+                .map(|n| n.min(properties.max_sampler_anisotropy))
+                .filter(|n| *n > 1.0)
+        };
+
+        let max_lod_bias = properties.max_sampler_lod_bias;
+        let sampler = Sampler::new(
+            self.device.clone(),
+            SamplerCreateInfo {
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_mode,
+                address_mode,
+                mip_lod_bias: hints.mip_lod_bias.clamp(-max_lod_bias, max_lod_bias),
+                anisotropy,
+                ..SamplerCreateInfo::simple_repeat_linear()
+            }
+        )?;
+
+        self.samplers.push((*hints, sampler.clone()));
+        Ok(sampler)
+    }
+}
@@ -0,0 +1,148 @@
+use crate::error::{Error, MResult};
+use crate::renderer::vulkan::VulkanRenderer;
+use std::boxed::Box;
+use std::string::ToString;
+use std::sync::Arc;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer};
+use vulkano::device::Queue;
+use vulkano::sync::GpuFuture;
+use vulkano::sync::future::FenceSignalFuture;
+
+pub(crate) type VulkanPendingUpload = FenceSignalFuture<Box<dyn GpuFuture + Send + Sync>>;
+
+/// Upload commands that are submitted together.
+pub struct VulkanUploadBatch {
+    /// Copies. These are run on the dedicated transfer queue if there is one.
+    transfer: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+
+    /// Commands that need a graphics queue (e.g. blits). These are run after `transfer`.
+    ///
+    /// If there is no dedicated transfer queue, `transfer` is used instead.
+    graphics: Option<AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>>,
+    has_graphics_commands: bool
+}
+
+impl VulkanUploadBatch {
+    fn new(vulkan_renderer: &VulkanRenderer) -> MResult<Self> {
+        let transfer = AutoCommandBufferBuilder::primary(
+            &vulkan_renderer.command_buffer_allocator,
+            vulkan_renderer.upload_queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+
+        let graphics = match vulkan_renderer.transfer_queue.is_some() {
+            true => Some(AutoCommandBufferBuilder::primary(
+                &vulkan_renderer.command_buffer_allocator,
+                vulkan_renderer.queue.queue_family_index(),
+                CommandBufferUsage::OneTimeSubmit,
+            )?),
+            false => None
+        };
+
+        Ok(Self { transfer, graphics, has_graphics_commands: false })
+    }
+
+    /// Get the command buffer for copying to buffers and images.
+    pub fn transfer_commands(&mut self) -> &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        &mut self.transfer
+    }
+
+    /// Get the command buffer for commands that need a graphics queue.
+    ///
+    /// These are run after all transfer commands in the batch are complete.
+    pub fn graphics_commands(&mut self) -> &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        self.has_graphics_commands = true;
+        self.graphics.as_mut().unwrap_or(&mut self.transfer)
+    }
+}
+
+impl VulkanRenderer {
+    /// Get the queue that copies are uploaded on.
+    fn upload_queue(&self) -> &Arc<Queue> {
+        self.transfer_queue.as_ref().unwrap_or(&self.queue)
+    }
+
+    /// Start recording uploads into a single batch.
+    pub fn begin_upload_batch(&mut self) -> MResult<()> {
+        if self.upload_batch.is_some() {
+            return Err(Error::from_data_error_string("An upload batch is already being recorded".to_string()))
+        }
+        self.upload_batch = Some(VulkanUploadBatch::new(self)?);
+        Ok(())
+    }
+
+    /// Submit the current upload batch without waiting for it to finish.
+    pub fn submit_upload_batch(&mut self) -> MResult<()> {
+        let batch = self
+            .upload_batch
+            .take()
+            .ok_or_else(|| Error::from_data_error_string("No upload batch is being recorded".to_string()))?;
+
+        let future = self.submit_batch(batch)?.then_signal_fence_and_flush()?;
+        self.pending_uploads.push(future);
+        Ok(())
+    }
+
+    /// Return `true` if any submitted upload batches are still in progress.
+    pub fn uploads_pending(&mut self) -> bool {
+        self.pending_uploads.retain(|u| u.is_signaled().is_ok_and(|signaled| !signaled));
+        !self.pending_uploads.is_empty()
+    }
+
+    /// Block until all submitted upload batches are complete.
+    pub fn wait_for_uploads(&mut self) -> MResult<()> {
+        for upload in self.pending_uploads.drain(..) {
+            upload.wait(None)?;
+        }
+        Ok(())
+    }
+
+    /// Record upload commands.
+    ///
+    /// If no upload batch is being recorded, the commands are submitted immediately, and subsequent frames will wait
+    /// for them to finish.
+    pub(crate) fn record_upload(&mut self, record: impl FnOnce(&mut VulkanUploadBatch) -> MResult<()>) -> MResult<()> {
+        if let Some(batch) = self.upload_batch.as_mut() {
+            return record(batch)
+        }
+
+        self.record_immediate_upload(record)
+    }
+
+    /// Record upload commands and submit them immediately, even if an upload batch is being recorded.
+    ///
+    /// Subsequent frames will wait for them to finish.
+    pub(crate) fn record_immediate_upload(&mut self, record: impl FnOnce(&mut VulkanUploadBatch) -> MResult<()>) -> MResult<()> {
+        let mut batch = VulkanUploadBatch::new(self)?;
+        record(&mut batch)?;
+
+        let execution = self.submit_batch(batch)?;
+        let future = self.future
+            .take()
+            .unwrap_or_else(|| vulkano::sync::now(self.device.clone()).boxed_send_sync())
+            .join(execution)
+            .then_signal_fence_and_flush()?
+            .boxed_send_sync();
+
+        self.future = Some(future);
+        Ok(())
+    }
+
+    fn submit_batch(&self, batch: VulkanUploadBatch) -> MResult<Box<dyn GpuFuture + Send + Sync>> {
+        let VulkanUploadBatch { transfer, graphics, has_graphics_commands } = batch;
+
+        let mut future = vulkano::sync::now(self.device.clone())
+            .then_execute(self.upload_queue().clone(), transfer.build()?)
+            .map_err(|e| Error::from_vulkan_error(e.to_string()))?
+            .boxed_send_sync();
+
+        if let Some(graphics) = graphics.filter(|_| has_graphics_commands) {
+            future = future
+                .then_execute(self.queue.clone(), graphics.build()?)
+                .map_err(|e| Error::from_vulkan_error(e.to_string()))?
+                .boxed_send_sync();
+        }
+
+        Ok(future)
+    }
+}
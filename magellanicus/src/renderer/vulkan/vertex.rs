@@ -58,3 +58,11 @@ pub struct VulkanFogData {
     pub sky_fog_min_opacity: f32,
     pub sky_fog_max_opacity: f32,
 }
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+#[derive(BufferContents)]
+pub struct VulkanOverlayData {
+    pub tint: [f32; 4],
+    pub blend_mode: u32,
+}
@@ -0,0 +1,1272 @@
+mod bitmap;
+mod bsp;
+mod material;
+mod overlay;
+mod pipeline;
+mod sampler;
+mod vertex;
+
+use crate::error::{Error, MResult};
+use crate::renderer::backend::sealed::Sealed;
+use crate::renderer::data::{BSPGeometry, OverlayQuad, BSP};
+use crate::renderer::player_viewport::PlayerViewport;
+use crate::renderer::wgpu::overlay::WgpuOverlay;
+use crate::renderer::wgpu::sampler::MAX_ANISOTROPY;
+use crate::renderer::wgpu::vertex::{WgpuBlitData, WgpuFogData, WgpuModelData, WgpuOverlayVertex};
+use crate::renderer::{make_player_viewports, AddBSPParameter, AddBSPParameterLightmapMaterial, AddBitmapBitmapParameter, AddShaderParameter, BitmapSamplerHints, Camera, DeviceInfo, DeviceSelection, DeviceType, LogCallback, LogSeverity, Renderer, RendererBackend, RendererParameters, Resolution, TextureRecompression, DEFAULT_BACKGROUND, MSAA};
+use crate::vertex::ModelTriangle;
+pub use bitmap::*;
+pub use bsp::*;
+use glam::{Mat4, Vec3};
+pub use material::*;
+pub use pipeline::*;
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+use std::boxed::Box;
+use std::collections::BTreeMap;
+use std::eprintln;
+use std::format;
+use std::string::{String, ToString};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::vec;
+use std::vec::Vec;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{Adapter, AddressMode, Backends, BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, Buffer, BufferDescriptor, BufferUsages, Color, CommandEncoderDescriptor, CompositeAlphaMode, Device, DeviceDescriptor, ErrorFilter, Extent3d, Features, FilterMode, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, IndexFormat, Instance, InstanceDescriptor, Limits, LoadOp, Maintain, MapMode, Operations, Origin3d, PowerPreference, PresentMode, Queue, RenderPass, RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline, RequestAdapterOptions, Sampler, SamplerDescriptor, Surface, SurfaceConfiguration, SurfaceError, Texture, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView, COPY_BYTES_PER_ROW_ALIGNMENT};
+
+/// Renderer backend built on wgpu.
+///
+/// This draws the same things as the Vulkan backend, but it can run on any API wgpu supports, including software
+/// adapters. It can also render without a window (see [`Renderer::new_wgpu_headless`]), in which case frames can be
+/// read back with [`WgpuRenderer::read_frame`].
+///
+/// wgpu always validates how it is used, so [`RendererParameters::validation`] has no effect, and errors are sent to
+/// [`RendererParameters::log_callback`].
+pub struct WgpuRenderer {
+    current_resolution: Resolution,
+    instance: Arc<Instance>,
+    adapter: Adapter,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    surface: Option<(Arc<Surface>, SurfaceConfiguration)>,
+    headless_output: Option<Texture>,
+    output_format: TextureFormat,
+    render_targets: WgpuRenderTargets,
+    pipelines: BTreeMap<WgpuPipelineType, Arc<RenderPipeline>>,
+    bind_group_layouts: WgpuBindGroupLayouts,
+    default_2d_sampler: Arc<Sampler>,
+    overlay_sampler: Sampler,
+    lightmap_sampler: Sampler,
+    samplers: Vec<(BitmapSamplerHints, Arc<Sampler>)>,
+    default_anisotropy: Option<f32>,
+    texture_recompression: Option<TextureRecompression>,
+    two_channel_normal_maps: bool,
+    supports_block_compression: bool,
+    samples_per_pixel: u32,
+    color_format: TextureFormat,
+    linear_lighting: bool,
+    recording_upload_batch: bool,
+    pending_uploads: Vec<Arc<AtomicBool>>,
+    log_callback: Option<LogCallback>,
+    device_lost: Arc<AtomicBool>,
+    start_time: Instant
+}
+
+/// Images that a frame is drawn to before it is copied to the output.
+struct WgpuRenderTargets {
+    color: TextureView,
+    depth: TextureView,
+    resolve: Option<TextureView>,
+
+    /// Reads the color (or resolved color) image when copying it to the output.
+    blit_bind_group: BindGroup
+}
+
+impl Renderer<WgpuRenderer> {
+    /// Initialize a new renderer with the wgpu backend.
+    ///
+    /// # Safety
+    ///
+    /// `surface` must outlive the renderer.
+    ///
+    /// Errors if:
+    /// - `parameters` is invalid
+    /// - the renderer backend could not be initialized for some reason
+    pub unsafe fn new_wgpu(surface: &(impl HasRawWindowHandle + HasRawDisplayHandle), parameters: RendererParameters) -> MResult<Self> {
+        let player_viewports = make_player_viewports(&parameters)?;
+        let backend = WgpuRenderer::new(&parameters, surface)?;
+        Self::new_with_backend(backend, player_viewports, &parameters)
+    }
+
+    /// Initialize a new renderer with the wgpu backend that draws to an image rather than a window.
+    ///
+    /// Use [`WgpuRenderer::read_frame`] through [`Renderer::backend`] to get what was drawn.
+    ///
+    /// Errors if:
+    /// - `parameters` is invalid
+    /// - the renderer backend could not be initialized for some reason
+    pub fn new_wgpu_headless(parameters: RendererParameters) -> MResult<Self> {
+        let player_viewports = make_player_viewports(&parameters)?;
+        let backend = WgpuRenderer::new_headless(&parameters)?;
+        Self::new_with_backend(backend, player_viewports, &parameters)
+    }
+
+    /// Describe all wgpu adapters on the system.
+    ///
+    /// Pass [`DeviceInfo::index`] or [`DeviceInfo::name`] to [`RendererParameters::device`] to render with a specific
+    /// adapter.
+    ///
+    /// This is named differently from the Vulkan backend's `enumerate_devices` so that calls stay unambiguous when
+    /// both backends are enabled.
+    pub fn enumerate_wgpu_devices() -> MResult<Vec<DeviceInfo>> {
+        WgpuRenderer::enumerate_devices()
+    }
+}
+
+impl WgpuRenderer {
+    /// Initialize a renderer that draws to a window.
+    ///
+    /// # Safety
+    ///
+    /// `surface` must outlive the renderer.
+    pub unsafe fn new(
+        renderer_parameters: &RendererParameters,
+        surface: &(impl HasRawWindowHandle + HasRawDisplayHandle)
+    ) -> MResult<Self> {
+        let instance = new_instance();
+        let surface = instance
+            .create_surface(surface)
+            .map_err(|e| Error::from_wgpu_error(format!("Can't create a surface: {e}")))?;
+        Self::new_with_surface(renderer_parameters, Arc::new(instance), Some(Arc::new(surface)))
+    }
+
+    /// Initialize a renderer that draws to an image instead of a window.
+    pub fn new_headless(renderer_parameters: &RendererParameters) -> MResult<Self> {
+        Self::new_with_surface(renderer_parameters, Arc::new(new_instance()), None)
+    }
+
+    /// Replace the adapter and device, such as after the device was lost.
+    ///
+    /// Everything made with the old device must be dropped first, as it can't be used with the new one. The surface is
+    /// kept and reconfigured for the new device.
+    pub fn recreate_device(&mut self, renderer_parameters: &RendererParameters) -> MResult<()> {
+        let surface = self.surface.as_ref().map(|(surface, _)| surface.clone());
+        let mut renderer = Self::new_with_surface(renderer_parameters, self.instance.clone(), surface)?;
+        renderer.start_time = self.start_time;
+        *self = renderer;
+
+        self.log(LogSeverity::Info, "The device was recreated");
+        Ok(())
+    }
+
+    fn new_with_surface(renderer_parameters: &RendererParameters, instance: Arc<Instance>, surface: Option<Arc<Surface>>) -> MResult<Self> {
+        let log_callback = renderer_parameters.log_callback.clone();
+        let adapter = select_adapter(&instance, surface.as_deref(), &renderer_parameters.device)?;
+
+        // Block-compressed textures are decoded on the CPU if this isn't supported.
+        let features = adapter.features() & (Features::TEXTURE_COMPRESSION_BC | Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &DeviceDescriptor {
+                label: Some("magellanicus"),
+                features,
+                limits: adapter.limits()
+            },
+            None
+        )).map_err(|e| Error::from_wgpu_error(format!("Can't create a device: {e}")))?;
+
+        // wgpu 0.17 has no device-lost callback, so loss is detected from the errors it causes.
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let error_callback = log_callback.clone();
+        let error_device_lost = device_lost.clone();
+        device.on_uncaptured_error(Box::new(move |e| {
+            if is_device_lost_error(&e) {
+                error_device_lost.store(true, Ordering::Relaxed);
+            }
+            log(&error_callback, LogSeverity::Error, &e.to_string())
+        }));
+
+        if let Some(n) = renderer_parameters.anisotropic_filtering {
+            if !(1.0..=MAX_ANISOTROPY).contains(&n) {
+                return Err(
+                    Error::from_wgpu_unsupported_error(format!("{n}x AF is unsupported by wgpu; supported values are 1-{MAX_ANISOTROPY}"))
+                )
+            }
+        }
+
+        let color_format = if renderer_parameters.linear_lighting { TextureFormat::Rgba16Float } else { TextureFormat::Rgba8Unorm };
+        let samples_per_pixel = renderer_parameters.msaa as u32;
+        let supported_sample_counts = supported_sample_counts(&adapter, color_format);
+        if !supported_sample_counts.contains(&renderer_parameters.msaa) {
+            return Err(
+                Error::from_wgpu_unsupported_error(format!("{}x MSAA is unsupported by your device; only these are supported:{}",
+                                                           samples_per_pixel,
+                                                           supported_sample_counts.iter().map(|s| format!(" {}", *s as u32)).collect::<String>())));
+        }
+
+        // Linear lighting is rendered into a float target, then converted to sRGB when blitting to the output.
+        let (surface, output_format) = match surface {
+            Some(surface) => {
+                let capabilities = surface.get_capabilities(&adapter);
+                let output_format = if renderer_parameters.linear_lighting {
+                    capabilities
+                        .formats
+                        .iter()
+                        .copied()
+                        .find(|f| f.is_srgb())
+                        .ok_or_else(|| Error::from_wgpu_unsupported_error("Linear lighting requires an sRGB surface format, but your device has none".to_string()))?
+                }
+                else {
+                    capabilities
+                        .formats
+                        .iter()
+                        .copied()
+                        .find(|f| !f.is_srgb())
+                        .or(capabilities.formats.first().copied())
+                        .ok_or_else(|| Error::from_wgpu_unsupported_error("The surface can't be presented to by your device".to_string()))?
+                };
+
+                let present_mode = if renderer_parameters.vsync {
+                    // This is always supported.
+                    PresentMode::Fifo
+                }
+                else {
+                    [PresentMode::Immediate, PresentMode::Mailbox]
+                        .into_iter()
+                        .find(|m| capabilities.present_modes.contains(m))
+                        .unwrap_or(PresentMode::Fifo)
+                };
+
+                let configuration = SurfaceConfiguration {
+                    usage: TextureUsages::RENDER_ATTACHMENT,
+                    format: output_format,
+                    width: renderer_parameters.resolution.width,
+                    height: renderer_parameters.resolution.height,
+                    present_mode,
+                    alpha_mode: capabilities.alpha_modes.first().copied().unwrap_or(CompositeAlphaMode::Auto),
+                    view_formats: Vec::new()
+                };
+
+                (Some((surface, configuration)), output_format)
+            },
+            None => (None, if renderer_parameters.linear_lighting { TextureFormat::Rgba8UnormSrgb } else { TextureFormat::Rgba8Unorm })
+        };
+
+        let bind_group_layouts = WgpuBindGroupLayouts::new(&device);
+        let pipelines = error_scope(&device, || load_all_pipelines(&device, &bind_group_layouts, samples_per_pixel, color_format, output_format))?;
+
+        let default_2d_sampler = Arc::new(device.create_sampler(&SamplerDescriptor {
+            label: Some("default 2D sampler"),
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            address_mode_w: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            anisotropy_clamp: renderer_parameters.anisotropic_filtering.map(|n| n as u16).unwrap_or(1),
+            ..Default::default()
+        }));
+
+        let overlay_sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("overlay sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let lightmap_sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("lightmap sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let mut renderer = Self {
+            current_resolution: renderer_parameters.resolution,
+            render_targets: make_render_targets(&device, &bind_group_layouts, renderer_parameters.resolution, samples_per_pixel, color_format, output_format),
+            instance,
+            adapter,
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+            surface,
+            headless_output: None,
+            output_format,
+            pipelines,
+            bind_group_layouts,
+            samplers: vec![(BitmapSamplerHints::default(), default_2d_sampler.clone())],
+            default_2d_sampler,
+            overlay_sampler,
+            lightmap_sampler,
+            default_anisotropy: renderer_parameters.anisotropic_filtering,
+            texture_recompression: renderer_parameters.texture_recompression,
+            two_channel_normal_maps: renderer_parameters.two_channel_normal_maps,
+            supports_block_compression: features.contains(Features::TEXTURE_COMPRESSION_BC),
+            samples_per_pixel,
+            color_format,
+            linear_lighting: renderer_parameters.linear_lighting,
+            recording_upload_batch: false,
+            pending_uploads: Vec::new(),
+            log_callback,
+            device_lost,
+            start_time: Instant::now()
+        };
+
+        renderer.configure_output()?;
+        Ok(renderer)
+    }
+
+    /// Describe all adapters on the system.
+    pub fn enumerate_devices() -> MResult<Vec<DeviceInfo>> {
+        let instance = new_instance();
+        let devices = instance
+            .enumerate_adapters(Backends::all())
+            .enumerate()
+            .map(|(index, adapter)| {
+                let info = adapter.get_info();
+                DeviceInfo {
+                    index,
+                    name: info.name,
+                    device_type: match info.device_type {
+                        wgpu::DeviceType::DiscreteGpu => DeviceType::DiscreteGpu,
+                        wgpu::DeviceType::IntegratedGpu => DeviceType::IntegratedGpu,
+                        wgpu::DeviceType::VirtualGpu => DeviceType::VirtualGpu,
+                        wgpu::DeviceType::Cpu => DeviceType::Cpu,
+                        wgpu::DeviceType::Other => DeviceType::Other
+                    },
+                    driver_name: Some(info.driver).filter(|d| !d.is_empty()),
+                    driver_info: Some(info.driver_info).filter(|d| !d.is_empty()),
+                    api_version: None,
+                    device_local_memory: None,
+                    supports_block_compression: adapter.features().contains(Features::TEXTURE_COMPRESSION_BC),
+                    max_anisotropic_filtering: Some(MAX_ANISOTROPY),
+                    supported_msaa: supported_sample_counts(&adapter, TextureFormat::Rgba8Unorm),
+                    suitable: is_suitable(&adapter)
+                }
+            })
+            .collect();
+
+        Ok(devices)
+    }
+
+    pub fn draw_frame(renderer: &mut Renderer<WgpuRenderer>) -> MResult<bool> {
+        if renderer.renderer.is_device_lost() {
+            return Err(Error::DeviceLost { backend: "wgpu" })
+        }
+
+        // Release finished upload batches
+        renderer.renderer.uploads_pending();
+
+        let surface_texture = match renderer.renderer.surface.as_ref() {
+            Some((surface, _)) => match surface.get_current_texture() {
+                Ok(t) => Some(t),
+                Err(SurfaceError::Outdated | SurfaceError::Lost) => return Ok(false),
+
+                // Skip this frame; the next one will likely be fine.
+                Err(SurfaceError::Timeout) => {
+                    renderer.renderer.log(LogSeverity::Warning, "Timed out waiting for the surface texture; skipping frame");
+                    return Ok(false)
+                },
+                Err(SurfaceError::OutOfMemory) => return Err(Error::OutOfMemory { backend: "wgpu", error: "Can't acquire the surface texture".to_string() })
+            },
+            None => None
+        };
+
+        let output = match surface_texture.as_ref() {
+            Some(t) => &t.texture,
+            None => renderer
+                .renderer
+                .headless_output
+                .as_ref()
+                .ok_or_else(|| Error::from_wgpu_error("The renderer has neither a surface nor a headless output".to_string()))?
+        };
+        let output_view = output.create_view(&Default::default());
+
+        let overlays = renderer.take_overlays();
+
+        let renderer: &Renderer<WgpuRenderer> = renderer;
+        Self::draw_frame_to_view(renderer, &output_view, &overlays)?;
+
+        let suboptimal = match surface_texture {
+            Some(t) => {
+                let suboptimal = t.suboptimal;
+                t.present();
+                suboptimal
+            },
+            None => false
+        };
+
+        Ok(!suboptimal)
+    }
+
+    /// Return `true` if the device was lost.
+    ///
+    /// wgpu only reports this through the errors that using a lost device causes, so this may not return `true` until
+    /// something has been drawn or uploaded since the loss.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    pub fn get_time(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    /// Read the last frame drawn as 8-bit RGBA pixels in row-major order.
+    ///
+    /// Returns `Err` if the renderer draws to a window rather than being headless.
+    pub fn read_frame(&self) -> MResult<Vec<u8>> {
+        let output = self
+            .headless_output
+            .as_ref()
+            .ok_or_else(|| Error::from_data_error_string("Only frames of headless renderers can be read".to_string()))?;
+
+        let Resolution { width, height } = self.current_resolution;
+        let row_length = width as usize * 4;
+        let padded_row_length = row_length.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT as usize);
+
+        let buffer = error_scope(&self.device, || {
+            let buffer = self.device.create_buffer(&BufferDescriptor {
+                label: Some("frame readback"),
+                size: (padded_row_length * height as usize) as u64,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false
+            });
+
+            let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor { label: Some("frame readback") });
+            encoder.copy_texture_to_buffer(
+                ImageCopyTexture {
+                    texture: output,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All
+                },
+                ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_row_length as u32),
+                        rows_per_image: Some(height)
+                    }
+                },
+                Extent3d { width, height, depth_or_array_layers: 1 }
+            );
+            self.queue.submit([encoder.finish()]);
+            buffer
+        })?;
+
+        let slice = buffer.slice(..);
+        let mapped = Arc::new(AtomicBool::new(false));
+        let mapped_callback = mapped.clone();
+        slice.map_async(MapMode::Read, move |result| mapped_callback.store(result.is_ok(), Ordering::Release));
+        self.device.poll(Maintain::Wait);
+        if !mapped.load(Ordering::Acquire) {
+            return Err(Error::from_wgpu_error("Can't map the frame readback buffer".to_string()))
+        }
+
+        let pixels = slice
+            .get_mapped_range()
+            .chunks_exact(padded_row_length)
+            .flat_map(|row| row[..row_length].iter().copied())
+            .collect();
+
+        Ok(pixels)
+    }
+
+    /// Convert an sRGB color from tag data into the color space that the pipelines render in.
+    ///
+    /// Alpha is left unchanged.
+    pub fn to_output_color(&self, color: [f32; 4]) -> [f32; 4] {
+        if !self.linear_lighting {
+            return color
+        }
+
+        let to_linear = |c: f32| if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) };
+        [to_linear(color[0]), to_linear(color[1]), to_linear(color[2]), color[3]]
+    }
+
+    /// Return `true` if lighting is done in linear space.
+    pub fn linear_lighting(&self) -> bool {
+        self.linear_lighting
+    }
+
+    pub(crate) fn log(&self, severity: LogSeverity, message: &str) {
+        log(&self.log_callback, severity, message)
+    }
+
+    pub fn rebuild_swapchain(&mut self, renderer_parameters: &RendererParameters) -> MResult<()> {
+        self.current_resolution = renderer_parameters.resolution;
+        self.render_targets = make_render_targets(
+            &self.device,
+            &self.bind_group_layouts,
+            self.current_resolution,
+            self.samples_per_pixel,
+            self.color_format,
+            self.output_format
+        );
+        self.configure_output()
+    }
+
+    /// Configure the surface, or create the output image if headless, at the current resolution.
+    fn configure_output(&mut self) -> MResult<()> {
+        let Resolution { width, height } = self.current_resolution;
+        let device = self.device.clone();
+
+        match self.surface.as_mut() {
+            Some((surface, configuration)) => {
+                configuration.width = width;
+                configuration.height = height;
+                error_scope(&device, || surface.configure(&device, configuration))
+            },
+            None => {
+                self.headless_output = Some(error_scope(&device, || device.create_texture(&TextureDescriptor {
+                    label: Some("headless output"),
+                    size: Extent3d { width, height, depth_or_array_layers: 1 },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: self.output_format,
+                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+                    view_formats: &[]
+                }))?);
+                Ok(())
+            }
+        }
+    }
+
+    /// Start recording uploads into a single batch.
+    pub fn begin_upload_batch(&mut self) -> MResult<()> {
+        if self.recording_upload_batch {
+            return Err(Error::from_data_error_string("An upload batch is already being recorded".to_string()))
+        }
+        self.recording_upload_batch = true;
+        Ok(())
+    }
+
+    /// Submit the current upload batch without waiting for it to finish.
+    ///
+    /// wgpu holds on to uploads until the next submission, so uploads made outside of a batch are submitted with the
+    /// next frame instead.
+    pub fn submit_upload_batch(&mut self) -> MResult<()> {
+        if !self.recording_upload_batch {
+            return Err(Error::from_data_error_string("No upload batch is being recorded".to_string()))
+        }
+        self.recording_upload_batch = false;
+
+        self.queue.submit([]);
+
+        let done = Arc::new(AtomicBool::new(false));
+        let done_callback = done.clone();
+        self.queue.on_submitted_work_done(move || done_callback.store(true, Ordering::Release));
+        self.pending_uploads.push(done);
+        Ok(())
+    }
+
+    /// Return `true` if any submitted upload batches are still in progress.
+    pub fn uploads_pending(&mut self) -> bool {
+        if !self.pending_uploads.is_empty() {
+            self.device.poll(Maintain::Poll);
+            self.pending_uploads.retain(|u| !u.load(Ordering::Acquire));
+        }
+        !self.pending_uploads.is_empty()
+    }
+
+    /// Block until all submitted upload batches are complete.
+    pub fn wait_for_uploads(&mut self) -> MResult<()> {
+        if !self.pending_uploads.is_empty() {
+            self.device.poll(Maintain::Wait);
+            self.pending_uploads.clear();
+        }
+        Ok(())
+    }
+
+    fn draw_frame_to_view(renderer: &Renderer<WgpuRenderer>, output_view: &TextureView, overlays: &[Vec<OverlayQuad>]) -> MResult<()> {
+        let wgpu_renderer = &renderer.renderer;
+        let currently_loaded_bsp = renderer
+            .current_bsp
+            .as_ref()
+            .and_then(|f| renderer.bsps.get(f))
+            .cloned();
+
+        let (width, height) = (wgpu_renderer.current_resolution.width as f32, wgpu_renderer.current_resolution.height as f32);
+
+        // Everything used in a render pass has to outlive it, so create it all up front.
+        let viewports = renderer
+            .player_viewports
+            .iter()
+            .zip(overlays)
+            .map(|(viewport, overlay)| WgpuViewport::new(renderer, viewport, currently_loaded_bsp.as_deref(), overlay, width, height))
+            .collect::<MResult<Vec<_>>>()?;
+        let split_screen_bars = Self::make_split_screen_bars(renderer, width, height);
+
+        let mut encoder = wgpu_renderer.device.create_command_encoder(&CommandEncoderDescriptor { label: Some("frame") });
+
+        {
+            let targets = &wgpu_renderer.render_targets;
+            let [r, g, b, a] = DEFAULT_BACKGROUND.map(|c| c as f64);
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("scene"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &targets.color,
+                    resolve_target: targets.resolve.as_ref(),
+                    ops: Operations {
+                        load: LoadOp::Clear(Color { r, g, b, a }),
+                        store: true
+                    }
+                })],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &targets.depth,
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(1.0),
+                        store: true
+                    }),
+                    stencil_ops: None
+                })
+            });
+
+            for viewport in &viewports {
+                viewport.draw(renderer, currently_loaded_bsp.as_deref(), &mut pass);
+            }
+
+            if !split_screen_bars.is_empty() {
+                pass.set_viewport(0.0, 0.0, width, height, 0.0, 1.0);
+                for bar in &split_screen_bars {
+                    bar.draw(wgpu_renderer, &mut pass);
+                }
+            }
+        }
+
+        {
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("blit"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: output_view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true
+                    }
+                })],
+                depth_stencil_attachment: None
+            });
+            pass.set_pipeline(&wgpu_renderer.pipelines[&WgpuPipelineType::Blit]);
+            pass.set_bind_group(0, &wgpu_renderer.render_targets.blit_bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        wgpu_renderer.queue.submit([encoder.finish()]);
+        Ok(())
+    }
+
+    fn draw_bsp_geometry<'a>(
+        renderer: &'a Renderer<WgpuRenderer>,
+        currently_loaded_bsp: &'a BSP<WgpuRenderer>,
+        camera: &Camera,
+        last_shader: &mut Option<&'a Arc<str>>,
+        geometry: &'a BSPGeometry<WgpuRenderer>,
+        shader: &'a Arc<dyn WgpuMaterial>,
+        pass: &mut RenderPass<'a>
+    ) {
+        let backend = &geometry.backend;
+        if backend.index_count == 0 {
+            return
+        }
+
+        let this_shader = &geometry.shader;
+        let repeat_shader = if *last_shader != Some(this_shader) {
+            *last_shader = Some(this_shader);
+            false
+        }
+        else {
+            true
+        };
+
+        let mut desired_lightmap = geometry.lightmap_index;
+        if !camera.lightmaps {
+            desired_lightmap = None;
+        }
+
+        let lightmap = desired_lightmap
+            .and_then(|i| currently_loaded_bsp.backend.lightmap_bind_groups.get(&i))
+            .unwrap_or(&currently_loaded_bsp.backend.null_lightmap);
+        pass.set_bind_group(1, lightmap, &[]);
+
+        pass.set_index_buffer(backend.index_buffer.slice(..), IndexFormat::Uint16);
+        pass.set_vertex_buffer(0, backend.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, backend.texture_coords_buffer.slice(..));
+        pass.set_vertex_buffer(2, backend.lightmap_texture_coords_buffer.as_ref().unwrap_or(&backend.texture_coords_buffer).slice(..));
+
+        shader.draw(renderer, backend.index_count, repeat_shader, pass);
+    }
+
+    fn make_split_screen_bars(renderer: &Renderer<WgpuRenderer>, width: f32, height: f32) -> Vec<WgpuColorBox> {
+        if renderer.player_viewports.len() <= 1 {
+            return Vec::new();
+        }
+
+        let color = [0.0, 0.0, 0.0, 1.0];
+
+        let base_thickness = 2.0;
+        let scale = (width / 640.0).min(height / 480.0).max(1.0);
+        let line_thickness_horizontal = base_thickness / height * scale;
+        let line_thickness_vertical = base_thickness / width * scale;
+
+        let mut bars = vec![
+            WgpuColorBox::new(&renderer.renderer, 0.0, 0.5 - line_thickness_horizontal / 2.0, 1.0, line_thickness_horizontal, color)
+        ];
+
+        if renderer.player_viewports.len() > 2 {
+            let y;
+            let line_height;
+
+            if renderer.player_viewports.len() == 3 {
+                y = 0.5;
+                line_height = 0.5;
+            } else {
+                y = 0.0;
+                line_height = 1.0;
+            }
+
+            bars.push(WgpuColorBox::new(&renderer.renderer, 0.5 - line_thickness_vertical / 2.0, y, line_thickness_vertical, line_height, color));
+        }
+
+        bars
+    }
+}
+
+impl Sealed for WgpuRenderer {}
+
+impl RendererBackend for WgpuRenderer {
+    type Bitmap = WgpuBitmapData;
+    type Shader = WgpuMaterialShaderData;
+    type Geometry = ();
+    type BSP = WgpuBSPData;
+    type BSPGeometry = WgpuBSPGeometryData;
+
+    fn create_bitmap(&mut self, parameter: &AddBitmapBitmapParameter, resident_mipmap: Option<u32>, name: &str) -> MResult<Self::Bitmap> {
+        WgpuBitmapData::new(self, parameter, resident_mipmap, name)
+    }
+
+    fn set_bitmap_resident_mipmap(&mut self, bitmap: &mut Self::Bitmap, mipmap: u32, name: &str) -> MResult<()> {
+        bitmap.set_resident_mipmap(self, mipmap, name)
+    }
+
+    fn estimate_bitmap_memory_usage(&self, parameter: &AddBitmapBitmapParameter) -> u64 {
+        WgpuBitmapData::estimate_memory_usage(self, parameter)
+    }
+
+    fn create_shader(renderer: &mut Renderer<Self>, parameter: AddShaderParameter, _name: &str) -> MResult<Self::Shader> {
+        WgpuMaterialShaderData::new_from_parameters(renderer, parameter)
+    }
+
+    fn create_bsp_geometry(
+        renderer: &mut Renderer<Self>,
+        parameter: &AddBSPParameter,
+        material: &AddBSPParameterLightmapMaterial,
+        lightmap_index: Option<usize>,
+        name: &str
+    ) -> MResult<Self::BSPGeometry> {
+        WgpuBSPGeometryData::new(renderer, parameter, material, lightmap_index, name)
+    }
+
+    fn create_bsp(
+        renderer: &mut Renderer<Self>,
+        parameter: &AddBSPParameter,
+        _cluster_surfaces: &[Vec<Vec<Vec<ModelTriangle>>>],
+        _name: &str
+    ) -> MResult<Self::BSP> {
+        WgpuBSPData::new(renderer, parameter)
+    }
+
+    fn begin_upload_batch(&mut self) -> MResult<()> {
+        WgpuRenderer::begin_upload_batch(self)
+    }
+
+    fn submit_upload_batch(&mut self) -> MResult<()> {
+        WgpuRenderer::submit_upload_batch(self)
+    }
+
+    fn uploads_pending(&mut self) -> bool {
+        WgpuRenderer::uploads_pending(self)
+    }
+
+    fn wait_for_uploads(&mut self) -> MResult<()> {
+        WgpuRenderer::wait_for_uploads(self)
+    }
+
+    fn rebuild_swapchain(&mut self, parameters: &RendererParameters) -> MResult<()> {
+        WgpuRenderer::rebuild_swapchain(self, parameters)
+    }
+
+    fn get_time(&self) -> Duration {
+        WgpuRenderer::get_time(self)
+    }
+
+    fn draw_frame(renderer: &mut Renderer<Self>) -> MResult<bool> {
+        WgpuRenderer::draw_frame(renderer)
+    }
+
+    fn is_device_lost(&self) -> bool {
+        WgpuRenderer::is_device_lost(self)
+    }
+
+    fn recreate_device(&mut self, parameters: &RendererParameters) -> MResult<()> {
+        WgpuRenderer::recreate_device(self, parameters)
+    }
+}
+
+/// Resources for drawing a viewport.
+struct WgpuViewport {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    camera: Camera,
+    scene: Option<WgpuViewportScene>,
+    overlay: WgpuOverlay
+}
+
+/// Resources for drawing the BSP in a viewport.
+struct WgpuViewportScene {
+    sky_box: WgpuColorBox,
+    model: BindGroup,
+    fog: BindGroup
+}
+
+impl WgpuViewport {
+    fn new(
+        renderer: &Renderer<WgpuRenderer>,
+        player_viewport: &PlayerViewport,
+        currently_loaded_bsp: Option<&BSP<WgpuRenderer>>,
+        overlay: &[OverlayQuad],
+        width: f32,
+        height: f32
+    ) -> MResult<Self> {
+        let x = player_viewport.rel_x * width;
+        let y = player_viewport.rel_y * height;
+        let width = player_viewport.rel_width * width;
+        let height = player_viewport.rel_height * height;
+        let camera = player_viewport.camera;
+
+        let scene = currently_loaded_bsp.map(|bsp| WgpuViewportScene::new(renderer, bsp, camera, width / height));
+        let overlay = WgpuOverlay::new(renderer, overlay, width, height)?;
+
+        Ok(Self { x, y, width, height, camera, scene, overlay })
+    }
+
+    fn draw<'a>(&'a self, renderer: &'a Renderer<WgpuRenderer>, currently_loaded_bsp: Option<&'a BSP<WgpuRenderer>>, pass: &mut RenderPass<'a>) {
+        pass.set_viewport(self.x, self.y, self.width, self.height, 0.0, 1.0);
+
+        if let (Some(scene), Some(currently_loaded_bsp)) = (self.scene.as_ref(), currently_loaded_bsp) {
+            scene.sky_box.draw(&renderer.renderer, pass);
+
+            pass.set_bind_group(0, &scene.model, &[]);
+            pass.set_bind_group(2, &scene.fog, &[]);
+
+            let geo_shader_iterator = currently_loaded_bsp
+                .geometry_indices_sorted_by_material
+                .iter()
+                .map(|g| &currently_loaded_bsp.geometries[*g])
+                .filter_map(|g| renderer.shaders.get(&g.shader).map(|s| (g, &s.backend.pipeline_data)));
+
+            let opaque = geo_shader_iterator.clone().filter(|s| !s.1.is_transparent());
+            let transparent = geo_shader_iterator.clone().filter(|s| s.1.is_transparent());
+
+            // Draw non-transparent shaders first
+            let mut last_shader = None;
+            for (geometry, shader) in opaque.chain(transparent) {
+                WgpuRenderer::draw_bsp_geometry(renderer, currently_loaded_bsp, &self.camera, &mut last_shader, geometry, shader, pass);
+            }
+        }
+
+        self.overlay.draw(pass);
+    }
+}
+
+impl WgpuViewportScene {
+    fn new(renderer: &Renderer<WgpuRenderer>, currently_loaded_bsp: &BSP<WgpuRenderer>, camera: Camera, aspect_ratio: f32) -> Self {
+        let wgpu_renderer = &renderer.renderer;
+
+        let cluster_index = currently_loaded_bsp.bsp_data.find_cluster(camera.position);
+        let cluster = cluster_index.map(|c| &currently_loaded_bsp.bsp_data.clusters[c]);
+        let sky = cluster.and_then(|c| c.sky.as_ref()).and_then(|s| renderer.skies.get(s.as_str()));
+
+        let z_near = 0.0625;
+        let mut z_far = currently_loaded_bsp.draw_distance;
+        let fog_data;
+        if let (true, Some(sky)) = (camera.fog, sky) {
+            // TODO: determine which fog color
+            fog_data = FogData {
+                color: wgpu_renderer.to_output_color([sky.outdoor_fog_color[0], sky.outdoor_fog_color[1], sky.outdoor_fog_color[2], 0.0]),
+                distance_from: sky.outdoor_fog_start_distance,
+                distance_to: sky.outdoor_fog_opaque_distance,
+                min_opacity: 0.0,
+                max_opacity: sky.outdoor_fog_maximum_density,
+            };
+
+            // Occlude things that won't be visible anyway
+            if fog_data.max_opacity == 1.0 {
+                z_far = z_far.min(fog_data.distance_to);
+            }
+        }
+        else {
+            fog_data = FogData::default();
+        }
+
+        let sky_color = [fog_data.color[0], fog_data.color[1], fog_data.color[2], 1.0];
+        let sky_box = WgpuColorBox::new(wgpu_renderer, 0.0, 0.0, 1.0, 1.0, sky_color);
+
+        z_far = z_far.max(z_near + 1.0);
+        let proj = Mat4::perspective_lh(
+            camera.fov,
+            aspect_ratio,
+            z_near,
+            z_far
+        );
+        let view = Mat4::look_to_lh(
+            camera.position.into(),
+            camera.rotation.into(),
+            Vec3::new(0.0, 0.0, -1.0)
+        );
+
+        let [camera_x, camera_y, camera_z] = camera.position;
+        let model = uniform_bind_group(wgpu_renderer, &wgpu_renderer.bind_group_layouts.model, "model data", bytemuck::bytes_of(&WgpuModelData {
+            camera: [camera_x, camera_y, camera_z, 0.0],
+            offset: [0.0; 4],
+            view: view.to_cols_array_2d(),
+            proj: proj.to_cols_array_2d()
+        }));
+
+        let fog = uniform_bind_group(wgpu_renderer, &wgpu_renderer.bind_group_layouts.fog, "fog data", bytemuck::bytes_of(&WgpuFogData {
+            sky_fog_color: fog_data.color,
+            sky_fog_from: fog_data.distance_from,
+            sky_fog_to: fog_data.distance_to,
+            sky_fog_min_opacity: fog_data.min_opacity,
+            sky_fog_max_opacity: fog_data.max_opacity
+        }));
+
+        Self { sky_box, model, fog }
+    }
+}
+
+/// Box of a single color, drawn relative to the current viewport.
+struct WgpuColorBox {
+    vertices: Buffer,
+    bind_group: BindGroup
+}
+
+impl WgpuColorBox {
+    fn new(renderer: &WgpuRenderer, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) -> Self {
+        let vertices = renderer.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("color box vertices"),
+            contents: bytemuck::cast_slice(&WgpuOverlayVertex::quad(x, y, width, height, [0.0; 4])),
+            usage: BufferUsages::VERTEX
+        });
+        let bind_group = uniform_bind_group(renderer, &renderer.bind_group_layouts.color_box, "color box", bytemuck::bytes_of(&color));
+        Self { vertices, bind_group }
+    }
+
+    fn draw<'a>(&'a self, renderer: &'a WgpuRenderer, pass: &mut RenderPass<'a>) {
+        pass.set_pipeline(&renderer.pipelines[&WgpuPipelineType::ColorBox]);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertices.slice(..));
+        pass.draw(0..6, 0..1);
+    }
+}
+
+struct FogData {
+    color: [f32; 4],
+    distance_from: f32,
+    distance_to: f32,
+    min_opacity: f32,
+    max_opacity: f32
+}
+
+impl Default for FogData {
+    fn default() -> Self {
+        Self {
+            color: [0.0f32; 4],
+            distance_from: 0.0,
+            distance_to: 1.0,
+            min_opacity: 0.0,
+            max_opacity: 0.0
+        }
+    }
+}
+
+/// Make a bind group with a uniform buffer containing `contents` on binding 0.
+fn uniform_bind_group(renderer: &WgpuRenderer, layout: &wgpu::BindGroupLayout, label: &str, contents: &[u8]) -> BindGroup {
+    let buffer = renderer.device.create_buffer_init(&BufferInitDescriptor {
+        label: Some(label),
+        contents,
+        usage: BufferUsages::UNIFORM
+    });
+
+    renderer.device.create_bind_group(&BindGroupDescriptor {
+        label: Some(label),
+        layout,
+        entries: &[BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }]
+    })
+}
+
+fn make_render_targets(
+    device: &Device,
+    bind_group_layouts: &WgpuBindGroupLayouts,
+    resolution: Resolution,
+    samples_per_pixel: u32,
+    color_format: TextureFormat,
+    output_format: TextureFormat
+) -> WgpuRenderTargets {
+    let make_texture = |label: &str, format: TextureFormat, sample_count: u32, usage: TextureUsages| device
+        .create_texture(&TextureDescriptor {
+            label: Some(label),
+            size: Extent3d { width: resolution.width, height: resolution.height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count,
+            dimension: TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[]
+        })
+        .create_view(&Default::default());
+
+    let multisampled = samples_per_pixel != 1;
+    let color = make_texture(
+        "color",
+        color_format,
+        samples_per_pixel,
+        if multisampled { TextureUsages::RENDER_ATTACHMENT } else { TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING }
+    );
+    let depth = make_texture("depth", DEPTH_FORMAT, samples_per_pixel, TextureUsages::RENDER_ATTACHMENT);
+    let resolve = multisampled.then(|| make_texture("resolve", color_format, 1, TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING));
+
+    // The output encodes to sRGB when written to, which would brighten colors that are already in gamma space.
+    let blit_data = WgpuBlitData {
+        decode_srgb: (output_format.is_srgb() && !color_format.is_srgb() && color_format != TextureFormat::Rgba16Float) as u32,
+        _padding: [0; 3]
+    };
+    let blit_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("blit data"),
+        contents: bytemuck::bytes_of(&blit_data),
+        usage: BufferUsages::UNIFORM
+    });
+    let blit_bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("blit"),
+        layout: &bind_group_layouts.blit,
+        entries: &[
+            BindGroupEntry { binding: 0, resource: blit_buffer.as_entire_binding() },
+            BindGroupEntry { binding: 1, resource: BindingResource::TextureView(resolve.as_ref().unwrap_or(&color)) }
+        ]
+    });
+
+    WgpuRenderTargets { color, depth, resolve, blit_bind_group }
+}
+
+fn new_instance() -> Instance {
+    Instance::new(InstanceDescriptor {
+        backends: Backends::all(),
+        ..Default::default()
+    })
+}
+
+fn select_adapter(instance: &Instance, surface: Option<&Surface>, device: &DeviceSelection) -> MResult<Adapter> {
+    let can_use = |adapter: &Adapter| is_suitable(adapter) && surface.is_none_or(|s| adapter.is_surface_supported(s));
+
+    match device {
+        DeviceSelection::Automatic => {
+            let request = |force_fallback_adapter: bool| pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
+                power_preference: PowerPreference::HighPerformance,
+                force_fallback_adapter,
+                compatible_surface: surface
+            }));
+
+            // Fall back to a software adapter if there is no hardware one.
+            request(false)
+                .filter(can_use)
+                .or_else(|| request(true).filter(can_use))
+                .ok_or_else(|| Error::from_wgpu_unsupported_error("No suitable adapter was found".to_string()))
+        },
+        DeviceSelection::Index(index) => {
+            let adapter = instance
+                .enumerate_adapters(Backends::all())
+                .nth(*index)
+                .ok_or_else(|| Error::from_wgpu_unsupported_error(format!("There is no adapter #{index}")))?;
+
+            if !is_suitable(&adapter) {
+                return Err(Error::from_wgpu_unsupported_error(format!("Adapter #{index} ({}) is not suitable", adapter.get_info().name)))
+            }
+            if !can_use(&adapter) {
+                return Err(Error::from_wgpu_unsupported_error(format!("Adapter #{index} ({}) can't present to the surface", adapter.get_info().name)))
+            }
+
+            Ok(adapter)
+        },
+        DeviceSelection::Name(name) => {
+            let name_lowercase = name.to_lowercase();
+            instance
+                .enumerate_adapters(Backends::all())
+                .filter(|a| a.get_info().name.to_lowercase().contains(&name_lowercase))
+                .find(can_use)
+                .ok_or_else(|| Error::from_wgpu_unsupported_error(format!("No suitable adapter matching \"{name}\" was found")))
+        }
+    }
+}
+
+/// Return `true` if the adapter meets the renderer's minimum requirements.
+///
+/// The renderer only needs what WebGL2-class hardware provides (e.g. four bind groups and no storage buffers).
+fn is_suitable(adapter: &Adapter) -> bool {
+    Limits::downlevel_webgl2_defaults().check_limits(&adapter.limits())
+}
+
+/// Get the MSAA levels that can be used with the given color format (and the depth format).
+fn supported_sample_counts(adapter: &Adapter, color_format: TextureFormat) -> Vec<MSAA> {
+    let supported = |samples: u32| {
+        // Without adapter-specific format features, only what WebGPU guarantees can be used.
+        if !adapter.features().contains(Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+            return samples == 1 || samples == 4
+        }
+
+        [color_format, DEPTH_FORMAT]
+            .into_iter()
+            .all(|f| adapter.get_texture_format_features(f).flags.sample_count_supported(samples))
+    };
+
+    [MSAA::NoMSAA, MSAA::MSAA2x, MSAA::MSAA4x, MSAA::MSAA8x, MSAA::MSAA16x, MSAA::MSAA32x, MSAA::MSAA64x]
+        .into_iter()
+        .filter(|m| *m == MSAA::NoMSAA || supported(*m as u32))
+        .collect()
+}
+
+fn log(callback: &Option<LogCallback>, severity: LogSeverity, message: &str) {
+    match callback.as_ref() {
+        Some(callback) => callback(severity, message),
+        None if severity >= LogSeverity::Warning => eprintln!("{severity:?}: {message}"),
+        None => ()
+    }
+}
+
+/// Run `f`, returning any validation or out-of-memory errors it caused.
+pub(crate) fn error_scope<T>(device: &Device, f: impl FnOnce() -> T) -> MResult<T> {
+    device.push_error_scope(ErrorFilter::OutOfMemory);
+    device.push_error_scope(ErrorFilter::Validation);
+    let result = f();
+    let validation_error = pollster::block_on(device.pop_error_scope());
+    let memory_error = pollster::block_on(device.pop_error_scope());
+
+    match memory_error.or(validation_error) {
+        Some(e) => Err(e.into()),
+        None => Ok(result)
+    }
+}
+
+/// Return `true` if the error was caused by using a lost device.
+fn is_device_lost_error(error: &wgpu::Error) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        // wgpu-core's DeviceError::Lost; the error types in between aren't exposed by wgpu.
+        if e.to_string() == "Parent device is lost" {
+            return true
+        }
+        source = e.source();
+    }
+    false
+}
+
+impl From<wgpu::Error> for Error {
+    fn from(value: wgpu::Error) -> Self {
+        if is_device_lost_error(&value) {
+            return Self::DeviceLost { backend: "wgpu" }
+        }
+        match value {
+            wgpu::Error::OutOfMemory { .. } => Self::OutOfMemory { backend: "wgpu", error: value.to_string() },
+            wgpu::Error::Validation { .. } => Self::from_wgpu_error(format!("wgpu error! {value}"))
+        }
+    }
+}
+
+impl Error {
+    fn from_wgpu_error(error: String) -> Self {
+        Self::GraphicsAPIError { backend: "wgpu", error }
+    }
+    fn from_wgpu_unsupported_error(error: String) -> Self {
+        Self::UnsupportedFeature { backend: "wgpu", error }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::ToOwned;
+    use std::vec;
+    use crate::renderer::*;
+    use crate::vertex::{ModelTriangle, ModelVertex};
+
+    /// Make a headless renderer, failing if there is no adapter.
+    ///
+    /// Software adapters (e.g. llvmpipe, lavapipe, or WARP) are used if there is no hardware one.
+    fn headless_renderer() -> Renderer<WgpuRenderer> {
+        Renderer::new_wgpu_headless(RendererParameters::default()).expect("no wgpu adapter, not even a software one, was found")
+    }
+
+    fn center_pixel(renderer: &Renderer<WgpuRenderer>) -> [u8; 4] {
+        let Resolution { width, height } = renderer.renderer.current_resolution;
+        let pixels = renderer.renderer.read_frame().unwrap();
+        assert_eq!(pixels.len(), width as usize * height as usize * 4);
+
+        let offset = ((height / 2) as usize * width as usize + (width / 2) as usize) * 4;
+        pixels[offset..offset + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn headless_frame_without_bsp_is_cleared_to_black() {
+        let mut renderer = headless_renderer();
+
+        renderer.draw_frame().unwrap();
+        assert!(!renderer.is_device_lost());
+
+        let pixels = renderer.renderer.read_frame().unwrap();
+        assert!(pixels.chunks_exact(4).all(|p| p == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn headless_bsp_is_drawn_with_its_shader_bitmap() {
+        let mut renderer = headless_renderer();
+        renderer.add_bitmap("bitmap", test_bitmap()).unwrap();
+        renderer.add_shader("shader", AddShaderParameter {
+            data: AddShaderData::BasicShader(AddShaderBasicShaderData {
+                bitmap: Some("bitmap".to_owned()),
+                sequence: 0,
+                frame: SequenceFrame::default(),
+                shader_type: ShaderType::Environment,
+                alpha_tested: false
+            })
+        }).unwrap();
+
+        // A wall in front of the camera, facing both ways
+        let vertex = |position| ModelVertex { position, normal: [0.0, -1.0, 0.0], binormal: [0.0, 0.0, 1.0], tangent: [1.0, 0.0, 0.0], texture_coords: [0.5, 0.5] };
+        renderer.add_bsp("bsp", AddBSPParameter {
+            lightmap_bitmap: None,
+            lightmap_sets: vec![AddBSPParameterLightmapSet {
+                lightmap_index: None,
+                materials: vec![AddBSPParameterLightmapMaterial {
+                    shader_vertices: vec![vertex([-10.0, 5.0, -10.0]), vertex([10.0, 5.0, -10.0]), vertex([0.0, 5.0, 10.0])],
+                    lightmap_vertices: None,
+                    surfaces: vec![ModelTriangle { indices: [0, 1, 2] }, ModelTriangle { indices: [0, 2, 1] }],
+                    shader: "shader".to_owned()
+                }]
+            }],
+            bsp_data: BSPData::default()
+        }).unwrap();
+
+        renderer.draw_frame().unwrap();
+        assert_eq!(center_pixel(&renderer), [0, 0, 0, 255]);
+
+        renderer.set_current_bsp(Some("bsp")).unwrap();
+        renderer.set_camera_for_viewport(0, Camera { fog: false, ..Default::default() });
+        renderer.draw_frame().unwrap();
+        assert!(!renderer.is_device_lost());
+
+        // The bitmap is white, and without a lightmap, the BSP is fully lit.
+        assert_eq!(center_pixel(&renderer), [255, 255, 255, 255]);
+    }
+}
@@ -0,0 +1,513 @@
+use crate::error::{Error, MResult};
+use crate::renderer::bitmap_decode::{cubemap_face_to_layer, decode_validated_bitmap_to_rgba8};
+use crate::renderer::mipmap_iterator::{MipmapFaceIterator, MipmapType};
+use crate::renderer::wgpu::{error_scope, WgpuRenderer};
+use crate::renderer::{encode_r8g8b8a8_to_block_compressed, generate_mipmaps_r8g8b8a8, AddBitmapBitmapParameter, BitmapColorSpace, BitmapFormat, BitmapType, LogSeverity, TextureRecompression};
+use std::borrow::Cow;
+use std::format;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::vec;
+use std::vec::Vec;
+use wgpu::{Buffer, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Device, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, Maintain, MapMode, Origin3d, Queue, Sampler, Texture, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, COPY_BYTES_PER_ROW_ALIGNMENT};
+
+pub struct WgpuBitmapData {
+    pub texture: Texture,
+
+    /// View of the whole texture, as a cubemap for cubemaps.
+    pub view: TextureView,
+    pub sampler: Arc<Sampler>,
+
+    /// Set if the bitmap's mipmaps are streamed in and out.
+    streaming: Option<WgpuBitmapStreaming>
+}
+
+struct WgpuBitmapStreaming {
+    /// Size, format, and mipmap count of the bitmap with every mipmap loaded.
+    layout: WgpuBitmapLayout,
+
+    /// Mipmap of the bitmap that is the texture's first mipmap.
+    resident_mipmap: u32,
+
+    /// Lowest resolution mipmap that can be the texture's first mipmap.
+    ///
+    /// Block-compressed textures must be a whole number of blocks, so smaller mipmaps can't be the first mipmap.
+    lowest_mipmap: u32,
+
+    /// Mipmaps that aren't loaded into the texture, or `None` if they are.
+    host_mipmaps: Vec<Option<HostMipmap>>
+}
+
+/// A mipmap kept in host memory, with all of its layers.
+struct HostMipmap {
+    data: HostMipmapData,
+    bytes_per_row: u32
+}
+
+enum HostMipmapData {
+    Bytes(Vec<u8>),
+
+    /// Being copied out of the texture, and readable once the buffer is mapped.
+    Readback { buffer: Buffer, mapped: Arc<AtomicBool> }
+}
+
+#[derive(Copy, Clone)]
+struct WgpuBitmapLayout {
+    size: Extent3d,
+    mip_level_count: u32,
+    dimension: TextureDimension,
+    view_dimension: TextureViewDimension,
+    format: TextureFormat
+}
+
+impl WgpuBitmapLayout {
+    /// Get the size of a mipmap, rounded up to the block size for copies.
+    fn mipmap_size(&self, mipmap: u32) -> Extent3d {
+        self.size.mip_level_size(mipmap, self.dimension).physical_size(self.format)
+    }
+
+    /// Get the number of bytes per row of blocks and the number of rows of blocks in a layer of a mipmap.
+    fn mipmap_rows(&self, mipmap: u32) -> (u32, u32) {
+        let size = self.mipmap_size(mipmap);
+        let (block_width, block_height) = self.format.block_dimensions();
+        let block_size = self.format.block_size(None).unwrap_or(4);
+        (size.width / block_width * block_size, size.height / block_height)
+    }
+
+    fn create_texture(&self, device: &Device, name: &str, first_mipmap: u32, usage: TextureUsages) -> (Texture, TextureView) {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(name),
+            size: self.size.mip_level_size(first_mipmap, self.dimension),
+            mip_level_count: self.mip_level_count - first_mipmap,
+            sample_count: 1,
+            dimension: self.dimension,
+            format: self.format,
+            usage,
+            view_formats: &[]
+        });
+
+        let view = texture.create_view(&TextureViewDescriptor {
+            label: Some(name),
+            dimension: Some(self.view_dimension),
+            ..Default::default()
+        });
+
+        (texture, view)
+    }
+}
+
+impl WgpuBitmapData {
+    /// Estimate how much video memory a bitmap will use, in bytes.
+    ///
+    /// This accounts for formats that are converted when uploading and for mipmaps that are generated.
+    pub fn estimate_memory_usage(wgpu_renderer: &WgpuRenderer, parameter: &AddBitmapBitmapParameter) -> u64 {
+        let uncompressed_size = if block_compressed_format(wgpu_renderer, parameter).is_some() {
+            parameter.loaded_data_length()
+        }
+        else {
+            // Everything else is decoded to 32-bit
+            let block_pixel_count = parameter.format.block_pixel_length().pow(2) as u64;
+            parameter.loaded_data_length() / parameter.format.block_byte_size() as u64 * block_pixel_count * 4
+        };
+
+        // Assume DXT5/BC7 when recompressing, which is a quarter of the size
+        match get_recompression(wgpu_renderer, parameter) {
+            Some(_) => uncompressed_size / 4,
+            None => uncompressed_size
+        }
+    }
+
+    pub fn new(wgpu_renderer: &mut WgpuRenderer, parameter: &AddBitmapBitmapParameter, resident_mipmap: Option<u32>, name: &str) -> MResult<Self> {
+        let srgb = parameter.format.is_srgb() || (wgpu_renderer.linear_lighting && parameter.color_space == BitmapColorSpace::SRGB);
+        let loaded_mipmap_count = parameter.loaded_mipmap_count();
+
+        // Block-compressed bitmaps are uploaded as-is if possible. Everything else is decoded to 32-bit on the CPU, which
+        // also puts cubemap faces in layer order.
+        let (mut bitmap_format, mut format, mut bytes, mut mipmap_count, faces_in_layer_order) = match block_compressed_format(wgpu_renderer, parameter) {
+            Some(format) if loaded_mipmap_count == parameter.mipmap_count => {
+                (parameter.format, format, Cow::Borrowed(parameter.data.as_slice()), parameter.mipmap_count, false)
+            },
+            _ => {
+                let pixels: Vec<u8> = decode_validated_bitmap_to_rgba8(parameter)?
+                    .into_iter()
+                    .flat_map(|f| f.pixels)
+                    .flatten()
+                    .collect();
+                (BitmapFormat::A8R8G8B8, TextureFormat::Rgba8Unorm, Cow::Owned(pixels), parameter.mipmap_count, true)
+            }
+        };
+
+        if loaded_mipmap_count > mipmap_count {
+            let pixels = bytes.to_mut();
+            match generate_mipmaps_r8g8b8a8(
+                parameter.resolution.width,
+                parameter.resolution.height,
+                parameter.bitmap_type,
+                mipmap_count,
+                loaded_mipmap_count,
+                parameter.format.is_srgb() || parameter.color_space == BitmapColorSpace::SRGB,
+                pixels
+            ) {
+                Some(()) => mipmap_count = loaded_mipmap_count,
+                None => wgpu_renderer.log(LogSeverity::Warning, &format!("Can't generate mipmaps for {name} ({:?}); only using the supplied mipmaps", parameter.format))
+            }
+        }
+
+        if let Some(recompression) = get_recompression(wgpu_renderer, parameter) {
+            match recompress(recompression, parameter, mipmap_count, &bytes) {
+                Some((new_bitmap_format, new_format, pixels)) => {
+                    bitmap_format = new_bitmap_format;
+                    format = new_format;
+                    bytes = Cow::Owned(pixels);
+                },
+                None => wgpu_renderer.log(LogSeverity::Warning, &format!("Can't recompress {name} ({:?}); uploading uncompressed", parameter.format))
+            }
+        }
+
+        if srgb {
+            format = format.add_srgb_suffix();
+        }
+
+        let (dimension, view_dimension, depth_or_array_layers) = match parameter.bitmap_type {
+            BitmapType::Dim2D => (TextureDimension::D2, TextureViewDimension::D2, 1),
+            BitmapType::Dim3D { depth } => (TextureDimension::D3, TextureViewDimension::D3, depth),
+            BitmapType::Cubemap => (TextureDimension::D2, TextureViewDimension::Cube, 6)
+        };
+
+        let layout = WgpuBitmapLayout {
+            size: Extent3d {
+                width: parameter.resolution.width,
+                height: parameter.resolution.height,
+                depth_or_array_layers
+            },
+            mip_level_count: mipmap_count + 1,
+            dimension,
+            view_dimension,
+            format
+        };
+
+        let mipmap_type = match parameter.bitmap_type {
+            BitmapType::Cubemap => MipmapType::Cubemap,
+            BitmapType::Dim2D => MipmapType::TwoDimensional,
+            BitmapType::Dim3D { depth } => MipmapType::ThreeDimensional(NonZeroUsize::new(depth as usize).unwrap())
+        };
+
+        let block_size = bitmap_format.block_byte_size();
+        let block_pixel_length = bitmap_format.block_pixel_length();
+        let faces = MipmapFaceIterator::new(
+            NonZeroUsize::new(parameter.resolution.width as usize).unwrap(),
+            NonZeroUsize::new(parameter.resolution.height as usize).unwrap(),
+            mipmap_type,
+            NonZeroUsize::new(block_pixel_length).unwrap(),
+            Some(mipmap_count as usize)
+        );
+        let face_layer = |face_index: usize| match parameter.bitmap_type {
+            BitmapType::Cubemap if !faces_in_layer_order => cubemap_face_to_layer(face_index),
+            _ => face_index
+        };
+
+        let device = wgpu_renderer.device.clone();
+        let queue = wgpu_renderer.queue.clone();
+
+        let Some(resident_mipmap) = resident_mipmap else {
+            let (texture, view) = error_scope(&device, || {
+                let (texture, view) = layout.create_texture(&device, name, 0, TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST);
+
+                for face in faces {
+                    let start = face.block_offset * block_size;
+                    let end = start + face.block_count * block_size;
+                    queue.write_texture(
+                        ImageCopyTexture {
+                            texture: &texture,
+                            mip_level: face.mipmap_index as u32,
+                            origin: Origin3d { x: 0, y: 0, z: face_layer(face.face_index) as u32 },
+                            aspect: TextureAspect::All
+                        },
+                        &bytes[start..end],
+                        ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: Some((face.block_width * block_size) as u32),
+                            rows_per_image: Some(face.block_height as u32)
+                        },
+                        Extent3d {
+                            width: (face.block_width * block_pixel_length) as u32,
+                            height: (face.block_height * block_pixel_length) as u32,
+                            depth_or_array_layers: 1
+                        }
+                    );
+                }
+
+                (texture, view)
+            })?;
+
+            let sampler = wgpu_renderer.get_sampler(&parameter.sampler);
+            return Ok(Self { texture, view, sampler, streaming: None })
+        };
+
+        // Split the data into mipmaps with their layers in order, so each one can be moved in and out of the texture.
+        let mut host_mipmaps: Vec<Vec<u8>> = (0..layout.mip_level_count)
+            .map(|m| {
+                let (bytes_per_row, rows) = layout.mipmap_rows(m);
+                vec![0u8; (bytes_per_row * rows * layout.mipmap_size(m).depth_or_array_layers) as usize]
+            })
+            .collect();
+
+        for face in faces {
+            let start = face.block_offset * block_size;
+            let length = face.block_count * block_size;
+            let offset = face_layer(face.face_index) * length;
+            host_mipmaps[face.mipmap_index][offset..offset + length].copy_from_slice(&bytes[start..start + length]);
+        }
+
+        let (block_width, block_height) = format.block_dimensions();
+        let lowest_mipmap = (0..layout.mip_level_count)
+            .take_while(|m| {
+                let size = layout.size.mip_level_size(*m, dimension);
+                size.width.is_multiple_of(block_width) && size.height.is_multiple_of(block_height)
+            })
+            .last()
+            .unwrap_or(0);
+        let resident_mipmap = resident_mipmap.min(lowest_mipmap);
+
+        let (texture, view) = error_scope(&device, || {
+            let (texture, view) = layout.create_texture(&device, name, resident_mipmap, streamed_texture_usage());
+            for mipmap in resident_mipmap..layout.mip_level_count {
+                write_mipmap(&queue, &texture, &layout, mipmap - resident_mipmap, mipmap, &host_mipmaps[mipmap as usize], layout.mipmap_rows(mipmap).0);
+            }
+            (texture, view)
+        })?;
+
+        let host_mipmaps = host_mipmaps
+            .into_iter()
+            .enumerate()
+            .map(|(mipmap, bytes)| {
+                let bytes_per_row = layout.mipmap_rows(mipmap as u32).0;
+                Some(HostMipmap { data: HostMipmapData::Bytes(bytes), bytes_per_row }).filter(|_| (mipmap as u32) < resident_mipmap)
+            })
+            .collect();
+
+        let sampler = wgpu_renderer.get_sampler(&parameter.sampler);
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+            streaming: Some(WgpuBitmapStreaming { layout, resident_mipmap, lowest_mipmap, host_mipmaps })
+        })
+    }
+
+    /// Make `mipmap` the texture's first mipmap, copying mipmaps that stay loaded on the device, uploading mipmaps that
+    /// are loaded, and reading back mipmaps that are dropped.
+    ///
+    /// Mipmaps past the lowest resolution block-compressed mipmap stay loaded.
+    pub fn set_resident_mipmap(&mut self, wgpu_renderer: &WgpuRenderer, mipmap: u32, name: &str) -> MResult<()> {
+        let Some(streaming) = self.streaming.as_mut() else {
+            return Err(Error::from_data_error_string(format!("Can't stream {name} because it is not streamed")))
+        };
+
+        let old_mipmap = streaming.resident_mipmap;
+        let new_mipmap = mipmap.min(streaming.lowest_mipmap);
+        if new_mipmap == old_mipmap {
+            return Ok(())
+        }
+
+        let layout = streaming.layout;
+        let device = &wgpu_renderer.device;
+        let queue = &wgpu_renderer.queue;
+
+        // Dropped mipmaps may still be getting read back
+        let read_back_mipmaps = (new_mipmap..old_mipmap).any(|m| {
+            matches!(&streaming.host_mipmaps[m as usize], Some(HostMipmap { data: HostMipmapData::Readback { mapped, .. }, .. }) if !mapped.load(Ordering::Acquire))
+        });
+        if read_back_mipmaps {
+            device.poll(Maintain::Wait);
+        }
+
+        let (texture, view, readbacks) = error_scope(device, || -> MResult<_> {
+            let (texture, view) = layout.create_texture(device, name, new_mipmap, streamed_texture_usage());
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: Some(name) });
+
+            for mipmap in new_mipmap.max(old_mipmap)..layout.mip_level_count {
+                encoder.copy_texture_to_texture(
+                    ImageCopyTexture { texture: &self.texture, mip_level: mipmap - old_mipmap, origin: Origin3d::ZERO, aspect: TextureAspect::All },
+                    ImageCopyTexture { texture: &texture, mip_level: mipmap - new_mipmap, origin: Origin3d::ZERO, aspect: TextureAspect::All },
+                    layout.mipmap_size(mipmap)
+                );
+            }
+
+            for mipmap in new_mipmap..old_mipmap {
+                let Some(HostMipmap { data, bytes_per_row }) = streaming.host_mipmaps[mipmap as usize].take() else {
+                    return Err(Error::from_data_error_string(format!("Mipmap {mipmap} of {name} is missing")))
+                };
+
+                match data {
+                    HostMipmapData::Bytes(bytes) => write_mipmap(queue, &texture, &layout, mipmap - new_mipmap, mipmap, &bytes, bytes_per_row),
+                    HostMipmapData::Readback { buffer, mapped } => {
+                        if !mapped.load(Ordering::Acquire) {
+                            return Err(Error::from_wgpu_error(format!("Can't read back mipmap {mipmap} of {name}")))
+                        }
+                        write_mipmap(queue, &texture, &layout, mipmap - new_mipmap, mipmap, &buffer.slice(..).get_mapped_range(), bytes_per_row);
+                    }
+                }
+            }
+
+            let mut readbacks = Vec::new();
+            for mipmap in old_mipmap..new_mipmap {
+                let (bytes_per_row, rows) = layout.mipmap_rows(mipmap);
+                let bytes_per_row = bytes_per_row.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+                let size = layout.mipmap_size(mipmap);
+                let buffer = device.create_buffer(&BufferDescriptor {
+                    label: Some(name),
+                    size: (bytes_per_row * rows * size.depth_or_array_layers) as u64,
+                    usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                    mapped_at_creation: false
+                });
+
+                encoder.copy_texture_to_buffer(
+                    ImageCopyTexture { texture: &self.texture, mip_level: mipmap - old_mipmap, origin: Origin3d::ZERO, aspect: TextureAspect::All },
+                    ImageCopyBuffer {
+                        buffer: &buffer,
+                        layout: ImageDataLayout { offset: 0, bytes_per_row: Some(bytes_per_row), rows_per_image: Some(rows) }
+                    },
+                    size
+                );
+                readbacks.push((mipmap, buffer, bytes_per_row));
+            }
+
+            queue.submit([encoder.finish()]);
+            Ok((texture, view, readbacks))
+        })??;
+
+        for (mipmap, buffer, bytes_per_row) in readbacks {
+            let mapped = Arc::new(AtomicBool::new(false));
+            let mapped_callback = mapped.clone();
+            buffer.slice(..).map_async(MapMode::Read, move |result| mapped_callback.store(result.is_ok(), Ordering::Release));
+            streaming.host_mipmaps[mipmap as usize] = Some(HostMipmap { data: HostMipmapData::Readback { buffer, mapped }, bytes_per_row });
+        }
+
+        self.texture = texture;
+        self.view = view;
+        streaming.resident_mipmap = new_mipmap;
+        Ok(())
+    }
+}
+
+/// Streamed textures are copied into new textures when their resident mipmaps change.
+fn streamed_texture_usage() -> TextureUsages {
+    TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::COPY_SRC
+}
+
+/// Upload every layer of a mipmap from host memory.
+fn write_mipmap(queue: &Queue, texture: &Texture, layout: &WgpuBitmapLayout, mip_level: u32, mipmap: u32, bytes: &[u8], bytes_per_row: u32) {
+    queue.write_texture(
+        ImageCopyTexture { texture, mip_level, origin: Origin3d::ZERO, aspect: TextureAspect::All },
+        bytes,
+        ImageDataLayout { offset: 0, bytes_per_row: Some(bytes_per_row), rows_per_image: Some(layout.mipmap_rows(mipmap).1) },
+        layout.mipmap_size(mipmap)
+    );
+}
+
+/// Get the format to upload a block-compressed bitmap as without decoding it, if the device can sample it.
+///
+/// Block-compressed textures must be 2D (or cubemaps) with dimensions that are a multiple of the block size.
+fn block_compressed_format(wgpu_renderer: &WgpuRenderer, parameter: &AddBitmapBitmapParameter) -> Option<TextureFormat> {
+    if !can_block_compress(wgpu_renderer, parameter) {
+        return None
+    }
+
+    match parameter.format {
+        BitmapFormat::DXT1 => Some(TextureFormat::Bc1RgbaUnorm),
+        BitmapFormat::DXT3 => Some(TextureFormat::Bc2RgbaUnorm),
+        BitmapFormat::DXT5 => Some(TextureFormat::Bc3RgbaUnorm),
+        BitmapFormat::BC7 => Some(TextureFormat::Bc7RgbaUnorm),
+        BitmapFormat::BC4 => Some(TextureFormat::Bc4RUnorm),
+        BitmapFormat::BC5 => Some(TextureFormat::Bc5RgUnorm),
+        BitmapFormat::DXT1SRGB => Some(TextureFormat::Bc1RgbaUnormSrgb),
+        BitmapFormat::DXT3SRGB => Some(TextureFormat::Bc2RgbaUnormSrgb),
+        BitmapFormat::DXT5SRGB => Some(TextureFormat::Bc3RgbaUnormSrgb),
+        BitmapFormat::BC7SRGB => Some(TextureFormat::Bc7RgbaUnormSrgb),
+        _ => None
+    }
+}
+
+fn can_block_compress(wgpu_renderer: &WgpuRenderer, parameter: &AddBitmapBitmapParameter) -> bool {
+    wgpu_renderer.supports_block_compression
+        && !matches!(parameter.bitmap_type, BitmapType::Dim3D { .. })
+        && parameter.resolution.width.is_multiple_of(4)
+        && parameter.resolution.height.is_multiple_of(4)
+}
+
+/// Block-compressed encoding to recompress a bitmap to.
+#[derive(Copy, Clone)]
+enum Recompression {
+    Texture(TextureRecompression),
+
+    /// Keep only X and Y of a bump map, encoded to BC5.
+    TwoChannelNormalMap
+}
+
+/// Get the recompression to use for a bitmap, if any.
+///
+/// Only uncompressed 32-bit bitmaps (and 8-bit and 16-bit bitmaps, which are transcoded to 32-bit) are recompressed.
+fn get_recompression(wgpu_renderer: &WgpuRenderer, parameter: &AddBitmapBitmapParameter) -> Option<Recompression> {
+    if !can_block_compress(wgpu_renderer, parameter) {
+        return None
+    }
+
+    match parameter.format {
+        BitmapFormat::A8R8G8B8
+        | BitmapFormat::X8R8G8B8
+        | BitmapFormat::A8R8G8B8SRGB
+        | BitmapFormat::X8R8G8B8SRGB
+        | BitmapFormat::A8
+        | BitmapFormat::Y8
+        | BitmapFormat::AY8
+        | BitmapFormat::A8Y8 => wgpu_renderer.texture_recompression.map(Recompression::Texture),
+        BitmapFormat::P8 if wgpu_renderer.two_channel_normal_maps && parameter.is_opaque_p8() => Some(Recompression::TwoChannelNormalMap),
+        _ => None
+    }
+}
+
+/// Encode R8G8B8A8 pixels with `mipmap_count` mipmaps to a block-compressed format.
+fn recompress(
+    recompression: Recompression,
+    parameter: &AddBitmapBitmapParameter,
+    mipmap_count: u32,
+    bytes: &[u8]
+) -> Option<(BitmapFormat, TextureFormat, Vec<u8>)> {
+    let opaque = matches!(parameter.format, BitmapFormat::X8R8G8B8 | BitmapFormat::X8R8G8B8SRGB);
+    let pixels: Vec<u8> = bytes
+        .chunks_exact(4)
+        .flat_map(|p| [p[0], p[1], p[2], if opaque { 0xFF } else { p[3] }])
+        .collect();
+
+    let (bitmap_format, compressed_format) = match recompression {
+        Recompression::Texture(TextureRecompression::Fast) if pixels.chunks_exact(4).all(|p| p[3] == 0xFF) => (BitmapFormat::DXT1, TextureFormat::Bc1RgbaUnorm),
+        Recompression::Texture(TextureRecompression::Fast) => (BitmapFormat::DXT5, TextureFormat::Bc3RgbaUnorm),
+        Recompression::Texture(TextureRecompression::HighQuality) => (BitmapFormat::BC7, TextureFormat::Bc7RgbaUnorm),
+        Recompression::TwoChannelNormalMap => (BitmapFormat::BC5, TextureFormat::Bc5RgUnorm)
+    };
+
+    let mipmap_type = match parameter.bitmap_type {
+        BitmapType::Cubemap => MipmapType::Cubemap,
+        BitmapType::Dim2D => MipmapType::TwoDimensional,
+        BitmapType::Dim3D { depth } => MipmapType::ThreeDimensional(NonZeroUsize::new(depth as usize)?)
+    };
+
+    let faces = MipmapFaceIterator::new(
+        NonZeroUsize::new(parameter.resolution.width as usize)?,
+        NonZeroUsize::new(parameter.resolution.height as usize)?,
+        mipmap_type,
+        NonZeroUsize::new(1).unwrap(),
+        Some(mipmap_count as usize)
+    );
+
+    let mut data = Vec::new();
+    for face in faces {
+        let start = face.block_offset * 4;
+        let end = start + face.block_count * 4;
+        data.extend(encode_r8g8b8a8_to_block_compressed(bitmap_format, face.width, face.height, pixels.get(start..end)?)?);
+    }
+
+    Some((bitmap_format, compressed_format, data))
+}
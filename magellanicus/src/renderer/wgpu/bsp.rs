@@ -0,0 +1,117 @@
+use crate::error::MResult;
+use crate::renderer::wgpu::vertex::{WgpuModelVertex, WgpuModelVertexTextureCoords};
+use crate::renderer::wgpu::{error_scope, WgpuRenderer};
+use crate::renderer::{AddBSPParameter, AddBSPParameterLightmapMaterial, DefaultType, Renderer};
+use std::collections::BTreeMap;
+use std::format;
+use std::vec::Vec;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, Buffer, BufferUsages, Sampler, TextureView};
+
+pub struct WgpuBSPData {
+    pub lightmap_bind_groups: BTreeMap<usize, BindGroup>,
+    pub null_lightmap: BindGroup
+}
+
+impl WgpuBSPData {
+    pub fn new(renderer: &mut Renderer<WgpuRenderer>, param: &AddBSPParameter) -> MResult<Self> {
+        let wgpu_renderer = &renderer.renderer;
+        let make_bind_group = |label: &str, sampler: &Sampler, view: &TextureView| wgpu_renderer.device.create_bind_group(&BindGroupDescriptor {
+            label: Some(label),
+            layout: &wgpu_renderer.bind_group_layouts.lightmap,
+            entries: &[
+                BindGroupEntry { binding: 0, resource: BindingResource::Sampler(sampler) },
+                BindGroupEntry { binding: 1, resource: BindingResource::TextureView(view) }
+            ]
+        });
+
+        error_scope(&wgpu_renderer.device, || {
+            let mut lightmap_bind_groups = BTreeMap::new();
+            if let Some(n) = &param.lightmap_bitmap {
+                let bitmap = renderer
+                    .bitmaps
+                    .get(n.as_str())
+                    .unwrap();
+
+                for i in param.lightmap_sets.iter().filter_map(|b| b.lightmap_index) {
+                    if lightmap_bind_groups.contains_key(&i) {
+                        continue;
+                    }
+
+                    let bind_group = make_bind_group("lightmap", &wgpu_renderer.lightmap_sampler, &bitmap.bitmaps[i].backend.view);
+                    lightmap_bind_groups.insert(i, bind_group);
+                }
+            }
+
+            let null_lightmap = make_bind_group(
+                "null lightmap",
+                &wgpu_renderer.default_2d_sampler,
+                &renderer.get_default_2d(DefaultType::White).backend.view
+            );
+
+            Self { lightmap_bind_groups, null_lightmap }
+        })
+    }
+}
+
+pub struct WgpuBSPGeometryData {
+    pub vertex_buffer: Buffer,
+    pub texture_coords_buffer: Buffer,
+    pub lightmap_texture_coords_buffer: Option<Buffer>,
+    pub index_buffer: Buffer,
+    pub index_count: u32
+}
+
+impl WgpuBSPGeometryData {
+    pub fn new(renderer: &mut Renderer<WgpuRenderer>, _param: &AddBSPParameter, material: &AddBSPParameterLightmapMaterial, lightmap_index: Option<usize>, name: &str) -> MResult<Self> {
+        let device = &renderer.renderer.device;
+        let make_buffer = |label: &str, contents: &[u8], usage: BufferUsages| device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(label),
+            contents,
+            usage
+        });
+
+        error_scope(device, || {
+            let vertices: Vec<WgpuModelVertex> = material.shader_vertices.iter().map(|v| {
+                WgpuModelVertex {
+                    position: v.position,
+                    normal: v.normal,
+                    binormal: v.binormal,
+                    tangent: v.tangent,
+                }
+            }).collect();
+            let vertex_buffer = make_buffer(&format!("{name} vertices"), bytemuck::cast_slice(&vertices), BufferUsages::VERTEX);
+
+            let texture_coords: Vec<WgpuModelVertexTextureCoords> = material.shader_vertices.iter().map(|v| {
+                WgpuModelVertexTextureCoords {
+                    texture_coords: v.texture_coords
+                }
+            }).collect();
+            let texture_coords_buffer = make_buffer(&format!("{name} texture coordinates"), bytemuck::cast_slice(&texture_coords), BufferUsages::VERTEX);
+
+            let lightmap_texture_coords_buffer = material
+                .lightmap_vertices
+                .as_ref()
+                .and_then(|f| lightmap_index.is_some().then_some(f))
+                .map(|v| {
+                    let texture_coords: Vec<WgpuModelVertexTextureCoords> = v.iter().map(|v| {
+                        WgpuModelVertexTextureCoords {
+                            texture_coords: v.lightmap_texture_coords
+                        }
+                    }).collect();
+                    make_buffer(&format!("{name} lightmap texture coordinates"), bytemuck::cast_slice(&texture_coords), BufferUsages::VERTEX)
+                });
+
+            let indices: Vec<u16> = material
+                .surfaces
+                .iter()
+                .flat_map(|t| t.indices.iter())
+                .copied()
+                .collect();
+            let index_count = indices.len() as u32;
+            let index_buffer = make_buffer(&format!("{name} indices"), bytemuck::cast_slice(&indices), BufferUsages::INDEX);
+
+            WgpuBSPGeometryData { vertex_buffer, texture_coords_buffer, lightmap_texture_coords_buffer, index_buffer, index_count }
+        })
+    }
+}
@@ -0,0 +1,62 @@
+mod simple_shader;
+mod shader_environment;
+
+use crate::error::MResult;
+use crate::renderer::wgpu::material::shader_environment::WgpuShaderEnvironmentMaterial;
+use crate::renderer::wgpu::material::simple_shader::WgpuSimpleShaderMaterial;
+use crate::renderer::wgpu::WgpuRenderer;
+use crate::renderer::{AddShaderData, AddShaderParameter, Renderer};
+use std::sync::Arc;
+use wgpu::RenderPass;
+
+/// Material shader data
+///
+/// Vertex buffers are bound like this:
+///
+/// - slot 0 is vertex data, defined as [`WgpuModelVertex`](crate::renderer::wgpu::vertex::WgpuModelVertex)
+/// - slot 1 is texture coordinates, defined as [`WgpuModelVertexTextureCoords`](crate::renderer::wgpu::vertex::WgpuModelVertexTextureCoords)
+/// - slot 2 is lightmap texture coordinates, defined as [`WgpuModelVertexTextureCoords`](crate::renderer::wgpu::vertex::WgpuModelVertexTextureCoords)
+///
+/// Bind groups 0-2 are described in [`WgpuBindGroupLayouts`](crate::renderer::wgpu::pipeline::WgpuBindGroupLayouts).
+/// Anything on group 3 is shader-specific.
+pub struct WgpuMaterialShaderData {
+    pub pipeline_data: Arc<dyn WgpuMaterial>
+}
+
+impl WgpuMaterialShaderData {
+    pub fn new_from_parameters(renderer: &mut Renderer<WgpuRenderer>, shader: AddShaderParameter) -> MResult<Self> {
+        match shader.data {
+            AddShaderData::BasicShader(shader) => {
+                let shader = Arc::new(WgpuSimpleShaderMaterial::new(renderer, shader)?);
+                Ok(Self { pipeline_data: shader })
+            }
+            AddShaderData::ShaderEnvironment(shader) => {
+                let shader = Arc::new(WgpuShaderEnvironmentMaterial::new(renderer, shader)?);
+                Ok(Self { pipeline_data: shader })
+            }
+        }
+    }
+}
+
+pub trait WgpuMaterial: Send + Sync + 'static {
+    /// Record draw commands.
+    ///
+    /// All vertex buffers (vertices, texture coords, lightmap texture coords), the index buffer, and bind groups 0-2
+    /// will be set before this is called.
+    fn draw<'a>(
+        &'a self,
+        renderer: &Renderer<WgpuRenderer>,
+        index_count: u32,
+        repeat_shader: bool,
+        pass: &mut RenderPass<'a>
+    );
+
+    /// Return `true` if the material is transparent.
+    ///
+    /// If so, it needs to be rendered back-to-front.
+    ///
+    /// Default: `false`
+    fn is_transparent(&self) -> bool {
+        false
+    }
+}
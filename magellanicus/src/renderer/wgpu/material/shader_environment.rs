@@ -0,0 +1,112 @@
+use crate::error::MResult;
+use crate::renderer::wgpu::vertex::WgpuShaderEnvironmentData;
+use crate::renderer::wgpu::{error_scope, WgpuMaterial, WgpuPipelineType, WgpuRenderer};
+use crate::renderer::{AddShaderEnvironmentShaderData, DefaultType, Renderer, SequenceFrame};
+use std::sync::Arc;
+use std::vec;
+use std::vec::Vec;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, BufferUsages, RenderPass, RenderPipeline, TextureFormat};
+
+pub struct WgpuShaderEnvironmentMaterial {
+    pipeline: Arc<RenderPipeline>,
+
+    /// One bind group for each frame of the base map's sequence
+    bind_groups: Vec<BindGroup>,
+    base_map_frame: SequenceFrame
+}
+
+impl WgpuShaderEnvironmentMaterial {
+    pub fn new(renderer: &mut Renderer<WgpuRenderer>, add_shader_parameter: AddShaderEnvironmentShaderData) -> MResult<Self> {
+        let base_map_indices: Vec<usize> = match add_shader_parameter.base_map.as_ref() {
+            Some(b) => {
+                let bitmap = &renderer.bitmaps[b.as_str()];
+                let frame_count = bitmap.sequences[add_shader_parameter.base_map_sequence].frame_count();
+                (0..frame_count)
+                    .map(|f| bitmap.get_frame(add_shader_parameter.base_map_sequence, f).unwrap().0)
+                    .collect()
+            },
+            None => vec![0]
+        };
+
+        let primary_detail_map = &renderer.get_or_default_2d(&add_shader_parameter.primary_detail_map, 0, DefaultType::Gray).backend;
+        let secondary_detail_map = &renderer.get_or_default_2d(&add_shader_parameter.secondary_detail_map, 0, DefaultType::Gray).backend;
+        let micro_detail_map = &renderer.get_or_default_2d(&add_shader_parameter.micro_detail_map, 0, DefaultType::Gray).backend;
+        let bump_map = &renderer.get_or_default_2d(&add_shader_parameter.bump_map, 0, DefaultType::Vector).backend;
+        let cubemap = &renderer.get_or_default_cubemap(&add_shader_parameter.reflection_cube_map, 0, DefaultType::Null).backend;
+
+        let wgpu_renderer = &renderer.renderer;
+        let pipeline = wgpu_renderer.pipelines[&WgpuPipelineType::ShaderEnvironment].clone();
+
+        let uniform = WgpuShaderEnvironmentData {
+            primary_detail_map_scale: add_shader_parameter.primary_detail_map_scale,
+            secondary_detail_map_scale: add_shader_parameter.secondary_detail_map_scale,
+            bump_map_scale: add_shader_parameter.bump_map_scale,
+            micro_detail_map_scale: add_shader_parameter.micro_detail_map_scale,
+            flags: {
+                let mut flags = 0;
+                flags |= add_shader_parameter.alpha_tested as u32;
+                flags |= (add_shader_parameter.bump_map_is_specular_mask as u32) << 1;
+                flags |= ((bump_map.texture.format() == TextureFormat::Bc5RgUnorm) as u32) << 2;
+                flags
+            },
+            shader_environment_type: add_shader_parameter.shader_environment_type as u32,
+            detail_map_function: add_shader_parameter.detail_map_function as u32,
+            micro_detail_map_function: add_shader_parameter.micro_detail_map_function as u32,
+            parallel_color: wgpu_renderer.to_output_color([add_shader_parameter.parallel_color[0], add_shader_parameter.parallel_color[1], add_shader_parameter.parallel_color[2], add_shader_parameter.parallel_brightness]),
+            perpendicular_color: wgpu_renderer.to_output_color([add_shader_parameter.perpendicular_color[0], add_shader_parameter.perpendicular_color[1], add_shader_parameter.perpendicular_color[2], add_shader_parameter.perpendicular_brightness]),
+        };
+
+        let uniform_buffer = error_scope(&wgpu_renderer.device, || wgpu_renderer.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("shader_environment data"),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: BufferUsages::UNIFORM
+        }))?;
+
+        let mut bind_groups = Vec::with_capacity(base_map_indices.len());
+        for base_map_index in base_map_indices {
+            let base_map = &renderer.get_or_default_2d(&add_shader_parameter.base_map, base_map_index, DefaultType::White).backend;
+            bind_groups.push(error_scope(&wgpu_renderer.device, || wgpu_renderer.device.create_bind_group(&BindGroupDescriptor {
+                label: Some("shader_environment"),
+                layout: &wgpu_renderer.bind_group_layouts.shader_environment,
+                entries: &[
+                    BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                    BindGroupEntry { binding: 1, resource: BindingResource::Sampler(&base_map.sampler) },
+                    BindGroupEntry { binding: 2, resource: BindingResource::TextureView(&base_map.view) },
+                    BindGroupEntry { binding: 3, resource: BindingResource::TextureView(&primary_detail_map.view) },
+                    BindGroupEntry { binding: 4, resource: BindingResource::TextureView(&secondary_detail_map.view) },
+                    BindGroupEntry { binding: 5, resource: BindingResource::TextureView(&micro_detail_map.view) },
+                    BindGroupEntry { binding: 6, resource: BindingResource::TextureView(&bump_map.view) },
+                    BindGroupEntry { binding: 7, resource: BindingResource::TextureView(&cubemap.view) },
+                    BindGroupEntry { binding: 8, resource: BindingResource::Sampler(&primary_detail_map.sampler) },
+                    BindGroupEntry { binding: 9, resource: BindingResource::Sampler(&secondary_detail_map.sampler) },
+                    BindGroupEntry { binding: 10, resource: BindingResource::Sampler(&micro_detail_map.sampler) },
+                    BindGroupEntry { binding: 11, resource: BindingResource::Sampler(&bump_map.sampler) },
+                    BindGroupEntry { binding: 12, resource: BindingResource::Sampler(&cubemap.sampler) },
+                ]
+            }))?);
+        }
+
+        Ok(Self { pipeline, bind_groups, base_map_frame: add_shader_parameter.base_map_frame })
+    }
+}
+
+impl WgpuMaterial for WgpuShaderEnvironmentMaterial {
+    fn draw<'a>(
+        &'a self,
+        renderer: &Renderer<WgpuRenderer>,
+        index_count: u32,
+        repeat_shader: bool,
+        pass: &mut RenderPass<'a>
+    ) {
+        if !repeat_shader {
+            let frame = self.base_map_frame
+                .resolve(self.bind_groups.len(), renderer.get_time())
+                .unwrap_or(0);
+
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(3, &self.bind_groups[frame], &[]);
+        }
+        pass.draw_indexed(0..index_count, 0, 0..1);
+    }
+}
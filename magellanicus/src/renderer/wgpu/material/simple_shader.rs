@@ -0,0 +1,84 @@
+use crate::error::MResult;
+use crate::renderer::wgpu::{error_scope, WgpuMaterial, WgpuPipelineType, WgpuRenderer};
+use crate::renderer::{AddShaderBasicShaderData, DefaultType, LogSeverity, Renderer, SequenceFrame};
+use std::format;
+use std::sync::Arc;
+use std::vec;
+use std::vec::Vec;
+use wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, RenderPass, RenderPipeline, TextureDimension};
+
+pub struct WgpuSimpleShaderMaterial {
+    pipeline: Arc<RenderPipeline>,
+
+    /// One bind group for each frame of the sequence
+    bind_groups: Vec<BindGroup>,
+    frame: SequenceFrame
+}
+
+impl WgpuSimpleShaderMaterial {
+    pub fn new(renderer: &mut Renderer<WgpuRenderer>, add_shader_parameter: AddShaderBasicShaderData) -> MResult<Self> {
+        let bitmap_indices: Vec<usize> = match add_shader_parameter.bitmap.as_ref() {
+            Some(b) => {
+                let bitmap = &renderer.bitmaps[b.as_str()];
+                let frame_count = bitmap.sequences[add_shader_parameter.sequence].frame_count();
+                (0..frame_count)
+                    .map(|f| bitmap.get_frame(add_shader_parameter.sequence, f).unwrap().0)
+                    .collect()
+            },
+            None => vec![0]
+        };
+
+        let pipeline = renderer.renderer.pipelines[&WgpuPipelineType::SimpleTexture].clone();
+
+        let mut bind_groups = Vec::with_capacity(bitmap_indices.len());
+        for bitmap_index in bitmap_indices {
+            let diffuse = &renderer.get_or_default_2d(&add_shader_parameter.bitmap, bitmap_index, DefaultType::White).backend;
+
+            if diffuse.texture.depth_or_array_layers() != 1 || diffuse.texture.dimension() != TextureDimension::D2 {
+                renderer.renderer.log(LogSeverity::Warning, &format!("Can't display {} in a simple shader material. Using fallback...", add_shader_parameter.bitmap.as_ref().unwrap()));
+                return WgpuSimpleShaderMaterial::new(renderer, AddShaderBasicShaderData {
+                    bitmap: None,
+                    sequence: 0,
+                    frame: SequenceFrame::default(),
+                    ..add_shader_parameter
+                })
+            }
+
+            let wgpu_renderer = &renderer.renderer;
+            bind_groups.push(error_scope(&wgpu_renderer.device, || wgpu_renderer.device.create_bind_group(&BindGroupDescriptor {
+                label: Some("simple shader"),
+                layout: &wgpu_renderer.bind_group_layouts.simple_texture,
+                entries: &[
+                    BindGroupEntry { binding: 0, resource: BindingResource::Sampler(&diffuse.sampler) },
+                    BindGroupEntry { binding: 1, resource: BindingResource::TextureView(&diffuse.view) }
+                ]
+            }))?);
+        }
+
+        Ok(Self { pipeline, bind_groups, frame: add_shader_parameter.frame })
+    }
+}
+
+impl WgpuMaterial for WgpuSimpleShaderMaterial {
+    fn draw<'a>(
+        &'a self,
+        renderer: &Renderer<WgpuRenderer>,
+        index_count: u32,
+        repeat_shader: bool,
+        pass: &mut RenderPass<'a>
+    ) {
+        if !repeat_shader {
+            let frame = self.frame
+                .resolve(self.bind_groups.len(), renderer.get_time())
+                .unwrap_or(0);
+
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(3, &self.bind_groups[frame], &[]);
+        }
+        pass.draw_indexed(0..index_count, 0, 0..1);
+    }
+
+    fn is_transparent(&self) -> bool {
+        true
+    }
+}
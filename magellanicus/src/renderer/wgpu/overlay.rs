@@ -0,0 +1,119 @@
+use crate::error::{Error, MResult, ResourceKind};
+use crate::renderer::data::{OverlayQuad, OverlayTexture};
+use crate::renderer::wgpu::vertex::{WgpuOverlayData, WgpuOverlayVertex};
+use crate::renderer::wgpu::{WgpuPipelineType, WgpuRenderer};
+use crate::renderer::Renderer;
+use std::format;
+use std::ops::Range;
+use std::sync::Arc;
+use std::vec::Vec;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, Buffer, BufferUsages, RenderPass, RenderPipeline};
+
+/// Overlay quads of a viewport, uploaded and ready to be drawn.
+pub struct WgpuOverlay {
+    vertices: Option<Buffer>,
+
+    /// Pipeline, bind group, and vertices of each run of quads that are drawn together.
+    batches: Vec<(Arc<RenderPipeline>, BindGroup, Range<u32>)>
+}
+
+impl WgpuOverlay {
+    /// Upload all queued overlay quads for a viewport that is `width` x `height` pixels.
+    ///
+    /// The HUD canvas is scaled uniformly to fit the viewport and centered inside of it. Consecutive quads that share a
+    /// texture, blend mode, and tint are drawn together.
+    pub fn new(renderer: &Renderer<WgpuRenderer>, overlay: &[OverlayQuad], width: f32, height: f32) -> MResult<Self> {
+        if overlay.is_empty() {
+            return Ok(Self { vertices: None, batches: Vec::new() })
+        }
+
+        let wgpu_renderer = &renderer.renderer;
+        let device = &wgpu_renderer.device;
+
+        let [canvas_width, canvas_height] = renderer.hud_canvas;
+        let scale = (width / canvas_width).min(height / canvas_height);
+        let offset_x = (width - canvas_width * scale) / 2.0;
+        let offset_y = (height - canvas_height * scale) / 2.0;
+
+        let vertices: Vec<WgpuOverlayVertex> = overlay
+            .iter()
+            .flat_map(|quad| {
+                let [x, y] = quad.position;
+                let [w, h] = quad.size;
+                WgpuOverlayVertex::quad(
+                    (offset_x + x * scale) / width,
+                    (offset_y + y * scale) / height,
+                    w * scale / width,
+                    h * scale / height,
+                    quad.texture_coords
+                )
+            })
+            .collect();
+
+        let vertices = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("overlay vertices"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: BufferUsages::VERTEX
+        });
+
+        let mut batches = Vec::new();
+        let mut first_vertex = 0;
+        for batch in overlay.chunk_by(OverlayQuad::batches_with) {
+            let quad = &batch[0];
+            let bitmap = match &quad.texture {
+                OverlayTexture::Bitmap { path, bitmap_index } => renderer
+                    .bitmaps
+                    .get(path)
+                    .and_then(|b| b.bitmaps.get(*bitmap_index))
+                    .ok_or_else(|| Error::missing_dependency(ResourceKind::Bitmap, &**path, format!("Overlay refers to bitmap #{bitmap_index} of {path} which is no longer loaded")))?,
+                OverlayTexture::Font { path } => &renderer
+                    .fonts
+                    .get(path)
+                    .ok_or_else(|| Error::missing_dependency(ResourceKind::Font, &**path, format!("Overlay refers to font {path} which is no longer loaded")))?
+                    .bitmap
+            };
+
+            let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("overlay data"),
+                contents: bytemuck::bytes_of(&WgpuOverlayData {
+                    tint: wgpu_renderer.to_output_color(quad.tint),
+                    blend_mode: quad.blend_mode as u32,
+                    _padding: [0; 3]
+                }),
+                usage: BufferUsages::UNIFORM
+            });
+
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("overlay"),
+                layout: &wgpu_renderer.bind_group_layouts.overlay,
+                entries: &[
+                    BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                    BindGroupEntry { binding: 1, resource: BindingResource::Sampler(&wgpu_renderer.overlay_sampler) },
+                    BindGroupEntry { binding: 2, resource: BindingResource::TextureView(&bitmap.backend.view) }
+                ]
+            });
+
+            let pipeline = wgpu_renderer.pipelines[&WgpuPipelineType::overlay(quad.blend_mode)].clone();
+            let vertex_count = (batch.len() * 6) as u32;
+            batches.push((pipeline, bind_group, first_vertex..first_vertex + vertex_count));
+            first_vertex += vertex_count;
+        }
+
+        Ok(Self { vertices: Some(vertices), batches })
+    }
+
+    /// Draw the overlay in the current viewport.
+    pub fn draw<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        let Some(vertices) = self.vertices.as_ref() else {
+            return
+        };
+
+        pass.set_vertex_buffer(0, vertices.slice(..));
+        for (pipeline, bind_group, vertices) in &self.batches {
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(vertices.clone(), 0..1);
+        }
+    }
+}
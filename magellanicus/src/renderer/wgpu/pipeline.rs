@@ -0,0 +1,332 @@
+use crate::renderer::wgpu::vertex::{WgpuModelVertex, WgpuModelVertexTextureCoords, WgpuOverlayVertex};
+use crate::renderer::OverlayBlendMode;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::vec;
+use std::vec::Vec;
+use wgpu::{BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendComponent, BlendFactor, BlendOperation, BlendState, BufferBindingType, ColorTargetState, ColorWrites, CompareFunction, DepthStencilState, Device, Face, FragmentState, FrontFace, MultisampleState, PipelineLayoutDescriptor, PrimitiveState, RenderPipeline, RenderPipelineDescriptor, SamplerBindingType, ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, TextureSampleType, TextureViewDimension, VertexBufferLayout, VertexState};
+
+pub const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+
+/// Bind group layouts shared between pipelines.
+///
+/// Material pipelines are bound like this, matching the descriptor sets of the Vulkan renderer:
+///
+/// - group 0 is ModelData, defined as [`WgpuModelData`](crate::renderer::wgpu::vertex::WgpuModelData)
+/// - group 1, binding 0 is a sampler for lightmaps
+/// - group 1, binding 1 is a texture for lightmaps
+/// - group 2 is FogData, defined as [`WgpuFogData`](crate::renderer::wgpu::vertex::WgpuFogData)
+///
+/// Anything on group 3 is shader-specific.
+pub struct WgpuBindGroupLayouts {
+    pub model: BindGroupLayout,
+    pub lightmap: BindGroupLayout,
+    pub fog: BindGroupLayout,
+    pub shader_environment: BindGroupLayout,
+    pub simple_texture: BindGroupLayout,
+    pub color_box: BindGroupLayout,
+    pub overlay: BindGroupLayout,
+    pub blit: BindGroupLayout
+}
+
+impl WgpuBindGroupLayouts {
+    pub fn new(device: &Device) -> Self {
+        let layout = |label: &str, entries: &[BindGroupLayoutEntry]| device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries
+        });
+
+        let shader_environment_entries: Vec<BindGroupLayoutEntry> = [uniform_entry(0, ShaderStages::FRAGMENT), sampler_entry(1)]
+            .into_iter()
+            .chain((2..=6).map(|b| texture_entry(b, TextureViewDimension::D2)))
+            .chain([texture_entry(7, TextureViewDimension::Cube)])
+            .chain((8..=12).map(sampler_entry))
+            .collect();
+
+        Self {
+            model: layout("model data", &[uniform_entry(0, ShaderStages::VERTEX_FRAGMENT)]),
+            lightmap: layout("lightmap", &[sampler_entry(0), texture_entry(1, TextureViewDimension::D2)]),
+            fog: layout("fog data", &[uniform_entry(0, ShaderStages::FRAGMENT)]),
+            shader_environment: layout("shader_environment", &shader_environment_entries),
+            simple_texture: layout("simple texture", &[sampler_entry(0), texture_entry(1, TextureViewDimension::D2)]),
+            color_box: layout("color box", &[uniform_entry(0, ShaderStages::FRAGMENT)]),
+            overlay: layout("overlay", &[uniform_entry(0, ShaderStages::FRAGMENT), sampler_entry(1), texture_entry(2, TextureViewDimension::D2)]),
+            blit: layout("blit", &[
+                uniform_entry(0, ShaderStages::FRAGMENT),
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false
+                    },
+                    count: None
+                }
+            ])
+        }
+    }
+}
+
+fn uniform_entry(binding: u32, visibility: ShaderStages) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None
+        },
+        count: None
+    }
+}
+
+fn sampler_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::FRAGMENT,
+        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+        count: None
+    }
+}
+
+fn texture_entry(binding: u32, view_dimension: TextureViewDimension) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            sample_type: TextureSampleType::Float { filterable: true },
+            view_dimension,
+            multisampled: false
+        },
+        count: None
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WgpuPipelineType {
+    /// Draws a texture.
+    SimpleTexture,
+
+    /// Draw a box of a given color.
+    ColorBox,
+
+    /// shader_environment
+    ShaderEnvironment,
+
+    /// Draws an alpha blended sprite over a viewport.
+    OverlayAlphaBlend,
+
+    /// Draws an additive sprite over a viewport.
+    OverlayAdd,
+
+    /// Draws a multiplied sprite over a viewport.
+    OverlayMultiply,
+
+    /// Copies the color buffer to the output.
+    Blit
+}
+
+impl WgpuPipelineType {
+    pub fn overlay(blend_mode: OverlayBlendMode) -> Self {
+        match blend_mode {
+            OverlayBlendMode::AlphaBlend => Self::OverlayAlphaBlend,
+            OverlayBlendMode::Add => Self::OverlayAdd,
+            OverlayBlendMode::Multiply => Self::OverlayMultiply
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, PartialEq)]
+enum DepthAccess {
+    /// The depth as determined by the vertex must be less than or equal, without writing to the depth buffer.
+    ///
+    /// This is used primarily for transparent shaders.
+    DepthReadOnlyTransparent,
+
+    /// The depth as determined by the vertex must be less than or equal.
+    ///
+    /// This is used if one needs to write to the depth buffer.
+    #[default]
+    DepthWrite,
+
+    /// The depth buffer is completely ignored.
+    ///
+    /// Draw on top of whatever is there.
+    NoDepth,
+
+    /// There is no depth buffer (i.e. drawing to the output).
+    NoDepthBuffer
+}
+
+struct PipelineSettings<'a> {
+    label: &'a str,
+    source: &'static str,
+    bind_group_layouts: &'a [&'a BindGroupLayout],
+    vertex_buffers: Vec<VertexBufferLayout<'static>>,
+    depth_access: DepthAccess,
+    blend: Option<BlendState>,
+    cull_mode: Option<Face>,
+    samples: u32,
+    format: TextureFormat
+}
+
+fn load_pipeline(device: &Device, settings: PipelineSettings) -> Arc<RenderPipeline> {
+    let module = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some(settings.label),
+        source: ShaderSource::Wgsl(Cow::Borrowed(settings.source))
+    });
+
+    let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some(settings.label),
+        bind_group_layouts: settings.bind_group_layouts,
+        push_constant_ranges: &[]
+    });
+
+    let depth_stencil = match settings.depth_access {
+        DepthAccess::NoDepthBuffer => None,
+        access => Some(DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: access == DepthAccess::DepthWrite,
+            depth_compare: match access {
+                DepthAccess::NoDepth => CompareFunction::Always,
+                _ => CompareFunction::LessEqual
+            },
+            stencil: Default::default(),
+            bias: Default::default()
+        })
+    };
+
+    Arc::new(device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(settings.label),
+        layout: Some(&layout),
+        vertex: VertexState {
+            module: &module,
+            entry_point: "vs_main",
+            buffers: &settings.vertex_buffers
+        },
+        primitive: PrimitiveState {
+            front_face: FrontFace::Cw,
+            cull_mode: settings.cull_mode,
+            ..Default::default()
+        },
+        depth_stencil,
+        multisample: MultisampleState {
+            count: settings.samples,
+            ..Default::default()
+        },
+        fragment: Some(FragmentState {
+            module: &module,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format: settings.format,
+                blend: settings.blend,
+                write_mask: ColorWrites::ALL
+            })]
+        }),
+        multiview: None
+    }))
+}
+
+pub fn load_all_pipelines(
+    device: &Device,
+    layouts: &WgpuBindGroupLayouts,
+    samples: u32,
+    color_format: TextureFormat,
+    output_format: TextureFormat
+) -> BTreeMap<WgpuPipelineType, Arc<RenderPipeline>> {
+    let material_vertex_buffers = || vec![
+        WgpuModelVertex::layout(),
+        WgpuModelVertexTextureCoords::layout(),
+        WgpuModelVertexTextureCoords::lightmap_layout()
+    ];
+
+    let mut pipelines = BTreeMap::new();
+
+    pipelines.insert(WgpuPipelineType::ShaderEnvironment, load_pipeline(device, PipelineSettings {
+        label: "shader_environment",
+        source: concat!(
+            include_str!("pipeline/include/material.wgsl"),
+            include_str!("pipeline/include/blend.wgsl"),
+            include_str!("pipeline/shader_environment.wgsl")
+        ),
+        bind_group_layouts: &[&layouts.model, &layouts.lightmap, &layouts.fog, &layouts.shader_environment],
+        vertex_buffers: material_vertex_buffers(),
+        depth_access: DepthAccess::DepthWrite,
+        blend: None,
+        cull_mode: Some(Face::Back),
+        samples,
+        format: color_format
+    }));
+
+    pipelines.insert(WgpuPipelineType::SimpleTexture, load_pipeline(device, PipelineSettings {
+        label: "simple texture",
+        source: concat!(
+            include_str!("pipeline/include/material.wgsl"),
+            include_str!("pipeline/simple_texture.wgsl")
+        ),
+        bind_group_layouts: &[&layouts.model, &layouts.lightmap, &layouts.fog, &layouts.simple_texture],
+        vertex_buffers: material_vertex_buffers(),
+        depth_access: DepthAccess::DepthReadOnlyTransparent,
+        blend: Some(BlendState {
+            color: BlendComponent { src_factor: BlendFactor::One, dst_factor: BlendFactor::One, operation: BlendOperation::Add },
+            alpha: BlendComponent { src_factor: BlendFactor::One, dst_factor: BlendFactor::One, operation: BlendOperation::Add }
+        }),
+        cull_mode: Some(Face::Back),
+        samples,
+        format: color_format
+    }));
+
+    pipelines.insert(WgpuPipelineType::ColorBox, load_pipeline(device, PipelineSettings {
+        label: "color box",
+        source: include_str!("pipeline/color_box.wgsl"),
+        bind_group_layouts: &[&layouts.color_box],
+        vertex_buffers: vec![WgpuOverlayVertex::layout()],
+        depth_access: DepthAccess::NoDepth,
+        blend: Some(BlendState::ALPHA_BLENDING),
+        cull_mode: None,
+        samples,
+        format: color_format
+    }));
+
+    for blend_mode in [OverlayBlendMode::AlphaBlend, OverlayBlendMode::Add, OverlayBlendMode::Multiply] {
+        let blend = match blend_mode {
+            OverlayBlendMode::AlphaBlend => BlendState::ALPHA_BLENDING,
+            OverlayBlendMode::Add => BlendState {
+                color: BlendComponent { src_factor: BlendFactor::SrcAlpha, dst_factor: BlendFactor::One, operation: BlendOperation::Add },
+                alpha: BlendComponent { src_factor: BlendFactor::Zero, dst_factor: BlendFactor::One, operation: BlendOperation::Add }
+            },
+            OverlayBlendMode::Multiply => BlendState {
+                color: BlendComponent { src_factor: BlendFactor::Dst, dst_factor: BlendFactor::Zero, operation: BlendOperation::Add },
+                alpha: BlendComponent { src_factor: BlendFactor::Zero, dst_factor: BlendFactor::One, operation: BlendOperation::Add }
+            }
+        };
+
+        pipelines.insert(WgpuPipelineType::overlay(blend_mode), load_pipeline(device, PipelineSettings {
+            label: "overlay",
+            source: include_str!("pipeline/overlay.wgsl"),
+            bind_group_layouts: &[&layouts.overlay],
+            vertex_buffers: vec![WgpuOverlayVertex::layout()],
+            depth_access: DepthAccess::NoDepth,
+            blend: Some(blend),
+            cull_mode: None,
+            samples,
+            format: color_format
+        }));
+    }
+
+    pipelines.insert(WgpuPipelineType::Blit, load_pipeline(device, PipelineSettings {
+        label: "blit",
+        source: include_str!("pipeline/blit.wgsl"),
+        bind_group_layouts: &[&layouts.blit],
+        vertex_buffers: Vec::new(),
+        depth_access: DepthAccess::NoDepthBuffer,
+        blend: None,
+        cull_mode: None,
+        samples: 1,
+        format: output_format
+    }));
+
+    pipelines
+}
@@ -0,0 +1,33 @@
+// Copies the color buffer to the output (the surface or the headless output image).
+
+struct BlitData {
+    decode_srgb: u32,
+}
+
+@group(0) @binding(0) var<uniform> blit_data: BlitData;
+@group(0) @binding(1) var color_buffer: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle that covers the whole output
+    let position = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(position * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    var color = textureLoad(color_buffer, vec2<i32>(position.xy), 0).rgb;
+
+    // The output encodes to sRGB when written to, but the color buffer is already in gamma space.
+    if blit_data.decode_srgb != 0u {
+        color = srgb_to_linear(color);
+    }
+
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,15 @@
+struct InputColor {
+    color: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> color: InputColor;
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(position.x * 2.0 - 1.0, 1.0 - position.y * 2.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return color.color;
+}
@@ -0,0 +1,18 @@
+fn alpha_blend(base: vec4<f32>, other: vec4<f32>) -> vec4<f32> {
+    return mix(base, other, vec4<f32>(other.a));
+}
+
+fn double_biased_multiply(base: vec3<f32>, other: vec3<f32>) -> vec3<f32> {
+    let multiplied = base * (other * 2.0);
+    return clamp(multiplied, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn double_biased_add(base: vec3<f32>, other: vec3<f32>) -> vec3<f32> {
+    let added = base + (other * 2.0) - vec3<f32>(1.0);
+    return clamp(added, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn multiply(base: vec3<f32>, other: vec3<f32>) -> vec3<f32> {
+    let multiplied = base * other;
+    return clamp(multiplied, vec3<f32>(0.0), vec3<f32>(1.0));
+}
@@ -0,0 +1,36 @@
+// Shared bindings of all material pipelines. This is prepended to each material shader.
+
+struct ModelData {
+    camera: vec3<f32>,
+    offset: vec3<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+}
+
+struct FogData {
+    sky_fog_color: vec4<f32>,
+    sky_fog_from: f32,
+    sky_fog_to: f32,
+    min_opacity: f32,
+    max_opacity: f32,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: ModelData;
+
+@group(1) @binding(0) var lightmap_sampler: sampler;
+@group(1) @binding(1) var lightmap_texture: texture_2d<f32>;
+
+@group(2) @binding(0) var<uniform> sky_fog_data: FogData;
+
+// The projection matrix is the same one the Vulkan renderer uses, where Y points down, so flip it.
+fn transform_position(position: vec3<f32>) -> vec4<f32> {
+    let clip = uniforms.proj * uniforms.view * vec4<f32>(position, 1.0);
+    return vec4<f32>(clip.x, -clip.y, clip.z, clip.w);
+}
+
+fn apply_fog(distance_from_camera: f32, color: vec3<f32>) -> vec3<f32> {
+    let clamped = clamp(distance_from_camera, sky_fog_data.sky_fog_from, sky_fog_data.sky_fog_to);
+    let interpolation = (clamped - sky_fog_data.sky_fog_from) / (sky_fog_data.sky_fog_to - sky_fog_data.sky_fog_from);
+    let fog_density = interpolation * sky_fog_data.max_opacity;
+    return mix(color, sky_fog_data.sky_fog_color.rgb, vec3<f32>(fog_density));
+}
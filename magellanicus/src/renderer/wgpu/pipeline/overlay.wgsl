@@ -0,0 +1,35 @@
+const OVERLAY_BLEND_MODE_MULTIPLY: u32 = 2u;
+
+struct OverlayData {
+    tint: vec4<f32>,
+    blend_mode: u32,
+}
+
+@group(0) @binding(0) var<uniform> overlay_data: OverlayData;
+@group(0) @binding(1) var s: sampler;
+@group(0) @binding(2) var tex: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) texture_coords: vec2<f32>) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = vec4<f32>(position.x * 2.0 - 1.0, 1.0 - position.y * 2.0, 0.0, 1.0);
+    output.tex_coords = texture_coords;
+    return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(tex, s, input.tex_coords) * overlay_data.tint;
+
+    // Multiplying is done by the blender, so fade towards white instead of relying on alpha.
+    if overlay_data.blend_mode == OVERLAY_BLEND_MODE_MULTIPLY {
+        color = vec4<f32>(mix(vec3<f32>(1.0), color.rgb, vec3<f32>(color.a)), color.a);
+    }
+
+    return color;
+}
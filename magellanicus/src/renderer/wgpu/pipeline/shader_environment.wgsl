@@ -0,0 +1,194 @@
+struct ShaderEnvironmentData {
+    primary_detail_map_scale: f32,
+    secondary_detail_map_scale: f32,
+    bump_map_scale: f32,
+    micro_detail_map_scale: f32,
+
+    flags: u32,
+    shader_environment_type: u32,
+    detail_map_function: u32,
+    micro_detail_map_function: u32,
+
+    parallel_color: vec4<f32>, // a = brightness
+    perpendicular_color: vec4<f32>, // a = brightness
+}
+
+const SHADER_ENVIRONMENT_TYPE_NORMAL: u32 = 0u;
+const SHADER_ENVIRONMENT_TYPE_BLENDED_BASE_SPECULAR: u32 = 2u;
+
+const SHADER_ENVIRONMENT_FLAGS_ALPHA_TEST: u32 = 1u;
+const SHADER_ENVIRONMENT_FLAGS_BUMPMAP_ALPHA_SPECULAR_MASK: u32 = 2u;
+const SHADER_ENVIRONMENT_FLAGS_TWO_CHANNEL_BUMP_MAP: u32 = 4u;
+
+@group(3) @binding(0) var<uniform> shader_environment_data: ShaderEnvironmentData;
+@group(3) @binding(1) var base_map_sampler: sampler;
+@group(3) @binding(2) var base_map: texture_2d<f32>;
+@group(3) @binding(3) var primary_detail_map: texture_2d<f32>;
+@group(3) @binding(4) var secondary_detail_map: texture_2d<f32>;
+@group(3) @binding(5) var micro_detail_map: texture_2d<f32>;
+@group(3) @binding(6) var bump_map: texture_2d<f32>;
+@group(3) @binding(7) var cubemap: texture_cube<f32>;
+@group(3) @binding(8) var primary_detail_map_sampler: sampler;
+@group(3) @binding(9) var secondary_detail_map_sampler: sampler;
+@group(3) @binding(10) var micro_detail_map_sampler: sampler;
+@group(3) @binding(11) var bump_map_sampler: sampler;
+@group(3) @binding(12) var cubemap_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) texture_coords: vec2<f32>,
+    @location(2) lightmap_texture_coords: vec2<f32>,
+    @location(4) normal: vec3<f32>,
+    @location(5) binormal: vec3<f32>,
+    @location(6) tangent: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) base_map_texture_coordinates: vec2<f32>,
+    @location(1) lightmap_texture_coordinates: vec2<f32>,
+    @location(2) camera_position: vec3<f32>,
+    @location(3) vertex_position: vec3<f32>,
+    @location(4) normal: vec3<f32>,
+    @location(5) binormal: vec3<f32>,
+    @location(6) tangent: vec3<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var output: VertexOutput;
+    output.vertex_position = input.position + uniforms.offset;
+    output.camera_position = uniforms.camera;
+    output.position = transform_position(output.vertex_position);
+    output.base_map_texture_coordinates = input.texture_coords;
+    output.lightmap_texture_coordinates = input.lightmap_texture_coords;
+    output.normal = input.normal;
+    output.binormal = input.binormal;
+    output.tangent = input.tangent;
+    return output;
+}
+
+fn blend_with_mix_type(color: vec3<f32>, other: vec3<f32>, blend_type: u32) -> vec3<f32> {
+    switch blend_type {
+        case 0u: {
+            return double_biased_multiply(color, other);
+        }
+        case 1u: {
+            return multiply(color, other);
+        }
+        case 2u: {
+            return double_biased_add(color, other);
+        }
+        default: {
+            return vec3<f32>(0.0);
+        }
+    }
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let camera_difference = input.camera_position - input.vertex_position;
+    let distance_from_camera = distance(input.camera_position, input.vertex_position);
+    let texture_coordinates = input.base_map_texture_coordinates;
+    let normal = input.normal;
+
+    // Everything is sampled before discarding, since sampling has to be done in uniform control flow.
+    var base_map_color = textureSample(base_map, base_map_sampler, texture_coordinates);
+    var bump_color = textureSample(bump_map, bump_map_sampler, texture_coordinates * shader_environment_data.bump_map_scale);
+    let primary_detail_map_color = textureSample(
+        primary_detail_map,
+        primary_detail_map_sampler,
+        texture_coordinates * shader_environment_data.primary_detail_map_scale
+    );
+    let secondary_detail_map_color = textureSample(
+        secondary_detail_map,
+        secondary_detail_map_sampler,
+        texture_coordinates * shader_environment_data.secondary_detail_map_scale
+    );
+    let micro_detail_map_color = textureSample(
+        micro_detail_map,
+        micro_detail_map_sampler,
+        texture_coordinates * shader_environment_data.micro_detail_map_scale
+    );
+    let lightmap_color = textureSample(lightmap_texture, lightmap_sampler, input.lightmap_texture_coordinates);
+
+    var bump_vector = bump_color.rgb * 2.0 - 1.0;
+
+    // Two-channel bump maps only store X and Y
+    if (shader_environment_data.flags & SHADER_ENVIRONMENT_FLAGS_TWO_CHANNEL_BUMP_MAP) != 0u {
+        bump_vector.z = sqrt(clamp(1.0 - dot(bump_vector.xy, bump_vector.xy), 0.0, 1.0));
+    }
+    let camera_normal = normalize(camera_difference);
+    let normal_on_camera = dot(normal, camera_normal);
+    let reflection_normal = normalize(2.0 * normal_on_camera * normal - camera_normal);
+    let reflection_color = textureSample(cubemap, cubemap_sampler, reflection_normal + vec3<f32>(bump_vector.xy, 0.0)).xyz;
+
+    // Alpha testing
+    if (shader_environment_data.flags & SHADER_ENVIRONMENT_FLAGS_ALPHA_TEST) != 0u {
+        // TODO: Is it just normal that discards 0-alpha pixels? The alpha is used for blending and specular on other
+        // types, so it makes no sense to test alpha on those types.
+        if shader_environment_data.shader_environment_type == SHADER_ENVIRONMENT_TYPE_NORMAL && base_map_color.a == 0.0 {
+            discard;
+        }
+
+        if bump_color.a <= 0.5 {
+            discard;
+        }
+    }
+    bump_color.a = 1.0;
+
+    var blended_detail: vec4<f32>;
+    switch shader_environment_data.shader_environment_type {
+        // normal
+        case 0u: {
+            blended_detail = mix(secondary_detail_map_color, primary_detail_map_color, vec4<f32>(secondary_detail_map_color.a));
+        }
+        // blended, blended base specular
+        case 1u, 2u: {
+            blended_detail = mix(secondary_detail_map_color, primary_detail_map_color, vec4<f32>(base_map_color.a));
+        }
+        default: {
+            return vec4<f32>(1.0);
+        }
+    }
+
+    // Specular
+    let specular_color = pow(reflection_color, vec3<f32>(8.0));
+    let diffuse_reflection = normal_on_camera * normal_on_camera;
+    let reflect_attenuation = mix(shader_environment_data.parallel_color.a, shader_environment_data.perpendicular_color.a, diffuse_reflection);
+    var specular = mix(shader_environment_data.parallel_color.rgb, shader_environment_data.perpendicular_color.rgb, vec3<f32>(diffuse_reflection));
+    specular = mix(specular_color, reflection_color, specular);
+    specular *= reflect_attenuation;
+
+    var specular_mask: f32;
+    if (shader_environment_data.flags & SHADER_ENVIRONMENT_FLAGS_BUMPMAP_ALPHA_SPECULAR_MASK) != 0u {
+        specular_mask = bump_color.a;
+    }
+    else if shader_environment_data.shader_environment_type == SHADER_ENVIRONMENT_TYPE_BLENDED_BASE_SPECULAR {
+        specular_mask = blended_detail.a;
+    }
+    else {
+        specular_mask = base_map_color.a;
+    }
+    specular *= specular_mask;
+
+    // Specular
+    base_map_color = vec4<f32>(clamp(base_map_color.rgb + specular, vec3<f32>(0.0), vec3<f32>(1.0)), base_map_color.a);
+
+    // Lightmap stage
+    base_map_color = vec4<f32>(base_map_color.rgb * lightmap_color.rgb, base_map_color.a);
+
+    // Detail
+    var scratch_color = blended_detail.rgb;
+    scratch_color = blend_with_mix_type(base_map_color.rgb, scratch_color, shader_environment_data.detail_map_function);
+    scratch_color = blend_with_mix_type(micro_detail_map_color.rgb, scratch_color, shader_environment_data.micro_detail_map_function);
+
+    // Bumpmap
+    let base_shading = dot(bump_vector, vec3<f32>(0.0, 0.0, 1.0));
+    scratch_color *= vec3<f32>(base_shading);
+
+    // Fog stage
+    scratch_color = apply_fog(distance_from_camera, scratch_color);
+
+    return vec4<f32>(scratch_color, 1.0);
+}
@@ -0,0 +1,32 @@
+@group(3) @binding(0) var s: sampler;
+@group(3) @binding(1) var tex: texture_2d<f32>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) texture_coords: vec2<f32>,
+    @location(2) lightmap_texture_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coords: vec2<f32>,
+    @location(1) lightmap_texture_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = transform_position(input.position + uniforms.offset);
+    output.texture_coords = input.texture_coords;
+    output.lightmap_texture_coords = input.lightmap_texture_coords;
+    return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let lightmap_color = textureSample(lightmap_texture, lightmap_sampler, input.lightmap_texture_coords);
+    let color = textureSample(tex, s, input.texture_coords);
+
+    // FIXME: Fog isn't applied, as it messes with additive transparent stuff
+    return vec4<f32>(color.rgb * lightmap_color.rgb, 1.0);
+}
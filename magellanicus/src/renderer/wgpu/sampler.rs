@@ -0,0 +1,54 @@
+use crate::renderer::wgpu::WgpuRenderer;
+use crate::renderer::{BitmapAddressMode, BitmapFilter, BitmapSamplerHints};
+use std::sync::Arc;
+use wgpu::{AddressMode, FilterMode, Sampler, SamplerDescriptor};
+
+/// Highest anisotropy that wgpu accepts.
+pub const MAX_ANISOTROPY: f32 = 16.0;
+
+impl WgpuRenderer {
+    /// Get a sampler matching the given hints, creating it if no bitmap has used them yet.
+    ///
+    /// wgpu has no LOD bias, so [`BitmapSamplerHints::mip_lod_bias`] is ignored.
+    pub(crate) fn get_sampler(&mut self, hints: &BitmapSamplerHints) -> Arc<Sampler> {
+        if let Some((_, sampler)) = self.samplers.iter().find(|(h, _)| h == hints) {
+            return sampler.clone()
+        }
+
+        let [address_mode_u, address_mode_v, address_mode_w] = hints.address_mode.map(|m| match m {
+            BitmapAddressMode::Repeat => AddressMode::Repeat,
+            BitmapAddressMode::MirroredRepeat => AddressMode::MirrorRepeat,
+            BitmapAddressMode::ClampToEdge => AddressMode::ClampToEdge
+        });
+
+        let filter = match hints.filter {
+            BitmapFilter::Point => FilterMode::Nearest,
+            BitmapFilter::Linear => FilterMode::Linear
+        };
+
+        // Anisotropy requires linear filtering.
+        let anisotropy = match hints.filter {
+            BitmapFilter::Point => None,
+            BitmapFilter::Linear => hints
+                .max_anisotropy
+                .or(self.default_anisotropy)
+                .map(|n| n.min(MAX_ANISOTROPY))
+                .filter(|n| *n > 1.0)
+        };
+
+        let sampler = Arc::new(self.device.create_sampler(&SamplerDescriptor {
+            label: Some("bitmap sampler"),
+            address_mode_u,
+            address_mode_v,
+            address_mode_w,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            anisotropy_clamp: anisotropy.map(|n| n as u16).unwrap_or(1),
+            ..Default::default()
+        }));
+
+        self.samplers.push((*hints, sampler.clone()));
+        sampler
+    }
+}
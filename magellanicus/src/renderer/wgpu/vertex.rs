@@ -0,0 +1,135 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{vertex_attr_array, BufferAddress, VertexAttribute, VertexBufferLayout, VertexStepMode};
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct WgpuModelVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub binormal: [f32; 3],
+    pub tangent: [f32; 3]
+}
+
+impl WgpuModelVertex {
+    const ATTRIBUTES: [VertexAttribute; 4] = vertex_attr_array![0 => Float32x3, 4 => Float32x3, 5 => Float32x3, 6 => Float32x3];
+
+    pub fn layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: core::mem::size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct WgpuModelVertexTextureCoords {
+    pub texture_coords: [f32; 2]
+}
+
+impl WgpuModelVertexTextureCoords {
+    const ATTRIBUTES: [VertexAttribute; 1] = vertex_attr_array![1 => Float32x2];
+    const LIGHTMAP_ATTRIBUTES: [VertexAttribute; 1] = vertex_attr_array![2 => Float32x2];
+
+    pub fn layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: core::mem::size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES
+        }
+    }
+
+    /// Layout for lightmap texture coordinates, which are stored the same way as texture coordinates.
+    pub fn lightmap_layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            attributes: &Self::LIGHTMAP_ATTRIBUTES,
+            ..Self::layout()
+        }
+    }
+}
+
+/// Vertex of a 2D quad, such as an overlay or a color box.
+///
+/// Positions are relative to the viewport, where (0, 0) is the top-left corner and (1, 1) is the bottom-right corner.
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct WgpuOverlayVertex {
+    pub position: [f32; 2],
+    pub texture_coords: [f32; 2]
+}
+
+impl WgpuOverlayVertex {
+    const ATTRIBUTES: [VertexAttribute; 2] = vertex_attr_array![0 => Float32x2, 1 => Float32x2];
+
+    pub fn layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: core::mem::size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES
+        }
+    }
+
+    /// Get the six vertices of a rectangle, in (left, top, right, bottom) texture coordinates.
+    pub fn quad(x: f32, y: f32, width: f32, height: f32, texture_coords: [f32; 4]) -> [Self; 6] {
+        let [left, top, right, bottom] = texture_coords;
+        let corners = [
+            Self { position: [x, y], texture_coords: [left, top] },
+            Self { position: [x, y + height], texture_coords: [left, bottom] },
+            Self { position: [x + width, y + height], texture_coords: [right, bottom] },
+            Self { position: [x + width, y], texture_coords: [right, top] },
+        ];
+        [0, 1, 2, 0, 2, 3].map(|i| corners[i])
+    }
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct WgpuModelData {
+    pub camera: [f32; 4],
+    pub offset: [f32; 4],
+    pub view: [[f32; 4]; 4],
+    pub proj: [[f32; 4]; 4]
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct WgpuFogData {
+    pub sky_fog_color: [f32; 4],
+    pub sky_fog_from: f32,
+    pub sky_fog_to: f32,
+    pub sky_fog_min_opacity: f32,
+    pub sky_fog_max_opacity: f32
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct WgpuShaderEnvironmentData {
+    pub primary_detail_map_scale: f32,
+    pub secondary_detail_map_scale: f32,
+    pub bump_map_scale: f32,
+    pub micro_detail_map_scale: f32,
+    pub flags: u32,
+    pub shader_environment_type: u32,
+    pub detail_map_function: u32,
+    pub micro_detail_map_function: u32,
+    pub parallel_color: [f32; 4],
+    pub perpendicular_color: [f32; 4]
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct WgpuOverlayData {
+    pub tint: [f32; 4],
+    pub blend_mode: u32,
+    pub _padding: [u32; 3]
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct WgpuBlitData {
+    /// Nonzero if the output is sRGB but the color buffer is not linear, so colors are decoded to cancel out the
+    /// output's sRGB encoding.
+    pub decode_srgb: u32,
+    pub _padding: [u32; 3]
+}
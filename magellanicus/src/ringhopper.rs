@@ -0,0 +1,390 @@
+//! Conversion from [ringhopper](https://github.com/FishAndRips/ringhopper) tags to renderer parameters.
+//!
+//! Use [`Renderer::load_ringhopper_scenario`] to load everything a scenario needs in one call, or the `convert_`
+//! functions to convert tags individually.
+
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::format;
+use std::string::{String, ToString};
+use std::vec::Vec;
+use ::ringhopper::definitions::{Bitmap, BitmapDataFormat, BitmapDataType, BitmapType as TagBitmapType, BitmapUsage, Scenario, ScenarioStructureBSP, ShaderEnvironment, ShaderModel, ShaderTransparentChicago, ShaderTransparentChicagoExtended, ShaderTransparentGeneric, ShaderTransparentGlass, ShaderTransparentMeter, Sky};
+use ::ringhopper::primitives::primitive::{TagGroup, TagPath};
+use ::ringhopper::primitives::tag::PrimaryTagStructDyn;
+use ::ringhopper::tag::bitmap::MipmapTextureIterator;
+use ::ringhopper::tag::scenario_structure_bsp::get_uncompressed_vertices_for_bsp_material;
+use crate::error::{Error, MResult};
+use crate::renderer::{AddBSPParameter, AddBSPParameterLightmapMaterial, AddBSPParameterLightmapSet, AddBitmapBitmapParameter, AddBitmapParameter, AddBitmapSequenceParameter, AddShaderBasicShaderData, AddShaderData, AddShaderEnvironmentShaderData, AddShaderParameter, AddSkyParameter, BSP3DNode, BSP3DNodeChild, BSP3DPlane, BSPCluster, BSPData, BSPLeaf, BSPPortal, BSPSubcluster, BitmapColorSpace, BitmapFormat, BitmapSamplerHints, BitmapSprite, BitmapType, Renderer, RendererBackend, Resolution, SequenceFrame, ShaderEnvironmentMapFunction, ShaderEnvironmentType, ShaderReflectionType, ShaderType, ValidationIssue};
+use crate::vertex::{LightmapVertex, ModelTriangle, ModelVertex};
+
+/// Tags loaded by ringhopper, keyed by their path.
+pub type RinghopperTags = HashMap<TagPath, Box<dyn PrimaryTagStructDyn>>;
+
+impl<B: RendererBackend> Renderer<B> {
+    /// Convert and load every bitmap, shader, sky, and BSP in `tags`.
+    ///
+    /// `scenario` is used to resolve the skies referenced by BSP clusters, and `tags` should contain all of its
+    /// dependencies. Resources are loaded in dependency order (bitmaps, shaders, skies, then BSPs), and loading stops
+    /// at the first tag that fails to convert or load; resources loaded before that are kept.
+    ///
+    /// Returns every warning found when validating BSPs, along with the path of the BSP it was found in.
+    pub fn load_ringhopper_scenario(&mut self, scenario: &Scenario, tags: &RinghopperTags) -> MResult<Vec<(String, ValidationIssue)>> {
+        for (path, tag) in tags.iter().filter(|t| t.0.group() == TagGroup::Bitmap) {
+            let bitmap = tag.get_ref::<Bitmap>().ok_or_else(|| mismatched_tag_error(path))?;
+            self.add_bitmap(&path.to_string(), convert_bitmap(path, bitmap)?).map_err(|e| with_path(e, "bitmap", path))?;
+        }
+
+        for (path, tag) in tags.iter().filter(|t| t.0.group().subgroup() == Some(TagGroup::Shader)) {
+            self.add_shader(&path.to_string(), convert_shader(path, tag.as_ref())?).map_err(|e| with_path(e, "shader", path))?;
+        }
+
+        for (path, tag) in tags.iter().filter(|t| t.0.group() == TagGroup::Sky) {
+            let sky = tag.get_ref::<Sky>().ok_or_else(|| mismatched_tag_error(path))?;
+            self.add_sky(&path.to_string(), convert_sky(sky)).map_err(|e| with_path(e, "sky", path))?;
+        }
+
+        let mut warnings = Vec::new();
+        for (path, tag) in tags.iter().filter(|t| t.0.group() == TagGroup::ScenarioStructureBSP) {
+            let bsp = tag.get_ref::<ScenarioStructureBSP>().ok_or_else(|| mismatched_tag_error(path))?;
+            let bsp = convert_bsp(path, bsp, scenario)?;
+            let bsp_path = path.to_native_path();
+            warnings.extend(bsp.validation_report(self).warnings().map(|w| (bsp_path.clone(), w.clone())));
+            self.add_bsp(&bsp_path, bsp).map_err(|e| with_path(e, "BSP", path))?;
+        }
+
+        Ok(warnings)
+    }
+}
+
+/// Add the tag path to data errors, since the renderer only knows the path it was given.
+fn with_path(error: Error, kind: &str, path: &TagPath) -> Error {
+    if error.is_data_error() {
+        Error::from_data_error_string(format!("Failed to load {kind} {path}: {error}"))
+    }
+    else {
+        error
+    }
+}
+
+/// Error for a tag whose data is not of the group its path says it is.
+fn mismatched_tag_error(path: &TagPath) -> Error {
+    Error::from_data_error_string(format!("{path} does not contain {} tag data", path.group()))
+}
+
+/// Convert a bitmap tag.
+pub fn convert_bitmap(path: &TagPath, bitmap: &Bitmap) -> MResult<AddBitmapParameter> {
+    let mut bitmaps = Vec::with_capacity(bitmap.bitmap_data.items.len());
+    for (bitmap_index, b) in bitmap.bitmap_data.items.iter().enumerate() {
+        let format = convert_bitmap_format(b.format);
+        let length = MipmapTextureIterator::new_from_bitmap_data(b)
+            .map_err(|e| Error::from_data_error_string(format!("Error with reading bitmap data #{bitmap_index} from {path}: {e:?}")))?
+            .map(|b| b.block_count)
+            .sum::<usize>() * format.block_byte_size();
+        let start = b.pixel_data_offset as usize;
+        let data = start
+            .checked_add(length)
+            .and_then(|end| bitmap.processed_pixel_data.bytes.get(start..end))
+            .ok_or_else(|| Error::from_data_error_string(format!("Can't read {length} bytes from {start} in a buffer of {} bytes for bitmap data #{bitmap_index} in {path}", bitmap.processed_pixel_data.bytes.len())))?;
+
+        bitmaps.push(AddBitmapBitmapParameter {
+            format,
+            bitmap_type: match b._type {
+                BitmapDataType::CubeMap => BitmapType::Cubemap,
+                BitmapDataType::_3dTexture => BitmapType::Dim3D { depth: b.depth as u32 },
+                _ => BitmapType::Dim2D
+            },
+            resolution: Resolution { width: b.width as u32, height: b.height as u32 },
+            mipmap_count: b.mipmap_count as u32,
+            data: data.to_vec(),
+            palette: None,
+            color_space: match bitmap.usage {
+                BitmapUsage::AlphaBlend | BitmapUsage::HeightMap | BitmapUsage::VectorMap => BitmapColorSpace::Linear,
+                _ => BitmapColorSpace::SRGB
+            },
+            sampler: match bitmap._type {
+                TagBitmapType::Sprites | TagBitmapType::InterfaceBitmaps => BitmapSamplerHints::clamped(),
+                _ => BitmapSamplerHints::default()
+            },
+            generate_mipmaps: false
+        });
+    }
+
+    let mut sequences = Vec::with_capacity(bitmap.bitmap_group_sequence.items.len());
+    for (sequence_index, s) in bitmap.bitmap_group_sequence.items.iter().enumerate() {
+        let sequence = if bitmap._type == TagBitmapType::Sprites {
+            let mut sprites = Vec::with_capacity(s.sprites.items.len());
+            for (sprite_index, s) in s.sprites.items.iter().enumerate() {
+                sprites.push(BitmapSprite {
+                    bitmap: s.bitmap_index
+                        .map(|o| o as usize)
+                        .ok_or_else(|| Error::from_data_error_string(format!("Sprite {sprite_index} of sequence {sequence_index} of bitmap {path} has a null bitmap index")))?,
+                    top: s.top as f32,
+                    left: s.left as f32,
+                    bottom: s.bottom as f32,
+                    right: s.right as f32
+                });
+            }
+            AddBitmapSequenceParameter::Sprites { sprites }
+        }
+        else {
+            let first = match s.first_bitmap_index {
+                Some(n) => n as usize,
+                None if s.bitmap_count == 0 => 0,
+                None => return Err(Error::from_data_error_string(format!("Sequence {sequence_index} of bitmap {path} has a null bitmap index")))
+            };
+            AddBitmapSequenceParameter::Bitmap { first, count: s.bitmap_count as usize }
+        };
+        sequences.push(sequence);
+    }
+
+    Ok(AddBitmapParameter { bitmaps, sequences })
+}
+
+fn convert_bitmap_format(format: BitmapDataFormat) -> BitmapFormat {
+    match format {
+        BitmapDataFormat::A8 => BitmapFormat::A8,
+        BitmapDataFormat::Y8 => BitmapFormat::Y8,
+        BitmapDataFormat::AY8 => BitmapFormat::AY8,
+        BitmapDataFormat::A8Y8 => BitmapFormat::A8Y8,
+        BitmapDataFormat::R5G6B5 => BitmapFormat::R5G6B5,
+        BitmapDataFormat::A1R5G5B5 => BitmapFormat::A1R5G5B5,
+        BitmapDataFormat::A4R4G4B4 => BitmapFormat::A4R4G4B4,
+        BitmapDataFormat::X8R8G8B8 => BitmapFormat::X8R8G8B8,
+        BitmapDataFormat::A8R8G8B8 => BitmapFormat::A8R8G8B8,
+        BitmapDataFormat::DXT1 => BitmapFormat::DXT1,
+        BitmapDataFormat::DXT3 => BitmapFormat::DXT3,
+        BitmapDataFormat::DXT5 => BitmapFormat::DXT5,
+        BitmapDataFormat::P8 => BitmapFormat::P8,
+        BitmapDataFormat::BC7 => BitmapFormat::BC7,
+    }
+}
+
+/// Convert a tag of any shader group.
+///
+/// Errors if `tag` is not a shader.
+pub fn convert_shader(path: &TagPath, tag: &dyn PrimaryTagStructDyn) -> MResult<AddShaderParameter> {
+    let basic_shader = |bitmap: Option<&TagPath>, shader_type: ShaderType, alpha_tested: bool| AddShaderParameter {
+        data: AddShaderData::BasicShader(AddShaderBasicShaderData {
+            bitmap: bitmap.map(|b| b.to_string()),
+            sequence: 0,
+            frame: SequenceFrame::default(),
+            shader_type,
+            alpha_tested
+        })
+    };
+
+    let shader = match tag.group() {
+        TagGroup::ShaderEnvironment => {
+            let tag = tag.get_ref::<ShaderEnvironment>().ok_or_else(|| mismatched_tag_error(path))?;
+            let invalid = |e: Error| Error::from_data_error_string(format!("Shader {path}: {e}"));
+            AddShaderParameter {
+                data: AddShaderData::ShaderEnvironment(AddShaderEnvironmentShaderData {
+                    alpha_tested: tag.properties.flags.alpha_tested,
+                    bump_map_is_specular_mask: tag.properties.flags.bump_map_is_specular_mask,
+                    base_map: tag.diffuse.base_map.path().map(|p| p.to_string()),
+                    base_map_sequence: 0,
+                    base_map_frame: SequenceFrame::default(),
+                    primary_detail_map: tag.diffuse.primary_detail_map.path().map(|p| p.to_string()),
+                    secondary_detail_map: tag.diffuse.secondary_detail_map.path().map(|p| p.to_string()),
+                    micro_detail_map: tag.diffuse.micro_detail_map.path().map(|p| p.to_string()),
+                    bump_map: tag.bump.bump_map.path().map(|p| p.to_string()),
+                    reflection_cube_map: tag.reflection.reflection_cube_map.path().map(|p| p.to_string()),
+                    primary_detail_map_scale: tag.diffuse.primary_detail_map_scale as f32,
+                    secondary_detail_map_scale: tag.diffuse.secondary_detail_map_scale as f32,
+                    micro_detail_map_scale: tag.diffuse.micro_detail_map_scale as f32,
+                    bump_map_scale: tag.bump.bump_map_scale as f32,
+                    parallel_color: [
+                        tag.specular.parallel_color.red as f32,
+                        tag.specular.parallel_color.green as f32,
+                        tag.specular.parallel_color.blue as f32,
+                    ],
+                    perpendicular_color: [
+                        tag.specular.perpendicular_color.red as f32,
+                        tag.specular.perpendicular_color.green as f32,
+                        tag.specular.perpendicular_color.blue as f32,
+                    ],
+                    parallel_brightness: tag.reflection.parallel_brightness as f32,
+                    perpendicular_brightness: tag.reflection.perpendicular_brightness as f32,
+                    shader_environment_type: ShaderEnvironmentType::try_from(tag.properties.shader_environment_type as u32).map_err(invalid)?,
+                    detail_map_function: ShaderEnvironmentMapFunction::try_from(tag.diffuse.detail_map_function as u32).map_err(invalid)?,
+                    micro_detail_map_function: ShaderEnvironmentMapFunction::try_from(tag.diffuse.micro_detail_map_function as u32).map_err(invalid)?,
+                    reflection_type: ShaderReflectionType::try_from(tag.reflection._type as u32).map_err(invalid)?,
+                })
+            }
+        },
+        TagGroup::ShaderModel => {
+            let tag = tag.get_ref::<ShaderModel>().ok_or_else(|| mismatched_tag_error(path))?;
+            basic_shader(tag.maps.base_map.path(), ShaderType::Model, !tag.properties.flags.not_alpha_tested)
+        },
+        TagGroup::ShaderTransparentChicago => {
+            let tag = tag.get_ref::<ShaderTransparentChicago>().ok_or_else(|| mismatched_tag_error(path))?;
+            basic_shader(tag.maps.items.first().and_then(|b| b.parameters.map.path()), ShaderType::TransparentChicago, true)
+        },
+        TagGroup::ShaderTransparentChicagoExtended => {
+            let tag = tag.get_ref::<ShaderTransparentChicagoExtended>().ok_or_else(|| mismatched_tag_error(path))?;
+            basic_shader(tag._4_stage_maps.items.first().and_then(|b| b.parameters.map.path()), ShaderType::TransparentChicago, true)
+        },
+        TagGroup::ShaderTransparentGeneric => {
+            let tag = tag.get_ref::<ShaderTransparentGeneric>().ok_or_else(|| mismatched_tag_error(path))?;
+            basic_shader(tag.maps.items.first().and_then(|b| b.parameters.map.path()), ShaderType::TransparentGeneric, true)
+        },
+        TagGroup::ShaderTransparentGlass => {
+            let tag = tag.get_ref::<ShaderTransparentGlass>().ok_or_else(|| mismatched_tag_error(path))?;
+            basic_shader(tag.diffuse.diffuse_map.path(), ShaderType::TransparentGlass, true)
+        },
+        TagGroup::ShaderTransparentMeter => {
+            let tag = tag.get_ref::<ShaderTransparentMeter>().ok_or_else(|| mismatched_tag_error(path))?;
+            basic_shader(tag.properties.map.path(), ShaderType::TransparentMeter, true)
+        },
+
+        // TODO: plasma and water maps
+        TagGroup::ShaderTransparentPlasma => basic_shader(None, ShaderType::TransparentPlasma, true),
+        TagGroup::ShaderTransparentWater => basic_shader(None, ShaderType::TransparentWater, true),
+
+        n => return Err(Error::from_data_error_string(format!("{path} is a {n} tag, not a shader")))
+    };
+
+    Ok(shader)
+}
+
+/// Convert a sky tag.
+pub fn convert_sky(sky: &Sky) -> AddSkyParameter {
+    AddSkyParameter {
+        geometry: None,
+        outdoor_fog_color: [sky.outdoor_fog.color.red as f32, sky.outdoor_fog.color.green as f32, sky.outdoor_fog.color.blue as f32],
+        outdoor_fog_maximum_density: sky.outdoor_fog.maximum_density as f32,
+        outdoor_fog_start_distance: sky.outdoor_fog.start_distance as f32,
+        outdoor_fog_opaque_distance: sky.outdoor_fog.opaque_distance as f32,
+        indoor_fog_color: [sky.indoor_fog.color.red as f32, sky.indoor_fog.color.green as f32, sky.indoor_fog.color.blue as f32],
+        indoor_fog_maximum_density: sky.indoor_fog.maximum_density as f32,
+        indoor_fog_start_distance: sky.indoor_fog.start_distance as f32,
+        indoor_fog_opaque_distance: sky.indoor_fog.opaque_distance as f32,
+    }
+}
+
+/// Convert a scenario_structure_bsp tag.
+///
+/// `scenario` is used to resolve the skies referenced by clusters.
+pub fn convert_bsp(path: &TagPath, bsp: &ScenarioStructureBSP, scenario: &Scenario) -> MResult<AddBSPParameter> {
+    let collision_bsp = bsp
+        .collision_bsp
+        .items
+        .first()
+        .ok_or_else(|| Error::from_data_error_string(format!("BSP {path} has no collision BSP")))?;
+
+    let mut leaves = Vec::with_capacity(bsp.leaves.items.len());
+    for (leaf_index, leaf) in bsp.leaves.items.iter().enumerate() {
+        let cluster = leaf
+            .cluster
+            .ok_or_else(|| Error::from_data_error_string(format!("Leaf #{leaf_index} of BSP {path} has a null cluster")))?;
+        leaves.push(BSPLeaf { cluster: cluster as usize });
+    }
+
+    let mut clusters = Vec::with_capacity(bsp.clusters.items.len());
+    for cluster in &bsp.clusters.items {
+        let sky = match cluster.sky {
+            Some(sky) => scenario
+                .skies
+                .items
+                .get(sky as usize)
+                .ok_or_else(|| Error::from_data_error_string(format!("BSP {path} references sky {sky} which isn't valid on the scenario")))?
+                .sky
+                .path()
+                .map(|s| s.to_string()),
+            None => None
+        };
+
+        clusters.push(BSPCluster {
+            sky,
+            subclusters: cluster.subclusters.items.iter().map(|s| BSPSubcluster {
+                surface_indices: s.surface_indices.items.iter().map(|i| i.index as usize).collect(),
+                world_bounds_from: [s.world_bounds_x.lower as f32, s.world_bounds_y.lower as f32, s.world_bounds_z.lower as f32],
+                world_bounds_to: [s.world_bounds_x.upper as f32, s.world_bounds_y.upper as f32, s.world_bounds_z.upper as f32],
+            }).collect(),
+            cluster_portals: cluster.portals.items.iter().map(|s| s.portal.unwrap_or(0xFFFF) as usize).collect()
+        });
+    }
+
+    let mut add_bsp = AddBSPParameter {
+        lightmap_bitmap: bsp.lightmaps_bitmap.path().map(|p| p.to_native_path()),
+        lightmap_sets: Vec::with_capacity(bsp.lightmaps.items.len()),
+        bsp_data: BSPData {
+            nodes: collision_bsp.bsp3d_nodes.items.iter().map(|i| BSP3DNode {
+                front_child: BSP3DNodeChild::from_flagged_u32(i.front_child),
+                back_child: BSP3DNodeChild::from_flagged_u32(i.back_child),
+                plane: i.plane as usize
+            }).collect(),
+            planes: collision_bsp.planes.items.iter().map(|i| BSP3DPlane {
+                angle: [i.plane.vector.x as f32, i.plane.vector.y as f32, i.plane.vector.z as f32],
+                offset: i.plane.d as f32
+            }).collect(),
+            leaves,
+            clusters,
+            portals: bsp.cluster_portals.items.iter().map(|p| BSPPortal {
+                front_cluster: p.front_cluster.unwrap_or(0xFFFF) as usize,
+                back_cluster: p.back_cluster.unwrap_or(0xFFFF) as usize,
+            }).collect()
+        },
+    };
+
+    for (lightmap_index, lightmap) in bsp.lightmaps.items.iter().enumerate() {
+        let mut add_lightmap = AddBSPParameterLightmapSet {
+            lightmap_index: lightmap.bitmap.map(|i| i as usize),
+            materials: Vec::with_capacity(lightmap.materials.items.len())
+        };
+
+        for (material_index, material) in lightmap.materials.items.iter().enumerate() {
+            let Some(shader_path) = material.shader.path() else {
+                continue
+            };
+
+            let broken_surfaces = || Error::from_data_error_string(format!("Material #{material_index} of Lightmap #{lightmap_index} of BSP {path} has broken surface indices."));
+            let surfaces = usize::try_from(material.surfaces).map_err(|_| broken_surfaces())?;
+            let surface_count = usize::try_from(material.surface_count).map_err(|_| broken_surfaces())?;
+            let surface_indices = surfaces
+                .checked_add(surface_count)
+                .and_then(|range_end| bsp.surfaces.items.get(surfaces..range_end))
+                .ok_or_else(broken_surfaces)?;
+
+            let indices = surface_indices
+                .iter()
+                .filter_map(|s| {
+                    let a = s.vertex0_index?;
+                    let b = s.vertex1_index?;
+                    let c = s.vertex2_index?;
+                    Some(ModelTriangle { indices: [a, b, c] })
+                })
+                .collect();
+
+            let (material, lightmap) = get_uncompressed_vertices_for_bsp_material(material).map_err(|e| {
+                Error::from_data_error_string(format!("Material #{material_index} of Lightmap #{lightmap_index} of BSP {path} has broken vertices: {e:?}"))
+            })?;
+
+            let shader_vertices = material
+                .map(|f| ModelVertex {
+                    position: [f.position.x as f32, f.position.y as f32, f.position.z as f32],
+                    normal: [f.normal.x as f32, f.normal.y as f32, f.normal.z as f32],
+                    binormal: [f.binormal.x as f32, f.binormal.y as f32, f.binormal.z as f32],
+                    tangent: [f.tangent.x as f32, f.tangent.y as f32, f.tangent.z as f32],
+                    texture_coords: [f.texture_coords.x as f32, f.texture_coords.y as f32]
+                })
+                .collect();
+
+            let lightmap: Vec<LightmapVertex> = lightmap
+                .map(|f| LightmapVertex {
+                    lightmap_texture_coords: [f.texture_coords.x as f32, f.texture_coords.y as f32]
+                })
+                .collect();
+
+            add_lightmap.materials.push(AddBSPParameterLightmapMaterial {
+                shader_vertices,
+                lightmap_vertices: (!lightmap.is_empty()).then_some(lightmap),
+                surfaces: indices,
+                shader: shader_path.to_native_path()
+            });
+        }
+        add_bsp.lightmap_sets.push(add_lightmap);
+    }
+
+    Ok(add_bsp)
+}
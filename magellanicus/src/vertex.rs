@@ -1,4 +1,5 @@
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct ModelVertex {
     pub position: [f32; 3],
@@ -9,12 +10,14 @@ pub struct ModelVertex {
 }
 
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct LightmapVertex {
     pub lightmap_texture_coords: [f32; 2]
 }
 
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct ModelTriangle {
     pub indices: [u16; 3]